@@ -11,6 +11,8 @@ pub struct Module {
     pub types: Vec<TypeDef>,
     pub effect_sets: Vec<EffectSetDef>,
     pub functions: Vec<FnDef>,
+    pub helpers: Vec<HelperDef>,
+    pub externs: Vec<ExternDef>,
     pub extra_meta: Vec<(String, MetaValue)>,
     pub span: Span,
 }
@@ -39,6 +41,8 @@ pub struct Delta {
 #[derive(Debug, Clone)]
 pub struct TypeDef {
     pub name: String,
+    /// Type parameters of a generic type: `(type (Page T) ...)`
+    pub type_params: Vec<String>,
     pub invariants: Vec<InvariantExpr>,
     pub fields: Vec<FieldDef>,
     pub extra_meta: Vec<(String, MetaValue)>,
@@ -61,12 +65,58 @@ pub struct FieldDef {
     pub generated: bool,
     pub min_len: Option<i64>,
     pub max_len: Option<i64>,
+    /// `:min` / `:max` — inclusive range of a numeric field
+    pub min: Option<Number>,
+    pub max: Option<Number>,
+    /// `:scale` — digits after the decimal point a `Decimal` field keeps
+    pub scale: Option<u32>,
+    /// `:default` — literal a create input may leave out, e.g. `:default :member`
+    pub default: Option<MetaValue>,
     pub format: Option<String>,
     pub unique_within: Option<String>,
     pub extra_meta: Vec<(String, MetaValue)>,
     pub span: Span,
 }
 
+/// A numeric literal in an annotation, such as `:min 0` or `:max 99.99`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(n) => *n as f64,
+            Number::Float(f) => *f,
+        }
+    }
+
+    /// The value as fixed-point units and scale: 19.99 is (1999, 2). `None` when the
+    /// units do not fit in an `i64`. The lexer only accepts float literals whose parts
+    /// are exactly the digits written.
+    pub fn decimal_parts(&self) -> Option<(i64, u32)> {
+        match self {
+            Number::Int(n) => Some((*n, 0)),
+            Number::Float(f) => {
+                let text = f.to_string();
+                let scale = text.split_once('.').map_or(0, |(_, frac)| frac.len() as u32);
+                Some((text.replace('.', "").parse().ok()?, scale))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
 /// Effect set definition
 #[derive(Debug, Clone)]
 pub struct EffectSetDef {
@@ -99,6 +149,8 @@ pub struct FnDef {
     pub latency_budget: Option<Duration>,
     pub called_by: Vec<String>,
     pub idempotency_key: Option<Expr>,
+    /// `:on-store-error :tag` — err variant that store errors no arm matches map onto
+    pub on_store_error: Option<String>,
     pub params: Vec<ParamDef>,
     pub returns: ReturnsDef,
     pub body: Expr,
@@ -106,6 +158,53 @@ pub struct FnDef {
     pub span: Span,
 }
 
+/// Helper definition: (defn token-expired? [s Session] Bool body)
+/// A pure function other functions call, with a plain return type; never an endpoint.
+#[derive(Debug, Clone)]
+pub struct HelperDef {
+    pub name: String,
+    pub params: Vec<ParamDef>,
+    pub returns: TypeExpr,
+    pub body: Expr,
+    pub span: Span,
+}
+
+impl HelperDef {
+    /// The helper as a function declaring no effects or variants, for the code it
+    /// shares with `fn`s
+    pub fn as_fn(&self) -> FnDef {
+        FnDef {
+            name: self.name.clone(),
+            provenance: None,
+            effects: Vec::new(),
+            total: false,
+            latency_budget: None,
+            called_by: Vec::new(),
+            idempotency_key: None,
+            on_store_error: None,
+            params: self.params.clone(),
+            returns: ReturnsDef {
+                variants: Vec::new(),
+                span: self.span.clone(),
+            },
+            body: self.body.clone(),
+            extra_meta: Vec::new(),
+            span: self.span.clone(),
+        }
+    }
+}
+
+/// External function declaration: (extern verify-password [String String] (result Unit Unit) :effects [])
+/// The host application implements it; Pact knows only its signature and effects.
+#[derive(Debug, Clone)]
+pub struct ExternDef {
+    pub name: String,
+    pub params: Vec<TypeExpr>,
+    pub returns: TypeExpr,
+    pub effects: Vec<String>, // names of effect sets
+    pub span: Span,
+}
+
 /// Duration value
 #[derive(Debug, Clone)]
 pub struct Duration {
@@ -169,6 +268,21 @@ pub enum TypeExpr {
     List(Box<TypeExpr>),                        // (list ValidationError)
     Union(Vec<Variant>),                        // (union ...)
     Enum(Vec<String>),                          // (enum :admin :member :guest)
+    Optional(Box<TypeExpr>),                    // (optional String)
+    Ref(String),                                // (ref User)
+    MapOf(Box<TypeExpr>, Box<TypeExpr>),        // (map String Int)
+    Generic(String, Vec<TypeExpr>),             // (Page User)
+    Result(Box<TypeExpr>, Box<TypeExpr>),       // (result Unit String)
+}
+
+impl TypeExpr {
+    /// The type without its `(optional ...)` wrapper, if any
+    pub fn non_optional(&self) -> &TypeExpr {
+        match self {
+            TypeExpr::Optional(inner) => inner,
+            other => other,
+        }
+    }
 }
 
 /// Expressions
@@ -182,6 +296,8 @@ pub enum Expr {
     StringLit(String, Span),
     /// Integer literal
     IntLit(i64, Span),
+    /// Float literal
+    FloatLit(f64, Span),
     /// Boolean literal
     BoolLit(bool, Span),
     /// Let binding: (let [bindings...] body)
@@ -225,6 +341,21 @@ pub enum Expr {
     },
     /// Map literal: {:key value ...}
     MapLit(Vec<(String, Expr)>, Span),
+    /// List literal: [a b c]
+    ListLit(Vec<Expr>, Span),
+    /// Anonymous function: (fn [x] body), as passed to map, filter and fold
+    Lambda {
+        params: Vec<String>,
+        body: Box<Expr>,
+        span: Span,
+    },
+    /// Iteration over a list for its effects: (for-each [x xs] body)
+    ForEach {
+        var: String,
+        list: Box<Expr>,
+        body: Box<Expr>,
+        span: Span,
+    },
     /// Wildcard pattern `_`
     Wildcard(Span),
 }
@@ -259,6 +390,7 @@ pub enum Pattern {
 pub enum MetaValue {
     String(String),
     Int(i64),
+    Float(f64),
     Bool(bool),
     Symbol(String),
     Keyword(String),
@@ -0,0 +1,425 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version recorded in `pact.lock`; a different compiler regenerates every module.
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Dependency versions pinned in the generated Cargo.toml for `--runtime` crates.
+const PACT_RUNTIME_VERSION: &str = "0.1.0";
+const SERDE_VERSION: &str = "1.0.219";
+
+/// Which backend produced a module. Switching backends regenerates it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    V1,
+    Runtime,
+    RuntimeAsync,
+}
+
+impl Backend {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Backend::V1 => "v1",
+            Backend::Runtime => "runtime",
+            Backend::RuntimeAsync => "runtime-async",
+        }
+    }
+
+    fn uses_runtime(self) -> bool {
+        self != Backend::V1
+    }
+}
+
+/// `pact.lock`: what each module of a generated crate was compiled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lock {
+    pub compiler: String,
+    pub modules: Vec<LockEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockEntry {
+    /// Rust module name, e.g. "user_service"
+    pub name: String,
+    /// The `.pct` path, relative to the crate directory
+    pub source: String,
+    pub source_hash: String,
+    pub backend: String,
+}
+
+impl Lock {
+    pub fn new() -> Self {
+        Lock {
+            compiler: COMPILER_VERSION.to_string(),
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Lock, String> {
+        let mut lock = Lock {
+            compiler: String::new(),
+            modules: Vec::new(),
+        };
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "[[module]]" {
+                lock.modules.push(LockEntry {
+                    name: String::new(),
+                    source: String::new(),
+                    source_hash: String::new(),
+                    backend: String::new(),
+                });
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| format!("pact.lock line {}: expected key = \"value\"", i + 1))?;
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .ok_or_else(|| format!("pact.lock line {}: expected a quoted value for '{}'", i + 1, key))?
+                .to_string();
+            match (lock.modules.last_mut(), key) {
+                (None, "compiler") => lock.compiler = value,
+                (Some(entry), "name") => entry.name = value,
+                (Some(entry), "source") => entry.source = value,
+                (Some(entry), "source-hash") => entry.source_hash = value,
+                (Some(entry), "backend") => entry.backend = value,
+                _ => return Err(format!("pact.lock line {}: unknown key '{}'", i + 1, key)),
+            }
+        }
+        Ok(lock)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::from("# Generated by pact. Records the source each module was compiled from.\n");
+        out.push_str(&format!("compiler = \"{}\"\n", self.compiler));
+        for entry in &self.modules {
+            out.push_str("\n[[module]]\n");
+            out.push_str(&format!("name = \"{}\"\n", entry.name));
+            out.push_str(&format!("source = \"{}\"\n", entry.source));
+            out.push_str(&format!("source-hash = \"{}\"\n", entry.source_hash));
+            out.push_str(&format!("backend = \"{}\"\n", entry.backend));
+        }
+        out
+    }
+
+    /// Whether `source` was already compiled from this exact text, by this compiler and backend.
+    fn is_fresh(&self, source: &str, hash: &str, backend: Backend) -> bool {
+        self.compiler == COMPILER_VERSION
+            && self
+                .modules
+                .iter()
+                .any(|e| e.source == source && e.source_hash == hash && e.backend == backend.as_str())
+    }
+}
+
+impl Default for Lock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// FNV-1a over the source text; stable across runs and Rust versions.
+pub fn source_hash(source: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in source.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("fnv1a64:{:016x}", hash)
+}
+
+pub fn emit_cargo_toml(name: &str, backend: Backend) -> String {
+    let mut out = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\npath = \"src/lib.rs\"\n\n[dependencies]\n",
+        name
+    );
+    if backend.uses_runtime() {
+        out.push_str(&format!(
+            "pact-runtime = {{ path = \"../pact-runtime\", version = \"={}\" }}\n",
+            PACT_RUNTIME_VERSION
+        ));
+        out.push_str(&format!(
+            "serde = {{ version = \"={}\", features = [\"derive\"] }}\n",
+            SERDE_VERSION
+        ));
+    }
+    out
+}
+
+/// `lib.rs` declaring every module recorded in the lock.
+pub fn emit_lib_rs(lock: &Lock) -> String {
+    let mut out = String::from("// Generated by pact from pact.lock. Do not edit.\n\n");
+    for entry in &lock.modules {
+        out.push_str(&format!("pub mod {};\n", entry.name));
+    }
+    out
+}
+
+/// What `write_crate` did with each output file.
+#[derive(Debug, Default)]
+pub struct CrateReport {
+    pub written: Vec<PathBuf>,
+    pub unchanged: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+/// Compile `inputs` into the library crate at `dir`: Cargo.toml (only if missing),
+/// `src/<module>.rs`, `src/lib.rs` and `pact.lock`. Sources whose hash, backend and
+/// compiler version match the lock are not recompiled. `compile` turns source text into
+/// `(module name, rust code)`.
+pub fn write_crate(
+    dir: &Path,
+    inputs: &[PathBuf],
+    backend: Backend,
+    mut compile: impl FnMut(&Path, &str) -> Result<(String, String), String>,
+) -> Result<CrateReport, String> {
+    let src_dir = dir.join("src");
+    fs::create_dir_all(&src_dir).map_err(|e| format!("Failed to create directories: {}", e))?;
+
+    let lock_path = dir.join("pact.lock");
+    let old_lock = match fs::read_to_string(&lock_path) {
+        Ok(text) => Lock::parse(&text)?,
+        Err(_) => Lock::new(),
+    };
+    // Entries from another compiler are kept only to be replaced as their sources recompile
+    let mut lock = Lock {
+        compiler: COMPILER_VERSION.to_string(),
+        modules: old_lock.modules.clone(),
+    };
+    let mut report = CrateReport::default();
+
+    for input in inputs {
+        let text = fs::read_to_string(input).map_err(|e| format!("Failed to read '{}': {}", input.display(), e))?;
+        let source_key = source_key(input, dir)?;
+        let hash = source_hash(&text);
+
+        let previous = old_lock.modules.iter().find(|e| e.source == source_key);
+        if let Some(entry) = previous {
+            let output = src_dir.join(format!("{}.rs", entry.name));
+            if old_lock.is_fresh(&source_key, &hash, backend) && output.exists() {
+                report.unchanged.push(output);
+                continue;
+            }
+        }
+
+        let (name, code) = compile(input, &text)?;
+        // An entry whose source is gone (moved, or recorded by an older compiler) gives way
+        if let Some(other) = lock
+            .modules
+            .iter()
+            .find(|e| e.name == name && e.source != source_key && dir.join(&e.source).exists())
+        {
+            return Err(format!(
+                "'{}' and '{}' both define module '{}'",
+                other.source, source_key, name
+            ));
+        }
+        lock.modules.retain(|e| e.source != source_key && e.name != name);
+        lock.modules.push(LockEntry {
+            name: name.clone(),
+            source: source_key,
+            source_hash: hash,
+            backend: backend.as_str().to_string(),
+        });
+        write_if_changed(&src_dir.join(format!("{}.rs", name)), &code, &mut report)?;
+    }
+    lock.modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // Cargo.toml is the user's once written; only point out missing runtime dependencies
+    let cargo_path = dir.join("Cargo.toml");
+    let needs_runtime = lock.modules.iter().any(|e| e.backend != Backend::V1.as_str());
+    match fs::read_to_string(&cargo_path) {
+        Ok(existing) => {
+            if needs_runtime && !existing.contains("pact-runtime") {
+                report.warnings.push(format!(
+                    "{} has no pact-runtime dependency, which --runtime modules need",
+                    cargo_path.display()
+                ));
+            }
+        }
+        Err(_) => {
+            let crate_name = dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("pact-generated")
+                .replace('_', "-");
+            let deps = if needs_runtime { Backend::Runtime } else { Backend::V1 };
+            write_if_changed(&cargo_path, &emit_cargo_toml(&crate_name, deps), &mut report)?;
+        }
+    }
+    write_if_changed(&src_dir.join("lib.rs"), &emit_lib_rs(&lock), &mut report)?;
+    write_if_changed(&lock_path, &lock.render(), &mut report)?;
+    Ok(report)
+}
+
+/// How the lock names a source: its path from the crate directory, so the same file
+/// given as `a.pct`, `./a.pct` or an absolute path, from any working directory, is one entry.
+fn source_key(input: &Path, dir: &Path) -> Result<String, String> {
+    let source = fs::canonicalize(input).map_err(|e| format!("Failed to read '{}': {}", input.display(), e))?;
+    let base = fs::canonicalize(dir).map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?;
+    Ok(relative_path(&source, &base).display().to_string())
+}
+
+/// `path` from `base`, both absolute: `/work/examples/a.pct` from `/work/domain` is `../examples/a.pct`.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let common = path.components().zip(base.components()).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        // Nothing shared, e.g. another drive
+        return path.to_path_buf();
+    }
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+    relative
+}
+
+fn write_if_changed(path: &Path, content: &str, report: &mut CrateReport) -> Result<(), String> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        report.unchanged.push(path.to_path_buf());
+        return Ok(());
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    report.written.push(path.to_path_buf());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_stub(_path: &Path, source: &str) -> Result<(String, String), String> {
+        let name = source.split_whitespace().nth(1).unwrap_or("m").trim_end_matches(')');
+        Ok((name.to_string(), format!("// {}\n", source)))
+    }
+
+    #[test]
+    fn test_lock_round_trip() {
+        let lock = Lock {
+            compiler: "0.1.0".to_string(),
+            modules: vec![LockEntry {
+                name: "user_service".to_string(),
+                source: "examples/user-service.pct".to_string(),
+                source_hash: source_hash("(module user-service)"),
+                backend: "runtime".to_string(),
+            }],
+        };
+        assert_eq!(Lock::parse(&lock.render()).unwrap(), lock);
+        assert!(Lock::parse("compiler = 0.1.0").is_err());
+        assert!(Lock::parse("[[module]]\ncolour = \"red\"").is_err());
+    }
+
+    #[test]
+    fn test_cargo_toml_pins_runtime_dependencies() {
+        let toml = emit_cargo_toml("domain", Backend::Runtime);
+        assert!(toml.contains("name = \"domain\""));
+        assert!(toml.contains("pact-runtime = { path = \"../pact-runtime\", version = \"=0.1.0\" }"));
+        assert!(toml.contains("serde = { version = \"=1.0.219\", features = [\"derive\"] }"));
+        // v1 output needs nothing
+        assert!(emit_cargo_toml("domain", Backend::V1).ends_with("[dependencies]\n"));
+    }
+
+    #[test]
+    fn test_write_crate_is_incremental() {
+        let dir = std::env::temp_dir().join("pact-crate-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.pct");
+        let b = dir.join("b.pct");
+        fs::write(&a, "(module alpha)").unwrap();
+        fs::write(&b, "(module beta)").unwrap();
+        let out = dir.join("domain");
+
+        let report = write_crate(&out, &[a.clone(), b.clone()], Backend::Runtime, compile_stub).unwrap();
+        assert!(report.written.contains(&out.join("src/alpha.rs")));
+        assert!(out.join("Cargo.toml").exists());
+        assert_eq!(
+            fs::read_to_string(out.join("src/lib.rs")).unwrap(),
+            "// Generated by pact from pact.lock. Do not edit.\n\npub mod alpha;\npub mod beta;\n"
+        );
+
+        // Nothing changed: nothing is recompiled or rewritten
+        let mut compiled = Vec::new();
+        let report = write_crate(&out, &[a.clone(), b.clone()], Backend::Runtime, |p, s| {
+            compiled.push(p.to_path_buf());
+            compile_stub(p, s)
+        })
+        .unwrap();
+        assert!(compiled.is_empty());
+        assert!(report.written.is_empty());
+
+        // Only the edited source recompiles; compiling it alone keeps the other module
+        fs::write(&b, "(module beta :version 2)").unwrap();
+        let report = write_crate(&out, std::slice::from_ref(&b), Backend::Runtime, compile_stub).unwrap();
+        assert!(report.written.contains(&out.join("src/beta.rs")));
+        assert!(!report.written.contains(&out.join("src/lib.rs")));
+        let lock = Lock::parse(&fs::read_to_string(out.join("pact.lock")).unwrap()).unwrap();
+        assert_eq!(lock.modules.len(), 2);
+
+        // Another backend regenerates, and a hand-kept Cargo.toml missing its runtime is flagged
+        fs::write(out.join("Cargo.toml"), "[package]\nname = \"domain\"\n").unwrap();
+        let report = write_crate(&out, std::slice::from_ref(&a), Backend::V1, compile_stub).unwrap();
+        assert!(report.written.contains(&out.join("pact.lock")));
+        assert_eq!(report.warnings.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lock_keys_sources_by_path_from_the_crate() {
+        let dir = std::env::temp_dir().join("pact-crate-key-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("examples")).unwrap();
+        let a = dir.join("examples/a.pct");
+        fs::write(&a, "(module alpha)").unwrap();
+        let out = dir.join("domain");
+
+        write_crate(&out, std::slice::from_ref(&a), Backend::V1, compile_stub).unwrap();
+        let lock = Lock::parse(&fs::read_to_string(out.join("pact.lock")).unwrap()).unwrap();
+        assert_eq!(lock.modules[0].source, "../examples/a.pct");
+
+        // The same file spelled another way is the same, unchanged entry
+        let mut compiled = Vec::new();
+        write_crate(&out, &[dir.join("./examples/../examples/a.pct")], Backend::V1, |p, s| {
+            compiled.push(p.to_path_buf());
+            compile_stub(p, s)
+        })
+        .unwrap();
+        assert!(compiled.is_empty());
+
+        // An entry recorded under a path that no longer resolves is replaced, not a duplicate
+        let stale = lock.render().replace("../examples/a.pct", "examples/a.pct");
+        fs::write(out.join("pact.lock"), stale).unwrap();
+        write_crate(&out, std::slice::from_ref(&a), Backend::V1, compile_stub).unwrap();
+        let lock = Lock::parse(&fs::read_to_string(out.join("pact.lock")).unwrap()).unwrap();
+        assert_eq!(lock.modules.len(), 1);
+        assert_eq!(lock.modules[0].source, "../examples/a.pct");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_write_crate_rejects_duplicate_module_names() {
+        let dir = std::env::temp_dir().join("pact-crate-dup-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.pct");
+        let b = dir.join("b.pct");
+        fs::write(&a, "(module alpha)").unwrap();
+        fs::write(&b, "(module alpha :version 2)").unwrap();
+
+        let err = write_crate(&dir.join("domain"), &[a, b], Backend::V1, compile_stub).unwrap_err();
+        assert!(err.contains("both define module 'alpha'"), "{}", err);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
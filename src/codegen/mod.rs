@@ -1,2 +1,4 @@
+pub mod crate_output;
 pub mod rust;
 pub mod rust_v2;
+pub mod typescript;
@@ -1,10 +1,22 @@
 use crate::ast::*;
+use crate::naming::{self, to_pascal, to_snake};
+use crate::semantic::enums::{self, EnumDef};
+use crate::semantic::externs;
+use crate::semantic::generics;
+use crate::semantic::operators;
+use crate::semantic::refs;
 
 pub struct RustCodegen {
     output: String,
     indent: usize,
 }
 
+impl Default for RustCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RustCodegen {
     pub fn new() -> Self {
         RustCodegen {
@@ -14,30 +26,75 @@ impl RustCodegen {
     }
 
     pub fn generate(mut self, module: &Module) -> String {
+        let enums = enums::collect_enums(module);
+        let module = &enums::name_enums(module, &enums);
+
         self.emit_header(module);
         self.emit_line("");
 
+        self.emit_prelude(module);
+        self.emit_line("");
+
+        let stores = collect_stores(module);
+
+        // Generate enums declared inline by fields and params
+        for enum_def in &enums {
+            self.emit_enum_def(enum_def);
+            self.emit_line("");
+        }
+
+        // Generate typed ids for types that (ref T) fields refer to
+        for typedef in refs::referenced_types(module) {
+            self.emit_id_type(&typedef.name);
+            self.emit_line("");
+        }
+
         // Generate types
         for typedef in &module.types {
-            self.emit_type_def(typedef);
+            self.emit_type_def(typedef, module);
             self.emit_line("");
         }
 
+        // Generate query structs for stores holding a type defined here
+        for store in &stores {
+            self.emit_store_query(store, module);
+        }
+
+        // Generate input structs for functions with Map-typed params
+        for func in &module.functions {
+            self.emit_input_structs(func, module);
+        }
+
         // Generate effect traits
         for effect_set in &module.effect_sets {
-            self.emit_effect_trait(effect_set);
+            self.emit_effect_trait(effect_set, &stores);
+            self.emit_line("");
+        }
+
+        // Generate the in-memory reference implementation of the effect traits
+        self.emit_in_memory_context(module, &stores);
+
+        // Generate the trait the host implements externs through
+        if !module.externs.is_empty() {
+            self.emit_externs_trait(module);
             self.emit_line("");
         }
 
         // Generate return type enums for each function
         for func in &module.functions {
-            self.emit_return_enum(func);
+            self.emit_return_enum(func, module);
+            self.emit_line("");
+        }
+
+        // Generate helpers
+        for helper in &module.helpers {
+            self.emit_helper(helper, module, &stores, &enums);
             self.emit_line("");
         }
 
         // Generate functions
         for func in &module.functions {
-            self.emit_function(func, module);
+            self.emit_function(func, module, &stores, &enums);
             self.emit_line("");
         }
 
@@ -66,7 +123,305 @@ impl RustCodegen {
         self.emit_line("use std::fmt;");
     }
 
-    fn emit_type_def(&mut self, typedef: &TypeDef) {
+    /// The runtime support generated code relies on, so the module builds with no dependencies.
+    fn emit_prelude(&mut self, module: &Module) {
+        self.emit_line("// ---- Prelude -----------------------------------------------");
+        self.emit_line("");
+        self.emit_line("/// A 128-bit identifier, written in the usual 8-4-4-4-12 hex form.");
+        self.emit_line("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]");
+        self.emit_line("pub struct Uuid(u128);");
+        self.emit_line("");
+        self.emit_line("impl Uuid {");
+        self.indent += 1;
+        self.emit_line("/// A random (version 4) id, drawn from the standard library's randomly keyed hasher.");
+        self.emit_line("pub fn new_v4() -> Self {");
+        self.indent += 1;
+        self.emit_line("use std::hash::{BuildHasher, Hasher};");
+        self.emit_line("let half = || std::collections::hash_map::RandomState::new().build_hasher().finish() as u128;");
+        self.emit_line("let bits = (half() << 64) | half();");
+        self.emit_line("Uuid((bits & !(0xf << 76) & !(0x3 << 62)) | (0x4 << 76) | (0x2 << 62))");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("pub fn parse_str(s: &str) -> Option<Self> {");
+        self.indent += 1;
+        self.emit_line("if s.len() != 36 {");
+        self.emit_line("    return None;");
+        self.emit_line("}");
+        self.emit_line("let mut bits = 0u128;");
+        self.emit_line("for (i, c) in s.chars().enumerate() {");
+        self.indent += 1;
+        self.emit_line("match i {");
+        self.emit_line("    8 | 13 | 18 | 23 if c == '-' => {}");
+        self.emit_line("    8 | 13 | 18 | 23 => return None,");
+        self.emit_line("    _ => bits = (bits << 4) | c.to_digit(16)? as u128,");
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("Some(Uuid(bits))");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl fmt::Display for Uuid {");
+        self.indent += 1;
+        self.emit_line("fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.indent += 1;
+        self.emit_line("let b = self.0;");
+        self.emit_line("write!(");
+        self.emit_line("    f,");
+        self.emit_line("    \"{:08x}-{:04x}-{:04x}-{:04x}-{:012x}\",");
+        self.emit_line("    b >> 96,");
+        self.emit_line("    (b >> 80) & 0xffff,");
+        self.emit_line("    (b >> 64) & 0xffff,");
+        self.emit_line("    (b >> 48) & 0xffff,");
+        self.emit_line("    b & 0xffff_ffff_ffff");
+        self.emit_line(")");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("#[derive(Debug, Clone, PartialEq)]");
+        self.emit_line("pub struct ValidationError {");
+        self.emit_line("    pub field: String,");
+        self.emit_line("    pub message: String,");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// A failed store write, as generated functions match on it.");
+        self.emit_line("#[derive(Debug, Clone, PartialEq)]");
+        self.emit_line("pub enum StoreError {");
+        self.emit_line("    /// A `:unique-within` field already holds this value");
+        self.emit_line("    UniqueViolation { field: String },");
+        self.emit_line("    /// A `(ref T)` field names a row the store of `T` does not hold");
+        self.emit_line("    MissingReference { field: String },");
+        self.emit_line("    /// Any other failure, e.g. the backing store is unreachable");
+        self.emit_line("    Unavailable(String),");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("pub fn validate_uuid(s: &str) -> Result<Uuid, ValidationError> {");
+        self.emit_line("    Uuid::parse_str(s).ok_or_else(|| ValidationError { field: \"id\".into(), message: format!(\"'{}' is not a UUID\", s) })");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("pub fn non_empty<T>(items: &[T]) -> bool {");
+        self.emit_line("    !items.is_empty()");
+        self.emit_line("}");
+        self.emit_line("");
+        if uses_decimal(module) {
+            self.emit_decimal();
+            self.emit_line("");
+        }
+        self.emit_line("// ------------------------------------------------------------");
+    }
+
+    /// Fixed-point `Decimal`, only emitted for modules with `Decimal` fields or params.
+    fn emit_decimal(&mut self) {
+        self.emit_line("/// A fixed-point number: `units` scaled by 10^-`scale`, so 19.99 is `Decimal::new(1999, 2)`.");
+        self.emit_line("#[derive(Debug, Clone, Copy)]");
+        self.emit_line("pub struct Decimal {");
+        self.emit_line("    units: i64,");
+        self.emit_line("    scale: u32,");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl Decimal {");
+        self.indent += 1;
+        self.emit_line("pub fn new(units: i64, scale: u32) -> Self {");
+        self.emit_line("    Decimal { units, scale }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// Digits after the decimal point");
+        self.emit_line("pub fn scale(&self) -> u32 {");
+        self.emit_line("    self.scale");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("pub fn parse_str(s: &str) -> Option<Self> {");
+        self.indent += 1;
+        self.emit_line("let (whole, frac) = s.split_once('.').unwrap_or((s, \"\"));");
+        self.emit_line("if whole.is_empty() || !frac.chars().all(|c| c.is_ascii_digit()) {");
+        self.emit_line("    return None;");
+        self.emit_line("}");
+        self.emit_line("let units = format!(\"{}{}\", whole, frac).parse().ok()?;");
+        self.emit_line("Some(Decimal { units, scale: frac.len() as u32 })");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// `self + other` at the larger scale, or `None` when it does not fit");
+        self.emit_line("pub fn checked_add(self, other: Self) -> Option<Self> {");
+        self.emit_line("    let scale = self.scale.max(other.scale);");
+        self.emit_line("    let units = self.units_at(scale)?.checked_add(other.units_at(scale)?)?;");
+        self.emit_line("    Some(Decimal { units: i64::try_from(units).ok()?, scale })");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// `self - other` at the larger scale, or `None` when it does not fit");
+        self.emit_line("pub fn checked_sub(self, other: Self) -> Option<Self> {");
+        self.emit_line("    self.checked_add(Decimal { units: other.units.checked_neg()?, scale: other.scale })");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// `self * other` at the summed scale, or `None` when it does not fit");
+        self.emit_line("pub fn checked_mul(self, other: Self) -> Option<Self> {");
+        self.emit_line("    Some(Decimal { units: self.units.checked_mul(other.units)?, scale: self.scale.checked_add(other.scale)? })");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// The units at a scale at least this one's, or `None` past `i128`");
+        self.emit_line("fn units_at(&self, scale: u32) -> Option<i128> {");
+        self.emit_line("    if self.units == 0 {");
+        self.emit_line("        return Some(0);");
+        self.emit_line("    }");
+        self.emit_line("    10i128.checked_pow(scale - self.scale)?.checked_mul(self.units as i128)");
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl PartialEq for Decimal {");
+        self.emit_line("    fn eq(&self, other: &Self) -> bool {");
+        self.emit_line("        self.cmp(other) == std::cmp::Ordering::Equal");
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl Eq for Decimal {}");
+        self.emit_line("");
+        self.emit_line("impl PartialOrd for Decimal {");
+        self.emit_line("    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {");
+        self.emit_line("        Some(self.cmp(other))");
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl Ord for Decimal {");
+        self.emit_line("    fn cmp(&self, other: &Self) -> std::cmp::Ordering {");
+        self.emit_line("        let scale = self.scale.max(other.scale);");
+        self.emit_line("        match (self.units_at(scale), other.units_at(scale)) {");
+        self.emit_line("            (Some(a), Some(b)) => a.cmp(&b),");
+        self.emit_line("            // Only the rescaled side can pass i128, and then it outweighs any i64");
+        self.emit_line("            (None, _) => self.units.cmp(&0),");
+        self.emit_line("            (_, None) => 0.cmp(&other.units),");
+        self.emit_line("        }");
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        // Sums keep the larger scale and products add the scales, so both stay exact.
+        // A result that does not fit panics rather than wrapping around.
+        for (op, method, symbol) in [("Add", "add", "+"), ("Sub", "sub", "-"), ("Mul", "mul", "*")] {
+            self.emit_line(&format!("impl std::ops::{} for Decimal {{", op));
+            self.emit_line("    type Output = Self;");
+            self.emit_line(&format!("    fn {}(self, other: Self) -> Self {{", method));
+            self.emit_line(&format!(
+                "        self.checked_{}(other).unwrap_or_else(|| panic!(\"Decimal overflow: {{}} {} {{}}\", self, other))",
+                method, symbol
+            ));
+            self.emit_line("    }");
+            self.emit_line("}");
+            self.emit_line("");
+        }
+        self.emit_line("impl std::ops::Neg for Decimal {");
+        self.emit_line("    type Output = Self;");
+        self.emit_line("    fn neg(self) -> Self {");
+        self.emit_line("        let units = self.units.checked_neg().unwrap_or_else(|| panic!(\"Decimal overflow: -{}\", self));");
+        self.emit_line("        Decimal { units, scale: self.scale }");
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl fmt::Display for Decimal {");
+        self.indent += 1;
+        self.emit_line("fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.indent += 1;
+        self.emit_line("let sign = if self.units < 0 { \"-\" } else { \"\" };");
+        self.emit_line("let abs = self.units.unsigned_abs();");
+        self.emit_line("if self.scale == 0 {");
+        self.emit_line("    return write!(f, \"{}{}\", sign, abs);");
+        self.emit_line("}");
+        self.emit_line("let width = self.scale as usize;");
+        self.emit_line("match 10u64.checked_pow(self.scale) {");
+        self.emit_line("    Some(divisor) => write!(f, \"{}{}.{:0width$}\", sign, abs / divisor, abs % divisor),");
+        self.emit_line("    // More fractional digits than a u64 holds: all of the units are fraction");
+        self.emit_line("    None => write!(f, \"{}0.{:0width$}\", sign, abs),");
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// An inline enum, displayed and parsed as its keywords. The first variant is the
+    /// default, for fields `build` leaves unset.
+    fn emit_enum_def(&mut self, enum_def: &EnumDef) {
+        let name = &enum_def.name;
+        self.emit_line(&format!(
+            "/// One of {}",
+            enum_def.variants.iter().map(|v| format!(":{}", v)).collect::<Vec<_>>().join(" ")
+        ));
+        self.emit_line("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]");
+        self.emit_line(&format!("pub enum {} {{", name));
+        self.indent += 1;
+        for (i, variant) in enum_def.variants.iter().enumerate() {
+            if i == 0 {
+                self.emit_line("#[default]");
+            }
+            self.emit_line(&format!("{},", EnumDef::variant_ident(variant)));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl fmt::Display for {} {{", name));
+        self.indent += 1;
+        self.emit_line("fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.indent += 1;
+        self.emit_line("f.write_str(match self {");
+        self.indent += 1;
+        for variant in &enum_def.variants {
+            self.emit_line(&format!("{}::{} => \"{}\",", name, EnumDef::variant_ident(variant), variant));
+        }
+        self.indent -= 1;
+        self.emit_line("})");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl std::str::FromStr for {} {{", name));
+        self.indent += 1;
+        self.emit_line("type Err = String;");
+        self.emit_line("");
+        self.emit_line("fn from_str(s: &str) -> Result<Self, Self::Err> {");
+        self.indent += 1;
+        self.emit_line("match s {");
+        self.indent += 1;
+        for variant in &enum_def.variants {
+            self.emit_line(&format!("\"{}\" => Ok({}::{}),", variant, name, EnumDef::variant_ident(variant)));
+        }
+        self.emit_line(&format!(
+            "_ => Err(format!(\"'{{}}' is not one of {}\", s)),",
+            enum_def.variants.join(", ")
+        ));
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// `UserId(Uuid)`: the id a `(ref User)` field holds
+    fn emit_id_type(&mut self, type_name: &str) {
+        let id_type = refs::id_type(type_name);
+        self.emit_line(&format!("/// The id of a {}, as `(ref {})` fields hold it", type_name, type_name));
+        self.emit_line("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]");
+        self.emit_line(&format!("pub struct {}(pub Uuid);", id_type));
+        self.emit_line("");
+        self.emit_line(&format!("impl From<Uuid> for {} {{", id_type));
+        self.emit_line("    fn from(id: Uuid) -> Self {");
+        self.emit_line(&format!("        {}(id)", id_type));
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl fmt::Display for {} {{", id_type));
+        self.emit_line("    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.emit_line("        self.0.fmt(f)");
+        self.emit_line("    }");
+        self.emit_line("}");
+    }
+
+    fn emit_type_def(&mut self, typedef: &TypeDef, module: &Module) {
         // Doc comment with invariants
         if !typedef.invariants.is_empty() {
             self.emit_line(&format!("/// Type: {}", typedef.name));
@@ -77,11 +432,13 @@ impl RustCodegen {
             }
         }
 
-        self.emit_line("#[derive(Debug, Clone)]");
-        self.emit_line(&format!("pub struct {} {{", typedef.name));
+        // Store queries compare field values, which may be other module types
+        let params = type_params(typedef);
+        self.emit_line("#[derive(Debug, Clone, PartialEq)]");
+        self.emit_line(&format!("pub struct {}{} {{", typedef.name, params));
         self.indent += 1;
         for field in &typedef.fields {
-            let rust_type = type_expr_to_rust(&field.type_expr);
+            let rust_type = field_type_to_rust(field, typedef, module);
             let mut annotations = Vec::new();
             if field.immutable {
                 annotations.push("immutable");
@@ -98,6 +455,18 @@ impl RustCodegen {
             if let Some(max) = field.max_len {
                 self.emit_line(&format!("/// max_len: {}", max));
             }
+            if let Some(min) = field.min {
+                self.emit_line(&format!("/// min: {}", min));
+            }
+            if let Some(max) = field.max {
+                self.emit_line(&format!("/// max: {}", max));
+            }
+            if let Some(scale) = field.scale {
+                self.emit_line(&format!("/// scale: {}", scale));
+            }
+            if let Some(ref default) = field.default {
+                self.emit_line(&format!("/// default: {}", default_text(default)));
+            }
             if let Some(ref fmt) = field.format {
                 self.emit_line(&format!("/// format: {}", fmt));
             }
@@ -111,36 +480,118 @@ impl RustCodegen {
 
         // Generate validation method
         self.emit_line("");
-        self.emit_line(&format!("impl {} {{", typedef.name));
+        self.emit_line(&format!("impl{} {}{} {{", params, typedef.name, params));
         self.indent += 1;
-        self.emit_line("pub fn validate(&self) -> Result<(), Vec<String>> {");
+        self.emit_line("pub fn validate(&self) -> Vec<ValidationError> {");
         self.indent += 1;
-        self.emit_line("let mut errors = Vec::new();");
+        if typedef.fields.iter().any(|f| !field_checks(f, "").is_empty()) {
+            self.emit_line("let mut errors = Vec::new();");
+        } else {
+            self.emit_line("let errors = Vec::new();");
+        }
 
         for field in &typedef.fields {
-            let field_snake = to_snake(&field.name);
-            if let Some(min) = field.min_len {
-                self.emit_line(&format!(
-                    "if self.{}.len() < {} {{ errors.push(format!(\"{{}} must be at least {} characters\", \"{}\")); }}",
-                    field_snake, min, min, field.name
-                ));
-            }
-            if let Some(max) = field.max_len {
-                self.emit_line(&format!(
-                    "if self.{}.len() > {} {{ errors.push(format!(\"{{}} must be at most {} characters\", \"{}\")); }}",
-                    field_snake, max, max, field.name
-                ));
-            }
+            let optional = matches!(field.type_expr, TypeExpr::Optional(_));
+            self.emit_field_checks(field, &format!("self.{}", to_snake(&field.name)), optional);
+        }
+
+        self.emit_line("errors");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// The checks of one field on `access`; an optional value is checked only when present.
+    fn emit_field_checks(&mut self, field: &FieldDef, access: &str, optional: bool) {
+        let value = if optional { "v".to_string() } else { access.to_string() };
+        let checks = field_checks(field, &value);
+        if checks.is_empty() {
+            return;
+        }
+        if optional {
+            // Numbers are `Copy`; text is checked by reference
+            let binding = if is_numeric(&field.type_expr) { access.to_string() } else { format!("&{}", access) };
+            self.emit_line(&format!("if let Some(v) = {} {{", binding));
+            self.indent += 1;
+        }
+        for (failed, message) in checks {
+            self.emit_line(&format!(
+                "if {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"{}\".into() }}); }}",
+                failed, field.name, message
+            ));
         }
+        if optional {
+            self.indent -= 1;
+            self.emit_line("}");
+        }
+    }
 
-        self.emit_line("if errors.is_empty() { Ok(()) } else { Err(errors) }");
+    /// `UserStoreQuery`: one optional filter per field of the stored type.
+    fn emit_store_query(&mut self, store: &StoreInfo, module: &Module) {
+        let Some(typedef) = &store.type_def else {
+            return;
+        };
+        let query = format!("{}Query", to_pascal(&store.target));
+        self.emit_line(&format!("/// A lookup in `{}`; unset fields match anything.", store.target));
+        self.emit_line("#[derive(Debug, Clone, Default, PartialEq)]");
+        self.emit_line(&format!("pub struct {} {{", query));
+        self.indent += 1;
+        for field in &typedef.fields {
+            self.emit_line(&format!(
+                "pub {}: Option<{}>,",
+                to_snake(&field.name),
+                field_type_to_rust(field, typedef, module)
+            ));
+        }
         self.indent -= 1;
         self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl {} {{", query));
+        self.indent += 1;
+        self.emit_line(&format!("pub fn matches(&self, item: &{}) -> bool {{", typedef.name));
+        self.indent += 1;
+        if typedef.fields.is_empty() {
+            self.emit_line("let _ = item;");
+            self.emit_line("true");
+        }
+        for (i, field) in typedef.fields.iter().enumerate() {
+            let field_snake = to_snake(&field.name);
+            self.emit_line(&format!(
+                "{}self.{}.as_ref().map_or(true, |v| *v == item.{})",
+                if i > 0 { "&& " } else { "" },
+                field_snake,
+                field_snake
+            ));
+        }
         self.indent -= 1;
         self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+    }
+
+    /// `{Fn}Input` for a map-typed param. A field whose same-named type field has a
+    /// `:default` may be left out (`None`); `build` fills in the default.
+    fn emit_input_structs(&mut self, func: &FnDef, module: &Module) {
+        for param in &func.params {
+            if let TypeExpr::Map(fields) = &param.type_expr {
+                self.emit_line("#[derive(Debug, Clone)]");
+                self.emit_line(&format!("pub struct {}Input {{", to_pascal(&func.name)));
+                self.indent += 1;
+                for (name, type_expr) in fields {
+                    self.emit_line(&format!("pub {}: {},", to_snake(name), input_field_type(name, type_expr, module)));
+                }
+                self.indent -= 1;
+                self.emit_line("}");
+                self.emit_line("");
+            }
+        }
     }
 
-    fn emit_effect_trait(&mut self, effect_set: &EffectSetDef) {
+    /// Stores holding a type defined in this module use it as their item and a generated
+    /// `{Store}Query`; otherwise both are associated types, as is every store's error type.
+    fn emit_effect_trait(&mut self, effect_set: &EffectSetDef, stores: &[StoreInfo]) {
         let trait_name = to_pascal(&effect_set.name);
         self.emit_line(&format!(
             "/// Effect set: {} — {:?}",
@@ -154,25 +605,50 @@ impl RustCodegen {
         self.emit_line(&format!("pub trait {} {{", trait_name));
         self.indent += 1;
 
+        let mut associated: Vec<String> = Vec::new();
+        for effect in &effect_set.effects {
+            let store_pascal = to_pascal(&effect.target);
+            let known = find_store(stores, &effect.target).is_some_and(|s| s.type_def.is_some());
+            let mut wanted = Vec::new();
+            match effect.kind {
+                EffectKind::Reads if !known => {
+                    wanted.push(format!("type {}Item;", store_pascal));
+                    wanted.push(format!("type {}Query;", store_pascal));
+                }
+                EffectKind::Writes => {
+                    if !known {
+                        wanted.push(format!("type {}Item;", store_pascal));
+                    }
+                    wanted.push(format!("type {}Error: Into<StoreError>;", store_pascal));
+                }
+                _ => {}
+            }
+            for decl in wanted {
+                if !associated.contains(&decl) {
+                    self.emit_line(&decl);
+                    associated.push(decl);
+                }
+            }
+        }
+
         for effect in &effect_set.effects {
+            let (item, query) = store_types(stores, &effect.target, "Self::");
             match effect.kind {
                 EffectKind::Reads => {
-                    let store_pascal = to_pascal(&effect.target);
                     self.emit_line(&format!(
-                        "fn query_{}<Q>(&self, query: Q) -> Option<{}Item> where Q: Into<{}Query>;",
+                        "fn query_{}(&self, query: {}) -> Option<{}>;",
                         to_snake(&effect.target),
-                        store_pascal,
-                        store_pascal
+                        query,
+                        item
                     ));
                 }
                 EffectKind::Writes => {
-                    let store_pascal = to_pascal(&effect.target);
                     self.emit_line(&format!(
-                        "fn insert_{}(&mut self, item: {}Item) -> Result<{}Item, {}Error>;",
+                        "fn insert_{}(&mut self, item: {}) -> Result<{}, Self::{}Error>;",
                         to_snake(&effect.target),
-                        store_pascal,
-                        store_pascal,
-                        store_pascal
+                        item,
+                        item,
+                        to_pascal(&effect.target)
                     ));
                 }
                 EffectKind::Sends => {
@@ -188,7 +664,123 @@ impl RustCodegen {
         self.emit_line("}");
     }
 
-    fn emit_return_enum(&mut self, func: &FnDef) {
+    /// `InMemoryContext`: a `Vec` per store and per `:sends` target, implementing every
+    /// effect trait whose stores hold a type defined in this module.
+    fn emit_in_memory_context(&mut self, module: &Module, stores: &[StoreInfo]) {
+        let mut sends: Vec<&str> = Vec::new();
+        for effect_set in &module.effect_sets {
+            for effect in &effect_set.effects {
+                if matches!(effect.kind, EffectKind::Sends) && !sends.contains(&effect.target.as_str()) {
+                    sends.push(&effect.target);
+                }
+            }
+        }
+
+        self.emit_line("/// In-memory reference implementation of the effect traits, for tests and prototyping.");
+        self.emit_line("#[derive(Debug, Default)]");
+        self.emit_line("pub struct InMemoryContext {");
+        self.indent += 1;
+        for store in stores {
+            if let Some(typedef) = &store.type_def {
+                self.emit_line(&format!("pub {}: Vec<{}>,", to_snake(&store.target), typedef.name));
+            }
+        }
+        for target in &sends {
+            self.emit_line(&format!("/// Payloads sent to `{}`", target));
+            self.emit_line(&format!("pub {}: Vec<Vec<u8>>,", to_snake(target)));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+
+        for effect_set in &module.effect_sets {
+            let trait_name = to_pascal(&effect_set.name);
+            if let Some(effect) = effect_set.effects.iter().find(|e| {
+                !matches!(e.kind, EffectKind::Sends)
+                    && find_store(stores, &e.target).is_none_or(|s| s.type_def.is_none())
+            }) {
+                self.emit_line(&format!(
+                    "// No in-memory {}: {} holds no type defined in this module",
+                    trait_name, effect.target
+                ));
+                self.emit_line("");
+                continue;
+            }
+
+            self.emit_line(&format!("impl {} for InMemoryContext {{", trait_name));
+            self.indent += 1;
+            let mut errors: Vec<&str> = Vec::new();
+            for effect in &effect_set.effects {
+                if matches!(effect.kind, EffectKind::Writes) && !errors.contains(&effect.target.as_str()) {
+                    self.emit_line(&format!("type {}Error = StoreError;", to_pascal(&effect.target)));
+                    errors.push(&effect.target);
+                }
+            }
+            for effect in &effect_set.effects {
+                let store = to_snake(&effect.target);
+                let (item, query) = store_types(stores, &effect.target, "Self::");
+                self.emit_line("");
+                match effect.kind {
+                    EffectKind::Reads => {
+                        self.emit_line(&format!(
+                            "fn query_{}(&self, query: {}) -> Option<{}> {{",
+                            store, query, item
+                        ));
+                        self.emit_line(&format!(
+                            "    self.{}.iter().find(|item| query.matches(item)).cloned()",
+                            store
+                        ));
+                        self.emit_line("}");
+                    }
+                    EffectKind::Writes => {
+                        self.emit_line(&format!(
+                            "fn insert_{}(&mut self, item: {}) -> Result<{}, StoreError> {{",
+                            store, item, item
+                        ));
+                        self.indent += 1;
+                        let unique = find_store(stores, &effect.target)
+                            .and_then(|s| s.type_def.as_ref())
+                            .map(|t| {
+                                t.fields
+                                    .iter()
+                                    .filter(|f| f.unique_within.as_deref() == Some(effect.target.as_str()))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+                        for field in unique {
+                            let field_snake = to_snake(&field.name);
+                            self.emit_line(&format!(
+                                "if self.{}.iter().any(|e| e.{} == item.{}) {{",
+                                store, field_snake, field_snake
+                            ));
+                            self.emit_line(&format!(
+                                "    return Err(StoreError::UniqueViolation {{ field: \"{}\".into() }});",
+                                field.name
+                            ));
+                            self.emit_line("}");
+                        }
+                        self.emit_line(&format!("self.{}.push(item.clone());", store));
+                        self.emit_line("Ok(item)");
+                        self.indent -= 1;
+                        self.emit_line("}");
+                    }
+                    EffectKind::Sends => {
+                        self.emit_line(&format!(
+                            "fn send_{}(&mut self, payload: impl Into<Vec<u8>>) {{",
+                            store
+                        ));
+                        self.emit_line(&format!("    self.{}.push(payload.into());", store));
+                        self.emit_line("}");
+                    }
+                }
+            }
+            self.indent -= 1;
+            self.emit_line("}");
+            self.emit_line("");
+        }
+    }
+
+    fn emit_return_enum(&mut self, func: &FnDef, module: &Module) {
         let enum_name = format!("{}Result", to_pascal(&func.name));
 
         if let Some(ref prov) = func.provenance {
@@ -238,11 +830,30 @@ impl RustCodegen {
                         self.emit_line(&format!("/// HTTP {}", status));
                     }
                     let variant_name = to_pascal(tag);
-                    let payload_type = type_expr_to_rust(payload);
-                    if payload_type == "()" {
-                        self.emit_line(&format!("{},", variant_name));
-                    } else {
-                        self.emit_line(&format!("{}({}),", variant_name, payload_type));
+                    match payload {
+                        TypeExpr::Map(fields) if fields.is_empty() => {
+                            self.emit_line(&format!("{} {{}},", variant_name));
+                        }
+                        TypeExpr::Map(fields) => {
+                            let fields: Vec<String> = fields
+                                .iter()
+                                .map(|(name, type_expr)| {
+                                    format!(
+                                        "{}: {}",
+                                        to_snake(name),
+                                        resolve_field_type(name, type_expr, module, func)
+                                    )
+                                })
+                                .collect();
+                            self.emit_line(&format!("{} {{ {} }},", variant_name, fields.join(", ")));
+                        }
+                        TypeExpr::Named(name) if name == "Unit" => {
+                            self.emit_line(&format!("{},", variant_name));
+                        }
+                        _ => {
+                            let payload_type = type_expr_to_rust(payload);
+                            self.emit_line(&format!("{}({}),", variant_name, payload_type));
+                        }
                     }
                 }
             }
@@ -273,19 +884,12 @@ impl RustCodegen {
                     ..
                 } => {
                     let status = http_status.unwrap_or(500);
-                    let variant_name = to_pascal(tag);
-                    let payload_type = type_expr_to_rust(payload);
-                    if payload_type == "()" {
-                        self.emit_line(&format!(
-                            "{}::{} => {},",
-                            enum_name, variant_name, status
-                        ));
-                    } else {
-                        self.emit_line(&format!(
-                            "{}::{}(_) => {},",
-                            enum_name, variant_name, status
-                        ));
-                    }
+                    self.emit_line(&format!(
+                        "{}::{} => {},",
+                        enum_name,
+                        variant_pattern(tag, payload, "_"),
+                        status
+                    ));
                 }
             }
         }
@@ -315,17 +919,16 @@ impl RustCodegen {
                     ));
                 }
                 VariantKind::Err { tag, payload, .. } => {
-                    let variant_name = to_pascal(tag);
-                    let payload_type = type_expr_to_rust(payload);
-                    if payload_type == "()" {
+                    let pattern = variant_pattern(tag, payload, "v");
+                    if pattern.ends_with("(v)") {
                         self.emit_line(&format!(
-                            "{}::{} => write!(f, \"Error: {}\"),",
-                            enum_name, variant_name, tag
+                            "{}::{} => write!(f, \"Error({}): {{:?}}\", v),",
+                            enum_name, pattern, tag
                         ));
                     } else {
                         self.emit_line(&format!(
-                            "{}::{}(v) => write!(f, \"Error({}): {{:?}}\", v),",
-                            enum_name, variant_name, tag
+                            "{}::{} => write!(f, \"Error: {}\"),",
+                            enum_name, pattern, tag
                         ));
                     }
                 }
@@ -340,7 +943,7 @@ impl RustCodegen {
         self.emit_line("}");
     }
 
-    fn emit_function(&mut self, func: &FnDef, module: &Module) {
+    fn emit_function(&mut self, func: &FnDef, module: &Module, stores: &[StoreInfo], enums: &[EnumDef]) {
         let fn_name = to_snake(&func.name);
         let return_type = format!("{}Result", to_pascal(&func.name));
 
@@ -353,11 +956,20 @@ impl RustCodegen {
 
         // Build parameter list
         let mut params = Vec::new();
-        if !trait_bounds.is_empty() {
-            params.push(format!("ctx: &mut Ctx"));
+        if !trait_bounds.is_empty() && touches_store(&func.body) {
+            params.push("ctx: &mut Ctx".to_string());
+        } else if !trait_bounds.is_empty() {
+            params.push("_ctx: &mut Ctx".to_string());
+        }
+        if externs::needs_externs(&func.body, module) {
+            params.push("externs: &impl Externs".to_string());
         }
         for param in &func.params {
-            let rust_type = type_expr_to_rust(&param.type_expr);
+            let rust_type = match &param.type_expr {
+                TypeExpr::Map(_) => format!("{}Input", to_pascal(&func.name)),
+                _ if is_text_uuid(func, &param.name) => "&str".to_string(),
+                other => type_expr_to_rust(other),
+            };
             params.push(format!("{}: {}", to_snake(&param.name), rust_type));
         }
 
@@ -420,37 +1032,95 @@ impl RustCodegen {
             return_type
         ));
         self.indent += 1;
-        self.emit_expr(&func.body, &return_type);
+        let ctx = EmitCtx {
+            return_type: &return_type,
+            func,
+            module,
+            stores,
+            enums,
+        };
+        self.emit_expr(&func.body, &ctx);
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// `(extern verify-password [String String] (result Unit Unit))` →
+    /// `fn verify_password(&self, arg1: String, arg2: String) -> Result<(), ()>`
+    fn emit_externs_trait(&mut self, module: &Module) {
+        self.emit_line("/// Functions the host application implements, one per `extern`.");
+        self.emit_line("pub trait Externs {");
+        self.indent += 1;
+        for ext in &module.externs {
+            let mut params = vec!["&self".to_string()];
+            for (i, param) in ext.params.iter().enumerate() {
+                params.push(format!("arg{}: {}", i + 1, type_expr_to_rust(param)));
+            }
+            self.emit_line(&format!(
+                "fn {}({}) -> {};",
+                to_snake(&ext.name.replace(['?', '!'], "")),
+                params.join(", "),
+                type_expr_to_rust(&ext.returns)
+            ));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// `(defn token-expired? [s Session] Bool ...)` → `pub fn token_expired(s: Session) -> bool`
+    fn emit_helper(&mut self, helper: &HelperDef, module: &Module, stores: &[StoreInfo], enums: &[EnumDef]) {
+        let mut params: Vec<String> = Vec::new();
+        if externs::needs_externs(&helper.body, module) {
+            params.push("externs: &impl Externs".to_string());
+        }
+        for param in &helper.params {
+            params.push(format!("{}: {}", to_snake(&param.name), type_expr_to_rust(&param.type_expr)));
+        }
+        self.emit_line(&format!(
+            "pub fn {}({}) -> {} {{",
+            to_snake(&helper.name.replace(['?', '!'], "")),
+            params.join(", "),
+            type_expr_to_rust(&helper.returns)
+        ));
+        self.indent += 1;
+        let func = helper.as_fn();
+        let ctx = EmitCtx {
+            return_type: "",
+            func: &func,
+            module,
+            stores,
+            enums,
+        };
+        self.emit_expr(&helper.body, &ctx);
         self.indent -= 1;
         self.emit_line("}");
     }
 
-    fn emit_expr(&mut self, expr: &Expr, return_type: &str) {
+    fn emit_expr(&mut self, expr: &Expr, ctx: &EmitCtx) {
         match expr {
             Expr::Let {
                 bindings, body, ..
             } => {
                 for (name, value) in bindings {
                     self.emit_indent();
-                    self.output.push_str(&format!("let {} = ", to_snake(name)));
-                    self.emit_expr_inline(value, return_type);
-                    self.output.push_str(";\n");
+                    self.emit_binding(name, value, ctx);
                 }
-                self.emit_expr(body, return_type);
+                self.emit_expr(body, ctx);
             }
             Expr::Match { expr, arms, .. } => {
                 self.emit_indent();
                 self.output.push_str("match ");
-                self.emit_expr_inline(expr, return_type);
+                self.emit_expr_inline(expr, ctx);
                 self.output.push_str(" {\n");
                 self.indent += 1;
+                let enum_def = enums::match_enum(expr, arms, ctx.module, ctx.func, ctx.enums);
                 for arm in arms {
                     self.emit_indent();
-                    self.emit_pattern(&arm.pattern);
+                    self.emit_pattern(&arm.pattern, enum_def);
                     self.output.push_str(" => ");
-                    self.emit_expr_inline(&arm.body, return_type);
+                    self.emit_expr_inline(&arm.body, ctx);
                     self.output.push_str(",\n");
                 }
+                self.emit_store_error_arm(arms, ctx);
                 self.indent -= 1;
                 self.emit_indent();
                 self.output.push_str("}\n");
@@ -463,69 +1133,92 @@ impl RustCodegen {
             } => {
                 self.emit_indent();
                 self.output.push_str("if ");
-                self.emit_expr_inline(cond, return_type);
+                self.emit_expr_inline(cond, ctx);
                 self.output.push_str(" {\n");
                 self.indent += 1;
-                self.emit_expr(then_branch, return_type);
+                self.emit_expr(then_branch, ctx);
                 self.indent -= 1;
                 self.emit_indent();
                 self.output.push_str("} else {\n");
                 self.indent += 1;
-                self.emit_expr(else_branch, return_type);
+                self.emit_expr(else_branch, ctx);
                 self.indent -= 1;
                 self.emit_indent();
                 self.output.push_str("}\n");
             }
             _ => {
                 self.emit_indent();
-                self.emit_expr_inline(expr, return_type);
+                self.emit_expr_inline(expr, ctx);
                 self.output.push('\n');
             }
         }
     }
 
-    fn emit_expr_inline(&mut self, expr: &Expr, return_type: &str) {
+    fn emit_expr_inline(&mut self, expr: &Expr, ctx: &EmitCtx) {
         match expr {
             Expr::Ref(name, _) => {
                 self.output.push_str(&to_snake(name));
             }
-            Expr::Keyword(kw, _) => {
-                self.output.push_str(&format!("\"{}\"", kw));
-            }
+            Expr::Keyword(kw, _) => match enums::keyword_enum(kw, ctx.enums) {
+                Some(def) => {
+                    self.output.push_str(&format!("{}::{}", def.name, EnumDef::variant_ident(kw)));
+                }
+                None => self.output.push_str(&format!("\"{}\"", kw)),
+            },
             Expr::StringLit(s, _) => {
                 self.output.push_str(&format!("\"{}\"", s));
             }
             Expr::IntLit(n, _) => {
                 self.output.push_str(&n.to_string());
             }
+            Expr::FloatLit(n, _) => {
+                self.output.push_str(&format!("{:?}", n));
+            }
             Expr::BoolLit(b, _) => {
                 self.output.push_str(&b.to_string());
             }
             Expr::Ok(inner, _) => {
-                self.output.push_str(&format!("{}::Ok(", return_type));
-                self.emit_expr_inline(inner, return_type);
+                self.output.push_str(&format!("{}::Ok(", ctx.return_type));
+                self.emit_expr_inline(inner, ctx);
                 self.output.push(')');
             }
             Expr::Err { tag, payload, .. } => {
                 let variant = to_pascal(tag);
-                self.output
-                    .push_str(&format!("{}::{}(", return_type, variant));
-                self.emit_expr_inline(payload, return_type);
-                self.output.push(')');
-            }
-            Expr::Call { name, args, .. } => {
-                self.output.push_str(&to_snake(name));
-                self.output.push('(');
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
+                let declared = ctx.func.returns.variants.iter().find_map(|v| match &v.kind {
+                    VariantKind::Err { tag: t, payload, .. } if t == tag => Some(payload),
+                    _ => None,
+                });
+                match (declared, payload.as_ref()) {
+                    // Named fields, converted to the types the variant declares
+                    (Some(TypeExpr::Map(fields)), Expr::MapLit(entries, _)) => {
+                        self.output.push_str(&format!("{}::{} {{", ctx.return_type, variant));
+                        for (i, (key, val)) in entries.iter().enumerate() {
+                            let field_type = fields
+                                .iter()
+                                .find(|(n, _)| n == key)
+                                .map(|(n, t)| resolve_field_type(n, t, ctx.module, ctx.func))
+                                .unwrap_or_default();
+                            self.output.push_str(if i > 0 { ", " } else { " " });
+                            self.output.push_str(&format!("{}: ", to_snake(key)));
+                            self.emit_value(val, &field_type, ctx);
+                        }
+                        self.output.push_str(" }");
+                    }
+                    (Some(TypeExpr::Named(name)), _) if name == "Unit" => {
+                        self.output.push_str(&format!("{}::{}", ctx.return_type, variant));
+                    }
+                    _ => {
+                        self.output.push_str(&format!("{}::{}(", ctx.return_type, variant));
+                        self.emit_expr_inline(payload, ctx);
+                        self.output.push(')');
                     }
-                    self.emit_expr_inline(arg, return_type);
                 }
-                self.output.push(')');
+            }
+            Expr::Call { name, args, .. } => {
+                self.emit_call(name, args, ctx);
             }
             Expr::FieldAccess { expr, field, .. } => {
-                self.emit_expr_inline(expr, return_type);
+                self.emit_expr_inline(expr, ctx);
                 self.output.push('.');
                 self.output.push_str(&to_snake(field));
             }
@@ -538,65 +1231,431 @@ impl RustCodegen {
                     }
                     self.output.push_str(&to_snake(key));
                     self.output.push_str(": ");
-                    self.emit_expr_inline(val, return_type);
+                    self.emit_expr_inline(val, ctx);
+                }
+                self.output.push_str(" }");
+            }
+            Expr::ListLit(items, _) => {
+                self.output.push_str("vec![");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.emit_expr_inline(item, ctx);
+                }
+                self.output.push(']');
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.emit_closure(params, body, ctx);
+            }
+            // (for-each [x xs] body) → for x in xs.iter().cloned() { let _ = body; }, or a
+            // match that returns the first store error when the body writes
+            Expr::ForEach { var, list, body, .. } => {
+                self.output.push_str(&format!("for {} in ", to_snake(var)));
+                self.emit_expr_inline(list, ctx);
+                if is_store_write(body) {
+                    // The first failed write ends the function like a let-bound one
+                    self.output.push_str(".iter().cloned() { match ");
+                    self.emit_expr_inline(body, ctx);
+                    self.output.push_str(&format!(" {{ Ok(_) => {{}} {} }} }}", store_error_return(ctx)));
+                } else {
+                    self.output.push_str(".iter().cloned() { let _ = ");
+                    self.emit_expr_inline(body, ctx);
+                    self.output.push_str("; }");
+                }
+            }
+            Expr::Wildcard(_) => {
+                self.output.push('_');
+            }
+            Expr::Let {
+                bindings, body, ..
+            } => {
+                self.output.push_str("{\n");
+                self.indent += 1;
+                for (name, value) in bindings {
+                    self.emit_indent();
+                    self.emit_binding(name, value, ctx);
                 }
+                self.emit_indent();
+                self.emit_expr_inline(body, ctx);
+                self.output.push('\n');
+                self.indent -= 1;
+                self.emit_indent();
+                self.output.push('}');
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.output.push_str("match ");
+                self.emit_expr_inline(expr, ctx);
+                self.output.push_str(" {\n");
+                self.indent += 1;
+                let enum_def = enums::match_enum(expr, arms, ctx.module, ctx.func, ctx.enums);
+                for arm in arms {
+                    self.emit_indent();
+                    self.emit_pattern(&arm.pattern, enum_def);
+                    self.output.push_str(" => ");
+                    self.emit_expr_inline(&arm.body, ctx);
+                    self.output.push_str(",\n");
+                }
+                self.emit_store_error_arm(arms, ctx);
+                self.indent -= 1;
+                self.emit_indent();
+                self.output.push('}');
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.output.push_str("if ");
+                self.emit_expr_inline(cond, ctx);
+                self.output.push_str(" { ");
+                self.emit_expr_inline(then_branch, ctx);
+                self.output.push_str(" } else { ");
+                self.emit_expr_inline(else_branch, ctx);
                 self.output.push_str(" }");
             }
-            Expr::Wildcard(_) => {
-                self.output.push('_');
+        }
+    }
+
+    /// An expression stored into a field of type `rust_type`: text is converted with
+    /// `.to_string()`, and fields read off other values are cloned.
+    fn emit_value(&mut self, expr: &Expr, rust_type: &str, ctx: &EmitCtx) {
+        self.emit_expr_inline(expr, ctx);
+        match expr {
+            Expr::StringLit(..) => self.output.push_str(".to_string()"),
+            Expr::Ref(..) | Expr::FieldAccess { .. } if rust_type == "String" => {
+                self.output.push_str(".to_string()")
+            }
+            Expr::FieldAccess { .. } => self.output.push_str(".clone()"),
+            _ => {}
+        }
+    }
+
+    fn emit_call(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        // Operators first: cleaning the name would turn `!=` into `=`
+        if let Some((_, op)) = operators::operator(name) {
+            self.emit_operator(op, args, ctx);
+            return;
+        }
+        if ctx.module.externs.iter().any(|e| e.name == name) {
+            self.output.push_str(&format!("externs.{}(", to_snake(&name.replace(['?', '!'], ""))));
+            self.emit_owned_args(args, ctx);
+            self.output.push(')');
+            return;
+        }
+        if ctx.module.helpers.iter().any(|h| h.name == name) {
+            self.emit_helper_call(name, args, ctx);
+            return;
+        }
+        let clean_name = name.replace(['?', '!'], "");
+        match clean_name.as_str() {
+            // query(user-store, {id: uuid}) → DbRead::query_user_store(ctx, UserStoreQuery { .. })
+            "query" => {
+                let target = match args.first() {
+                    Some(Expr::Ref(target, _)) => target.as_str(),
+                    _ => "",
+                };
+                let typedef = find_store(ctx.stores, target).and_then(|s| s.type_def.as_ref());
+                self.output.push_str(&format!(
+                    "{}::query_{}(ctx, ",
+                    effect_trait(ctx, EffectKind::Reads, target),
+                    to_snake(target)
+                ));
+                match (typedef, args.get(1)) {
+                    (Some(typedef), Some(Expr::MapLit(entries, _))) => {
+                        self.output.push_str(&format!("{}Query {{ ", to_pascal(target)));
+                        for (key, val) in entries {
+                            let field_type = typedef
+                                .fields
+                                .iter()
+                                .find(|f| f.name == *key)
+                                .map(|f| type_expr_to_rust(&f.type_expr))
+                                .unwrap_or_default();
+                            self.output.push_str(&format!("{}: Some(", to_snake(key)));
+                            self.emit_value(val, &field_type, ctx);
+                            self.output.push_str("), ");
+                        }
+                        self.output.push_str("..Default::default() })");
+                    }
+                    (_, query) => {
+                        if let Some(query) = query {
+                            self.emit_expr_inline(query, ctx);
+                        }
+                        self.output.push(')');
+                    }
+                }
+            }
+            // insert!(user-store, x) → DbWrite::insert_user_store(ctx, x), with the error as a StoreError
+            "insert" => {
+                let target = match args.first() {
+                    Some(Expr::Ref(target, _)) => target.as_str(),
+                    _ => "",
+                };
+                let checks = ref_checks(ctx, target);
+                if !checks.is_empty() {
+                    // { let item = x; if <missing ref> { Err(..) } else { insert } }
+                    self.output.push_str("{ let item = ");
+                    if let Some(item) = args.get(1) {
+                        self.emit_expr_inline(item, ctx);
+                    }
+                    self.output.push_str("; ");
+                    for (field, missing) in &checks {
+                        self.output.push_str(&format!(
+                            "if {} {{ Err(StoreError::MissingReference {{ field: \"{}\".into() }}) }} else ",
+                            missing, field
+                        ));
+                    }
+                    self.output.push_str(&format!(
+                        "{{ {}::insert_{}(ctx, item).map_err(Into::<StoreError>::into) }} }}",
+                        effect_trait(ctx, EffectKind::Writes, target),
+                        to_snake(target)
+                    ));
+                    return;
+                }
+                self.output.push_str(&format!(
+                    "{}::insert_{}(ctx, ",
+                    effect_trait(ctx, EffectKind::Writes, target),
+                    to_snake(target)
+                ));
+                if let Some(item) = args.get(1) {
+                    self.emit_expr_inline(item, ctx);
+                }
+                self.output.push_str(").map_err(Into::<StoreError>::into)");
+            }
+            // build(User, input) → User { name: input.name.clone(), .. }
+            "build" => match (args.first(), args.get(1)) {
+                (Some(Expr::Ref(type_name, _)), Some(source)) => {
+                    self.emit_build(type_name, source, ctx);
+                }
+                _ => self.emit_plain_call(&clean_name, args, ctx),
+            },
+            // validate-against(User, input) → User { .. }.validate()
+            "validate-against" => match (args.first(), args.get(1)) {
+                (Some(Expr::Ref(type_name, _)), Some(source)) => {
+                    self.emit_build(type_name, source, ctx);
+                    self.output.push_str(".validate()");
+                }
+                _ => self.emit_plain_call(&clean_name, args, ctx),
+            },
+            // map(f, xs) → xs.iter().cloned().map(f).collect::<Vec<_>>(), and likewise
+            // filter and fold
+            "map" | "filter" | "fold" if args.len() == if clean_name == "fold" { 3 } else { 2 } => {
+                self.emit_iteration(&clean_name, args, ctx);
+            }
+            // non-empty?(errors) → non_empty(&errors)
+            "non-empty" => {
+                self.output.push_str("non_empty(&");
+                if let Some(arg) = args.first() {
+                    self.emit_expr_inline(arg, ctx);
+                }
+                self.output.push(')');
+            }
+            _ => self.emit_plain_call(&clean_name, args, ctx),
+        }
+    }
+
+    /// `(- a b)` → `a - b` and `(not a)` → `!a`, with operands that are operators
+    /// themselves in parentheses
+    fn emit_operator(&mut self, op: &str, args: &[Expr], ctx: &EmitCtx) {
+        if let [operand] = args {
+            self.output.push_str(op);
+            self.emit_operand(operand, ctx);
+            return;
+        }
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(&format!(" {} ", op));
+            }
+            self.emit_operand(arg, ctx);
+        }
+    }
+
+    fn emit_operand(&mut self, arg: &Expr, ctx: &EmitCtx) {
+        if operators::is_operator_call(arg) {
+            self.output.push('(');
+            self.emit_expr_inline(arg, ctx);
+            self.output.push(')');
+        } else {
+            self.emit_expr_inline(arg, ctx);
+        }
+    }
+
+    /// An iterator chain over the last argument, which is cloned element by element
+    /// so the list stays usable afterwards
+    fn emit_iteration(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        let (f, list) = (&args[0], &args[args.len() - 1]);
+        self.emit_expr_inline(list, ctx);
+        self.output.push_str(".iter().cloned()");
+        match (name, f) {
+            ("map", _) => {
+                self.output.push_str(".map(");
+                self.emit_expr_inline(f, ctx);
+                self.output.push_str(").collect::<Vec<_>>()");
+            }
+            // The predicate sees `&T`; bind an owned copy so its body reads like map's
+            ("filter", Expr::Lambda { params, body, .. }) => {
+                let param = params.first().map(|p| to_snake(p)).unwrap_or_else(|| "_".to_string());
+                self.output.push_str(&format!(".filter(|{0}| {{ let {0} = {0}.clone(); ", param));
+                self.emit_expr_inline(body, ctx);
+                self.output.push_str(" }).collect::<Vec<_>>()");
             }
-            Expr::Let {
-                bindings, body, ..
-            } => {
-                self.output.push_str("{\n");
-                self.indent += 1;
-                for (name, value) in bindings {
-                    self.emit_indent();
-                    self.output.push_str(&format!("let {} = ", to_snake(name)));
-                    self.emit_expr_inline(value, return_type);
-                    self.output.push_str(";\n");
-                }
-                self.emit_indent();
-                self.emit_expr_inline(body, return_type);
-                self.output.push('\n');
-                self.indent -= 1;
-                self.emit_indent();
-                self.output.push('}');
+            ("filter", _) => {
+                self.output.push_str(".filter(|item| ");
+                self.emit_expr_inline(f, ctx);
+                self.output.push_str("(item.clone())).collect::<Vec<_>>()");
             }
-            Expr::Match { expr, arms, .. } => {
-                self.output.push_str("match ");
-                self.emit_expr_inline(expr, return_type);
-                self.output.push_str(" {\n");
-                self.indent += 1;
-                for arm in arms {
-                    self.emit_indent();
-                    self.emit_pattern(&arm.pattern);
-                    self.output.push_str(" => ");
-                    self.emit_expr_inline(&arm.body, return_type);
-                    self.output.push_str(",\n");
-                }
-                self.indent -= 1;
-                self.emit_indent();
-                self.output.push('}');
+            _ => {
+                self.output.push_str(".fold(");
+                self.emit_expr_inline(&args[1], ctx);
+                self.output.push_str(", ");
+                self.emit_expr_inline(f, ctx);
+                self.output.push(')');
             }
-            Expr::If {
-                cond,
-                then_branch,
-                else_branch,
-                ..
-            } => {
-                self.output.push_str("if ");
-                self.emit_expr_inline(cond, return_type);
-                self.output.push_str(" { ");
-                self.emit_expr_inline(then_branch, return_type);
-                self.output.push_str(" } else { ");
-                self.emit_expr_inline(else_branch, return_type);
-                self.output.push_str(" }");
+        }
+    }
+
+    /// `(fn [a b] body)` → `|a, b| body`
+    fn emit_closure(&mut self, params: &[String], body: &Expr, ctx: &EmitCtx) {
+        let params: Vec<String> = params.iter().map(|p| to_snake(p)).collect();
+        self.output.push_str(&format!("|{}| ", params.join(", ")));
+        self.emit_expr_inline(body, ctx);
+    }
+
+    /// `(token-expired? s)` → `token_expired(s.clone())`, passing `externs` first to
+    /// helpers that reach an extern
+    fn emit_helper_call(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        self.output.push_str(&to_snake(&name.replace(['?', '!'], "")));
+        self.output.push('(');
+        let helper = ctx.module.helpers.iter().find(|h| h.name == name);
+        if helper.is_some_and(|h| externs::needs_externs(&h.body, ctx.module)) {
+            self.output.push_str(if args.is_empty() { "externs" } else { "externs, " });
+        }
+        self.emit_owned_args(args, ctx);
+        self.output.push(')');
+    }
+
+    /// Arguments to helpers and externs, which take them by value: the caller may still
+    /// need what it passes, so names and fields are cloned, and text literals owned
+    fn emit_owned_args(&mut self, args: &[Expr], ctx: &EmitCtx) {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
             }
+            self.emit_expr_inline(arg, ctx);
+            match arg {
+                Expr::Ref(..) | Expr::FieldAccess { .. } => self.output.push_str(".clone()"),
+                Expr::StringLit(..) => self.output.push_str(".to_string()"),
+                _ => {}
+            }
+        }
+    }
+
+    fn emit_plain_call(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        self.output.push_str(&to_snake(name));
+        self.output.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.emit_expr_inline(arg, ctx);
+        }
+        self.output.push(')');
+    }
+
+    /// A struct literal for `type_name`, with fields taken from a map literal or an input
+    /// param. Generated fields get fresh values; anything else missing is defaulted.
+    fn emit_build(&mut self, type_name: &str, source: &Expr, ctx: &EmitCtx) {
+        let Some(typedef) = ctx.module.types.iter().find(|t| t.name == type_name) else {
+            self.output.push_str(&format!("{}::from(", type_name));
+            self.emit_expr_inline(source, ctx);
+            self.output.push(')');
+            return;
+        };
+        self.output.push_str(&format!("{} {{", type_name));
+        for (i, field) in typedef.fields.iter().enumerate() {
+            let field_snake = to_snake(&field.name);
+            let rust_type = field_type_to_rust(field, typedef, ctx.module);
+            self.output.push_str(if i > 0 { ", " } else { " " });
+            self.output.push_str(&format!("{}: ", field_snake));
+            let from_map = match source {
+                Expr::MapLit(entries, _) => entries.iter().find(|(k, _)| *k == field.name).map(|(_, v)| v),
+                _ => None,
+            };
+            // The input param and the type it gives this field
+            let from_input = match source {
+                Expr::Ref(param, _) => ctx.func.params.iter().find_map(|p| match &p.type_expr {
+                    TypeExpr::Map(fields) if p.name == *param => {
+                        fields.iter().find(|(n, _)| *n == field.name).map(|(_, t)| (param, t))
+                    }
+                    _ => None,
+                }),
+                _ => None,
+            };
+            if let (Some(value), TypeExpr::Ref(target)) = (from_map, &field.type_expr) {
+                // A UUID (or an id already typed) becomes the typed id
+                self.output.push_str(&format!("{}::from(", refs::id_type(target)));
+                self.emit_value(value, &rust_type, ctx);
+                self.output.push(')');
+            } else if let Some(value) = from_map {
+                self.emit_value(value, &rust_type, ctx);
+            } else if let Some((param, input_type)) = from_input {
+                let input_optional =
+                    matches!(input_type, TypeExpr::Optional(_)) || input_defaulted(&field.name, ctx.module);
+                let input = typed_id_input(
+                    format!("{}.{}.clone()", to_snake(param), field_snake),
+                    input_type,
+                    input_optional,
+                    field,
+                );
+                let input = boxed_input(input, input_optional, generics::boxed(field, &typedef.name, ctx.module));
+                self.output.push_str(&fill_from_input(&input, input_optional, field));
+            } else if let Some(default) = &field.default {
+                self.output.push_str(&default_literal(default, &field.type_expr));
+            } else if field.generated && rust_type == "Uuid" {
+                self.output.push_str("Uuid::new_v4()");
+            } else {
+                self.output.push_str("Default::default()");
+            }
+        }
+        self.output.push_str(" }");
+    }
+
+    /// `let x = value;`. A bound `insert!` binds the stored row: a store error returns
+    /// the `:on-store-error` variant, or panics in functions that are not `:total`.
+    fn emit_binding(&mut self, name: &str, value: &Expr, ctx: &EmitCtx) {
+        self.output.push_str(&format!("let {} = ", to_snake(name)));
+        if !is_store_write(value) {
+            self.emit_expr_inline(value, ctx);
+            self.output.push_str(";\n");
+            return;
         }
+        self.output.push_str("match ");
+        self.emit_expr_inline(value, ctx);
+        self.output.push_str(&format!(" {{ Ok(row) => row, {} }};\n", store_error_return(ctx)));
     }
 
-    fn emit_pattern(&mut self, pattern: &Pattern) {
+    /// Arm for store errors no pattern matched: the declared `:on-store-error` variant,
+    /// or a panic in functions that are not `:total`.
+    fn emit_store_error_arm(&mut self, arms: &[MatchArm], ctx: &EmitCtx) {
+        if !needs_err_catchall(arms) {
+            return;
+        }
+        let arm = match &ctx.func.on_store_error {
+            Some(tag) => match store_error_arm(ctx.func, tag, ctx.return_type) {
+                Some(arm) => arm,
+                None => return,
+            },
+            None if !ctx.func.total => "Err(e) => panic!(\"unexpected store error: {:?}\", e)".to_string(),
+            None => return,
+        };
+        self.emit_indent();
+        self.output.push_str(&arm);
+        self.output.push_str(",\n");
+    }
+
+    /// `enum_def` is the enum the match is over, whose variants keyword patterns name.
+    fn emit_pattern(&mut self, pattern: &Pattern, enum_def: Option<&EnumDef>) {
         match pattern {
             Pattern::Wildcard(_) => self.output.push('_'),
             Pattern::Var(name, _) => self.output.push_str(&to_snake(name)),
@@ -611,7 +1670,7 @@ impl RustCodegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg);
+                                self.emit_pattern(arg, enum_def);
                             }
                         }
                         self.output.push(')');
@@ -625,7 +1684,7 @@ impl RustCodegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg);
+                                self.emit_pattern(arg, enum_def);
                             }
                         }
                         self.output.push(')');
@@ -634,12 +1693,16 @@ impl RustCodegen {
                         self.output.push_str("Err(");
                         if args.is_empty() {
                             self.output.push('_');
+                        } else if let Some(Pattern::Keyword(kw, _)) = args.first() {
+                            // (err :unique-violation) matches a StoreError variant
+                            self.output
+                                .push_str(&format!("StoreError::{} {{ .. }}", to_pascal(kw)));
                         } else {
                             for (i, arg) in args.iter().enumerate() {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg);
+                                self.emit_pattern(arg, enum_def);
                             }
                         }
                         self.output.push(')');
@@ -655,16 +1718,19 @@ impl RustCodegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg);
+                                self.emit_pattern(arg, enum_def);
                             }
                             self.output.push(')');
                         }
                     }
                 }
             }
-            Pattern::Keyword(kw, _) => {
-                self.output.push_str(&format!("\"{}\"", kw));
-            }
+            Pattern::Keyword(kw, _) => match enum_def {
+                Some(def) if def.variants.contains(kw) => {
+                    self.output.push_str(&format!("{}::{}", def.name, EnumDef::variant_ident(kw)));
+                }
+                _ => self.output.push_str(&format!("\"{}\"", kw)),
+            },
         }
     }
 
@@ -685,12 +1751,232 @@ impl RustCodegen {
     }
 }
 
+struct EmitCtx<'a> {
+    return_type: &'a str,
+    func: &'a FnDef,
+    module: &'a Module,
+    stores: &'a [StoreInfo<'a>],
+    enums: &'a [EnumDef],
+}
+
+/// A store some effect set reads or writes.
+struct StoreInfo<'a> {
+    /// Effect target, e.g. "user-store"
+    target: String,
+    /// The module type it holds, when defined here
+    type_def: Option<&'a TypeDef>,
+}
+
+/// Every store named by an effect, matched to its type by `:unique-within` or by name.
+fn collect_stores(module: &Module) -> Vec<StoreInfo<'_>> {
+    let mut stores: Vec<StoreInfo> = Vec::new();
+    for effect_set in &module.effect_sets {
+        for effect in &effect_set.effects {
+            if matches!(effect.kind, EffectKind::Sends) || find_store(&stores, &effect.target).is_some() {
+                continue;
+            }
+            stores.push(StoreInfo {
+                target: effect.target.clone(),
+                type_def: naming::stored_type(&effect.target, module),
+            });
+        }
+    }
+    stores
+}
+
+fn find_store<'s, 'a>(stores: &'s [StoreInfo<'a>], target: &str) -> Option<&'s StoreInfo<'a>> {
+    stores.iter().find(|s| s.target == target)
+}
+
+/// (item, query) types of a store in a trait signature: the stored type and its query
+/// struct, or associated types prefixed with `assoc` (e.g. `Self::`).
+fn store_types(stores: &[StoreInfo], target: &str, assoc: &str) -> (String, String) {
+    let store_pascal = to_pascal(target);
+    match find_store(stores, target).and_then(|s| s.type_def) {
+        Some(typedef) => (typedef.name.clone(), format!("{}Query", store_pascal)),
+        None => (
+            format!("{}{}Item", assoc, store_pascal),
+            format!("{}{}Query", assoc, store_pascal),
+        ),
+    }
+}
+
+/// For each `(ref T)` field of the type inserted into `target` whose store the function
+/// reads: the field, and the condition under which `item` names a missing row.
+fn ref_checks(ctx: &EmitCtx, target: &str) -> Vec<(String, String)> {
+    let Some(typedef) = find_store(ctx.stores, target).and_then(|s| s.type_def) else {
+        return Vec::new();
+    };
+    refs::ref_fields(typedef)
+        .into_iter()
+        .filter_map(|(field, ref_type)| {
+            let store = refs::read_store(ctx.func, ctx.module, ref_type)?;
+            let missing = |id: &str| {
+                format!(
+                    "{}::query_{}(ctx, {}Query {{ id: Some({}.0), ..Default::default() }}).is_none()",
+                    effect_trait(ctx, EffectKind::Reads, store),
+                    to_snake(store),
+                    to_pascal(store),
+                    id
+                )
+            };
+            let value = format!("item.{}", to_snake(&field.name));
+            let condition = match field.type_expr {
+                TypeExpr::Optional(_) => format!("match {} {{ Some(id) => {}, None => false }}", value, missing("id")),
+                _ => missing(&value),
+            };
+            Some((field.name.clone(), condition))
+        })
+        .collect()
+}
+
+/// The first of a function's effect traits that declares `kind` on `target`. Calls go
+/// through it by name, since several traits may declare the same method.
+fn effect_trait(ctx: &EmitCtx, kind: EffectKind, target: &str) -> String {
+    ctx.func
+        .effects
+        .iter()
+        .find(|name| {
+            ctx.module.effect_sets.iter().any(|es| {
+                es.name == **name && es.effects.iter().any(|e| e.kind == kind && e.target == target)
+            })
+        })
+        .map(|name| to_pascal(name))
+        .unwrap_or_else(|| "Ctx".to_string())
+}
+
+/// Whether a body reads or writes a store through `ctx`.
+fn touches_store(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call { name, args, .. } => {
+            matches!(name.as_str(), "query" | "insert!") || args.iter().any(touches_store)
+        }
+        Expr::Let { bindings, body, .. } => {
+            bindings.iter().any(|(_, value)| touches_store(value)) || touches_store(body)
+        }
+        Expr::Match { expr, arms, .. } => touches_store(expr) || arms.iter().any(|arm| touches_store(&arm.body)),
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => touches_store(cond) || touches_store(then_branch) || touches_store(else_branch),
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => touches_store(expr),
+        Expr::Err { payload, .. } => touches_store(payload),
+        Expr::MapLit(entries, _) => entries.iter().any(|(_, value)| touches_store(value)),
+        Expr::ListLit(items, _) => items.iter().any(touches_store),
+        Expr::Lambda { body, .. } => touches_store(body),
+        Expr::ForEach { list, body, .. } => touches_store(list) || touches_store(body),
+        _ => false,
+    }
+}
+
+/// A UUID param the body checks with `validate-uuid` arrives as text (`&str`); other
+/// UUID params were validated at the boundary and arrive as `Uuid`.
+fn is_text_uuid(func: &FnDef, param: &str) -> bool {
+    fn validates(expr: &Expr, param: &str) -> bool {
+        match expr {
+            Expr::Call { name, args, .. } => {
+                (name == "validate-uuid" && matches!(args.first(), Some(Expr::Ref(r, _)) if r == param))
+                    || args.iter().any(|a| validates(a, param))
+            }
+            Expr::Let { bindings, body, .. } => {
+                bindings.iter().any(|(_, value)| validates(value, param)) || validates(body, param)
+            }
+            Expr::Match { expr, arms, .. } => {
+                validates(expr, param) || arms.iter().any(|arm| validates(&arm.body, param))
+            }
+            Expr::If {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => validates(cond, param) || validates(then_branch, param) || validates(else_branch, param),
+            _ => false,
+        }
+    }
+    func.params
+        .iter()
+        .any(|p| p.name == param && matches!(&p.type_expr, TypeExpr::Named(n) if n == "UUID"))
+        && validates(&func.body, param)
+}
+
+/// `Variant`, `Variant { .. }` or `Variant(binding)`, by payload shape.
+fn variant_pattern(tag: &str, payload: &TypeExpr, binding: &str) -> String {
+    let variant = to_pascal(tag);
+    match payload {
+        TypeExpr::Map(_) => format!("{} {{ .. }}", variant),
+        TypeExpr::Named(name) if name == "Unit" => variant,
+        _ => format!("{}({})", variant, binding),
+    }
+}
+
+fn is_store_write(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call { name, .. } => name == "insert!",
+        Expr::Let { body, .. } => is_store_write(body),
+        _ => false,
+    }
+}
+
+/// `Err(e) => return ..` for a store error nothing matches: the `:on-store-error`
+/// variant, or a panic in functions that are not `:total`
+fn store_error_return(ctx: &EmitCtx) -> String {
+    ctx.func
+        .on_store_error
+        .as_ref()
+        .and_then(|tag| store_error_arm(ctx.func, tag, ctx.return_type))
+        .map(|arm| arm.replacen("=> ", "=> return ", 1))
+        .unwrap_or_else(|| "Err(e) => panic!(\"unexpected store error: {:?}\", e)".to_string())
+}
+
+/// Check if a match expression has Err patterns with keyword arguments (StoreError variants)
+/// and no `(err _)` arm, and thus needs a catch-all arm for exhaustiveness.
+fn needs_err_catchall(arms: &[MatchArm]) -> bool {
+    let err_arm = |catch_all: bool| {
+        arms.iter().any(|arm| match &arm.pattern {
+            Pattern::Constructor { name, args, .. } if name == "err" => {
+                let is_catch_all = args
+                    .iter()
+                    .all(|a| matches!(a, Pattern::Wildcard(_) | Pattern::Var(_, _)));
+                is_catch_all == catch_all
+            }
+            _ => false,
+        })
+    };
+    err_arm(false) && !err_arm(true)
+}
+
+/// `Err(e) => Result::Variant ..` for the variant named by `:on-store-error`.
+fn store_error_arm(func: &FnDef, tag: &str, return_type: &str) -> Option<String> {
+    let payload = func.returns.variants.iter().find_map(|v| match &v.kind {
+        VariantKind::Err { tag: t, payload, .. } if t == tag => Some(payload),
+        _ => None,
+    })?;
+    let variant = to_pascal(tag);
+    match payload {
+        TypeExpr::Map(fields) if fields.is_empty() => Some(format!("Err(_) => {}::{} {{}}", return_type, variant)),
+        TypeExpr::Map(fields) if fields.len() == 1 => Some(format!(
+            "Err(e) => {}::{} {{ {}: format!(\"{{:?}}\", e) }}",
+            return_type,
+            variant,
+            to_snake(&fields[0].0)
+        )),
+        TypeExpr::Named(name) if name == "Unit" => Some(format!("Err(_) => {}::{}", return_type, variant)),
+        TypeExpr::Named(name) if name == "String" => {
+            Some(format!("Err(e) => {}::{}(format!(\"{{:?}}\", e))", return_type, variant))
+        }
+        _ => None,
+    }
+}
+
 fn type_expr_to_rust(type_expr: &TypeExpr) -> String {
     match type_expr {
         TypeExpr::Named(name) => match name.as_str() {
             "UUID" => "Uuid".to_string(),
             "String" => "String".to_string(),
             "Int" => "i64".to_string(),
+            "Float" => "f64".to_string(),
             "Bool" => "bool".to_string(),
             "Unit" => "()".to_string(),
             other => other.to_string(),
@@ -710,32 +1996,222 @@ fn type_expr_to_rust(type_expr: &TypeExpr) -> String {
             // For inline unions, generate an enum name placeholder
             "UnionType".to_string()
         }
-        TypeExpr::Enum(variants) => {
-            // Enums become their own type
-            format!("/* enum: {} */", variants.join(" | "))
+        // Enums of fields and params are named before codegen; any other is its keyword text
+        TypeExpr::Enum(_) => "String".to_string(),
+        TypeExpr::Optional(inner) => format!("Option<{}>", type_expr_to_rust(inner)),
+        TypeExpr::Ref(target) => refs::id_type(target),
+        TypeExpr::MapOf(key, value) => format!(
+            "std::collections::HashMap<{}, {}>",
+            type_expr_to_rust(key),
+            type_expr_to_rust(value)
+        ),
+        TypeExpr::Result(ok, err) => format!("Result<{}, {}>", type_expr_to_rust(ok), type_expr_to_rust(err)),
+        TypeExpr::Generic(name, args) => {
+            let args: Vec<String> = args.iter().map(type_expr_to_rust).collect();
+            format!("{}<{}>", name, args.join(", "))
         }
     }
 }
 
-/// Convert a kebab-case name to snake_case
-fn to_snake(name: &str) -> String {
-    name.replace('-', "_").replace('/', "_")
+/// The Rust type of a field of `owner`. A field leading back to `owner` other than
+/// through a `Vec` or map is boxed, so the type has a size.
+fn field_type_to_rust(field: &FieldDef, owner: &TypeDef, module: &Module) -> String {
+    if !generics::boxed(field, &owner.name, module) {
+        return type_expr_to_rust(&field.type_expr);
+    }
+    match &field.type_expr {
+        TypeExpr::Optional(inner) => format!("Option<Box<{}>>", type_expr_to_rust(inner)),
+        other => format!("Box<{}>", type_expr_to_rust(other)),
+    }
 }
 
-/// Convert a kebab-case name to PascalCase
-fn to_pascal(name: &str) -> String {
-    name.split(|c| c == '-' || c == '_' || c == '/')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + &chars.as_str().to_lowercase()
-                }
-            }
+/// `<T, U>` after the name of a generic type; empty otherwise
+fn type_params(typedef: &TypeDef) -> String {
+    if typedef.type_params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", typedef.type_params.join(", "))
+    }
+}
+
+/// Range and scale checks of a numeric field: the condition under which `value` fails, and the message.
+fn numeric_checks(field: &FieldDef, value: &str) -> Vec<(String, String)> {
+    let mut checks = Vec::new();
+    if let Some(min) = field.min {
+        checks.push((
+            format!("{} < {}", value, bound_literal(min, &field.type_expr)),
+            format!("must be at least {}", min),
+        ));
+    }
+    if let Some(max) = field.max {
+        checks.push((
+            format!("{} > {}", value, bound_literal(max, &field.type_expr)),
+            format!("must be at most {}", max),
+        ));
+    }
+    if let Some(scale) = field.scale {
+        checks.push((
+            format!("{}.scale() > {}", value, scale),
+            format!("must have at most {} decimal places", scale),
+        ));
+    }
+    checks
+}
+
+/// Every validation check of a field: the condition under which `value` fails, and the message.
+fn field_checks(field: &FieldDef, value: &str) -> Vec<(String, String)> {
+    let mut checks = Vec::new();
+    if let Some(min) = field.min_len {
+        checks.push((format!("{}.len() < {}", value, min), format!("must be at least {} characters", min)));
+    }
+    if let Some(max) = field.max_len {
+        checks.push((format!("{}.len() > {}", value, max), format!("must be at most {} characters", max)));
+    }
+    checks.extend(numeric_checks(field, value));
+    checks
+}
+
+fn is_numeric(type_expr: &TypeExpr) -> bool {
+    matches!(type_expr.non_optional(), TypeExpr::Named(n) if matches!(n.as_str(), "Int" | "Float" | "Decimal"))
+}
+
+/// A `:default` as a Rust value of the field's type: `Role::Member`, `"anon".to_string()`, `Some(3)`
+fn default_literal(value: &MetaValue, type_expr: &TypeExpr) -> String {
+    match (type_expr, value) {
+        (TypeExpr::Optional(inner), _) => format!("Some({})", default_literal(value, inner)),
+        (TypeExpr::Named(n), MetaValue::Keyword(kw)) => format!("{}::{}", n, EnumDef::variant_ident(kw)),
+        (TypeExpr::Named(n), MetaValue::Int(i)) if n == "Float" => format!("{:?}", *i as f64),
+        (TypeExpr::Named(n), MetaValue::Int(i)) if n == "Decimal" => format!("Decimal::new({}, 0)", i),
+        (TypeExpr::Named(n), MetaValue::Float(f)) if n == "Decimal" => {
+            let (units, scale) = Number::Float(*f).decimal_parts().expect("the lexer only accepts float literals that fit");
+            format!("Decimal::new({}, {})", units, scale)
+        }
+        (_, MetaValue::String(text)) => format!("{:?}.to_string()", text),
+        (_, MetaValue::Int(i)) => i.to_string(),
+        (_, MetaValue::Float(f)) => format!("{:?}", f),
+        (_, MetaValue::Bool(b)) => b.to_string(),
+        _ => "Default::default()".to_string(),
+    }
+}
+
+/// A `:default` as written in the source
+fn default_text(value: &MetaValue) -> String {
+    match value {
+        MetaValue::String(text) => format!("{:?}", text),
+        MetaValue::Keyword(kw) => format!(":{}", kw),
+        MetaValue::Int(i) => i.to_string(),
+        MetaValue::Float(f) => format!("{:?}", f),
+        MetaValue::Bool(b) => b.to_string(),
+        _ => "?".to_string(),
+    }
+}
+
+/// A field's value from its input: wrapped in `Some` when the field is optional and
+/// the input is not, with the field's default when an optional input is left out.
+fn fill_from_input(input: &str, input_optional: bool, field: &FieldDef) -> String {
+    let field_optional = matches!(field.type_expr, TypeExpr::Optional(_));
+    match (input_optional, field_optional, &field.default) {
+        (false, false, _) => input.to_string(),
+        (false, true, _) => format!("Some({})", input),
+        (true, true, None) => input.to_string(),
+        (true, true, Some(default)) => format!("{}.or({})", input, default_literal(default, &field.type_expr)),
+        (true, false, Some(default)) => format!("{}.unwrap_or({})", input, default_literal(default, &field.type_expr)),
+        (true, false, None) => format!("{}.unwrap_or_default()", input),
+    }
+}
+
+/// A UUID input for a `(ref T)` field, wrapped in the typed id
+fn typed_id_input(input: String, input_type: &TypeExpr, input_optional: bool, field: &FieldDef) -> String {
+    match (field.type_expr.non_optional(), input_type.non_optional()) {
+        (TypeExpr::Ref(target), TypeExpr::Named(n)) if n == "UUID" && input_optional => {
+            format!("{}.map({}::from)", input, refs::id_type(target))
+        }
+        (TypeExpr::Ref(target), TypeExpr::Named(n)) if n == "UUID" => format!("{}::from({})", refs::id_type(target), input),
+        _ => input,
+    }
+}
+
+/// A recursive field's input, boxed as the field is
+fn boxed_input(input: String, input_optional: bool, boxed: bool) -> String {
+    match (boxed, input_optional) {
+        (false, _) => input,
+        (true, true) => format!("{}.map(Box::new)", input),
+        (true, false) => format!("Box::new({})", input),
+    }
+}
+
+/// The Rust type of an input struct field: `Option<T>` when the same-named field of a
+/// module type has a `:default`, so callers may leave it out
+fn input_field_type(name: &str, type_expr: &TypeExpr, module: &Module) -> String {
+    match type_expr {
+        TypeExpr::Optional(_) => type_expr_to_rust(type_expr),
+        _ if input_defaulted(name, module) => format!("Option<{}>", type_expr_to_rust(type_expr)),
+        _ => type_expr_to_rust(type_expr),
+    }
+}
+
+fn input_defaulted(name: &str, module: &Module) -> bool {
+    module.types.iter().flat_map(|t| &t.fields).any(|f| f.name == name && f.default.is_some())
+}
+
+/// A `:min`/`:max` bound as a Rust literal of the field's type
+fn bound_literal(bound: Number, type_expr: &TypeExpr) -> String {
+    match type_expr.non_optional() {
+        TypeExpr::Named(n) if n == "Float" => format!("{:?}", bound.as_f64()),
+        TypeExpr::Named(n) if n == "Decimal" => {
+            let (units, scale) = bound.decimal_parts().expect("the lexer only accepts float literals that fit");
+            format!("Decimal::new({}, {})", units, scale)
+        }
+        _ => bound.to_string(),
+    }
+}
+
+/// Whether any field, param or return type is a `Decimal`, which needs the prelude's fixed-point type
+fn uses_decimal(module: &Module) -> bool {
+    fn mentions(type_expr: &TypeExpr) -> bool {
+        match type_expr {
+            TypeExpr::Named(n) => n == "Decimal",
+            TypeExpr::Map(fields) => fields.iter().any(|(_, t)| mentions(t)),
+            TypeExpr::List(inner) | TypeExpr::Optional(inner) => mentions(inner),
+            TypeExpr::MapOf(key, value) | TypeExpr::Result(key, value) => mentions(key) || mentions(value),
+            TypeExpr::Generic(_, args) => args.iter().any(mentions),
+            TypeExpr::Union(variants) => variants.iter().any(variant_mentions),
+            TypeExpr::Enum(_) | TypeExpr::Ref(_) => false,
+        }
+    }
+    fn variant_mentions(variant: &Variant) -> bool {
+        match &variant.kind {
+            VariantKind::Ok { type_expr, .. } => mentions(type_expr),
+            VariantKind::Err { payload, .. } => mentions(payload),
+        }
+    }
+    module.types.iter().flat_map(|t| &t.fields).any(|f| mentions(&f.type_expr))
+        || module.functions.iter().any(|f| {
+            f.params.iter().any(|p| mentions(&p.type_expr)) || f.returns.variants.iter().any(variant_mentions)
         })
-        .collect()
+        || module.helpers.iter().any(|h| h.params.iter().any(|p| mentions(&p.type_expr)) || mentions(&h.returns))
+        || module.externs.iter().any(|e| e.params.iter().any(mentions) || mentions(&e.returns))
+}
+
+/// The Rust type of a field in an error payload. Payloads such as `{:id id}` name a
+/// value rather than a type: params give their type (text UUID params, `String`),
+/// otherwise a same-named field of a module type does.
+fn resolve_field_type(field_name: &str, type_expr: &TypeExpr, module: &Module, func: &FnDef) -> String {
+    if let TypeExpr::Named(name) = type_expr {
+        if matches!(name.as_str(), "UUID" | "String" | "Int" | "Float" | "Decimal" | "Bool") {
+            return type_expr_to_rust(type_expr);
+        }
+        if let Some(param) = func.params.iter().find(|p| p.name == *name) {
+            if is_text_uuid(func, &param.name) {
+                return "String".to_string();
+            }
+            return type_expr_to_rust(&param.type_expr);
+        }
+        if let Some(field) = module.types.iter().flat_map(|t| &t.fields).find(|f| f.name == field_name) {
+            return type_expr_to_rust(&field.type_expr);
+        }
+    }
+    type_expr_to_rust(type_expr)
 }
 
 #[cfg(test)]
@@ -801,7 +2277,7 @@ mod tests {
         let output = generate(input);
         assert!(output.contains("pub enum GetThingResult {"));
         assert!(output.contains("Ok(Uuid),"));
-        assert!(output.contains("NotFound("));
+        assert!(output.contains("NotFound { id: Uuid },"));
         assert!(output.contains("fn http_status(&self) -> u16"));
     }
 
@@ -823,4 +2299,308 @@ mod tests {
         assert!(output.contains("pub fn create_user"));
         assert!(output.contains("fn http_status(&self) -> u16"));
     }
+
+    #[test]
+    fn test_output_is_self_contained() {
+        let output = generate(include_str!("../../examples/user-service.pct"));
+        // The prelude defines what generated code uses instead of external crates
+        assert!(output.contains("pub struct Uuid(u128);"));
+        assert!(output.contains("pub fn validate_uuid(s: &str) -> Result<Uuid, ValidationError> {"));
+        assert!(output.contains("pub fn non_empty<T>(items: &[T]) -> bool {"));
+        assert!(!output.contains("UserStoreItem"));
+        // Store errors are the implementation's choice, converted at the call
+        assert!(output.contains("type UserStoreError: Into<StoreError>;"));
+        assert!(output.contains("fn insert_user_store(&mut self, item: User) -> Result<User, Self::UserStoreError>;"));
+        assert!(output.contains("fn query_user_store(&self, query: UserStoreQuery) -> Option<User>;"));
+        assert!(output.contains("DbRead::query_user_store(ctx, UserStoreQuery { id: Some(uuid), ..Default::default() })"));
+        assert!(output.contains("DbWrite::insert_user_store(ctx, User { id: Uuid::new_v4(), name: input.name.clone(), email: input.email.clone() }).map_err(Into::<StoreError>::into)"));
+        assert!(output.contains("Err(StoreError::UniqueViolation { .. }) => CreateUserResult::DuplicateEmail { email: input.email.to_string() },"));
+        assert!(output.contains("Err(_) => CreateUserResult::StoreUnavailable {},"));
+    }
+
+    #[test]
+    fn test_in_memory_context_implements_effect_traits() {
+        let output = generate(include_str!("../../examples/user-service.pct"));
+        assert!(output.contains("pub struct InMemoryContext {"));
+        assert!(output.contains("pub user_store: Vec<User>,"));
+        assert!(output.contains("pub http_response: Vec<Vec<u8>>,"));
+        assert!(output.contains("impl DbWrite for InMemoryContext {"));
+        assert!(output.contains("type UserStoreError = StoreError;"));
+        assert!(output.contains("if self.user_store.iter().any(|e| e.email == item.email) {"));
+        assert!(output.contains("impl HttpRespond for InMemoryContext {"));
+
+        // Stores whose type is not defined here stay abstract
+        let output = generate(
+            "(module test :version 1 (effect-set audit [:writes audit-store :reads audit-store]))",
+        );
+        assert!(output.contains("type AuditStoreItem;"));
+        assert!(output.contains("type AuditStoreQuery;"));
+        assert!(output.contains("fn query_audit_store(&self, query: Self::AuditStoreQuery) -> Option<Self::AuditStoreItem>;"));
+        assert!(output.contains("// No in-memory Audit: audit-store holds no type defined in this module"));
+    }
+
+    #[test]
+    fn test_numeric_fields_and_ranges() {
+        let output = generate(
+            r#"(module shop :version 1
+                (type Product
+                    (field price Decimal :scale 2 :min 0.01)
+                    (field weight Float :max 1000)
+                    (field stock Int :min 0)))"#,
+        );
+        assert!(output.contains("pub price: Decimal,"));
+        assert!(output.contains("pub weight: f64,"));
+        assert!(output.contains("pub struct Decimal {"));
+        assert!(output.contains("if self.price < Decimal::new(1, 2) { errors.push(ValidationError { field: \"price\".into(), message: \"must be at least 0.01\".into() }); }"));
+        assert!(output.contains("if self.price.scale() > 2 {"));
+        // Arithmetic is checked: overflow panics instead of wrapping
+        assert!(output.contains("pub fn checked_add(self, other: Self) -> Option<Self> {"));
+        assert!(output.contains("self.checked_mul(other).unwrap_or_else(|| panic!(\"Decimal overflow: {} * {}\", self, other))"));
+        assert!(!output.contains("as i64"));
+        assert!(output.contains("if self.weight > 1000.0 {"));
+        assert!(output.contains("if self.stock < 0 {"));
+
+        // The fixed-point type is only emitted when something uses it
+        assert!(!generate("(module m :version 1 (type T (field n Float)))").contains("pub struct Decimal"));
+    }
+
+    #[test]
+    fn test_enum_fields_and_keyword_matches() {
+        let output = generate(
+            r#"(module team :version 1
+                (type Member (field role (enum :admin :read-only)))
+                (fn can-edit
+                    (param member Member)
+                    (returns (union (ok Bool :http 200)))
+                    (match (. member role)
+                        :admin (ok true)
+                        :read-only (ok false))))"#,
+        );
+        assert!(output.contains("pub enum Role {"));
+        assert!(output.contains("pub role: Role,"));
+        assert!(output.contains("Role::ReadOnly => \"read-only\","));
+        assert!(output.contains("\"read-only\" => Ok(Role::ReadOnly),"));
+        assert!(output.contains("_ => Err(format!(\"'{}' is not one of admin, read-only\", s)),"));
+        assert!(output.contains("Role::Admin => CanEditResult::Ok(true),"));
+        assert!(!output.contains("/* enum"));
+    }
+
+    #[test]
+    fn test_optional_and_defaulted_fields() {
+        let output = generate(
+            r#"(module team :version 1
+                (type Member
+                    (field id UUID :immutable :generated)
+                    (field nickname (optional String) :min-len 2)
+                    (field role (enum :admin :member) :default :member))
+                (effect-set db-write [:writes member-store])
+                (fn create-member
+                    :effects [db-write]
+                    (param input {:nickname (optional String) :role (enum :admin :member)})
+                    (returns (union (ok Member :http 201)))
+                    (ok (insert! member-store (build Member input)))))"#,
+        );
+        assert!(output.contains("pub nickname: Option<String>,"));
+        assert!(output.contains("    /// default: :member\n    pub role: Role,"));
+        assert!(output.contains("if let Some(v) = &self.nickname {"));
+        // A defaulted field may be left out of the input and is filled in by build
+        assert!(output.contains("pub role: Option<Role>,"));
+        assert!(output.contains("role: input.role.clone().unwrap_or(Role::Member)"));
+    }
+
+    #[test]
+    fn test_refs_get_typed_ids_and_checked_inserts() {
+        let output = generate(
+            r#"(module blog :version 1
+                (type Author (field id UUID :immutable :generated))
+                (type Post
+                    (field id UUID :immutable :generated)
+                    (field author (ref Author))
+                    (field editor (optional (ref Author))))
+                (effect-set author-read [:reads author-store])
+                (effect-set post-write [:writes post-store])
+                (fn create-post
+                    :effects [post-write author-read]
+                    (param input {:author UUID :editor (optional (ref Author))})
+                    (returns (union (ok Post :http 201) (err :unknown-author {} :http 422)))
+                    (match (insert! post-store (build Post input))
+                        (ok p) (ok p)
+                        (err :missing-reference) (err :unknown-author {})
+                        (err _) (err :unknown-author {}))))"#,
+        );
+        assert!(output.contains("pub struct AuthorId(pub Uuid);"));
+        assert!(output.contains("pub author: AuthorId,"));
+        assert!(output.contains("pub editor: Option<AuthorId>,"));
+        assert!(output.contains("author: AuthorId::from(input.author.clone())"));
+        assert!(output.contains(
+            "if AuthorRead::query_author_store(ctx, AuthorStoreQuery { id: Some(item.author.0), ..Default::default() }).is_none() { Err(StoreError::MissingReference { field: \"author\".into() }) } else "
+        ));
+        assert!(output.contains("match item.editor { Some(id) => AuthorRead::query_author_store(ctx, AuthorStoreQuery { id: Some(id.0), ..Default::default() }).is_none(), None => false }"));
+        assert!(output.contains("{ PostWrite::insert_post_store(ctx, item).map_err(Into::<StoreError>::into) } }"));
+        assert!(output.contains("Err(StoreError::MissingReference { .. }) => CreatePostResult::UnknownAuthor { },"));
+    }
+
+    #[test]
+    fn test_generic_and_recursive_types() {
+        let output = generate(
+            r#"(module feed :version 1
+                (type Comment
+                    (field id UUID :immutable :generated)
+                    (field parent (optional Comment))
+                    (field replies (list Comment)))
+                (type (Page T) (field items (list T)) (field next-cursor (optional String)))
+                (effect-set comment-write [:writes comment-store])
+                (fn post-comment
+                    :effects [comment-write]
+                    (param input {:parent (optional Comment)})
+                    (returns (union (ok Comment :http 201)))
+                    (ok (insert! comment-store (build Comment input))))
+                (fn first-page
+                    (param page (Page Comment))
+                    (returns (union (ok (Page Comment))))
+                    (ok page)))"#,
+        );
+        assert!(output.contains("pub struct Page<T> {\n    pub items: Vec<T>,"));
+        assert!(output.contains("impl<T> Page<T> {"));
+        assert!(output.contains("pub parent: Option<Box<Comment>>,\n    pub replies: Vec<Comment>,"));
+        assert!(output.contains("parent: input.parent.clone().map(Box::new)"));
+        assert!(output.contains("pub fn first_page(page: Page<Comment>) -> FirstPageResult {"));
+        assert!(!output.contains("PageStoreQuery"));
+    }
+
+    #[test]
+    fn test_operators_are_native_rust_operators() {
+        let output = generate(
+            r#"(module shop :version 1
+                (type Item (field id UUID) (field qty Int) (field name String))
+                (effect-set item-read [:reads item-store])
+                (fn check
+                    :effects [item-read]
+                    (param id UUID)
+                    (param count Int)
+                    (returns (union (ok Int) (err :short {})))
+                    (match (query item-store {:id id})
+                        (some i) (let [left (- (. i qty) count)]
+                                   (if (and (>= left 0) (not (= (. i name) "")))
+                                     (ok (+ (* count 2) (- left)))
+                                     (err :short {})))
+                        (none) (err :short {}))))"#,
+        );
+        assert!(output.contains("let left = i.qty - count;"));
+        assert!(output.contains("if (left >= 0) && (!(i.name == \"\")) {"));
+        assert!(output.contains("Ok((count * 2) + (-left))"));
+    }
+
+    #[test]
+    fn test_collections_are_iterator_chains() {
+        let output = generate(
+            r#"(module shop :version 1
+                (fn totals
+                    (param prices (list Int))
+                    (returns (union (ok Int)))
+                    (let [doubled (map (fn [p] (* p 2)) prices)
+                          cheap (filter (fn [p] (< p 10)) [1 2 3])
+                          total (fold (fn [acc p] (+ acc p)) 0 cheap)]
+                      (ok total))))"#,
+        );
+        assert!(output.contains("let doubled = prices.iter().cloned().map(|p| p * 2).collect::<Vec<_>>();"));
+        assert!(output.contains("let cheap = vec![1, 2, 3].iter().cloned().filter(|p| { let p = p.clone(); p < 10 }).collect::<Vec<_>>();"));
+        assert!(output.contains("let total = cheap.iter().cloned().fold(0, |acc, p| acc + p);"));
+    }
+
+    #[test]
+    fn test_helpers_are_plain_functions() {
+        let output = generate(
+            r#"(module shop :version 1
+                (type Session (field id UUID) (field expires-at Int))
+                (defn expired? [s Session] Bool (<= (. s expires-at) 0))
+                (fn check
+                    (param s Session)
+                    (returns (union (ok Session) (err :expired {})))
+                    (if (expired? s) (err :expired {}) (ok s))))"#,
+        );
+        assert!(output.contains("pub fn expired(s: Session) -> bool {\n    s.expires_at <= 0\n}"));
+        assert!(output.contains("if expired(s.clone()) {"));
+        assert!(!output.contains("ExpiredResult"));
+    }
+
+    #[test]
+    fn test_externs_are_host_trait_calls() {
+        let output = generate(
+            r#"(module auth :version 1
+                (extern verify-password [String String] (result Unit Unit) :effects [])
+                (defn matches? [given String stored String] Bool
+                    (match (verify-password given stored) (ok _) true (err _) false))
+                (fn login
+                    (param password String)
+                    (returns (union (ok Bool)))
+                    (ok (matches? password "hash"))))"#,
+        );
+        assert!(output.contains("pub trait Externs {"));
+        assert!(output.contains("fn verify_password(&self, arg1: String, arg2: String) -> Result<(), ()>;"));
+        assert!(output.contains("pub fn matches(externs: &impl Externs, given: String, stored: String) -> bool"));
+        assert!(output.contains("match externs.verify_password(given.clone(), stored.clone())"));
+        assert!(output.contains("pub fn login(externs: &impl Externs, password: String) -> LoginResult"));
+        assert!(output.contains("matches(externs, password.clone(), \"hash\".to_string())"));
+    }
+
+    #[test]
+    fn test_every_example_compiles_with_rustc() {
+        let examples = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples")).unwrap();
+        let tmp_dir = std::env::temp_dir().join("pact-v1-rustc-test");
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+
+        let mut compiled = 0;
+        for entry in examples {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "pct") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let file = tmp_dir.join(format!("{}.rs", path.file_stem().unwrap().to_string_lossy().replace('-', "_")));
+            std::fs::write(&file, generate(&source)).unwrap();
+            let output = std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+                .args(["--edition", "2021", "--crate-type", "lib", "-A", "warnings", "--out-dir"])
+                .arg(&tmp_dir)
+                .arg(&file)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "{} does not compile:\n{}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            compiled += 1;
+        }
+        assert!(compiled > 0);
+
+        // Cleanup
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_for_each_writes_return_the_first_store_error() {
+        let input = r#"(module batch :version 1
+            (type Item (field id UUID :immutable :generated) (field name String))
+            (effect-set db-write [:writes item-store])
+            (extern new-item [String] Item :effects [])
+            (fn import-items
+                :effects [db-write]
+                MAPPING
+                (param names (list String))
+                (returns (union
+                    (ok Int :http 200)
+                    (err :store-unavailable {} :http 503)))
+                (let [_ (for-each [n names] (insert! item-store (new-item n)))]
+                    (ok 0))))"#;
+        let mapped = generate(&input.replace("MAPPING", ":on-store-error :store-unavailable"));
+        assert!(mapped.contains("for n in names.iter().cloned() { match "), "{}", mapped);
+        assert!(mapped.contains("{ Ok(_) => {} Err(_) => return ImportItemsResult::StoreUnavailable {} } }"), "{}", mapped);
+        assert!(!mapped.contains("{ let _ = "), "{}", mapped);
+
+        let unmapped = generate(&input.replace("MAPPING", ""));
+        assert!(unmapped.contains("{ Ok(_) => {} Err(e) => panic!(\"unexpected store error: {:?}\", e) } }"), "{}", unmapped);
+    }
 }
@@ -1,8 +1,24 @@
 use crate::ast::*;
+use crate::lexer::Span;
+use crate::naming::{self, to_pascal, to_snake};
+use crate::semantic::enums::{self, EnumDef};
+use crate::semantic::externs;
+use crate::semantic::generics;
+use crate::semantic::operators;
+use crate::semantic::queries;
+use crate::semantic::refs;
 
 pub struct RustV2Codegen {
     output: String,
     indent: usize,
+    /// Emit `async fn`s over `AsyncStore` and await store and gateway calls
+    async_fns: bool,
+}
+
+impl Default for RustV2Codegen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RustV2Codegen {
@@ -10,25 +26,86 @@ impl RustV2Codegen {
         RustV2Codegen {
             output: String::new(),
             indent: 0,
+            async_fns: false,
+        }
+    }
+
+    /// Generate `async fn` domain functions (`--async`).
+    pub fn with_async(mut self, async_fns: bool) -> Self {
+        self.async_fns = async_fns;
+        self
+    }
+
+    /// `.await` after store and gateway calls in async mode.
+    fn await_suffix(&self) -> &'static str {
+        if self.async_fns {
+            ".await"
+        } else {
+            ""
+        }
+    }
+
+    fn store_trait(&self) -> &'static str {
+        if self.async_fns {
+            "AsyncStore"
+        } else {
+            "Store"
         }
     }
 
     pub fn generate(mut self, module: &Module) -> String {
+        let enums = enums::collect_enums(module);
+        let module = &enums::name_enums(module, &enums);
+
         self.emit_header(module);
         self.emit_line("");
 
+        if self.async_fns {
+            self.emit_async_store();
+            self.emit_line("");
+        }
+
         // Collect type info for resolving field types in error payloads
         let type_defs: Vec<&TypeDef> = module.types.iter().collect();
 
+        // Generate enums declared inline by fields and params
+        for enum_def in &enums {
+            self.emit_enum_def(enum_def);
+            self.emit_line("");
+        }
+
+        // Generate typed ids for types that (ref T) fields refer to
+        for typedef in refs::referenced_types(module) {
+            self.emit_id_type(&typedef.name);
+            self.emit_line("");
+        }
+        if !refs::referenced_types(module).is_empty() {
+            self.emit_insert_error();
+            self.emit_line("");
+        }
+
         // Generate types
         for typedef in &module.types {
-            self.emit_type_def(typedef);
+            self.emit_type_def(typedef, module);
             self.emit_line("");
         }
 
         // Generate input structs for functions with Map-typed params
         for func in &module.functions {
-            self.emit_input_structs(func);
+            self.emit_input_structs(func, module);
+        }
+
+        // Generate gateway traits for :sends effects
+        let gateways = collect_gateways(module);
+        for gateway in &gateways {
+            self.emit_gateway(gateway);
+            self.emit_line("");
+        }
+
+        // Generate the trait the host implements externs through
+        if module.externs.iter().any(|e| externs::sends_through(e, module).is_none()) {
+            self.emit_externs_trait(module);
+            self.emit_line("");
         }
 
         // Generate return type enums for each function
@@ -37,9 +114,15 @@ impl RustV2Codegen {
             self.emit_line("");
         }
 
+        // Generate helpers
+        for helper in &module.helpers {
+            self.emit_helper(helper, module, &type_defs, &enums);
+            self.emit_line("");
+        }
+
         // Generate functions
         for func in &module.functions {
-            self.emit_function(func, module, &type_defs);
+            self.emit_function(func, module, &type_defs, &gateways, &enums);
             self.emit_line("");
         }
 
@@ -70,7 +153,155 @@ impl RustV2Codegen {
         self.emit_line("use std::fmt;");
     }
 
-    fn emit_type_def(&mut self, typedef: &TypeDef) {
+    /// `AsyncStore` for async domain functions, and `SharedStore`, which adapts a
+    /// synchronous `Store` by locking it only for the duration of each call.
+    fn emit_async_store(&mut self) {
+        self.emit_line("/// Store access for async domain functions.");
+        self.emit_line("pub trait AsyncStore<T> {");
+        self.indent += 1;
+        self.emit_line("fn query_by_id(&self, id: &Uuid) -> impl std::future::Future<Output = Option<T>> + Send;");
+        self.emit_line("fn insert(&mut self, item: T) -> impl std::future::Future<Output = Result<T, StoreError>> + Send;");
+        self.emit_line("fn list_all(&self) -> impl std::future::Future<Output = Vec<T>> + Send;");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("/// A synchronous store shared between tasks. Each call locks it only for its own");
+        self.emit_line("/// duration, so no lock is held across an `.await`. A lock poisoned by a panicking");
+        self.emit_line("/// task still serves reads, and fails writes as `StoreError::Unavailable`.");
+        self.emit_line("pub struct SharedStore<S>(std::sync::Arc<std::sync::Mutex<S>>);");
+        self.emit_line("");
+        self.emit_line("impl<S> SharedStore<S> {");
+        self.indent += 1;
+        self.emit_line("pub fn new(store: S) -> Self {");
+        self.emit_line("    SharedStore(std::sync::Arc::new(std::sync::Mutex::new(store)))");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("pub fn lock(&self) -> std::sync::LockResult<std::sync::MutexGuard<'_, S>> {");
+        self.emit_line("    self.0.lock()");
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl<S> Clone for SharedStore<S> {");
+        self.emit_line("    fn clone(&self) -> Self {");
+        self.emit_line("        SharedStore(self.0.clone())");
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("impl<T: Send, S: Store<T>> AsyncStore<T> for SharedStore<S> {");
+        self.indent += 1;
+        self.emit_line("fn query_by_id(&self, id: &Uuid) -> impl std::future::Future<Output = Option<T>> + Send {");
+        self.emit_line("    std::future::ready(self.0.lock().unwrap_or_else(|e| e.into_inner()).query_by_id(id))");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("fn insert(&mut self, item: T) -> impl std::future::Future<Output = Result<T, StoreError>> + Send {");
+        self.emit_line("    std::future::ready(match self.0.lock() {");
+        self.emit_line("        Ok(mut store) => store.insert(item),");
+        self.emit_line("        Err(e) => Err(StoreError::Unavailable(e.to_string())),");
+        self.emit_line("    })");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line("fn list_all(&self) -> impl std::future::Future<Output = Vec<T>> + Send {");
+        self.emit_line("    std::future::ready(self.0.lock().unwrap_or_else(|e| e.into_inner()).list_all())");
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// An inline enum, serialized, displayed and parsed as its keywords. The first
+    /// variant is the default, for fields `build` leaves unset.
+    fn emit_enum_def(&mut self, enum_def: &EnumDef) {
+        let name = &enum_def.name;
+        self.emit_line(&format!(
+            "/// One of {}",
+            enum_def.variants.iter().map(|v| format!(":{}", v)).collect::<Vec<_>>().join(" ")
+        ));
+        self.emit_line("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]");
+        self.emit_line(&format!("pub enum {} {{", name));
+        self.indent += 1;
+        for (i, variant) in enum_def.variants.iter().enumerate() {
+            if i == 0 {
+                self.emit_line("#[default]");
+            }
+            self.emit_line(&format!("#[serde(rename = \"{}\")]", variant));
+            self.emit_line(&format!("{},", EnumDef::variant_ident(variant)));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl fmt::Display for {} {{", name));
+        self.indent += 1;
+        self.emit_line("fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.indent += 1;
+        self.emit_line("f.write_str(match self {");
+        self.indent += 1;
+        for variant in &enum_def.variants {
+            self.emit_line(&format!("{}::{} => \"{}\",", name, EnumDef::variant_ident(variant), variant));
+        }
+        self.indent -= 1;
+        self.emit_line("})");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl std::str::FromStr for {} {{", name));
+        self.indent += 1;
+        self.emit_line("type Err = String;");
+        self.emit_line("");
+        self.emit_line("fn from_str(s: &str) -> Result<Self, Self::Err> {");
+        self.indent += 1;
+        self.emit_line("match s {");
+        self.indent += 1;
+        for variant in &enum_def.variants {
+            self.emit_line(&format!("\"{}\" => Ok({}::{}),", variant, name, EnumDef::variant_ident(variant)));
+        }
+        self.emit_line(&format!(
+            "_ => Err(format!(\"'{{}}' is not one of {}\", s)),",
+            enum_def.variants.join(", ")
+        ));
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// The error of an `insert!` whose item has `(ref T)` fields. pact-runtime's
+    /// `StoreError` has no variant for a missing row, and `Unavailable` means an outage.
+    fn emit_insert_error(&mut self) {
+        self.emit_line("/// Why an `insert!` of a row with `(ref T)` fields failed");
+        self.emit_line("#[derive(Debug)]");
+        self.emit_line("pub enum InsertError {");
+        self.emit_line("    /// The field names no row of the store that holds its type");
+        self.emit_line("    MissingReference { field: String },");
+        self.emit_line("    Store(StoreError),");
+        self.emit_line("}");
+    }
+
+    /// `UserId(Uuid)`: the id a `(ref User)` field holds, serialized as the bare UUID
+    fn emit_id_type(&mut self, type_name: &str) {
+        let id_type = refs::id_type(type_name);
+        self.emit_line(&format!("/// The id of a {}, as `(ref {})` fields hold it", type_name, type_name));
+        self.emit_line("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]");
+        self.emit_line("#[serde(transparent)]");
+        self.emit_line(&format!("pub struct {}(pub Uuid);", id_type));
+        self.emit_line("");
+        self.emit_line(&format!("impl From<Uuid> for {} {{", id_type));
+        self.emit_line("    fn from(id: Uuid) -> Self {");
+        self.emit_line(&format!("        {}(id)", id_type));
+        self.emit_line("    }");
+        self.emit_line("}");
+        self.emit_line("");
+        self.emit_line(&format!("impl fmt::Display for {} {{", id_type));
+        self.emit_line("    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {");
+        self.emit_line("        self.0.fmt(f)");
+        self.emit_line("    }");
+        self.emit_line("}");
+    }
+
+    fn emit_type_def(&mut self, typedef: &TypeDef, module: &Module) {
         // Doc comment with invariants
         if !typedef.invariants.is_empty() {
             self.emit_line(&format!("/// Type: {}", typedef.name));
@@ -81,11 +312,12 @@ impl RustV2Codegen {
             }
         }
 
+        let params = type_params(typedef);
         self.emit_line("#[derive(Debug, Clone, Serialize, Deserialize)]");
-        self.emit_line(&format!("pub struct {} {{", typedef.name));
+        self.emit_line(&format!("pub struct {}{} {{", typedef.name, params));
         self.indent += 1;
         for field in &typedef.fields {
-            let rust_type = type_expr_to_rust(&field.type_expr);
+            let rust_type = field_type_to_rust(field, typedef, module);
             self.emit_line(&format!("pub {}: {},", to_snake(&field.name), rust_type));
         }
         self.indent -= 1;
@@ -95,7 +327,7 @@ impl RustV2Codegen {
         let id_field = typedef.fields.iter().find(|f| f.name == "id");
         if id_field.is_some() {
             self.emit_line("");
-            self.emit_line(&format!("impl HasId for {} {{", typedef.name));
+            self.emit_line(&format!("impl{} HasId for {}{} {{", params, typedef.name, params));
             self.indent += 1;
             self.emit_line("fn id(&self) -> Uuid { self.id }");
             self.indent -= 1;
@@ -110,7 +342,7 @@ impl RustV2Codegen {
             .collect();
         if id_field.is_some() {
             self.emit_line("");
-            self.emit_line(&format!("impl HasUniqueFields for {} {{", typedef.name));
+            self.emit_line(&format!("impl{} HasUniqueFields for {}{} {{", params, typedef.name, params));
             self.indent += 1;
             self.emit_line("fn unique_fields(&self) -> Vec<(&'static str, String)> {");
             self.indent += 1;
@@ -139,7 +371,7 @@ impl RustV2Codegen {
 
         // Generate validate method
         self.emit_line("");
-        self.emit_line(&format!("impl {} {{", typedef.name));
+        self.emit_line(&format!("impl{} {}{} {{", params, typedef.name, params));
         self.indent += 1;
 
         // validate() on instances
@@ -147,27 +379,38 @@ impl RustV2Codegen {
         self.indent += 1;
         self.emit_line("let mut errors = Vec::new();");
         for field in &typedef.fields {
-            let field_snake = to_snake(&field.name);
-            if let Some(min) = field.min_len {
-                self.emit_line(&format!(
-                    "if self.{}.len() < {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"must be at least {} characters\".into() }}); }}",
-                    field_snake, min, field.name, min
-                ));
-            }
-            if let Some(max) = field.max_len {
-                self.emit_line(&format!(
-                    "if self.{}.len() > {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"must be at most {} characters\".into() }}); }}",
-                    field_snake, max, field.name, max
-                ));
-            }
+            let optional = matches!(field.type_expr, TypeExpr::Optional(_));
+            self.emit_field_checks(field, &format!("self.{}", to_snake(&field.name)), optional);
         }
         self.emit_line("errors");
         self.indent -= 1;
         self.emit_line("}");
 
+        // Generic types are built from their instances, never from an input struct
+        if !typedef.type_params.is_empty() {
+            self.indent -= 1;
+            self.emit_line("}");
+            return;
+        }
+
         // validate_input() on input struct — checks same constraints as validate
         // but takes a generic input with matching field names
         let input_struct_name = format!("Create{}Input", typedef.name);
+        // The fields of that struct, when a function here declares it
+        let input_fields = module
+            .functions
+            .iter()
+            .filter(|f| format!("{}Input", to_pascal(&f.name)) == input_struct_name)
+            .flat_map(|f| &f.params)
+            .find_map(|p| match &p.type_expr {
+                TypeExpr::Map(fields) => Some(fields),
+                _ => None,
+            });
+        // A field's type in the input struct; `None` when the struct leaves it out
+        let input_type = |field: &FieldDef| match input_fields {
+            Some(fields) => fields.iter().find(|(n, _)| *n == field.name).map(|(_, t)| t.clone()),
+            None => Some(field.type_expr.clone()),
+        };
         let non_generated_fields: Vec<&FieldDef> = typedef
             .fields
             .iter()
@@ -182,25 +425,48 @@ impl RustV2Codegen {
             self.indent += 1;
             self.emit_line("let mut errors = Vec::new();");
             for field in &non_generated_fields {
-                let field_snake = to_snake(&field.name);
-                if let Some(min) = field.min_len {
-                    self.emit_line(&format!(
-                        "if input.{}.len() < {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"must be at least {} characters\".into() }}); }}",
-                        field_snake, min, field.name, min
-                    ));
-                }
-                if let Some(max) = field.max_len {
-                    self.emit_line(&format!(
-                        "if input.{}.len() > {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"must be at most {} characters\".into() }}); }}",
-                        field_snake, max, field.name, max
-                    ));
-                }
+                let Some(input_type) = input_type(field) else {
+                    continue;
+                };
+                let optional = matches!(input_type, TypeExpr::Optional(_)) || input_defaulted(&field.name, module);
+                self.emit_field_checks(field, &format!("input.{}", to_snake(&field.name)), optional);
             }
             self.emit_line("errors");
             self.indent -= 1;
             self.emit_line("}");
         }
 
+        // find_by_<field>() lookups for unique fields, used by (query store {:field v})
+        for field in &unique_fields {
+            let field_snake = to_snake(&field.name);
+            let rust_type = type_expr_to_rust(&field.type_expr);
+            let (arg_type, compare) = if rust_type == "String" {
+                ("&str".to_string(), field_snake.clone())
+            } else {
+                (format!("&{}", rust_type), format!("*{}", field_snake))
+            };
+            self.emit_line("");
+            self.emit_line(&format!(
+                "pub {}fn find_by_{}(store: &impl {}<{}>, {}: {}) -> Option<{}> {{",
+                if self.async_fns { "async " } else { "" },
+                field_snake,
+                self.store_trait(),
+                typedef.name,
+                field_snake,
+                arg_type,
+                typedef.name
+            ));
+            self.indent += 1;
+            self.emit_line(&format!(
+                "store.list_all(){}.into_iter().find(|e| e.{} == {})",
+                self.await_suffix(),
+                field_snake,
+                compare
+            ));
+            self.indent -= 1;
+            self.emit_line("}");
+        }
+
         // from_input() constructor
         self.emit_line("");
         self.emit_line(&format!(
@@ -217,9 +483,22 @@ impl RustV2Codegen {
                     "Uuid" => self.emit_line(&format!("{}: Uuid::new_v4(),", field_snake)),
                     _ => self.emit_line(&format!("{}: Default::default(),", field_snake)),
                 }
-            } else {
-                self.emit_line(&format!("{}: input.{},", field_snake, field_snake));
+                continue;
             }
+            let value = match input_type(field) {
+                Some(input_type) => {
+                    let input_optional =
+                        matches!(input_type, TypeExpr::Optional(_)) || input_defaulted(&field.name, module);
+                    let input = typed_id_input(format!("input.{}", field_snake), &input_type, input_optional, field);
+                    let input = boxed_input(input, input_optional, generics::boxed(field, &typedef.name, module));
+                    fill_from_input(&input, input_optional, field)
+                }
+                None => match &field.default {
+                    Some(default) => default_literal(default, &field.type_expr),
+                    None => "Default::default()".to_string(),
+                },
+            };
+            self.emit_line(&format!("{}: {},", field_snake, value));
         }
         self.indent -= 1;
         self.emit_line("}");
@@ -230,7 +509,34 @@ impl RustV2Codegen {
         self.emit_line("}");
     }
 
-    fn emit_input_structs(&mut self, func: &FnDef) {
+    /// The checks of one field on `access`; an optional value is checked only when present.
+    fn emit_field_checks(&mut self, field: &FieldDef, access: &str, optional: bool) {
+        let value = if optional { "v".to_string() } else { access.to_string() };
+        let checks = field_checks(field, &value);
+        if checks.is_empty() {
+            return;
+        }
+        if optional {
+            // Numbers are `Copy`; text is checked by reference
+            let binding = if is_numeric(&field.type_expr) { access.to_string() } else { format!("&{}", access) };
+            self.emit_line(&format!("if let Some(v) = {} {{", binding));
+            self.indent += 1;
+        }
+        for (failed, message) in checks {
+            self.emit_line(&format!(
+                "if {} {{ errors.push(ValidationError {{ field: \"{}\".into(), message: \"{}\".into() }}); }}",
+                failed, field.name, message
+            ));
+        }
+        if optional {
+            self.indent -= 1;
+            self.emit_line("}");
+        }
+    }
+
+    /// `{Fn}Input` for a map-typed param. Optional fields, and fields whose same-named type
+    /// field has a `:default`, may be left out of the JSON; `from_input()` fills in defaults.
+    fn emit_input_structs(&mut self, func: &FnDef, module: &Module) {
         for param in &func.params {
             if let TypeExpr::Map(fields) = &param.type_expr {
                 let struct_name = format!("{}Input", to_pascal(&func.name));
@@ -238,7 +544,10 @@ impl RustV2Codegen {
                 self.emit_line(&format!("pub struct {} {{", struct_name));
                 self.indent += 1;
                 for (name, type_expr) in fields {
-                    let rust_type = type_expr_to_rust(type_expr);
+                    let rust_type = input_field_type(name, type_expr, module);
+                    if rust_type.starts_with("Option<") {
+                        self.emit_line("#[serde(default)]");
+                    }
                     self.emit_line(&format!("pub {}: {},", to_snake(name), rust_type));
                 }
                 self.indent -= 1;
@@ -288,7 +597,7 @@ impl RustV2Codegen {
                             self.output.push_str(&format!("{} {{", variant_name));
                             for (i, (name, type_expr)) in fields.iter().enumerate() {
                                 if i > 0 {
-                                    self.output.push_str(",");
+                                    self.output.push(',');
                                 }
                                 self.output.push_str(&format!(
                                     " {}: {}",
@@ -418,22 +727,194 @@ impl RustV2Codegen {
         self.emit_line("}");
     }
 
-    fn emit_function(&mut self, func: &FnDef, module: &Module, type_defs: &[&TypeDef]) {
+    /// `(extern verify-password [String String] (result Unit Unit))` →
+    /// `fn verify_password(&self, arg1: String, arg2: String) -> Result<(), ()>`.
+    /// Externs that send through a gateway are methods of that gateway instead.
+    fn emit_externs_trait(&mut self, module: &Module) {
+        self.emit_line("/// Functions the host application implements, one per `extern`.");
+        self.emit_line("pub trait Externs {");
+        self.indent += 1;
+        for ext in module.externs.iter().filter(|e| externs::sends_through(e, module).is_none()) {
+            let mut params = vec!["&self".to_string()];
+            for (i, param) in ext.params.iter().enumerate() {
+                params.push(format!("arg{}: {}", i + 1, type_expr_to_rust(param)));
+            }
+            self.emit_line(&format!(
+                "fn {}({}) -> {};",
+                to_snake(&ext.name.replace(['?', '!'], "")),
+                params.join(", "),
+                type_expr_to_rust(&ext.returns)
+            ));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    fn emit_gateway(&mut self, gateway: &Gateway) {
+        for method in &gateway.methods {
+            self.emit_line(&format!("/// Message sent through `{}`.", gateway.target));
+            self.emit_line("#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]");
+            self.emit_line(&format!("pub struct {} {{", method.message));
+            self.indent += 1;
+            for (name, rust_type) in &method.fields {
+                self.emit_line(&format!("pub {}: {},", name, rust_type));
+            }
+            self.indent -= 1;
+            self.emit_line("}");
+            self.emit_line("");
+        }
+
+        self.emit_line(&format!("/// Outbound gateway for `:sends {}` effects.", gateway.target));
+        if gateway.methods.is_empty() {
+            // Declared but never called: keep the trait so signatures stay stable
+            self.emit_line(&format!("pub trait {} {{}}", gateway.trait_name));
+        } else {
+            self.emit_line(&format!("pub trait {} {{", gateway.trait_name));
+            self.indent += 1;
+            for method in &gateway.methods {
+                self.emit_line(&format!(
+                    "fn {}(&mut self, message: {}) -> {};",
+                    method.name,
+                    method.message,
+                    self.gateway_return_type()
+                ));
+            }
+            self.indent -= 1;
+            self.emit_line("}");
+        }
+
+        // In-memory implementation for tests
+        self.emit_line("");
+        self.emit_line(&format!(
+            "/// In-memory `{}` that records every message, for tests.",
+            gateway.trait_name
+        ));
+        self.emit_line("#[derive(Debug, Default)]");
+        self.emit_line(&format!("pub struct Recording{} {{", gateway.trait_name));
+        self.indent += 1;
+        for method in &gateway.methods {
+            self.emit_line(&format!("pub {}: Vec<{}>,", method.name, method.message));
+        }
+        self.emit_line("/// When set, every send fails with this reason.");
+        self.emit_line("pub fail_with: Option<String>,");
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+        if gateway.methods.is_empty() {
+            self.emit_line(&format!(
+                "impl {} for Recording{} {{}}",
+                gateway.trait_name, gateway.trait_name
+            ));
+            return;
+        }
+        self.emit_line(&format!(
+            "impl {} for Recording{} {{",
+            gateway.trait_name, gateway.trait_name
+        ));
+        self.indent += 1;
+        for (i, method) in gateway.methods.iter().enumerate() {
+            if i > 0 {
+                self.emit_line("");
+            }
+            self.emit_line(&format!(
+                "fn {}(&mut self, message: {}) -> {} {{",
+                method.name,
+                method.message,
+                self.gateway_return_type()
+            ));
+            let (ready, end) = if self.async_fns { ("std::future::ready(", ")") } else { ("", "") };
+            self.indent += 1;
+            self.emit_line("if let Some(reason) = &self.fail_with {");
+            self.indent += 1;
+            self.emit_line(&format!("return {}Err(reason.clone()){};", ready, end));
+            self.indent -= 1;
+            self.emit_line("}");
+            self.emit_line(&format!("self.{}.push(message);", method.name));
+            self.emit_line(&format!("{}Ok(()){}", ready, end));
+            self.indent -= 1;
+            self.emit_line("}");
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    fn gateway_return_type(&self) -> &'static str {
+        if self.async_fns {
+            "impl std::future::Future<Output = Result<(), String>> + Send"
+        } else {
+            "Result<(), String>"
+        }
+    }
+
+    /// `(defn token-expired? [s Session] Bool ...)` → `pub fn token_expired(s: Session) -> bool`,
+    /// never async: helpers touch no store or gateway
+    fn emit_helper(&mut self, helper: &HelperDef, module: &Module, type_defs: &[&TypeDef], enums: &[EnumDef]) {
+        let mut params: Vec<String> = Vec::new();
+        if externs::needs_host_externs(&helper.body, module) {
+            params.push("externs: &impl Externs".to_string());
+        }
+        for param in &helper.params {
+            params.push(format!("{}: {}", to_snake(&param.name), type_expr_to_rust(&param.type_expr)));
+        }
+        self.emit_line(&format!(
+            "pub fn {}({}) -> {} {{",
+            to_snake(&helper.name.replace(['?', '!'], "")),
+            params.join(", "),
+            type_expr_to_rust(&helper.returns)
+        ));
+        self.indent += 1;
+        let func = helper.as_fn();
+        let option_queries = queries::option_matched_queries(&helper.body);
+        let ctx = EmitCtx {
+            return_type: "",
+            func: &func,
+            module,
+            type_defs,
+            stores: &[],
+            gateways: &[],
+            enums,
+            str_uuids: false,
+            option_queries: &option_queries,
+        };
+        self.emit_expr(&helper.body, &ctx);
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    fn emit_function(
+        &mut self,
+        func: &FnDef,
+        module: &Module,
+        type_defs: &[&TypeDef],
+        gateways: &[Gateway],
+        enums: &[EnumDef],
+    ) {
         let fn_name = to_snake(&func.name);
         let return_type = format!("{}Result", to_pascal(&func.name));
 
-        // Determine which store types are needed from effects
-        let store_types = collect_store_types(func, module);
+        // Determine which stores are needed from effects
+        let stores = collect_stores(func, module);
 
         // Build parameter list
         let mut params = Vec::new();
-        for (store_type, needs_mut) in &store_types {
-            if *needs_mut {
-                params.push(format!("store: &mut impl Store<{}>", store_type));
+        for store in &stores {
+            let store_trait = self.store_trait();
+            if store.needs_mut {
+                params.push(format!("{}: &mut impl {}<{}>", store.param, store_trait, store.type_name));
             } else {
-                params.push(format!("store: &impl Store<{}>", store_type));
+                params.push(format!("{}: &impl {}<{}>", store.param, store_trait, store.type_name));
             }
         }
+        let fn_gateways: Vec<&Gateway> = gateways
+            .iter()
+            .filter(|g| declared_sends(func, module).contains(&g.target.as_str()))
+            .collect();
+        for gateway in &fn_gateways {
+            params.push(format!("{}: &mut impl {}", to_snake(&gateway.target), gateway.trait_name));
+        }
+        if externs::needs_host_externs(&func.body, module) {
+            params.push("externs: &impl Externs".to_string());
+        }
         for param in &func.params {
             match &param.type_expr {
                 TypeExpr::Map(_) => {
@@ -460,20 +941,30 @@ impl RustV2Codegen {
         if func.total {
             self.emit_line("/// Total: handles all cases exhaustively");
         }
+        for store in untyped_stores(func, module) {
+            self.emit_line(&format!("/// No {} parameter: it holds no type defined in this module", store));
+        }
 
         self.emit_line(&format!(
-            "pub fn {}({}) -> {} {{",
+            "pub {}fn {}({}) -> {} {{",
+            if self.async_fns { "async " } else { "" },
             fn_name,
             params.join(", "),
             return_type
         ));
         self.indent += 1;
 
+        let option_queries = queries::option_matched_queries(&func.body);
         let ctx = EmitCtx {
             return_type: &return_type,
             func,
             module,
             type_defs,
+            stores: &stores,
+            gateways: &fn_gateways,
+            enums,
+            str_uuids: true,
+            option_queries: &option_queries,
         };
         self.emit_expr(&func.body, &ctx);
 
@@ -486,9 +977,7 @@ impl RustV2Codegen {
             Expr::Let { bindings, body, .. } => {
                 for (name, value) in bindings {
                     self.emit_indent();
-                    self.output.push_str(&format!("let {} = ", to_snake(name)));
-                    self.emit_expr_inline(value, ctx);
-                    self.output.push_str(";\n");
+                    self.emit_binding(name, value, ctx);
                 }
                 self.emit_expr(body, ctx);
             }
@@ -498,18 +987,18 @@ impl RustV2Codegen {
                 self.emit_expr_inline(expr, ctx);
                 self.output.push_str(" {\n");
                 self.indent += 1;
-                for arm in arms {
+                let enum_def = enums::match_enum(expr, arms, ctx.module, ctx.func, ctx.enums);
+                let insert_error = matches!(&**expr, Expr::Call { name, args, .. } if name == "insert!" && has_refs(args, ctx));
+                // Only rows with (ref T) fields can miss a reference
+                for arm in arms.iter().filter(|arm| insert_error || !is_missing_reference(&arm.pattern)) {
                     self.emit_indent();
-                    self.emit_pattern(&arm.pattern, ctx);
+                    self.emit_pattern(&arm.pattern, enum_def, insert_error);
                     self.output.push_str(" => ");
                     self.emit_expr_inline(&arm.body, ctx);
                     self.output.push_str(",\n");
                 }
                 // Add catch-all arm if any pattern matches on Err with a keyword (StoreError)
-                if needs_err_catchall(arms) {
-                    self.emit_indent();
-                    self.output.push_str("Err(e) => panic!(\"unexpected store error: {:?}\", e),\n");
-                }
+                self.emit_store_error_arm(arms, ctx);
                 self.indent -= 1;
                 self.emit_indent();
                 self.output.push_str("}\n");
@@ -546,17 +1035,26 @@ impl RustV2Codegen {
     fn emit_expr_inline(&mut self, expr: &Expr, ctx: &EmitCtx) {
         match expr {
             Expr::Ref(name, _) => {
-                self.output.push_str(&to_snake(name));
-            }
-            Expr::Keyword(kw, _) => {
-                self.output.push_str(&format!("\"{}\"", kw));
+                match ctx.stores.iter().find(|s| s.target == *name) {
+                    Some(store) => self.output.push_str(&store.param),
+                    None => self.output.push_str(&to_snake(name)),
+                }
             }
+            Expr::Keyword(kw, _) => match enums::keyword_enum(kw, ctx.enums) {
+                Some(def) => {
+                    self.output.push_str(&format!("{}::{}", def.name, EnumDef::variant_ident(kw)));
+                }
+                None => self.output.push_str(&format!("\"{}\"", kw)),
+            },
             Expr::StringLit(s, _) => {
                 self.output.push_str(&format!("\"{}\"", s));
             }
             Expr::IntLit(n, _) => {
                 self.output.push_str(&n.to_string());
             }
+            Expr::FloatLit(n, _) => {
+                self.output.push_str(&format!("{:?}", n));
+            }
             Expr::BoolLit(b, _) => {
                 self.output.push_str(&b.to_string());
             }
@@ -572,7 +1070,7 @@ impl RustV2Codegen {
                 let variant_info = ctx.func.returns.variants.iter().find(|v| {
                     matches!(&v.kind, VariantKind::Err { tag: t, .. } if t == tag)
                 });
-                let uses_named_fields = variant_info.map_or(false, |v| {
+                let uses_named_fields = variant_info.is_some_and(|v| {
                     matches!(&v.kind, VariantKind::Err { payload: TypeExpr::Map(_), .. })
                 });
 
@@ -635,8 +1133,8 @@ impl RustV2Codegen {
                     self.output.push(')');
                 }
             }
-            Expr::Call { name, args, .. } => {
-                self.emit_call(name, args, ctx);
+            Expr::Call { name, args, span } => {
+                self.emit_call(name, args, span, ctx);
             }
             Expr::FieldAccess { expr, field, .. } => {
                 self.emit_expr_inline(expr, ctx);
@@ -655,6 +1153,36 @@ impl RustV2Codegen {
                 }
                 self.output.push_str(" }");
             }
+            Expr::ListLit(items, _) => {
+                self.output.push_str("vec![");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.emit_expr_inline(item, ctx);
+                }
+                self.output.push(']');
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.emit_closure(params, body, ctx);
+            }
+            // (for-each [x xs] body) → for x in xs.iter().cloned() { let _ = body; }, or a
+            // match that returns the first store error when the body writes
+            // A loop rather than a closure, so the body may await in async mode
+            Expr::ForEach { var, list, body, .. } => {
+                self.output.push_str(&format!("for {} in ", to_snake(var)));
+                self.emit_expr_inline(list, ctx);
+                if is_store_write(body) {
+                    // The first failed write ends the function like a let-bound one
+                    self.output.push_str(".iter().cloned() { match ");
+                    self.emit_expr_inline(body, ctx);
+                    self.output.push_str(&format!(" {{ Ok(_) => {{}} {} }} }}", store_error_return(ctx)));
+                } else {
+                    self.output.push_str(".iter().cloned() { let _ = ");
+                    self.emit_expr_inline(body, ctx);
+                    self.output.push_str("; }");
+                }
+            }
             Expr::Wildcard(_) => {
                 self.output.push('_');
             }
@@ -663,10 +1191,7 @@ impl RustV2Codegen {
                 self.indent += 1;
                 for (name, value) in bindings {
                     self.emit_indent();
-                    self.output
-                        .push_str(&format!("let {} = ", to_snake(name)));
-                    self.emit_expr_inline(value, ctx);
-                    self.output.push_str(";\n");
+                    self.emit_binding(name, value, ctx);
                 }
                 self.emit_indent();
                 self.emit_expr_inline(body, ctx);
@@ -680,17 +1205,17 @@ impl RustV2Codegen {
                 self.emit_expr_inline(expr, ctx);
                 self.output.push_str(" {\n");
                 self.indent += 1;
-                for arm in arms {
+                let enum_def = enums::match_enum(expr, arms, ctx.module, ctx.func, ctx.enums);
+                let insert_error = matches!(&**expr, Expr::Call { name, args, .. } if name == "insert!" && has_refs(args, ctx));
+                // Only rows with (ref T) fields can miss a reference
+                for arm in arms.iter().filter(|arm| insert_error || !is_missing_reference(&arm.pattern)) {
                     self.emit_indent();
-                    self.emit_pattern(&arm.pattern, ctx);
+                    self.emit_pattern(&arm.pattern, enum_def, insert_error);
                     self.output.push_str(" => ");
                     self.emit_expr_inline(&arm.body, ctx);
                     self.output.push_str(",\n");
                 }
-                if needs_err_catchall(arms) {
-                    self.emit_indent();
-                    self.output.push_str("Err(e) => panic!(\"unexpected store error: {:?}\", e),\n");
-                }
+                self.emit_store_error_arm(arms, ctx);
                 self.indent -= 1;
                 self.emit_indent();
                 self.output.push('}');
@@ -712,8 +1237,34 @@ impl RustV2Codegen {
         }
     }
 
-    fn emit_call(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
-        let clean_name = name.replace('?', "").replace('!', "");
+    fn emit_call(&mut self, name: &str, args: &[Expr], span: &Span, ctx: &EmitCtx) {
+        // Operators first: cleaning the name would turn `!=` into `=`
+        if let Some((_, op)) = operators::operator(name) {
+            self.emit_operator(op, args, ctx);
+            return;
+        }
+        if let Some(ext) = ctx
+            .module
+            .externs
+            .iter()
+            .find(|e| e.name == name && externs::sends_through(e, ctx.module).is_none())
+        {
+            self.output.push_str(&format!("externs.{}(", to_snake(&name.replace(['?', '!'], ""))));
+            let params: Vec<&TypeExpr> = ext.params.iter().collect();
+            self.emit_owned_args(args, &params, ctx);
+            self.output.push(')');
+            return;
+        }
+        if ctx.module.helpers.iter().any(|h| h.name == name) {
+            self.emit_helper_call(name, args, ctx);
+            return;
+        }
+        let clean_name = name.replace(['?', '!'], "");
+
+        if let Some((gateway, payload)) = gateway_call(name, args, ctx.gateways) {
+            self.emit_gateway_call(name, gateway, payload, ctx);
+            return;
+        }
 
         match clean_name.as_str() {
             "validate-uuid" | "validate_uuid" => {
@@ -729,27 +1280,117 @@ impl RustV2Codegen {
                 self.output.push(')');
             }
             "query" => {
-                // query(user-store, {id: uuid}) → store.query_by_id(&uuid)
                 // args[0] = store ref, args[1] = map with query fields
-                self.output.push_str("store.query_by_id(");
-                if args.len() > 1 {
-                    if let Expr::MapLit(entries, _) = &args[1] {
-                        // Extract the ID value from the map
-                        if let Some((_, val)) = entries.iter().find(|(k, _)| k == "id") {
-                            self.output.push('&');
-                            self.emit_expr_inline(val, ctx);
+                let store = store_param(args, ctx);
+                let entries: &[(String, Expr)] = match args.get(1) {
+                    Some(Expr::MapLit(entries, _)) => entries,
+                    _ => &[],
+                };
+                let type_def = stored_type(args, ctx);
+                match entries {
+                    // query(user-store, {id: id}) with a `&str` id → parsed first; no entity
+                    // has an id that does not parse
+                    [(key, val)] if key == "id" && ctx.is_str_uuid(val) => {
+                        self.output.push_str("match Uuid::parse_str(");
+                        self.emit_expr_inline(val, ctx);
+                        self.output.push_str(&format!(
+                            ") {{ Ok(id) => {}.query_by_id(&id){}, Err(_) => None }}",
+                            store,
+                            self.await_suffix()
+                        ));
+                    }
+                    // query(user-store, {id: uuid}) → store.query_by_id(&uuid)
+                    [(key, val)] if key == "id" => {
+                        self.output.push_str(&format!("{}.query_by_id(&", store));
+                        self.emit_expr_inline(val, ctx);
+                        self.output.push(')');
+                        self.output.push_str(self.await_suffix());
+                    }
+                    // query(user-store, {email: e}) → User::find_by_email(store, &e), an Option
+                    [(key, val)]
+                        if type_def.is_some_and(|t| {
+                            t.fields.iter().any(|f| f.name == *key && f.unique_within.is_some())
+                        }) =>
+                    {
+                        let type_name = type_def.map_or("", |t| t.name.as_str());
+                        self.output.push_str(&format!(
+                            "{}::find_by_{}({}, &",
+                            type_name,
+                            to_snake(key),
+                            store
+                        ));
+                        self.emit_expr_inline(val, ctx);
+                        self.output.push(')');
+                        self.output.push_str(self.await_suffix());
+                    }
+                    // Any other filter → every matching entity, as a Vec, or the first one
+                    // when the result is matched as an Option
+                    _ => {
+                        let first = ctx.option_queries.contains(span);
+                        self.output.push_str(&format!(
+                            "{}.list_all(){}.into_iter().{}(|e| ",
+                            store,
+                            self.await_suffix(),
+                            if first { "find" } else { "filter" }
+                        ));
+                        if entries.is_empty() {
+                            self.output.push_str("true");
+                        }
+                        for (i, (key, val)) in entries.iter().enumerate() {
+                            if i > 0 {
+                                self.output.push_str(" && ");
+                            }
+                            let field = type_def.and_then(|t| t.fields.iter().find(|f| f.name == *key));
+                            let access = format!("e.{}", to_snake(key));
+                            match field.map(|f| uuid_comparison(&f.type_expr, &access)) {
+                                // A `&str` UUID param is parsed; one that does not parse matches nothing
+                                Some(Some(compare)) if ctx.is_str_uuid(val) => {
+                                    self.output.push_str("Uuid::parse_str(");
+                                    self.emit_expr_inline(val, ctx);
+                                    self.output.push_str(&format!(").is_ok_and(|id| {})", compare));
+                                }
+                                _ => {
+                                    self.output.push_str(&format!("{} == ", access));
+                                    self.emit_expr_inline(val, ctx);
+                                }
+                            }
                         }
+                        self.output.push_str(if first { ")" } else { ").collect::<Vec<_>>()" });
                     }
                 }
-                self.output.push(')');
             }
             "insert" => {
+                let checks = ref_checks(args, ctx, self.await_suffix());
+                if !checks.is_empty() {
+                    // { let item = ..; if <missing ref> { Err(..) } else { store.insert(item) } }
+                    self.output.push_str("{ let item = ");
+                    if args.len() > 1 {
+                        self.emit_expr_inline(&args[1], ctx);
+                    }
+                    self.output.push_str("; ");
+                    for (missing, field) in &checks {
+                        self.output.push_str(&format!(
+                            "if {} {{ Err(InsertError::MissingReference {{ field: \"{}\".into() }}) }} else ",
+                            missing, field
+                        ));
+                    }
+                    self.output.push_str(&format!(
+                        "{{ {}.insert(item){}.map_err(InsertError::Store) }} }}",
+                        store_param(args, ctx),
+                        self.await_suffix()
+                    ));
+                    return;
+                }
                 // insert!(user-store, build(User, input)) → store.insert(...)
-                self.output.push_str("store.insert(");
+                self.output.push_str(&format!("{}.insert(", store_param(args, ctx)));
                 if args.len() > 1 {
                     self.emit_expr_inline(&args[1], ctx);
                 }
                 self.output.push(')');
+                self.output.push_str(self.await_suffix());
+                if has_refs(args, ctx) {
+                    self.output.push_str(".map_err(InsertError::Store)");
+                }
             }
             "build" => {
                 // build(User, input) → User::from_input(input.clone())
@@ -794,6 +1435,11 @@ impl RustV2Codegen {
                     self.output.push(')');
                 }
             }
+            // map(f, xs) → xs.iter().cloned().map(f).collect::<Vec<_>>(), and likewise
+            // filter and fold
+            "map" | "filter" | "fold" if args.len() == if clean_name == "fold" { 3 } else { 2 } => {
+                self.emit_iteration(&clean_name, args, ctx);
+            }
             "non-empty" | "non_empty" => {
                 // non-empty?(errors) → non_empty(&errors)
                 self.output.push_str("non_empty(&");
@@ -817,7 +1463,188 @@ impl RustV2Codegen {
         }
     }
 
-    fn emit_pattern(&mut self, pattern: &Pattern, ctx: &EmitCtx) {
+    /// Arm for store errors no pattern matched: the declared `:on-store-error` variant,
+    /// or a panic in functions that are not `:total` (the semantic check rejects
+    /// `:total` functions with neither).
+    /// `let x = value;`. A bound `insert!` binds the stored row: a store error returns
+    /// the `:on-store-error` variant, or panics in functions that are not `:total`.
+    fn emit_binding(&mut self, name: &str, value: &Expr, ctx: &EmitCtx) {
+        self.output.push_str(&format!("let {} = ", to_snake(name)));
+        if !is_store_write(value) {
+            self.emit_expr_inline(value, ctx);
+            self.output.push_str(";\n");
+            return;
+        }
+        self.output.push_str("match ");
+        self.emit_expr_inline(value, ctx);
+        self.output.push_str(&format!(" {{ Ok(row) => row, {} }};\n", store_error_return(ctx)));
+    }
+
+    fn emit_store_error_arm(&mut self, arms: &[MatchArm], ctx: &EmitCtx) {
+        if !needs_err_catchall(arms) {
+            return;
+        }
+        let arm = match &ctx.func.on_store_error {
+            Some(tag) => match store_error_arm(ctx.func, tag, ctx.return_type) {
+                Some(arm) => arm,
+                None => return,
+            },
+            None if !ctx.func.total => "Err(e) => panic!(\"unexpected store error: {:?}\", e)".to_string(),
+            None => return,
+        };
+        self.emit_indent();
+        self.output.push_str(&arm);
+        self.output.push_str(",\n");
+    }
+
+    /// An iterator chain over the last argument, which is cloned element by element
+    /// so the list stays usable afterwards
+    fn emit_iteration(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        let (f, list) = (&args[0], &args[args.len() - 1]);
+        self.emit_expr_inline(list, ctx);
+        self.output.push_str(".iter().cloned()");
+        match (name, f) {
+            ("map", _) => {
+                self.output.push_str(".map(");
+                self.emit_expr_inline(f, ctx);
+                self.output.push_str(").collect::<Vec<_>>()");
+            }
+            // The predicate sees `&T`; bind an owned copy so its body reads like map's
+            ("filter", Expr::Lambda { params, body, .. }) => {
+                let param = params.first().map(|p| to_snake(p)).unwrap_or_else(|| "_".to_string());
+                self.output.push_str(&format!(".filter(|{0}| {{ let {0} = {0}.clone(); ", param));
+                self.emit_expr_inline(body, ctx);
+                self.output.push_str(" }).collect::<Vec<_>>()");
+            }
+            ("filter", _) => {
+                self.output.push_str(".filter(|item| ");
+                self.emit_expr_inline(f, ctx);
+                self.output.push_str("(item.clone())).collect::<Vec<_>>()");
+            }
+            _ => {
+                self.output.push_str(".fold(");
+                self.emit_expr_inline(&args[1], ctx);
+                self.output.push_str(", ");
+                self.emit_expr_inline(f, ctx);
+                self.output.push(')');
+            }
+        }
+    }
+
+    /// `(fn [a b] body)` → `|a, b| body`
+    fn emit_closure(&mut self, params: &[String], body: &Expr, ctx: &EmitCtx) {
+        let params: Vec<String> = params.iter().map(|p| to_snake(p)).collect();
+        self.output.push_str(&format!("|{}| ", params.join(", ")));
+        self.emit_expr_inline(body, ctx);
+    }
+
+    /// `(token-expired? s)` → `token_expired(s.clone())`, passing `externs` first to
+    /// helpers that reach an extern
+    fn emit_helper_call(&mut self, name: &str, args: &[Expr], ctx: &EmitCtx) {
+        self.output.push_str(&to_snake(&name.replace(['?', '!'], "")));
+        self.output.push('(');
+        let helper = ctx.module.helpers.iter().find(|h| h.name == name);
+        if helper.is_some_and(|h| externs::needs_host_externs(&h.body, ctx.module)) {
+            self.output.push_str(if args.is_empty() { "externs" } else { "externs, " });
+        }
+        let params: Vec<&TypeExpr> = helper.iter().flat_map(|h| &h.params).map(|p| &p.type_expr).collect();
+        self.emit_owned_args(args, &params, ctx);
+        self.output.push(')');
+    }
+
+    /// Arguments to helpers and externs, which take them by value: the caller may still
+    /// need what it passes, so names and fields are cloned, and text literals owned.
+    /// A `&str` UUID param is parsed where the callee takes a `Uuid`; one that does not
+    /// parse becomes the nil UUID, which, as in `query`, names no entity
+    fn emit_owned_args(&mut self, args: &[Expr], params: &[&TypeExpr], ctx: &EmitCtx) {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            if ctx.is_str_uuid(arg) {
+                let takes_uuid = matches!(params.get(i), Some(TypeExpr::Named(n)) if n == "UUID");
+                if takes_uuid {
+                    self.output.push_str("Uuid::parse_str(");
+                    self.emit_expr_inline(arg, ctx);
+                    self.output.push_str(").unwrap_or(Uuid::nil())");
+                } else {
+                    self.emit_expr_inline(arg, ctx);
+                    self.output.push_str(".to_string()");
+                }
+                continue;
+            }
+            self.emit_expr_inline(arg, ctx);
+            match arg {
+                Expr::Ref(..) | Expr::FieldAccess { .. } => self.output.push_str(".clone()"),
+                Expr::StringLit(..) => self.output.push_str(".to_string()"),
+                _ => {}
+            }
+        }
+    }
+
+    /// `(- a b)` → `a - b` and `(not a)` → `!a`, with operands that are operators
+    /// themselves in parentheses
+    fn emit_operator(&mut self, op: &str, args: &[Expr], ctx: &EmitCtx) {
+        if let [operand] = args {
+            self.output.push_str(op);
+            self.emit_operand(operand, ctx);
+            return;
+        }
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(&format!(" {} ", op));
+            }
+            self.emit_operand(arg, ctx);
+        }
+    }
+
+    fn emit_operand(&mut self, arg: &Expr, ctx: &EmitCtx) {
+        if operators::is_operator_call(arg) {
+            self.output.push('(');
+            self.emit_expr_inline(arg, ctx);
+            self.output.push(')');
+        } else {
+            self.emit_expr_inline(arg, ctx);
+        }
+    }
+
+    /// deliver-email(recipient, body) → email_gateway.deliver_email(DeliverEmailMessage { .. })
+    fn emit_gateway_call(&mut self, name: &str, gateway: &Gateway, payload: &[Expr], ctx: &EmitCtx) {
+        let Some(method) = gateway.methods.iter().find(|m| m.call == name) else {
+            return;
+        };
+        self.output.push_str(&format!(
+            "{}.{}({} {{ ",
+            to_snake(&gateway.target),
+            method.name,
+            method.message
+        ));
+        let values: Vec<&Expr> = match payload {
+            [Expr::MapLit(entries, _)] => entries.iter().map(|(_, v)| v).collect(),
+            _ => payload.iter().collect(),
+        };
+        for (i, ((field, rust_type), value)) in method.fields.iter().zip(values).enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.output.push_str(&format!("{}: ", field));
+            self.emit_expr_inline(value, ctx);
+            match value {
+                Expr::StringLit(..) => self.output.push_str(".to_string()"),
+                Expr::Ref(..) | Expr::FieldAccess { .. } if rust_type == "String" => {
+                    self.output.push_str(".to_string()")
+                }
+                Expr::Ref(..) | Expr::FieldAccess { .. } => self.output.push_str(".clone()"),
+                _ => {}
+            }
+        }
+        self.output.push_str(" })");
+        self.output.push_str(self.await_suffix());
+    }
+
+    /// `enum_def` is the enum the match is over, whose variants keyword patterns name.
+    /// `insert_error`: the value matched is an `InsertError` result rather than a `StoreError` one.
+    fn emit_pattern(&mut self, pattern: &Pattern, enum_def: Option<&EnumDef>, insert_error: bool) {
         match pattern {
             Pattern::Wildcard(_) => self.output.push('_'),
             Pattern::Var(name, _) => self.output.push_str(&to_snake(name)),
@@ -832,7 +1659,7 @@ impl RustV2Codegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg, ctx);
+                                self.emit_pattern(arg, enum_def, insert_error);
                             }
                         }
                         self.output.push(')');
@@ -846,7 +1673,7 @@ impl RustV2Codegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg, ctx);
+                                self.emit_pattern(arg, enum_def, insert_error);
                             }
                         }
                         self.output.push(')');
@@ -859,14 +1686,20 @@ impl RustV2Codegen {
                             // Check if first arg is a keyword — map to StoreError variant
                             if let Some(Pattern::Keyword(kw, _)) = args.first() {
                                 let variant = to_pascal(kw);
-                                self.output
-                                    .push_str(&format!("StoreError::{} {{ .. }}", variant));
+                                if kw == "missing-reference" {
+                                    self.output.push_str("InsertError::MissingReference { .. }");
+                                } else if insert_error {
+                                    self.output.push_str(&format!("InsertError::Store(StoreError::{} {{ .. }})", variant));
+                                } else {
+                                    self.output
+                                        .push_str(&format!("StoreError::{} {{ .. }}", variant));
+                                }
                             } else {
                                 for (i, arg) in args.iter().enumerate() {
                                     if i > 0 {
                                         self.output.push_str(", ");
                                     }
-                                    self.emit_pattern(arg, ctx);
+                                    self.emit_pattern(arg, enum_def, insert_error);
                                 }
                             }
                         }
@@ -883,16 +1716,19 @@ impl RustV2Codegen {
                                 if i > 0 {
                                     self.output.push_str(", ");
                                 }
-                                self.emit_pattern(arg, ctx);
+                                self.emit_pattern(arg, enum_def, insert_error);
                             }
                             self.output.push(')');
                         }
                     }
                 }
             }
-            Pattern::Keyword(kw, _) => {
-                self.output.push_str(&format!("\"{}\"", kw));
-            }
+            Pattern::Keyword(kw, _) => match enum_def {
+                Some(def) if def.variants.contains(kw) => {
+                    self.output.push_str(&format!("{}::{}", def.name, EnumDef::variant_ident(kw)));
+                }
+                _ => self.output.push_str(&format!("\"{}\"", kw)),
+            },
         }
     }
 
@@ -919,24 +1755,390 @@ struct EmitCtx<'a> {
     func: &'a FnDef,
     module: &'a Module,
     type_defs: &'a [&'a TypeDef],
+    stores: &'a [StoreParam],
+    gateways: &'a [&'a Gateway],
+    enums: &'a [EnumDef],
+    /// Whether UUID params arrive as `&str`, as in functions; helpers take them as `Uuid`
+    str_uuids: bool,
+    /// Queries whose result is matched with `(some x)` and `(none)`
+    option_queries: &'a [Span],
+}
+
+impl EmitCtx<'_> {
+    /// Whether `expr` is a UUID param passed as `&str`, to be parsed where a `Uuid` is needed
+    fn is_str_uuid(&self, expr: &Expr) -> bool {
+        self.str_uuids
+            && matches!(expr, Expr::Ref(name, _) if self.func.params.iter().any(|p| {
+                p.name == *name && matches!(&p.type_expr, TypeExpr::Named(n) if n == "UUID")
+            }))
+    }
+}
+
+/// A store a function touches through its effects, and the parameter it is passed as.
+struct StoreParam {
+    /// Effect target, e.g. "user-store"
+    target: String,
+    /// Entity type held by the store, e.g. "User"
+    type_name: String,
+    needs_mut: bool,
+    /// "store" when the function touches a single store, otherwise e.g. "user_store"
+    param: String,
+}
+
+/// An outbound gateway (`:sends` target) and the messages functions send through it.
+struct Gateway {
+    /// Effect target, e.g. "email-gateway"
+    target: String,
+    /// e.g. "EmailGateway"
+    trait_name: String,
+    methods: Vec<GatewayMethod>,
+}
+
+struct GatewayMethod {
+    /// The Pact call routed to this method, e.g. "deliver-email"
+    call: String,
+    /// e.g. "deliver_email"
+    name: String,
+    /// Payload struct, e.g. "DeliverEmailMessage"
+    message: String,
+    /// (field, rust type)
+    fields: Vec<(String, String)>,
+}
+
+/// `:sends` targets a function declares. `http-response` is the function's own
+/// return value, not a gateway.
+fn declared_sends<'a>(func: &FnDef, module: &'a Module) -> Vec<&'a str> {
+    let mut targets = Vec::new();
+    for effect_name in &func.effects {
+        if let Some(es) = module.effect_sets.iter().find(|es| &es.name == effect_name) {
+            for effect in &es.effects {
+                if matches!(effect.kind, EffectKind::Sends)
+                    && effect.target != "http-response"
+                    && !targets.contains(&effect.target.as_str())
+                {
+                    targets.push(effect.target.as_str());
+                }
+            }
+        }
+    }
+    targets
+}
+
+/// Collect gateways from `:sends` effects, with one method per distinct call routed to them.
+fn collect_gateways(module: &Module) -> Vec<Gateway> {
+    let mut gateways: Vec<Gateway> = Vec::new();
+    for es in &module.effect_sets {
+        for effect in &es.effects {
+            if matches!(effect.kind, EffectKind::Sends)
+                && effect.target != "http-response"
+                && !gateways.iter().any(|g| g.target == effect.target)
+            {
+                gateways.push(Gateway {
+                    target: effect.target.clone(),
+                    trait_name: to_pascal(&effect.target),
+                    methods: Vec::new(),
+                });
+            }
+        }
+    }
+
+    for func in &module.functions {
+        let sends = declared_sends(func, module);
+        let mut calls = Vec::new();
+        collect_calls(&func.body, &mut calls);
+        for (name, args) in calls {
+            let Some(gateway) = gateways
+                .iter_mut()
+                .find(|g| sends.contains(&g.target.as_str()) && routes_to(name, args, &g.target, module))
+            else {
+                continue;
+            };
+            if gateway.methods.iter().any(|m| m.call == name) {
+                continue;
+            }
+            let payload = gateway_payload(args, &gateway.target);
+            let declared = module.externs.iter().find(|e| e.name == name);
+            let mut fields: Vec<(String, String)> = match payload {
+                [Expr::MapLit(entries, _)] => entries
+                    .iter()
+                    .map(|(k, v)| (to_snake(k), message_field_type(v, func)))
+                    .collect(),
+                _ => payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| match v {
+                        Expr::Ref(r, _) => (to_snake(r.rsplit('.').next().unwrap_or(r)), message_field_type(v, func)),
+                        Expr::FieldAccess { field, .. } => (to_snake(field), message_field_type(v, func)),
+                        _ => (format!("arg{}", i), message_field_type(v, func)),
+                    })
+                    .collect(),
+            };
+            // An extern's message is typed by its declaration, not by what one caller passes
+            if let Some(ext) = declared {
+                for ((_, rust_type), param) in fields.iter_mut().zip(&ext.params) {
+                    *rust_type = match param {
+                        TypeExpr::Named(n) if n == "UUID" => "String".to_string(),
+                        t => type_expr_to_rust(t),
+                    };
+                }
+            }
+            gateway.methods.push(GatewayMethod {
+                call: name.to_string(),
+                name: to_snake(name),
+                message: format!("{}Message", to_pascal(&name.replace(['?', '!'], ""))),
+                fields,
+            });
+        }
+    }
+    gateways
+}
+
+/// A call goes through a gateway only when it sends explicitly: its first argument
+/// names the gateway, or it calls an extern whose effects send through the gateway.
+fn routes_to(name: &str, args: &[Expr], target: &str, module: &Module) -> bool {
+    matches!(args.first(), Some(Expr::Ref(r, _)) if r == target)
+        || module
+            .externs
+            .iter()
+            .any(|e| e.name == name && externs::sends_through(e, module) == Some(target))
+}
+
+/// The message arguments of a gateway call, without a leading gateway reference.
+fn gateway_payload<'e>(args: &'e [Expr], target: &str) -> &'e [Expr] {
+    match args.first() {
+        Some(Expr::Ref(r, _)) if r == target => &args[1..],
+        _ => args,
+    }
+}
+
+fn gateway_call<'g, 'e>(name: &str, args: &'e [Expr], gateways: &[&'g Gateway]) -> Option<(&'g Gateway, &'e [Expr])> {
+    gateways
+        .iter()
+        .find(|g| g.methods.iter().any(|m| m.call == name))
+        .map(|g| (*g, gateway_payload(args, &g.target)))
+}
+
+/// Rust type of a message field, from the value sent: params keep their type
+/// (UUID params arrive as `&str`), literals their literal type, anything else is text.
+fn message_field_type(value: &Expr, func: &FnDef) -> String {
+    match value {
+        Expr::IntLit(..) => "i64".to_string(),
+        Expr::FloatLit(..) => "f64".to_string(),
+        Expr::BoolLit(..) => "bool".to_string(),
+        Expr::Ref(name, _) => func
+            .params
+            .iter()
+            .find(|p| p.name == *name)
+            .map(|p| match &p.type_expr {
+                TypeExpr::Named(n) if n == "UUID" => "String".to_string(),
+                TypeExpr::Map(_) => format!("{}Input", to_pascal(&func.name)),
+                t => type_expr_to_rust(t),
+            })
+            .unwrap_or_else(|| "String".to_string()),
+        _ => "String".to_string(),
+    }
+}
+
+fn collect_calls<'e>(expr: &'e Expr, calls: &mut Vec<(&'e str, &'e [Expr])>) {
+    match expr {
+        Expr::Call { name, args, .. } => {
+            calls.push((name, args));
+            for arg in args {
+                collect_calls(arg, calls);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (_, value) in bindings {
+                collect_calls(value, calls);
+            }
+            collect_calls(body, calls);
+        }
+        Expr::Match { expr, arms, .. } => {
+            collect_calls(expr, calls);
+            for arm in arms {
+                collect_calls(&arm.body, calls);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_calls(cond, calls);
+            collect_calls(then_branch, calls);
+            collect_calls(else_branch, calls);
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => collect_calls(expr, calls),
+        Expr::Err { payload, .. } => collect_calls(payload, calls),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                collect_calls(val, calls);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                collect_calls(item, calls);
+            }
+        }
+        Expr::Lambda { body, .. } => collect_calls(body, calls),
+        Expr::ForEach { list, body, .. } => {
+            collect_calls(list, calls);
+            collect_calls(body, calls);
+        }
+        _ => {}
+    }
+}
+
+/// The module's definition of the type held by the store a builtin's first argument names.
+fn stored_type<'a>(args: &[Expr], ctx: &'a EmitCtx) -> Option<&'a TypeDef> {
+    let target = match args.first() {
+        Some(Expr::Ref(name, _)) => name,
+        _ => return None,
+    };
+    let store = ctx.stores.iter().find(|s| s.target == *target)?;
+    ctx.type_defs.iter().copied().find(|t| t.name == store.type_name)
+}
+
+/// Whether `insert!` stores a type with `(ref T)` fields, and so fails with `InsertError`
+fn has_refs(args: &[Expr], ctx: &EmitCtx) -> bool {
+    stored_type(args, ctx).is_some_and(|t| !refs::ref_fields(t).is_empty())
+}
+
+/// For each `(ref T)` field of the type `insert!` stores whose store the function reads:
+/// the condition under which `item` names a missing row, and the field.
+fn ref_checks(args: &[Expr], ctx: &EmitCtx, await_suffix: &str) -> Vec<(String, String)> {
+    let Some(typedef) = stored_type(args, ctx) else {
+        return Vec::new();
+    };
+    refs::ref_fields(typedef)
+        .into_iter()
+        .filter_map(|(field, ref_type)| {
+            let target = refs::read_store(ctx.func, ctx.module, ref_type)?;
+            let store = ctx.stores.iter().find(|s| s.target == target)?;
+            let value = format!("item.{}", to_snake(&field.name));
+            let missing = |id: &str| format!("{}.query_by_id(&{}.0){}.is_none()", store.param, id, await_suffix);
+            let condition = match field.type_expr {
+                TypeExpr::Optional(_) => format!("match {} {{ Some(id) => {}, None => false }}", value, missing("id")),
+                _ => missing(&value),
+            };
+            Some((condition, field.name.clone()))
+        })
+        .collect()
+}
+
+/// How a stored UUID or `(ref T)` field compares with a parsed `id`, or `None` for
+/// fields holding no id
+fn uuid_comparison(type_expr: &TypeExpr, access: &str) -> Option<String> {
+    match type_expr {
+        TypeExpr::Named(n) if n == "UUID" => Some(format!("{} == id", access)),
+        TypeExpr::Ref(_) => Some(format!("{}.0 == id", access)),
+        TypeExpr::Optional(inner) => match inner.as_ref() {
+            TypeExpr::Named(n) if n == "UUID" => Some(format!("{} == Some(id)", access)),
+            TypeExpr::Ref(_) => Some(format!("{}.as_ref().is_some_and(|r| r.0 == id)", access)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The parameter for the store named by a builtin's first argument. `check_queries`
+/// rejects builtins naming no store, and `check_effects` those naming a store the
+/// function does not declare, so every store reaching here has a parameter.
+fn store_param(args: &[Expr], ctx: &EmitCtx) -> String {
+    let target = match args.first() {
+        Some(Expr::Ref(name, _)) => name.as_str(),
+        _ => "store",
+    };
+    ctx.stores
+        .iter()
+        .find(|s| s.target == target)
+        .map_or_else(|| to_snake(target), |s| s.param.clone())
+}
+
+fn is_missing_reference(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Constructor { name, args, .. }
+        if name == "err" && matches!(args.first(), Some(Pattern::Keyword(kw, _)) if kw == "missing-reference"))
+}
+
+fn is_store_write(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call { name, .. } => name == "insert!",
+        Expr::Let { body, .. } => is_store_write(body),
+        _ => false,
+    }
+}
+
+/// `Err(e) => return ..` for a store error nothing matches: the `:on-store-error`
+/// variant, or a panic in functions that are not `:total`
+fn store_error_return(ctx: &EmitCtx) -> String {
+    ctx.func
+        .on_store_error
+        .as_ref()
+        .and_then(|tag| store_error_arm(ctx.func, tag, ctx.return_type))
+        .map(|arm| arm.replacen("=> ", "=> return ", 1))
+        .unwrap_or_else(|| "Err(e) => panic!(\"unexpected store error: {:?}\", e)".to_string())
 }
 
 /// Check if a match expression has Err patterns with keyword arguments (StoreError variants)
-/// and thus needs a catch-all arm for exhaustiveness.
+/// and no `(err _)` arm, and thus needs a catch-all arm for exhaustiveness.
 fn needs_err_catchall(arms: &[MatchArm]) -> bool {
-    arms.iter().any(|arm| {
-        matches!(
-            &arm.pattern,
-            Pattern::Constructor { name, args, .. }
-            if name == "err" && args.iter().any(|a| matches!(a, Pattern::Keyword(_, _)))
-        )
-    })
+    let err_arm = |catch_all: bool| {
+        arms.iter().any(|arm| match &arm.pattern {
+            Pattern::Constructor { name, args, .. } if name == "err" => {
+                let is_catch_all = args
+                    .iter()
+                    .all(|a| matches!(a, Pattern::Wildcard(_) | Pattern::Var(_, _)));
+                is_catch_all == catch_all
+            }
+            _ => false,
+        })
+    };
+    err_arm(false) && !err_arm(true)
 }
 
-/// Collect store types needed by a function based on its effects.
-/// Returns Vec<(TypeName, needs_mut)>
-fn collect_store_types(func: &FnDef, module: &Module) -> Vec<(String, bool)> {
-    let mut stores: Vec<(String, bool)> = Vec::new();
+/// `Err(e) => Result::Variant(..)` for the variant named by `:on-store-error`.
+/// The store error is rendered into a `String` payload, or dropped for `{}`/`Unit`.
+fn store_error_arm(func: &FnDef, tag: &str, return_type: &str) -> Option<String> {
+    let payload = func.returns.variants.iter().find_map(|v| match &v.kind {
+        VariantKind::Err { tag: t, payload, .. } if t == tag => Some(payload),
+        _ => None,
+    })?;
+    let variant = to_pascal(tag);
+    match payload {
+        TypeExpr::Map(fields) if fields.is_empty() => Some(format!("Err(_) => {}::{} {{}}", return_type, variant)),
+        TypeExpr::Map(fields) if fields.len() == 1 => Some(format!(
+            "Err(e) => {}::{} {{ {}: format!(\"{{:?}}\", e) }}",
+            return_type,
+            variant,
+            to_snake(&fields[0].0)
+        )),
+        TypeExpr::Named(name) if name == "Unit" => Some(format!("Err(_) => {}::{}", return_type, variant)),
+        TypeExpr::Named(name) if name == "String" => {
+            Some(format!("Err(e) => {}::{}(format!(\"{{:?}}\", e))", return_type, variant))
+        }
+        _ => None,
+    }
+}
+
+/// Collect the stores a function touches based on its effects.
+/// A single store is passed as `store`; several get one parameter each, named after the store.
+/// Stores the function's effects name that hold no type defined here, and so are not
+/// among its parameters
+fn untyped_stores<'a>(func: &FnDef, module: &'a Module) -> Vec<&'a str> {
+    let mut untyped: Vec<&str> = Vec::new();
+    for effect_set in module.effect_sets.iter().filter(|es| func.effects.contains(&es.name)) {
+        for effect in effect_set.effects.iter().filter(|e| !matches!(e.kind, EffectKind::Sends)) {
+            if naming::stored_type(&effect.target, module).is_none() && !untyped.contains(&effect.target.as_str()) {
+                untyped.push(&effect.target);
+            }
+        }
+    }
+    untyped
+}
+
+fn collect_stores(func: &FnDef, module: &Module) -> Vec<StoreParam> {
+    let mut stores: Vec<StoreParam> = Vec::new();
     for effect_name in &func.effects {
         if let Some(effect_set) = module.effect_sets.iter().find(|es| &es.name == effect_name) {
             for effect in &effect_set.effects {
@@ -944,41 +2146,44 @@ fn collect_store_types(func: &FnDef, module: &Module) -> Vec<(String, bool)> {
                 if matches!(effect.kind, EffectKind::Sends) {
                     continue;
                 }
+                // A store holding no type defined here has no item type to pass it with;
+                // the queries pass rejects a body touching it
+                let Some(type_def) = naming::stored_type(&effect.target, module) else {
+                    continue;
+                };
 
-                // Convert store name like "user-store" to type name "User"
-                let type_name = store_target_to_type(&effect.target);
                 let needs_mut = matches!(effect.kind, EffectKind::Writes);
 
-                // Check if we already have this store type
-                if let Some(existing) = stores.iter_mut().find(|(t, _)| t == &type_name) {
+                // Check if we already have this store
+                if let Some(existing) = stores.iter_mut().find(|s| s.target == effect.target) {
                     // Upgrade to mut if any effect needs writes
                     if needs_mut {
-                        existing.1 = true;
+                        existing.needs_mut = true;
                     }
                 } else {
-                    stores.push((type_name, needs_mut));
+                    stores.push(StoreParam {
+                        target: effect.target.clone(),
+                        type_name: type_def.name.clone(),
+                        needs_mut,
+                        param: to_snake(&effect.target),
+                    });
                 }
             }
         }
     }
+    if let [single] = stores.as_mut_slice() {
+        single.param = "store".to_string();
+    }
     stores
 }
 
-/// Convert a store target name like "user-store" to a type name like "User"
-fn store_target_to_type(target: &str) -> String {
-    let name = target
-        .strip_suffix("-store")
-        .or_else(|| target.strip_suffix("_store"))
-        .unwrap_or(target);
-    to_pascal(name)
-}
-
 fn type_expr_to_rust(type_expr: &TypeExpr) -> String {
     match type_expr {
         TypeExpr::Named(name) => match name.as_str() {
             "UUID" => "Uuid".to_string(),
             "String" => "String".to_string(),
             "Int" => "i64".to_string(),
+            "Float" => "f64".to_string(),
             "Bool" => "bool".to_string(),
             "Unit" => "()".to_string(),
             "ValidationError" => "ValidationError".to_string(),
@@ -995,9 +2200,161 @@ fn type_expr_to_rust(type_expr: &TypeExpr) -> String {
             format!("Vec<{}>", type_expr_to_rust(inner))
         }
         TypeExpr::Union(_variants) => "UnionType".to_string(),
-        TypeExpr::Enum(variants) => {
-            format!("/* enum: {} */", variants.join(" | "))
+        // Enums of fields and params are named before codegen; any other is its keyword text
+        TypeExpr::Enum(_) => "String".to_string(),
+        TypeExpr::Optional(inner) => format!("Option<{}>", type_expr_to_rust(inner)),
+        TypeExpr::Ref(target) => refs::id_type(target),
+        TypeExpr::MapOf(key, value) => format!(
+            "std::collections::HashMap<{}, {}>",
+            type_expr_to_rust(key),
+            type_expr_to_rust(value)
+        ),
+        TypeExpr::Result(ok, err) => format!("Result<{}, {}>", type_expr_to_rust(ok), type_expr_to_rust(err)),
+        TypeExpr::Generic(name, args) => {
+            let args: Vec<String> = args.iter().map(type_expr_to_rust).collect();
+            format!("{}<{}>", name, args.join(", "))
+        }
+    }
+}
+
+/// The Rust type of a field of `owner`. A field leading back to `owner` other than
+/// through a `Vec` or map is boxed, so the type has a size.
+fn field_type_to_rust(field: &FieldDef, owner: &TypeDef, module: &Module) -> String {
+    if !generics::boxed(field, &owner.name, module) {
+        return type_expr_to_rust(&field.type_expr);
+    }
+    match &field.type_expr {
+        TypeExpr::Optional(inner) => format!("Option<Box<{}>>", type_expr_to_rust(inner)),
+        other => format!("Box<{}>", type_expr_to_rust(other)),
+    }
+}
+
+/// `<T, U>` after the name of a generic type; empty otherwise
+fn type_params(typedef: &TypeDef) -> String {
+    if typedef.type_params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", typedef.type_params.join(", "))
+    }
+}
+
+/// Range and scale checks of a numeric field: the condition under which `value` fails, and the message.
+fn numeric_checks(field: &FieldDef, value: &str) -> Vec<(String, String)> {
+    let mut checks = Vec::new();
+    if let Some(min) = field.min {
+        checks.push((
+            format!("{} < {}", value, bound_literal(min, &field.type_expr)),
+            format!("must be at least {}", min),
+        ));
+    }
+    if let Some(max) = field.max {
+        checks.push((
+            format!("{} > {}", value, bound_literal(max, &field.type_expr)),
+            format!("must be at most {}", max),
+        ));
+    }
+    if let Some(scale) = field.scale {
+        checks.push((
+            format!("{}.scale() > {}", value, scale),
+            format!("must have at most {} decimal places", scale),
+        ));
+    }
+    checks
+}
+
+/// Every validation check of a field: the condition under which `value` fails, and the message.
+fn field_checks(field: &FieldDef, value: &str) -> Vec<(String, String)> {
+    let mut checks = Vec::new();
+    if let Some(min) = field.min_len {
+        checks.push((format!("{}.len() < {}", value, min), format!("must be at least {} characters", min)));
+    }
+    if let Some(max) = field.max_len {
+        checks.push((format!("{}.len() > {}", value, max), format!("must be at most {} characters", max)));
+    }
+    checks.extend(numeric_checks(field, value));
+    checks
+}
+
+fn is_numeric(type_expr: &TypeExpr) -> bool {
+    matches!(type_expr.non_optional(), TypeExpr::Named(n) if matches!(n.as_str(), "Int" | "Float" | "Decimal"))
+}
+
+/// A `:default` as a Rust value of the field's type: `Role::Member`, `"anon".to_string()`, `Some(3)`
+fn default_literal(value: &MetaValue, type_expr: &TypeExpr) -> String {
+    match (type_expr, value) {
+        (TypeExpr::Optional(inner), _) => format!("Some({})", default_literal(value, inner)),
+        (TypeExpr::Named(n), MetaValue::Keyword(kw)) => format!("{}::{}", n, EnumDef::variant_ident(kw)),
+        (TypeExpr::Named(n), MetaValue::Int(i)) if n == "Float" => format!("{:?}", *i as f64),
+        (TypeExpr::Named(n), MetaValue::Int(i)) if n == "Decimal" => format!("Decimal::new({}, 0)", i),
+        (TypeExpr::Named(n), MetaValue::Float(f)) if n == "Decimal" => {
+            let (units, scale) = Number::Float(*f).decimal_parts().expect("the lexer only accepts float literals that fit");
+            format!("Decimal::new({}, {})", units, scale)
         }
+        (_, MetaValue::String(text)) => format!("{:?}.to_string()", text),
+        (_, MetaValue::Int(i)) => i.to_string(),
+        (_, MetaValue::Float(f)) => format!("{:?}", f),
+        (_, MetaValue::Bool(b)) => b.to_string(),
+        _ => "Default::default()".to_string(),
+    }
+}
+
+/// A field's value from its input: wrapped in `Some` when the field is optional and
+/// the input is not, with the field's default when an optional input is left out.
+fn fill_from_input(input: &str, input_optional: bool, field: &FieldDef) -> String {
+    let field_optional = matches!(field.type_expr, TypeExpr::Optional(_));
+    match (input_optional, field_optional, &field.default) {
+        (false, false, _) => input.to_string(),
+        (false, true, _) => format!("Some({})", input),
+        (true, true, None) => input.to_string(),
+        (true, true, Some(default)) => format!("{}.or({})", input, default_literal(default, &field.type_expr)),
+        (true, false, Some(default)) => format!("{}.unwrap_or({})", input, default_literal(default, &field.type_expr)),
+        (true, false, None) => format!("{}.unwrap_or_default()", input),
+    }
+}
+
+/// A UUID input for a `(ref T)` field, wrapped in the typed id
+fn typed_id_input(input: String, input_type: &TypeExpr, input_optional: bool, field: &FieldDef) -> String {
+    match (field.type_expr.non_optional(), input_type.non_optional()) {
+        (TypeExpr::Ref(target), TypeExpr::Named(n)) if n == "UUID" && input_optional => {
+            format!("{}.map({}::from)", input, refs::id_type(target))
+        }
+        (TypeExpr::Ref(target), TypeExpr::Named(n)) if n == "UUID" => format!("{}::from({})", refs::id_type(target), input),
+        _ => input,
+    }
+}
+
+/// A recursive field's input, boxed as the field is
+fn boxed_input(input: String, input_optional: bool, boxed: bool) -> String {
+    match (boxed, input_optional) {
+        (false, _) => input,
+        (true, true) => format!("{}.map(Box::new)", input),
+        (true, false) => format!("Box::new({})", input),
+    }
+}
+
+/// The Rust type of an input struct field: `Option<T>` when the same-named field of a
+/// module type has a `:default`, so callers may leave it out
+fn input_field_type(name: &str, type_expr: &TypeExpr, module: &Module) -> String {
+    match type_expr {
+        TypeExpr::Optional(_) => type_expr_to_rust(type_expr),
+        _ if input_defaulted(name, module) => format!("Option<{}>", type_expr_to_rust(type_expr)),
+        _ => type_expr_to_rust(type_expr),
+    }
+}
+
+fn input_defaulted(name: &str, module: &Module) -> bool {
+    module.types.iter().flat_map(|t| &t.fields).any(|f| f.name == name && f.default.is_some())
+}
+
+/// A `:min`/`:max` bound as a Rust literal of the field's type; `Decimal` is pact-runtime's
+fn bound_literal(bound: Number, type_expr: &TypeExpr) -> String {
+    match type_expr.non_optional() {
+        TypeExpr::Named(n) if n == "Float" => format!("{:?}", bound.as_f64()),
+        TypeExpr::Named(n) if n == "Decimal" => {
+            let (units, scale) = bound.decimal_parts().expect("the lexer only accepts float literals that fit");
+            format!("Decimal::new({}, {})", units, scale)
+        }
+        _ => bound.to_string(),
     }
 }
 
@@ -1018,6 +2375,8 @@ fn resolve_field_type(
             "UUID" => return "Uuid".to_string(),
             "String" => return "String".to_string(),
             "Int" => return "i64".to_string(),
+            "Float" => return "f64".to_string(),
+            "Decimal" => return "Decimal".to_string(),
             "Bool" => return "bool".to_string(),
             _ => {}
         }
@@ -1047,31 +2406,6 @@ fn resolve_field_type(
     type_expr_to_rust(type_expr)
 }
 
-/// Convert a kebab-case name to snake_case, stripping ? and ! suffixes
-fn to_snake(name: &str) -> String {
-    name.replace('-', "_")
-        .replace('/', "_")
-        .replace('?', "")
-        .replace('!', "")
-}
-
-/// Convert a kebab-case name to PascalCase
-fn to_pascal(name: &str) -> String {
-    name.split(|c| c == '-' || c == '_' || c == '/')
-        .map(|part| {
-            let part = part.replace('?', "").replace('!', "");
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + &chars.as_str().to_lowercase()
-                }
-            }
-        })
-        .collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1260,4 +2594,398 @@ mod tests {
         assert!(!output.contains("pub trait DbRead"));
         assert!(!output.contains("pub trait DbWrite"));
     }
+
+    #[test]
+    fn test_multiple_stores_get_distinct_params() {
+        let input = include_str!("../../examples/auth-service.pct");
+        let output = generate(input);
+        assert!(output.contains(
+            "pub fn authenticate(user_store: &impl Store<User>, session_store: &mut impl Store<Session>, externs: &impl Externs, creds: Credentials)"
+        ));
+        // audit-store holds no type defined in the module, so there is no item type to pass it with
+        assert!(!output.contains("Store<Audit>"));
+        assert!(output.contains("/// No audit-store parameter: it holds no type defined in this module"));
+        // Calls are routed to the store named by their first argument
+        assert!(output.contains("User::find_by_username(user_store, &creds.username)"));
+        assert!(output.contains("session_store.insert(externs.new_session(u.clone()))"));
+        // A single store keeps the plain `store` name
+        assert!(output.contains("pub fn validate_token(store: &impl Store<Session>"));
+    }
+
+    #[test]
+    fn test_query_by_unique_and_arbitrary_fields() {
+        let input = r#"(module test :version 1
+            (type User
+                (field id UUID :immutable :generated)
+                (field email String :unique-within user-store)
+                (field role String))
+            (effect-set db-read [:reads user-store])
+            (fn find-user
+                :effects [db-read]
+                :total true
+                (param email String)
+                (returns (union (ok User :http 200)))
+                (query user-store {:email email}))
+            (fn list-admins
+                :effects [db-read]
+                :total true
+                (param role String)
+                (returns (union (ok User :http 200)))
+                (query user-store {:role role}))
+            (fn any-admin
+                :effects [db-read]
+                :total true
+                (param role String)
+                (returns (union (ok User :http 200) (err :not-found {} :http 404)))
+                (let [admin (query user-store {:role role})]
+                    (match admin
+                        (some a) (ok a)
+                        (none)   (err :not-found {})))))"#;
+        let output = generate(input);
+        assert!(output.contains("pub fn find_by_email(store: &impl Store<User>, email: &str) -> Option<User> {"));
+        assert!(output.contains("User::find_by_email(store, &email)"));
+        assert!(output.contains("store.list_all().into_iter().filter(|e| e.role == role).collect::<Vec<_>>()"));
+        // Matched as an Option, a query on a field that is not unique finds the first match
+        assert!(output.contains("let admin = store.list_all().into_iter().find(|e| e.role == role);"));
+    }
+
+    #[test]
+    fn test_sends_effects_generate_gateways() {
+        let input = include_str!("../../examples/notification.pct");
+        let output = generate(input);
+        assert!(output.contains("pub struct DeliverEmailMessage {\n    pub recipient: String,\n    pub rendered: String,\n}"));
+        assert!(output.contains("pub trait EmailGateway {\n    fn deliver_email(&mut self, message: DeliverEmailMessage) -> Result<(), String>;\n}"));
+        assert!(output.contains("pub struct RecordingEmailGateway {"));
+        assert!(output.contains("impl EmailGateway for RecordingEmailGateway {"));
+        // Declared but unused gateways still get a trait
+        assert!(output.contains("pub trait SmsGateway {}"));
+        assert!(output.contains("email_gateway: &mut impl EmailGateway, externs: &impl Externs, recipient: String"));
+        assert!(output.contains(
+            "email_gateway.deliver_email(DeliverEmailMessage { recipient: recipient.to_string(), rendered: rendered.to_string() })"
+        ));
+        // The HTTP response is the return value, not a gateway
+        let output = generate(include_str!("../../examples/user-service.pct"));
+        assert!(!output.contains("HttpResponse"));
+    }
+
+    #[test]
+    fn test_only_explicit_sends_route_to_gateways() {
+        let input = r#"(module mail :version 1
+            (type Signup (field id UUID) (field attempts Int))
+            (effect-set email-send [:sends email-gateway])
+            (extern deliver-email [String Int] (result Unit String) :effects [email-send])
+            (fn welcome
+                :effects [email-send]
+                (param address String)
+                (param signup Signup)
+                (returns (union (ok Bool) (err :bounced {:reason String})))
+                (if (valid-email? address)
+                    (match (deliver-email address signup.attempts)
+                        (err reason) (err :bounced {:reason reason})
+                        (ok _) (ok true))
+                    (ok false))))"#;
+        let output = generate(input);
+        assert!(output.contains("if valid_email(address)"), "{}", output);
+        assert!(!output.contains("email_gateway.valid_email"));
+        // Typed by the declaration, not by the field access passed
+        assert!(output.contains("pub struct DeliverEmailMessage {\n    pub address: String,\n    pub attempts: i64,\n}"));
+        assert!(output.contains("email_gateway.deliver_email(DeliverEmailMessage { address: address.to_string(), attempts: signup.attempts.clone() })"));
+        // Sending externs are gateway methods, not host externs
+        assert!(!output.contains("pub trait Externs"));
+        assert!(!output.contains("externs: &impl Externs"));
+    }
+
+    #[test]
+    fn test_async_functions_await_stores_and_gateways() {
+        let input = include_str!("../../examples/notification.pct");
+        let mut lexer = Lexer::new(input);
+        let tokens = lexer.tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        let output = RustV2Codegen::new().with_async(true).generate(&module);
+
+        assert!(output.contains("pub trait AsyncStore<T> {"));
+        assert!(output.contains("impl<T: Send, S: Store<T>> AsyncStore<T> for SharedStore<S> {"));
+        // A poisoned lock fails writes as a store error and never panics
+        assert!(output.contains("Err(e) => Err(StoreError::Unavailable(e.to_string())),"));
+        assert!(output.contains("self.0.lock().unwrap_or_else(|e| e.into_inner()).list_all()"));
+        assert!(!output.contains("lock().unwrap()"));
+        assert!(output.contains("pub async fn send_notification(template_store: &impl AsyncStore<Template>, notification_store: &mut impl AsyncStore<Notification>, email_gateway: &mut impl EmailGateway"));
+        assert!(output.contains("store.query_by_id(&id).await"));
+        assert!(output.contains(".insert(Notification::from_input("));
+        assert!(output.contains("rendered: rendered.to_string() }).await"));
+        assert!(output.contains("fn deliver_email(&mut self, message: DeliverEmailMessage) -> impl std::future::Future<Output = Result<(), String>> + Send;"));
+        assert!(output.contains("return std::future::ready(Err(reason.clone()));"));
+    }
+
+    #[test]
+    fn test_store_errors_map_to_declared_variant() {
+        let output = generate(include_str!("../../examples/user-service.pct"));
+        assert!(output.contains("Err(_) => CreateUserResult::StoreUnavailable {}"));
+        assert!(!output.contains("panic!"));
+    }
+
+    #[test]
+    fn test_enums_serialize_as_keywords() {
+        let output = generate(
+            r#"(module team :version 1
+                (type Member (field role (enum :admin :read-only)))
+                (fn make-admin
+                    (param name String)
+                    (returns (union (ok Member :http 200)))
+                    (ok (build Member {:role :admin}))))"#,
+        );
+        assert!(output.contains("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]\npub enum Role {"));
+        assert!(output.contains("    #[serde(rename = \"read-only\")]\n    ReadOnly,"));
+        assert!(output.contains("impl std::str::FromStr for Role {"));
+        assert!(output.contains("role: Role::Admin"));
+    }
+
+    #[test]
+    fn test_optional_and_defaulted_fields() {
+        let output = generate(
+            r#"(module team :version 1
+                (type Member
+                    (field id UUID :immutable :generated)
+                    (field nickname (optional String) :min-len 2)
+                    (field role (enum :admin :member) :default :member))
+                (effect-set db-write [:writes member-store])
+                (fn create-member
+                    :effects [db-write]
+                    (param input {:nickname (optional String) :role (enum :admin :member)})
+                    (returns (union (ok Member :http 201)))
+                    (ok (insert! member-store (build Member input)))))"#,
+        );
+        assert!(output.contains("pub nickname: Option<String>,"));
+        assert!(output.contains("    #[serde(default)]\n    pub role: Option<Role>,"));
+        assert!(output.contains("if let Some(v) = &input.nickname {"));
+        assert!(output.contains("role: input.role.unwrap_or(Role::Member),"));
+    }
+
+    #[test]
+    fn test_refs_get_typed_ids_and_checked_inserts() {
+        let output = generate(
+            r#"(module blog :version 1
+                (type Author (field id UUID :immutable :generated))
+                (type Post
+                    (field id UUID :immutable :generated)
+                    (field author (ref Author))
+                    (field editor (optional (ref Author))))
+                (effect-set author-read [:reads author-store])
+                (effect-set post-write [:writes post-store])
+                (fn create-post
+                    :effects [post-write author-read]
+                    (param input {:author UUID :editor (optional (ref Author))})
+                    (returns (union (ok Post :http 201) (err :unknown-author {} :http 422) (err :duplicate {} :http 409)))
+                    (match (insert! post-store (build Post input))
+                        (ok p) (ok p)
+                        (err :missing-reference) (err :unknown-author {})
+                        (err :unique-violation) (err :duplicate {})
+                        (err _) (err :unknown-author {}))))"#,
+        );
+        assert!(output.contains("#[serde(transparent)]\npub struct AuthorId(pub Uuid);"));
+        assert!(output.contains("pub author: AuthorId,"));
+        assert!(output.contains("author: AuthorId::from(input.author),"));
+        // A missing row is its own error; `StoreError::Unavailable` stays for outages
+        assert!(output.contains("pub enum InsertError {\n    /// The field names no row of the store that holds its type\n    MissingReference { field: String },\n    Store(StoreError),\n}"));
+        assert!(output.contains(
+            "if author_store.query_by_id(&item.author.0).is_none() { Err(InsertError::MissingReference { field: \"author\".into() }) } else "
+        ));
+        assert!(output.contains("{ post_store.insert(item).map_err(InsertError::Store) } }"));
+        assert!(output.contains("Err(InsertError::MissingReference { .. }) => CreatePostResult::UnknownAuthor"));
+        assert!(output.contains("Err(InsertError::Store(StoreError::UniqueViolation { .. })) => CreatePostResult::Duplicate"));
+        assert!(!output.contains("Unavailable"));
+    }
+
+    #[test]
+    fn test_generic_and_recursive_types() {
+        let output = generate(
+            r#"(module feed :version 1
+                (type Comment
+                    (field id UUID :immutable :generated)
+                    (field parent (optional Comment)))
+                (type (Page T) (field items (list T)) (field counts (map String Int)))
+                (fn first-page
+                    (param page (Page Comment))
+                    (returns (union (ok (Page Comment))))
+                    (ok page)))"#,
+        );
+        assert!(output.contains("pub struct Page<T> {\n    pub items: Vec<T>,\n    pub counts: std::collections::HashMap<String, i64>,"));
+        assert!(output.contains("pub parent: Option<Box<Comment>>,"));
+        assert!(output.contains("parent: input.parent.map(Box::new),"));
+        // Generic types are not built from input structs
+        assert!(!output.contains("CreatePageInput"));
+        assert!(output.contains("Ok(Page<Comment>)"));
+    }
+
+    #[test]
+    fn test_operators_are_native_rust_operators() {
+        let output = generate(
+            r#"(module shop :version 1
+                (type Item (field id UUID) (field qty Int) (field name String))
+                (effect-set item-read [:reads item-store])
+                (fn check
+                    :effects [item-read]
+                    (param id UUID)
+                    (param count Int)
+                    (returns (union (ok Int) (err :short {})))
+                    (match (query item-store {:id id})
+                        (some i) (let [left (- (. i qty) count)]
+                                   (if (and (>= left 0) (not (= (. i name) "")))
+                                     (ok (+ (* count 2) (- left)))
+                                     (err :short {})))
+                        (none) (err :short {}))))"#,
+        );
+        assert!(output.contains("let left = i.qty - count;"));
+        assert!(output.contains("if (left >= 0) && (!(i.name == \"\")) {"));
+        assert!(output.contains("Ok((count * 2) + (-left))"));
+    }
+
+    #[test]
+    fn test_collections_are_iterator_chains() {
+        let output = generate(
+            r#"(module shop :version 1
+                (fn totals
+                    (param prices (list Int))
+                    (returns (union (ok Int)))
+                    (let [doubled (map (fn [p] (* p 2)) prices)
+                          cheap (filter (fn [p] (< p 10)) [1 2 3])
+                          total (fold (fn [acc p] (+ acc p)) 0 cheap)]
+                      (ok total))))"#,
+        );
+        assert!(output.contains("let doubled = prices.iter().cloned().map(|p| p * 2).collect::<Vec<_>>();"));
+        assert!(output.contains("let cheap = vec![1, 2, 3].iter().cloned().filter(|p| { let p = p.clone(); p < 10 }).collect::<Vec<_>>();"));
+        assert!(output.contains("let total = cheap.iter().cloned().fold(0, |acc, p| acc + p);"));
+    }
+
+    #[test]
+    fn test_helpers_are_plain_functions() {
+        let output = generate(
+            r#"(module shop :version 1
+                (type Session (field id UUID) (field expires-at Int))
+                (defn expired? [s Session] Bool (<= (. s expires-at) 0))
+                (fn check
+                    (param s Session)
+                    (returns (union (ok Session) (err :expired {})))
+                    (if (expired? s) (err :expired {}) (ok s))))"#,
+        );
+        assert!(output.contains("pub fn expired(s: Session) -> bool {\n    s.expires_at <= 0\n}"));
+        assert!(output.contains("if expired(s.clone()) {"));
+        assert!(!output.contains("ExpiredResult"));
+    }
+
+    #[test]
+    fn test_externs_are_host_trait_calls() {
+        let output = generate(
+            r#"(module auth :version 1
+                (extern verify-password [String String] (result Unit Unit) :effects [])
+                (defn matches? [given String stored String] Bool
+                    (match (verify-password given stored) (ok _) true (err _) false))
+                (fn login
+                    (param password String)
+                    (returns (union (ok Bool)))
+                    (ok (matches? password "hash"))))"#,
+        );
+        assert!(output.contains("pub trait Externs {"));
+        assert!(output.contains("fn verify_password(&self, arg1: String, arg2: String) -> Result<(), ()>;"));
+        assert!(output.contains("pub fn matches(externs: &impl Externs, given: String, stored: String) -> bool"));
+        assert!(output.contains("match externs.verify_password(given.clone(), stored.clone())"));
+        assert!(output.contains("pub fn login(externs: &impl Externs, password: String) -> LoginResult"));
+        assert!(output.contains("matches(externs, password.clone(), \"hash\".to_string())"));
+    }
+
+    #[test]
+    fn test_uuid_params_are_parsed_for_externs_and_helpers() {
+        let output = generate(
+            r#"(module profile :version 1
+                (extern load-avatar [UUID] String :effects [])
+                (extern audit [String] Unit :effects [])
+                (defn avatar-of [id UUID] String (load-avatar id))
+                (fn show-avatar
+                    (param id UUID)
+                    (returns (union (ok String)))
+                    (let [_ (audit id)
+                          direct (load-avatar id)]
+                        (ok (avatar-of id)))))"#,
+        );
+        assert!(output.contains("fn load_avatar(&self, arg1: Uuid) -> String;"));
+        assert!(output.contains("pub fn avatar_of(externs: &impl Externs, id: Uuid) -> String"));
+        assert!(output.contains("pub fn show_avatar(externs: &impl Externs, id: &str) -> ShowAvatarResult"));
+        assert!(output.contains("externs.audit(id.to_string())"));
+        assert!(output.contains("externs.load_avatar(Uuid::parse_str(id).unwrap_or(Uuid::nil()))"));
+        assert!(output.contains("avatar_of(externs, Uuid::parse_str(id).unwrap_or(Uuid::nil()))"));
+        // Inside the helper the param is already a `Uuid`
+        assert!(output.contains("externs.load_avatar(id.clone())"));
+    }
+
+    #[test]
+    fn test_store_item_type_follows_unique_within() {
+        let output = generate(
+            r#"(module inventory :version 1
+                (type StockEntry
+                    (field id UUID :immutable :generated)
+                    (field sku String :unique-within stock-store)
+                    (field quantity Int))
+                (effect-set stock-read [:reads stock-store])
+                (fn find-stock
+                    :effects [stock-read]
+                    (param sku String)
+                    (returns (union (ok StockEntry :http 200) (err :not-found {} :http 404)))
+                    (match (query stock-store {:sku sku})
+                        (some s) (ok s)
+                        (none) (err :not-found {}))))"#,
+        );
+        assert!(output.contains("pub fn find_stock(store: &impl Store<StockEntry>, sku: String)"));
+        assert!(!output.contains("Store<Stock>"));
+    }
+
+    #[test]
+    fn test_queries_on_a_store_named_apart_from_its_type() {
+        let output = generate(
+            r#"(module inventory :version 1
+                (type StockEntry
+                    (field id UUID :immutable :generated)
+                    (field sku String :unique-within stock-store)
+                    (field product-id UUID))
+                (effect-set stock-read [:reads stock-store])
+                (fn find-stock
+                    :effects [stock-read]
+                    (param sku String)
+                    (returns (union (ok StockEntry :http 200) (err :not-found {} :http 404)))
+                    (match (query stock-store {:sku sku})
+                        (some s) (ok s)
+                        (none) (err :not-found {})))
+                (fn for-product
+                    :effects [stock-read]
+                    (param product-id UUID)
+                    (returns (union (ok (list StockEntry) :http 200)))
+                    (ok (query stock-store {:product-id product-id}))))"#,
+        );
+        assert!(output.contains("match StockEntry::find_by_sku(store, &sku) {"));
+        assert!(output.contains(".filter(|e| Uuid::parse_str(product_id).is_ok_and(|id| e.product_id == id))"));
+    }
+
+    #[test]
+    fn test_for_each_writes_return_the_first_store_error() {
+        let input = r#"(module batch :version 1
+            (type Item (field id UUID :immutable :generated) (field name String))
+            (effect-set db-write [:writes item-store])
+            (extern new-item [String] Item :effects [])
+            (fn import-items
+                :effects [db-write]
+                MAPPING
+                (param names (list String))
+                (returns (union
+                    (ok Int :http 200)
+                    (err :store-unavailable {} :http 503)))
+                (let [_ (for-each [n names] (insert! item-store (new-item n)))]
+                    (ok 0))))"#;
+        let mapped = generate(&input.replace("MAPPING", ":on-store-error :store-unavailable"));
+        assert!(mapped.contains("for n in names.iter().cloned() { match "), "{}", mapped);
+        assert!(mapped.contains("{ Ok(_) => {} Err(_) => return ImportItemsResult::StoreUnavailable {} } }"), "{}", mapped);
+        assert!(!mapped.contains("{ let _ = "), "{}", mapped);
+
+        let unmapped = generate(&input.replace("MAPPING", ""));
+        assert!(unmapped.contains("{ Ok(_) => {} Err(e) => panic!(\"unexpected store error: {:?}\", e) } }"), "{}", unmapped);
+    }
 }
@@ -0,0 +1,529 @@
+use crate::ast::*;
+use crate::naming::{to_camel, to_pascal, to_snake};
+use crate::scaffold::route_analysis::{self, HttpMethod, Route, RouteKind};
+
+/// Emits a TypeScript module for a frontend talking to the scaffolded API:
+/// interfaces for types, a discriminated union per function result, validators,
+/// and a fetch-based client whose methods follow the scaffold's API routes.
+pub struct TypeScriptCodegen {
+    output: String,
+    indent: usize,
+}
+
+impl Default for TypeScriptCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeScriptCodegen {
+    pub fn new() -> Self {
+        TypeScriptCodegen {
+            output: String::new(),
+            indent: 0,
+        }
+    }
+
+    pub fn generate(mut self, module: &Module) -> String {
+        self.emit_header(module);
+        self.emit_line("");
+
+        self.emit_line("export interface ValidationError {");
+        self.emit_line("  field: string;");
+        self.emit_line("  message: string;");
+        self.emit_line("}");
+        self.emit_line("");
+
+        // Interfaces and validators for types
+        for typedef in &module.types {
+            self.emit_interface(typedef);
+            self.emit_line("");
+            self.emit_validator(&typedef.name, &typedef.type_params, &typedef.fields);
+            self.emit_line("");
+        }
+
+        // Input interfaces for functions with Map-typed params
+        for func in &module.functions {
+            self.emit_input_interface(func, module);
+        }
+
+        // Result unions for each function
+        for func in &module.functions {
+            self.emit_result_union(func, module);
+            self.emit_line("");
+        }
+
+        self.emit_client(module);
+
+        self.output
+    }
+
+    fn emit_header(&mut self, module: &Module) {
+        self.emit_line("// ============================================================");
+        self.emit_line(&format!("// Generated from Pact module: {}", module.name));
+        if let Some(v) = module.version {
+            self.emit_line(&format!("// Version: {}", v));
+        }
+        self.emit_line("// DO NOT EDIT — regenerate from the .pct source");
+        self.emit_line("// ============================================================");
+    }
+
+    fn emit_interface(&mut self, typedef: &TypeDef) {
+        self.emit_line(&format!(
+            "export interface {}{} {{",
+            typedef.name,
+            type_params_ts(&typedef.type_params)
+        ));
+        self.indent += 1;
+        for field in &typedef.fields {
+            if field.immutable || field.generated {
+                let mut annotations = Vec::new();
+                if field.immutable {
+                    annotations.push("immutable");
+                }
+                if field.generated {
+                    annotations.push("generated");
+                }
+                self.emit_line(&format!("/** {} */", annotations.join(", ")));
+            }
+            self.emit_line(&format!(
+                "{}: {};",
+                to_snake(&field.name),
+                type_expr_to_ts(&field.type_expr)
+            ));
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// `validateUser(value)`: the `:min-len`, `:max-len` and `:format` checks of each field,
+    /// reported with the same messages as the Rust `validate()`.
+    /// Generic types get a generic validator: `validatePage<T>(value: Page<T>)`.
+    fn emit_validator(&mut self, name: &str, type_params: &[String], fields: &[FieldDef]) {
+        let params = type_params_ts(type_params);
+        self.emit_line(&format!(
+            "export function validate{}{}(value: {}{}): ValidationError[] {{",
+            name, params, name, params
+        ));
+        self.indent += 1;
+        self.emit_line("const errors: ValidationError[] = [];");
+        for field in fields {
+            let access = format!("value.{}", to_snake(&field.name));
+            // Optional and defaulted fields are only checked when present
+            let guard = if matches!(field.type_expr, TypeExpr::Optional(_)) || field.default.is_some() {
+                format!("{} != null && ", access)
+            } else {
+                String::new()
+            };
+            if let Some(min) = field.min_len {
+                self.emit_line(&format!(
+                    "if ({}{}.length < {}) errors.push({{ field: \"{}\", message: \"must be at least {} characters\" }});",
+                    guard, access, min, field.name, min
+                ));
+            }
+            if let Some(max) = field.max_len {
+                self.emit_line(&format!(
+                    "if ({}{}.length > {}) errors.push({{ field: \"{}\", message: \"must be at most {} characters\" }});",
+                    guard, access, max, field.name, max
+                ));
+            }
+            // Decimals travel as strings, so their bounds compare the parsed number
+            let is_decimal = matches!(field.type_expr.non_optional(), TypeExpr::Named(n) if n == "Decimal");
+            let number = if is_decimal { format!("Number({})", access) } else { access.clone() };
+            if let Some(min) = field.min {
+                self.emit_line(&format!(
+                    "if ({}{} < {}) errors.push({{ field: \"{}\", message: \"must be at least {}\" }});",
+                    guard, number, min, field.name, min
+                ));
+            }
+            if let Some(max) = field.max {
+                self.emit_line(&format!(
+                    "if ({}{} > {}) errors.push({{ field: \"{}\", message: \"must be at most {}\" }});",
+                    guard, number, max, field.name, max
+                ));
+            }
+            if let Some(scale) = field.scale {
+                self.emit_line(&format!(
+                    "if ({}!/^-?\\d+(\\.\\d{{0,{}}})?$/.test({})) errors.push({{ field: \"{}\", message: \"must have at most {} decimal places\" }});",
+                    guard, scale, access, field.name, scale
+                ));
+            }
+            if let Some(pattern) = field.format.as_deref().and_then(format_pattern) {
+                self.emit_line(&format!(
+                    "if ({}!{}.test({})) errors.push({{ field: \"{}\", message: \"must be a valid {}\" }});",
+                    guard,
+                    pattern,
+                    access,
+                    field.name,
+                    field.format.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+        self.emit_line("return errors;");
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    /// `CreateUserInput` and its validator. Input fields take the constraints of the
+    /// same-named field of a module type, so forms can be checked before posting.
+    fn emit_input_interface(&mut self, func: &FnDef, module: &Module) {
+        for param in &func.params {
+            if let TypeExpr::Map(fields) = &param.type_expr {
+                let name = format!("{}Input", to_pascal(&func.name));
+                self.emit_line(&format!("export interface {} {{", name));
+                self.indent += 1;
+                for (field_name, type_expr) in fields {
+                    // Optional and defaulted fields may be left out of the request body
+                    let omittable = matches!(type_expr, TypeExpr::Optional(_))
+                        || module.types.iter().flat_map(|t| &t.fields).any(|f| f.name == *field_name && f.default.is_some());
+                    self.emit_line(&format!(
+                        "{}{}: {};",
+                        to_snake(field_name),
+                        if omittable { "?" } else { "" },
+                        type_expr_to_ts(type_expr)
+                    ));
+                }
+                self.indent -= 1;
+                self.emit_line("}");
+                self.emit_line("");
+
+                let constrained: Vec<FieldDef> = fields
+                    .iter()
+                    .filter_map(|(field_name, type_expr)| {
+                        let mut field =
+                            module.types.iter().flat_map(|t| &t.fields).find(|f| f.name == *field_name).cloned()?;
+                        if matches!(type_expr, TypeExpr::Optional(_)) {
+                            field.type_expr = type_expr.clone();
+                        }
+                        Some(field)
+                    })
+                    .collect();
+                self.emit_validator(&name, &[], &constrained);
+                self.emit_line("");
+            }
+        }
+    }
+
+    /// One union member per variant, tagged by `variant` and `status`. Error members carry
+    /// the fields of the JSON body the scaffold sends (`{"error": tag, ...}`); a response
+    /// matching no variant is an `unexpected-response` with the body as text.
+    fn emit_result_union(&mut self, func: &FnDef, module: &Module) {
+        self.emit_line(&format!("export type {}Result =", to_pascal(&func.name)));
+        self.indent += 1;
+        for variant in &func.returns.variants {
+            match &variant.kind {
+                VariantKind::Ok { type_expr, http_status, .. } => {
+                    let status = http_status.unwrap_or(200);
+                    let value = match type_expr {
+                        TypeExpr::Named(n) if n == "Unit" => String::new(),
+                        other => format!("; value: {}", type_expr_to_ts(other)),
+                    };
+                    self.emit_line(&format!(
+                        "| {{ variant: \"ok\"; status: {}{} }}",
+                        status, value
+                    ));
+                }
+                VariantKind::Err { tag, payload, http_status, .. } => {
+                    let status = http_status.unwrap_or(500);
+                    let fields = match payload {
+                        TypeExpr::Map(fields) => fields
+                            .iter()
+                            .map(|(name, te)| {
+                                format!("; {}: {}", to_snake(name), resolve_field_type(name, te, module, func))
+                            })
+                            .collect(),
+                        TypeExpr::List(inner) => format!("; errors: {}[]", type_expr_to_ts(inner)),
+                        _ => String::new(),
+                    };
+                    self.emit_line(&format!(
+                        "| {{ variant: \"{}\"; status: {}{} }}",
+                        tag, status, fields
+                    ));
+                }
+            }
+        }
+        self.emit_line("| { variant: \"unexpected-response\"; status: number; body: string };");
+        self.indent -= 1;
+    }
+
+    /// `{Module}Client`: one method per API route of the scaffold. List routes resolve to
+    /// the items; function-backed routes resolve to the function's result union.
+    fn emit_client(&mut self, module: &Module) {
+        let table = route_analysis::analyze(module);
+        let client = format!("{}Client", to_pascal(&module.name));
+
+        self.emit_line(&format!("export class {} {{", client));
+        self.indent += 1;
+        self.emit_line("constructor(");
+        self.emit_line("  private readonly baseUrl: string,");
+        // A bare `fetch` called as `this.fetchImpl` gets the client as `this`, which
+        // browsers reject with "Illegal invocation"
+        self.emit_line("  private readonly fetchImpl: typeof fetch = (input, init) => fetch(input, init),");
+        self.emit_line(") {}");
+
+        for route in &table.routes {
+            if let (Some(path), Some(handler)) = (&route.api_path, &route.api_handler_name) {
+                self.emit_line("");
+                let known = module.types.iter().any(|t| t.name == route.store_type);
+                self.emit_client_method(route, path, handler, known);
+            }
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+        self.emit_line("");
+
+        // Only JSON bodies are parsed: a proxy's HTML error page or an empty 503 becomes
+        // an `unexpected-response` rather than a rejected promise
+        self.emit_line("async function toResult<R>(res: Response, okStatus: number): Promise<R> {");
+        self.emit_line("  const text = await res.text();");
+        self.emit_line("  let body: any = undefined;");
+        self.emit_line("  if (text !== \"\" && (res.headers.get(\"content-type\") ?? \"\").includes(\"application/json\")) {");
+        self.emit_line("    try {");
+        self.emit_line("      body = JSON.parse(text);");
+        self.emit_line("    } catch {");
+        self.emit_line("      body = undefined;");
+        self.emit_line("    }");
+        self.emit_line("  }");
+        self.emit_line("  const isError = body !== null && typeof body === \"object\" && \"error\" in body;");
+        self.emit_line("  if (res.status === okStatus && !isError && (body !== undefined || text === \"\")) {");
+        self.emit_line("    return { variant: \"ok\", status: res.status, value: body } as unknown as R;");
+        self.emit_line("  }");
+        self.emit_line("  if (!isError) {");
+        self.emit_line("    return { variant: \"unexpected-response\", status: res.status, body: text } as unknown as R;");
+        self.emit_line("  }");
+        self.emit_line("  const { error, ...fields } = body;");
+        self.emit_line("  return { variant: error, status: res.status, ...fields } as unknown as R;");
+        self.emit_line("}");
+    }
+
+    /// Items of stores holding no type defined in the module are typed `unknown`.
+    fn emit_client_method(&mut self, route: &Route, path: &str, handler: &str, known_type: bool) {
+        let method = match route.method {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+        };
+        let name = to_camel(handler.strip_prefix("api_").unwrap_or(handler));
+        let url = path.replace("{id}", "${encodeURIComponent(id)}");
+
+        let fn_route = route.function.as_ref();
+        let (params, result) = match (&route.kind, fn_route) {
+            (RouteKind::Show, Some(f)) => ("id: string".to_string(), f.result_enum.clone()),
+            (RouteKind::Create, Some(f)) => (
+                format!("input: {}", f.input_struct.as_deref().unwrap_or("unknown")),
+                f.result_enum.clone(),
+            ),
+            _ if known_type => (String::new(), format!("{}[]", route.store_type)),
+            _ => (String::new(), "unknown[]".to_string()),
+        };
+
+        self.emit_line(&format!("/** {} {} */", method, path));
+        self.emit_line(&format!("async {}({}): Promise<{}> {{", name, params, result));
+        self.indent += 1;
+        if route.kind == RouteKind::Create {
+            self.emit_line(&format!("const res = await this.fetchImpl(`${{this.baseUrl}}{}`, {{", url));
+            self.emit_line("  method: \"POST\",");
+            self.emit_line("  headers: { \"Content-Type\": \"application/json\" },");
+            self.emit_line("  body: JSON.stringify(input),");
+            self.emit_line("});");
+        } else {
+            self.emit_line(&format!("const res = await this.fetchImpl(`${{this.baseUrl}}{}`);", url));
+        }
+        match fn_route {
+            Some(f) => {
+                let ok_status = f.variants.iter().find(|v| v.is_ok).map_or(200, |v| v.http_status);
+                self.emit_line(&format!("return toResult<{}>(res, {});", result, ok_status));
+            }
+            None => {
+                self.emit_line(&format!(
+                    "if (!res.ok) throw new Error(`{} {} failed with status ${{res.status}}`);",
+                    method, path
+                ));
+                self.emit_line("return res.json();");
+            }
+        }
+        self.indent -= 1;
+        self.emit_line("}");
+    }
+
+    fn emit_line(&mut self, line: &str) {
+        if line.is_empty() {
+            self.output.push('\n');
+        } else {
+            for _ in 0..self.indent {
+                self.output.push_str("  ");
+            }
+            self.output.push_str(line);
+            self.output.push('\n');
+        }
+    }
+}
+
+/// A regex literal checking a `:format`, for the formats the frontend can check locally.
+fn format_pattern(format: &str) -> Option<&'static str> {
+    match format {
+        "email" => Some(r"/^[^@\s]+@[^@\s]+\.[^@\s]+$/"),
+        "uuid" => Some(r"/^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$/i"),
+        _ => None,
+    }
+}
+
+/// `<T, U>` after the name of a generic type; empty otherwise
+fn type_params_ts(type_params: &[String]) -> String {
+    if type_params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", type_params.join(", "))
+    }
+}
+
+fn type_expr_to_ts(type_expr: &TypeExpr) -> String {
+    match type_expr {
+        TypeExpr::Named(name) => match name.as_str() {
+            "UUID" | "String" => "string".to_string(),
+            "Int" | "Float" => "number".to_string(),
+            // Decimals are exact, so they are carried as strings like "19.99"
+            "Decimal" => "string".to_string(),
+            "Bool" => "boolean".to_string(),
+            "Unit" => "null".to_string(),
+            other => other.to_string(),
+        },
+        TypeExpr::Map(fields) => {
+            let field_strs: Vec<String> = fields
+                .iter()
+                .map(|(name, typ)| format!("{}: {}", to_snake(name), type_expr_to_ts(typ)))
+                .collect();
+            format!("{{ {} }}", field_strs.join("; "))
+        }
+        TypeExpr::List(inner) => format!("{}[]", type_expr_to_ts(inner)),
+        TypeExpr::Union(_) => "unknown".to_string(),
+        TypeExpr::Optional(inner) => format!("{} | null", type_expr_to_ts(inner)),
+        // Typed ids are UUIDs on the wire
+        TypeExpr::Ref(_) => "string".to_string(),
+        TypeExpr::MapOf(key, value) => format!("Record<{}, {}>", type_expr_to_ts(key), type_expr_to_ts(value)),
+        TypeExpr::Result(ok, err) => format!("{{ ok: {} }} | {{ err: {} }}", type_expr_to_ts(ok), type_expr_to_ts(err)),
+        TypeExpr::Generic(name, args) => {
+            let args: Vec<String> = args.iter().map(type_expr_to_ts).collect();
+            format!("{}<{}>", name, args.join(", "))
+        }
+        TypeExpr::Enum(variants) => variants
+            .iter()
+            .map(|v| format!("\"{}\"", v))
+            .collect::<Vec<_>>()
+            .join(" | "),
+    }
+}
+
+/// The TypeScript type of a field in an error payload. Payloads such as `{:id id}` name a
+/// value rather than a type: params give their type, otherwise a same-named field of a
+/// module type does.
+fn resolve_field_type(field_name: &str, type_expr: &TypeExpr, module: &Module, func: &FnDef) -> String {
+    if let TypeExpr::Named(name) = type_expr {
+        if matches!(name.as_str(), "UUID" | "String" | "Int" | "Float" | "Decimal" | "Bool") {
+            return type_expr_to_ts(type_expr);
+        }
+        if let Some(param) = func.params.iter().find(|p| p.name == *name) {
+            return type_expr_to_ts(&param.type_expr);
+        }
+        if let Some(field) = module.types.iter().flat_map(|t| &t.fields).find(|f| f.name == field_name) {
+            return type_expr_to_ts(&field.type_expr);
+        }
+    }
+    type_expr_to_ts(type_expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn generate(input: &str) -> String {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        TypeScriptCodegen::new().generate(&module)
+    }
+
+    fn user_service() -> String {
+        generate(include_str!("../../examples/user-service.pct"))
+    }
+
+    #[test]
+    fn test_to_camel() {
+        assert_eq!(to_camel("get_user"), "getUser");
+        assert_eq!(to_camel("list-users"), "listUsers");
+    }
+
+    #[test]
+    fn test_generates_interfaces_and_validators() {
+        let output = user_service();
+        assert!(output.contains("export interface User {\n  /** immutable, generated */\n  id: string;\n  name: string;\n  email: string;\n}"));
+        assert!(output.contains("export function validateUser(value: User): ValidationError[] {"));
+        assert!(output.contains("if (value.name.length < 1) errors.push({ field: \"name\", message: \"must be at least 1 characters\" });"));
+        assert!(output.contains("if (value.name.length > 200)"));
+        assert!(output.contains("if (!/^[^@\\s]+@[^@\\s]+\\.[^@\\s]+$/.test(value.email))"));
+        assert!(output.contains("export interface CreateUserInput {\n  name: string;\n  email: string;\n}"));
+        assert!(output.contains("export function validateCreateUserInput(value: CreateUserInput)"));
+    }
+
+    #[test]
+    fn test_generates_result_unions() {
+        let output = user_service();
+        assert!(output.contains(
+            "export type GetUserByIdResult =\n  | { variant: \"ok\"; status: 200; value: User }\n  | { variant: \"not-found\"; status: 404; id: string }\n  | { variant: \"invalid-id\"; status: 400; id: string }\n  | { variant: \"unexpected-response\"; status: number; body: string };"
+        ));
+        assert!(output.contains("| { variant: \"duplicate-email\"; status: 409; email: string }"));
+        assert!(output.contains("| { variant: \"validation-failed\"; status: 422; errors: ValidationError[] }"));
+        assert!(output.contains("| { variant: \"store-unavailable\"; status: 503 }\n"));
+    }
+
+    #[test]
+    fn test_client_methods_follow_api_routes() {
+        let output = user_service();
+        assert!(output.contains("export class UserServiceClient {"));
+        assert!(output.contains("async listUsers(): Promise<User[]> {"));
+        assert!(output.contains("async getUser(id: string): Promise<GetUserByIdResult> {"));
+        assert!(output.contains("this.fetchImpl(`${this.baseUrl}/api/users/${encodeURIComponent(id)}`)"));
+        assert!(output.contains("return toResult<GetUserByIdResult>(res, 200);"));
+        assert!(output.contains("async createUser(input: CreateUserInput): Promise<CreateUserResult> {"));
+        assert!(output.contains("body: JSON.stringify(input),"));
+        assert!(output.contains("return toResult<CreateUserResult>(res, 201);"));
+        // `fetch` keeps its own `this`, and only JSON bodies are parsed
+        assert!(output.contains("private readonly fetchImpl: typeof fetch = (input, init) => fetch(input, init),"));
+        assert!(output.contains("const text = await res.text();"));
+        assert!(output.contains("body = JSON.parse(text);"));
+        assert!(output.contains("return { variant: \"unexpected-response\", status: res.status, body: text } as unknown as R;"));
+        assert!(!output.contains("await res.json()"));
+    }
+
+    #[test]
+    fn test_optional_and_defaulted_fields() {
+        let output = generate(
+            r#"(module team :version 1
+                (type Member
+                    (field id UUID :immutable :generated)
+                    (field nickname (optional String) :min-len 2)
+                    (field role (enum :admin :member) :default :member))
+                (effect-set db-write [:writes member-store])
+                (fn create-member
+                    :effects [db-write]
+                    (param input {:nickname (optional String) :role (enum :admin :member)})
+                    (returns (union (ok Member :http 201)))
+                    (ok (insert! member-store (build Member input)))))"#,
+        );
+        assert!(output.contains("  nickname: string | null;"));
+        assert!(output.contains("  nickname?: string | null;\n  role?: \"admin\" | \"member\";"));
+        assert!(output.contains("if (value.nickname != null && value.nickname.length < 2)"));
+    }
+
+    #[test]
+    fn test_generic_types() {
+        let output = generate(
+            r#"(module feed :version 1
+                (type (Page T) (field items (list T)) (field counts (map String Int))))"#,
+        );
+        assert!(output.contains("export interface Page<T> {\n  items: T[];\n  counts: Record<string, number>;"));
+        assert!(output.contains("export function validatePage<T>(value: Page<T>): ValidationError[] {"));
+    }
+}
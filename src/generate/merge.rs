@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::lower::is_flag;
+use crate::parser::{Parser, SExpr, SExprKind};
+
+/// Result of merging a regenerated module into a hand-edited one.
+#[derive(Debug)]
+pub struct MergeResult {
+    pub source: String,
+    /// Conflicts (errors) and hand edits kept over a changed spec (warnings).
+    pub diagnostics: Vec<Diagnostic>,
+    /// Spec-driven changes applied to the hand-edited module, e.g. "added fn get-stock".
+    pub changes: Vec<String>,
+}
+
+/// Three-way merge of `.pct` modules.
+///
+/// `base` is the module as last generated, `user` is that module after hand edits,
+/// and `generated` is the module freshly generated from the updated spec. Items and
+/// their clauses (metadata, fields, params, returns, body) are compared structurally:
+/// whatever only one side changed wins, and clauses both sides changed are kept as
+/// edited and reported. Without a base, the spec owns everything except fn bodies.
+pub fn merge_modules(base: Option<&str>, user: &str, generated: &str) -> Result<MergeResult, String> {
+    let base_sexpr = base.map(parse_module).transpose()?;
+    let user_sexpr = parse_module(user)?;
+    let gen_sexpr = parse_module(generated)?;
+
+    let base_view = base_sexpr.as_ref().zip(base).map(|(s, src)| ModuleView::new(s, src));
+    let user_view = ModuleView::new(&user_sexpr, user);
+    let gen_view = ModuleView::new(&gen_sexpr, generated);
+
+    let mut merger = Merger { diagnostics: Vec::new(), changes: Vec::new() };
+    let source = merger.merge(base_view.as_ref(), &user_view, &gen_view);
+    Ok(MergeResult { source, diagnostics: merger.diagnostics, changes: merger.changes })
+}
+
+fn parse_module(source: &str) -> Result<SExpr, String> {
+    let tokens = Lexer::new(source).tokenize()?;
+    let sexprs = Parser::new(tokens).parse_program()?;
+    let module = sexprs.into_iter().next().ok_or("expected a module")?;
+    match module.kind {
+        SExprKind::List(ref items) if items.first().and_then(|s| s.as_symbol()) == Some("module") => Ok(module),
+        _ => Err("expected (module ...) at top level".into()),
+    }
+}
+
+/// A keyed piece of source: a `:key value` pair, a `(field ...)`, a whole `(fn ...)`, etc.
+struct Clause<'a> {
+    key: String,
+    text: &'a str,
+    node: Option<&'a SExpr>,
+}
+
+fn norm(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn same(a: Option<&str>, b: Option<&str>) -> bool {
+    a.map(norm) == b.map(norm)
+}
+
+fn find<'c, 'a>(clauses: &'c [Clause<'a>], key: &str) -> Option<&'c Clause<'a>> {
+    clauses.iter().find(|c| c.key == key)
+}
+
+/// Split the children of a `(form ...)` into keyed clauses. Keywords are paired with the
+/// value that follows them unless the lowerer reads them as flags; `(param x ...)`, `(field x ...)` and `(returns ...)` are
+/// keyed by head and name; everything else is part of the body.
+fn clauses<'a>(form: &str, items: &'a [SExpr], src: &'a str) -> Vec<Clause<'a>> {
+    let mut out: Vec<Clause<'a>> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut i = 0;
+    while i < items.len() {
+        let item = &items[i];
+        let (key, end, node) = if let Some(kw) = item.as_keyword() {
+            match items.get(i + 1) {
+                Some(value) if !is_flag(form, items, i) => {
+                    i += 1;
+                    (format!(":{}", kw), value.span.end, None)
+                }
+                _ => (format!(":{}", kw), item.span.end, None),
+            }
+        } else {
+            let key = match list_head(item) {
+                Some((head @ ("param" | "field" | "type" | "fn" | "effect-set"), Some(name))) => {
+                    format!("{} {}", head, name)
+                }
+                Some(("returns", _)) => "returns".to_string(),
+                _ => "body".to_string(),
+            };
+            (key, item.span.end, Some(item))
+        };
+        let count = seen.entry(key.clone()).or_insert(0);
+        *count += 1;
+        let key = if *count > 1 { format!("{}#{}", key, count) } else { key };
+        out.push(Clause { key, text: &src[item.span.start..end], node });
+        i += 1;
+    }
+    out
+}
+
+fn list_head(sexpr: &SExpr) -> Option<(&str, Option<&str>)> {
+    match &sexpr.kind {
+        SExprKind::List(items) => {
+            let head = items.first()?.as_symbol()?;
+            Some((head, items.get(1).and_then(|s| s.as_symbol())))
+        }
+        _ => None,
+    }
+}
+
+struct ModuleView<'a> {
+    name: &'a str,
+    header: Vec<Clause<'a>>,
+    items: Vec<Clause<'a>>,
+    src: &'a str,
+}
+
+impl<'a> ModuleView<'a> {
+    fn new(module: &'a SExpr, src: &'a str) -> Self {
+        let items = match &module.kind {
+            SExprKind::List(items) => items,
+            _ => unreachable!("parse_module only accepts lists"),
+        };
+        let name = items.get(1).and_then(|s| s.as_symbol()).unwrap_or("module");
+        let (header, items) = clauses("module", items.get(2..).unwrap_or(&[]), src)
+            .into_iter()
+            .partition(|c| c.node.is_none());
+        ModuleView { name, header, items, src }
+    }
+
+    fn version(&self) -> i64 {
+        find(&self.header, ":version")
+            .and_then(|c| c.text.trim_start_matches(":version").trim().parse().ok())
+            .unwrap_or(1)
+    }
+}
+
+/// Order keys as in `user`, splicing keys only `generated` has in after their
+/// predecessor there.
+fn merged_order(user: &[&str], generated: &[&str]) -> Vec<String> {
+    let mut order: Vec<String> = user.iter().map(|k| k.to_string()).collect();
+    for (i, key) in generated.iter().enumerate() {
+        if order.iter().any(|k| k == key) {
+            continue;
+        }
+        let pos = if i == 0 {
+            0
+        } else {
+            order.iter().position(|k| k == generated[i - 1]).map_or(order.len(), |p| p + 1)
+        };
+        order.insert(pos, key.to_string());
+    }
+    order
+}
+
+enum Pick {
+    User,
+    Generated,
+    Conflict,
+}
+
+fn pick(base: Option<&str>, user: Option<&str>, generated: Option<&str>) -> Pick {
+    if same(user, generated) || same(base, generated) {
+        Pick::User
+    } else if same(base, user) {
+        Pick::Generated
+    } else {
+        Pick::Conflict
+    }
+}
+
+struct Merger {
+    diagnostics: Vec<Diagnostic>,
+    changes: Vec<String>,
+}
+
+impl Merger {
+    fn merge(&mut self, base: Option<&ModuleView>, user: &ModuleView, generated: &ModuleView) -> String {
+        let mut items = Vec::new();
+        let user_keys: Vec<&str> = user.items.iter().map(|c| c.key.as_str()).collect();
+        let gen_keys: Vec<&str> = generated.items.iter().map(|c| c.key.as_str()).collect();
+        for key in merged_order(&user_keys, &gen_keys) {
+            let b = base.and_then(|b| find(&b.items, &key));
+            let u = find(&user.items, &key);
+            let n = find(&generated.items, &key);
+            if let Some(text) = self.merge_item(&key, b, u, n, base, user, generated) {
+                items.push((key, text));
+            }
+        }
+
+        // Module metadata: the spec owns it, except where it was edited by hand.
+        let mut header = Vec::new();
+        let user_keys: Vec<&str> = user.header.iter().map(|c| c.key.as_str()).collect();
+        let gen_keys: Vec<&str> = generated.header.iter().map(|c| c.key.as_str()).collect();
+        for key in merged_order(&user_keys, &gen_keys) {
+            if matches!(key.as_str(), ":version" | ":parent-version" | ":delta") {
+                continue;
+            }
+            let u = find(&user.header, &key).map(|c| c.text);
+            let n = find(&generated.header, &key).map(|c| c.text);
+            let b = match base {
+                Some(base) => find(&base.header, &key).map(|c| c.text),
+                None => u,
+            };
+            if let Some(text) = self.merge_clause(&format!("module {}", user.name), &key, b, u, n, false) {
+                header.push(text.to_string());
+            }
+        }
+
+        if self.changes.is_empty() {
+            for key in [":version", ":parent-version", ":delta"] {
+                if let Some(c) = find(&user.header, key) {
+                    header.push(c.text.to_string());
+                }
+            }
+        } else {
+            let version = user.version();
+            header.push(format!(":version {}", version + 1));
+            header.push(format!(":parent-version {}", version));
+            header.push(format!(
+                ":delta (regenerated {} \"{}\")",
+                user.name,
+                self.changes.join("; ").replace('"', "'")
+            ));
+        }
+
+        let mut out = format!("(module {}\n", user.name);
+        for clause in &header {
+            out.push_str(&format!("  {}\n", clause));
+        }
+        let mut prev: Option<&str> = None;
+        for (key, text) in &items {
+            let grouped = prev.is_some_and(|p| p.starts_with("effect-set")) && key.starts_with("effect-set");
+            if !grouped {
+                out.push('\n');
+            }
+            out.push_str(&format!("  {}\n", text));
+            prev = Some(key);
+        }
+        out.pop();
+        out.push_str(")\n");
+        out
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn merge_item(
+        &mut self,
+        key: &str,
+        b: Option<&Clause>,
+        u: Option<&Clause>,
+        n: Option<&Clause>,
+        base: Option<&ModuleView>,
+        user: &ModuleView,
+        generated: &ModuleView,
+    ) -> Option<String> {
+        match (u, n) {
+            (Some(u), Some(n)) if same(Some(u.text), Some(n.text)) => Some(u.text.to_string()),
+            (Some(u), Some(n)) => {
+                if base.is_some() && same(b.map(|c| c.text), Some(u.text)) {
+                    self.changes.push(format!("updated {}", key));
+                    return Some(n.text.to_string());
+                }
+                if base.is_some() && same(b.map(|c| c.text), Some(n.text)) {
+                    return Some(u.text.to_string());
+                }
+                match (list_items(u), list_items(n)) {
+                    (Some(ui), Some(ni)) if !key.starts_with("effect-set") => {
+                        let bi = b.and_then(list_items);
+                        Some(self.merge_form(key, bi, ui, ni, base.map(|v| v.src), user.src, generated.src))
+                    }
+                    _ if base.is_none() => {
+                        self.changes.push(format!("updated {}", key));
+                        Some(n.text.to_string())
+                    }
+                    _ => {
+                        self.conflict(key, None);
+                        Some(u.text.to_string())
+                    }
+                }
+            }
+            (Some(u), None) => match b {
+                Some(b) if same(Some(b.text), Some(u.text)) => {
+                    self.changes.push(format!("removed {}", key));
+                    None
+                }
+                Some(_) => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        format!("{} was removed from the spec but has hand edits; kept it", key),
+                        None,
+                    ));
+                    Some(u.text.to_string())
+                }
+                None => Some(u.text.to_string()),
+            },
+            (None, Some(n)) => match b {
+                // Deleted by hand and unchanged in the spec: stay deleted.
+                Some(b) if same(Some(b.text), Some(n.text)) => None,
+                Some(_) => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        format!("{} was deleted by hand but changed in the spec; restored it", key),
+                        None,
+                    ));
+                    self.changes.push(format!("restored {}", key));
+                    Some(n.text.to_string())
+                }
+                None => {
+                    self.changes.push(format!("added {}", key));
+                    Some(n.text.to_string())
+                }
+            },
+            (None, None) => None,
+        }
+    }
+
+    /// Merge a `(type ...)` or `(fn ...)` clause by clause.
+    #[allow(clippy::too_many_arguments)]
+    fn merge_form(
+        &mut self,
+        key: &str,
+        base: Option<&[SExpr]>,
+        user: &[SExpr],
+        generated: &[SExpr],
+        base_src: Option<&str>,
+        user_src: &str,
+        gen_src: &str,
+    ) -> String {
+        let head = key.split_whitespace().next().unwrap_or(key);
+        let base_clauses = base.zip(base_src).map(|(items, src)| clauses(head, &items[2..], src));
+        let user_clauses = clauses(head, &user[2..], user_src);
+        let gen_clauses = clauses(head, &generated[2..], gen_src);
+        let changes_before = self.changes.len();
+
+        let user_keys: Vec<&str> = user_clauses.iter().map(|c| c.key.as_str()).collect();
+        let gen_keys: Vec<&str> = gen_clauses.iter().map(|c| c.key.as_str()).collect();
+        let mut parts = Vec::new();
+        let mut touched = Vec::new();
+        for clause_key in merged_order(&user_keys, &gen_keys) {
+            let is_body = clause_key.starts_with("body");
+            let u = find(&user_clauses, &clause_key).map(|c| c.text);
+            let n = find(&gen_clauses, &clause_key).map(|c| c.text);
+            let b = match &base_clauses {
+                Some(bc) => find(bc, &clause_key).map(|c| c.text),
+                // No base: hand-written bodies win, the spec owns everything else.
+                None if is_body => n,
+                None => u,
+            };
+            if !same(u, n) && !same(b, n) && same(b, u) {
+                touched.push(clause_key.clone());
+            }
+            if let Some(text) = self.merge_clause(key, &clause_key, b, u, n, is_body) {
+                parts.push((is_body, text.to_string()));
+            }
+        }
+        self.changes.truncate(changes_before);
+        if !touched.is_empty() {
+            self.changes.push(format!("updated {} ({})", key, touched.join(", ")));
+        }
+
+        let name = user[1].as_symbol().unwrap_or("");
+        let mut out = format!("({} {}", head, name);
+        for (is_body, text) in parts {
+            out.push_str(if is_body { "\n\n    " } else { "\n    " });
+            out.push_str(&text);
+        }
+        out.push(')');
+        out
+    }
+
+    fn merge_clause<'t>(
+        &mut self,
+        item: &str,
+        clause: &str,
+        b: Option<&'t str>,
+        u: Option<&'t str>,
+        n: Option<&'t str>,
+        is_body: bool,
+    ) -> Option<&'t str> {
+        match pick(b, u, n) {
+            Pick::User => u,
+            Pick::Generated => {
+                self.changes.push(format!("updated {}", item));
+                n
+            }
+            Pick::Conflict if is_body => {
+                self.diagnostics.push(Diagnostic::warning(
+                    format!("{}: kept the hand-edited body; the generated body also changed", item),
+                    None,
+                ));
+                u
+            }
+            Pick::Conflict => {
+                self.conflict(item, Some(clause));
+                u.or(n)
+            }
+        }
+    }
+
+    fn conflict(&mut self, item: &str, clause: Option<&str>) {
+        let what = match clause {
+            Some(clause) => format!("{} {}", item, clause),
+            None => item.to_string(),
+        };
+        self.diagnostics.push(Diagnostic::error(
+            format!("conflict in {}: changed both by hand and in the spec; kept the hand-edited version", what),
+            None,
+        ));
+    }
+}
+
+fn list_items<'a>(clause: &Clause<'a>) -> Option<&'a [SExpr]> {
+    match &clause.node?.kind {
+        SExprKind::List(items) if items.len() >= 2 => Some(items),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::DiagnosticKind;
+    use crate::lower::Lowerer;
+
+    const BASE: &str = "\
+(module svc
+  :provenance {req: \"SPEC-1\"}
+  :version 1
+
+  (type User
+    (field name String :max-len 200)
+    (field id UUID :immutable))
+
+  (fn get-user
+    :effects    [db-read]
+    :total      true
+    :latency-budget 50ms
+    (param id UUID)
+    (returns (union
+      (ok   User :http 200)
+      (err  :not-found {:id id} :http 404)))
+
+    (match (query user-store {:id id})
+      (none)   (err :not-found {:id id})
+      (some u) (ok u))))
+";
+
+    fn lower(source: &str) -> crate::ast::Module {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    #[test]
+    fn test_hand_edits_survive_spec_changes() {
+        let user = BASE.replace("(some u) (ok u)", "(some u) (ok (redact u))");
+        let generated = BASE
+            .replace(":max-len 200", ":max-len 100")
+            .replace(":latency-budget 50ms", ":latency-budget 20ms")
+            .replace("(field id UUID :immutable))", "(field id UUID :immutable)\n    (field email String))");
+
+        let result = merge_modules(Some(BASE), &user, &generated).unwrap();
+        assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+
+        let module = lower(&result.source);
+        assert_eq!(module.version, Some(2));
+        assert_eq!(module.parent_version, Some(1));
+        assert_eq!(module.delta.as_ref().unwrap().operation, "regenerated");
+        assert_eq!(module.types[0].fields.len(), 3);
+        assert_eq!(module.types[0].fields[0].max_len, Some(100));
+        assert_eq!(module.functions[0].latency_budget.as_ref().unwrap().value, 20);
+        assert!(result.source.contains("(ok (redact u))"));
+    }
+
+    #[test]
+    fn test_conflicting_edits_are_reported() {
+        let user = BASE.replace(":latency-budget 50ms", ":latency-budget 80ms");
+        let generated = BASE.replace(":latency-budget 50ms", ":latency-budget 20ms");
+
+        let result = merge_modules(Some(BASE), &user, &generated).unwrap();
+        let errors: Vec<_> = result
+            .diagnostics
+            .iter()
+            .filter(|d| d.kind == DiagnosticKind::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("fn get-user :latency-budget"));
+        assert!(result.source.contains(":latency-budget 80ms"));
+    }
+
+    #[test]
+    fn test_unchanged_spec_leaves_module_alone() {
+        let user = BASE.replace("(ok u)", "(ok (redact u))");
+        let result = merge_modules(Some(BASE), &user, BASE).unwrap();
+        assert!(result.changes.is_empty());
+        assert_eq!(lower(&result.source).version, Some(1));
+        assert!(result.source.contains("(ok (redact u))"));
+    }
+
+    #[test]
+    fn test_without_base_spec_owns_headers_and_user_owns_bodies() {
+        let user = BASE
+            .replace("(ok u)", "(ok (redact u))")
+            .replace(":latency-budget 50ms", ":latency-budget 80ms");
+        let generated = BASE.replace(":latency-budget 50ms", ":latency-budget 20ms");
+
+        let result = merge_modules(None, &user, &generated).unwrap();
+        assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+        assert!(result.source.contains(":latency-budget 20ms"));
+        assert!(result.source.contains("(ok (redact u))"));
+    }
+
+    #[test]
+    fn test_keyword_values_stay_with_their_key() {
+        let base = BASE.replace(":latency-budget 50ms", ":latency-budget 50ms\n    :on-store-error :store-unavailable");
+        let user = base.replace("(ok u)", "(ok (redact u))");
+        let generated = base.replace(":latency-budget 50ms", ":latency-budget 20ms");
+
+        let result = merge_modules(Some(&base), &user, &generated).unwrap();
+        assert!(result.source.contains(":on-store-error :store-unavailable"), "{}", result.source);
+
+        let generated = base.replace(":on-store-error :store-unavailable", ":on-store-error :not-found");
+        let result = merge_modules(Some(&base), &user, &generated).unwrap();
+        assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+        assert_eq!(result.changes, vec!["updated fn get-user (:on-store-error)".to_string()]);
+        assert_eq!(lower(&result.source).functions[0].on_store_error.as_deref(), Some("not-found"));
+    }
+
+    #[test]
+    fn test_flags_are_read_as_the_lowerer_reads_them() {
+        let keys = |form: &str, src: &str| {
+            let sexpr = Parser::new(Lexer::new(src).tokenize().unwrap()).parse_program().unwrap().remove(0);
+            let items = sexpr.as_list().unwrap();
+            clauses(form, &items[3..], src).into_iter().map(|c| c.key).collect::<Vec<_>>()
+        };
+
+        let src = "(field sku String :indexed :unique-within stock-store :immutable :audited)";
+        assert_eq!(keys("field", src), [":indexed", ":unique-within", ":immutable", ":audited"]);
+        let field = lower(&format!("(module m (type T {}))", src)).types[0].fields[0].clone();
+        assert_eq!(field.unique_within.as_deref(), Some("stock-store"));
+        assert!(field.immutable);
+        assert_eq!(field.extra_meta.len(), 2);
+
+        // Outside fields every keyword takes a value, even a flag's name
+        let src = "(fn f String :generated :by-hand :total true)";
+        assert_eq!(keys("fn", src), [":generated", ":total"]);
+    }
+}
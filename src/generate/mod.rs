@@ -3,6 +3,8 @@ pub mod yaml_parser;
 pub mod spec_ast;
 pub mod spec_parser;
 pub mod pct_emitter;
+pub mod verify;
+pub mod merge;
 
 #[cfg(test)]
 mod integration_tests {
@@ -102,4 +104,119 @@ traceability:
         assert!(!module.functions.is_empty());
         assert!(module.functions[0].total);
     }
+
+    #[test]
+    fn test_multi_type_spec_generates_checked_module() {
+        let source = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/inventory.spec.yaml"
+        ))
+        .unwrap();
+        let yaml = YamlParser::new(&source).parse().unwrap();
+        let spec = spec_parser::parse_spec(&yaml).unwrap();
+        let pct = PctEmitter::new().emit(&spec);
+
+        let tokens = Lexer::new(&pct).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let mut lowerer = Lowerer::new();
+        let module = lowerer.lower_module(&sexprs[0]).unwrap();
+
+        assert_eq!(module.types.len(), 3);
+        let reserve = module.functions.iter().find(|f| f.name == "reserve-stock").unwrap();
+        assert_eq!(reserve.effects, vec!["reservation-write", "http-respond"]);
+
+        let errors: Vec<_> = crate::semantic::analyze(&module)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .collect();
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_flow_style_outputs_round_trip() {
+        let source = EXAMPLE_SPEC.replace(
+            "    outputs:\n      - success: the user found (200)\n      - not found: when the ID doesn't exist (404)\n",
+            "    outputs: [success (200), not found (404)]\n",
+        );
+        assert!(source.contains("outputs: ["));
+        let yaml = YamlParser::new(&source).parse().unwrap();
+        let spec = spec_parser::parse_spec(&yaml).unwrap();
+        let pct = PctEmitter::new().emit(&spec);
+        assert!(pct.contains("(err :not-found"), "{}", pct);
+        assert!(!pct.contains(":not-found-404"), "{}", pct);
+
+        let tokens = Lexer::new(&pct).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        let err_tags: Vec<(&str, Option<i64>)> = module.functions[0]
+            .returns
+            .variants
+            .iter()
+            .filter_map(|v| match &v.kind {
+                crate::ast::VariantKind::Err { tag, http_status, .. } => Some((tag.as_str(), *http_status)),
+                _ => None,
+            })
+            .collect();
+        assert!(err_tags.contains(&("not-found", Some(404))), "{:?}", err_tags);
+        // The body returns the variant `returns` declares
+        assert!(pct.contains("(none)   (err :not-found {:id uuid})"), "{}", pct);
+        let errors: Vec<_> = crate::semantic::analyze(&module)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .collect();
+        assert!(errors.is_empty(), "checking {}: {:?}", pct, errors);
+    }
+
+    #[test]
+    fn test_every_field_descriptor_lowers_and_checks() {
+        let source = "\
+spec: SPEC-2024-0050
+title: Teams
+owner: platform-team
+domain:
+  Member:
+    fields:
+      - id: auto-generated, immutable
+      - name: required, string, 1-200 chars
+      - role: one of: admin, member, guest, defaults to member
+      - tags: list of tags
+      - manager: optional, reference to Member
+      - score: between 0 and 100, defaults to 50
+      - joined-at: timestamp, auto-generated
+      - rate: decimal, 2 places, between 0.01 and 1000
+      - weight: float
+endpoints:
+  get-member:
+    description: Returns a member by ID
+    input: member id (from URL)
+    outputs:
+      - success: the member found (200)
+      - not found: when the ID doesn't exist (404)
+    constraints:
+      - read-only
+";
+        let yaml = YamlParser::new(source).parse().unwrap();
+        let spec = spec_parser::parse_spec(&yaml).unwrap();
+        let pct = PctEmitter::new().emit(&spec);
+
+        let tokens = Lexer::new(&pct).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let mut lowerer = Lowerer::new();
+        let module = lowerer.lower_module(&sexprs[0]).unwrap();
+        assert!(lowerer.diagnostics.is_empty(), "lowering {}: {:?}", pct, lowerer.diagnostics);
+
+        let member = &module.types[0];
+        let field = |name: &str| member.fields.iter().find(|f| f.name == name).unwrap();
+        assert!(matches!(&field("role").type_expr, crate::ast::TypeExpr::Enum(_)));
+        assert!(field("role").default.is_some());
+        assert!(matches!(&field("manager").type_expr, crate::ast::TypeExpr::Optional(inner)
+            if matches!(inner.as_ref(), crate::ast::TypeExpr::Ref(t) if t == "Member")));
+        assert!(field("score").min.is_some() && field("score").max.is_some());
+
+        let errors: Vec<_> = crate::semantic::analyze(&module)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .collect();
+        assert!(errors.is_empty(), "checking {}: {:?}", pct, errors);
+    }
 }
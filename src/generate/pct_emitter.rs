@@ -6,6 +6,12 @@ pub struct PctEmitter {
     indent: usize,
 }
 
+impl Default for PctEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PctEmitter {
     pub fn new() -> Self {
         PctEmitter {
@@ -21,7 +27,6 @@ impl PctEmitter {
 
     fn emit_module(&mut self, spec: &SpecDoc) {
         let module_name = self.derive_module_name(&spec.title);
-        let store_name = self.derive_store_name(spec);
 
         self.write(&format!("(module {}", module_name));
         self.indent += 2;
@@ -41,17 +46,17 @@ impl PctEmitter {
 
         // Emit type definitions
         for dt in &spec.domain_types {
-            self.emit_type_def(dt, &store_name);
+            self.emit_type_def(dt, &store_name(&dt.name));
             self.newline();
         }
 
         // Emit effect sets based on constraints
-        self.emit_effect_sets(spec, &store_name);
+        self.emit_effect_sets(spec);
 
         // Emit functions for endpoints
         let all_total = spec.quality.contains(&QualityRule::AllFunctionsTotal);
         for ep in &spec.endpoints {
-            self.emit_endpoint_fn(ep, spec, &store_name, all_total);
+            self.emit_endpoint_fn(ep, spec, all_total);
         }
 
         // Close module
@@ -86,14 +91,7 @@ impl PctEmitter {
     }
 
     fn emit_field(&mut self, field: &FieldSpec, store_name: &str) {
-        let type_str = match &field.field_type {
-            FieldType::StringType => "String",
-            FieldType::UuidType => "UUID",
-            FieldType::IntType => "Int",
-            FieldType::BoolType => "Bool",
-            FieldType::Unknown(s) if s.is_empty() => "String",
-            FieldType::Unknown(s) => s.as_str(),
-        };
+        let type_str = field_type_to_pct(&field.field_type);
 
         let mut parts = vec![format!("(field {} {}", field.name, type_str)];
 
@@ -109,9 +107,21 @@ impl PctEmitter {
         if let Some(max) = field.max_len {
             parts.push(format!(":max-len {}", max));
         }
+        if let Some(min) = field.min_value {
+            parts.push(format!(":min {}", min));
+        }
+        if let Some(max) = field.max_value {
+            parts.push(format!(":max {}", max));
+        }
+        if let Some(scale) = field.scale {
+            parts.push(format!(":scale {}", scale));
+        }
         if let Some(ref fmt) = field.format {
             parts.push(format!(":format :{}", fmt));
         }
+        if let Some(ref default) = field.default {
+            parts.push(format!(":default {}", default_to_pct(default, &field.field_type)));
+        }
         if field.unique {
             parts.push(format!(":unique-within {}", store_name));
         }
@@ -133,56 +143,59 @@ impl PctEmitter {
                     invariants.push(format!("(matches {} #/.+@.+\\..+/)", field.name));
                 }
             }
+            if let Some(min) = field.min_value {
+                invariants.push(format!("(>= {} {})", field.name, min));
+            }
+            if let Some(max) = field.max_value {
+                invariants.push(format!("(<= {} {})", field.name, max));
+            }
         }
         invariants
     }
 
-    fn emit_effect_sets(&mut self, spec: &SpecDoc, store_name: &str) {
-        let mut has_read = false;
-        let mut has_write = false;
+    /// One read and/or write effect set per store the endpoints touch.
+    fn emit_effect_sets(&mut self, spec: &SpecDoc) {
+        let mut any = false;
 
-        for ep in &spec.endpoints {
-            for c in &ep.constraints {
-                match c {
-                    Constraint::ReadOnly => has_read = true,
-                    Constraint::Write => has_write = true,
-                    _ => {}
-                }
+        for dt in &spec.domain_types {
+            let targets: Vec<&Endpoint> = spec
+                .endpoints
+                .iter()
+                .filter(|ep| target_type(ep, spec).map(|t| t.name.as_str()) == Some(dt.name.as_str()))
+                .collect();
+            let has_read = targets.iter().any(|ep| endpoint_access(ep) == Access::Read);
+            let has_write = targets.iter().any(|ep| endpoint_access(ep) == Access::Write);
+
+            let store = store_name(&dt.name);
+            let (read_set, write_set) = effect_set_names(spec, &dt.name);
+            if has_read {
+                self.newline();
+                self.write(&format!("(effect-set {}    [:reads  {}])", read_set, store));
             }
-            // Infer from input source if no explicit constraint
-            if ep.constraints.iter().all(|c| !matches!(c, Constraint::ReadOnly | Constraint::Write)) {
-                if ep.input.source == InputSource::Url {
-                    has_read = true;
-                } else if ep.input.source == InputSource::Body {
-                    has_write = true;
-                }
+            if has_write {
+                self.newline();
+                self.write(&format!(
+                    "(effect-set {}   [:writes {} :reads {}])",
+                    write_set, store, store
+                ));
             }
+            any |= has_read || has_write;
         }
 
-        if has_read {
-            self.newline();
-            self.write(&format!("(effect-set db-read    [:reads  {}])", store_name));
-        }
-        if has_write {
-            self.newline();
-            self.write(&format!(
-                "(effect-set db-write   [:writes {} :reads {}])",
-                store_name, store_name
-            ));
-        }
-        if has_read || has_write {
+        if any {
             self.newline();
             self.write("(effect-set http-respond [:sends http-response])");
         }
     }
 
-    fn emit_endpoint_fn(
-        &mut self,
-        ep: &Endpoint,
-        spec: &SpecDoc,
-        store_name: &str,
-        all_total: bool,
-    ) {
+    fn emit_endpoint_fn(&mut self, ep: &Endpoint, spec: &SpecDoc, all_total: bool) {
+        let target = target_type(ep, spec);
+        let type_name = target.map(|t| t.name.as_str()).unwrap_or("Entity");
+        let store = target
+            .map(|t| store_name(&t.name))
+            .unwrap_or_else(|| "data-store".into());
+        let (read_set, write_set) = effect_set_names(spec, type_name);
+
         self.newline();
         self.newline();
         self.write(&format!("(fn {}", ep.name));
@@ -197,18 +210,11 @@ impl PctEmitter {
         self.newline();
 
         // Effects
-        let is_read_only = ep.constraints.contains(&Constraint::ReadOnly)
-            || (ep.input.source == InputSource::Url
-                && !ep.constraints.contains(&Constraint::Write));
-        let is_write = ep.constraints.contains(&Constraint::Write)
-            || ep.input.source == InputSource::Body;
-
-        let effects = if is_write {
-            "[db-write http-respond]"
-        } else if is_read_only {
-            "[db-read http-respond]"
-        } else {
-            "[http-respond]"
+        let access = endpoint_access(ep);
+        let effects = match access {
+            Access::Write => format!("[{} http-respond]", write_set),
+            Access::Read => format!("[{} http-respond]", read_set),
+            Access::None => "[http-respond]".to_string(),
         };
         self.write(&format!(":effects    {}", effects));
         self.newline();
@@ -235,6 +241,13 @@ impl PctEmitter {
             }
         }
 
+        // Writes match (err :unique-violation); other store errors map onto a 503
+        let is_read = access == Access::Read || ep.input.source == InputSource::Url;
+        if !is_read {
+            self.write(":on-store-error :store-unavailable");
+            self.newline();
+        }
+
         // Called-by
         if !spec.traceability.known_dependencies.is_empty() {
             let deps: Vec<String> = spec
@@ -248,25 +261,23 @@ impl PctEmitter {
         }
 
         // Params
-        let primary_type = spec.domain_types.first().map(|t| t.name.as_str()).unwrap_or("Entity");
-
-        if is_read_only || ep.input.source == InputSource::Url {
+        if is_read {
             self.emit_url_param();
         } else {
-            self.emit_body_param(primary_type, spec);
+            self.emit_body_param(target);
         }
         self.newline();
 
         // Returns
-        self.emit_returns(ep, primary_type);
+        self.emit_returns(ep, type_name, !is_read);
         self.newline();
 
         // Body
         self.newline();
-        if is_read_only || ep.input.source == InputSource::Url {
-            self.emit_read_body(store_name);
+        if is_read {
+            self.emit_read_body(&store);
         } else {
-            self.emit_write_body(store_name, primary_type, ep);
+            self.emit_write_body(&store, type_name, ep);
         }
 
         self.indent -= 2;
@@ -283,24 +294,14 @@ impl PctEmitter {
         self.indent -= 2;
     }
 
-    fn emit_body_param(&mut self, _type_name: &str, spec: &SpecDoc) {
+    fn emit_body_param(&mut self, target: Option<&DomainType>) {
         // Build input field map from the domain type
-        let fields_str = if let Some(dt) = spec.domain_types.first() {
+        let fields_str = if let Some(dt) = target {
             let field_parts: Vec<String> = dt
                 .fields
                 .iter()
                 .filter(|f| !f.auto_generated && !f.immutable)
-                .map(|f| {
-                    let t = match &f.field_type {
-                        FieldType::StringType => "String",
-                        FieldType::UuidType => "UUID",
-                        FieldType::IntType => "Int",
-                        FieldType::BoolType => "Bool",
-                        FieldType::Unknown(s) if s.is_empty() => "String",
-                        FieldType::Unknown(s) => s.as_str(),
-                    };
-                    format!(":{} {}", f.name, t)
-                })
+                .map(|f| format!(":{} {}", f.name, field_type_to_pct(&f.field_type)))
                 .collect();
             format!("{{{}}}", field_parts.join(" "))
         } else {
@@ -318,7 +319,7 @@ impl PctEmitter {
         self.indent -= 2;
     }
 
-    fn emit_returns(&mut self, ep: &Endpoint, primary_type: &str) {
+    fn emit_returns(&mut self, ep: &Endpoint, primary_type: &str, store_errors: bool) {
         self.write("(returns (union");
         self.indent += 2;
 
@@ -344,6 +345,10 @@ impl PctEmitter {
                 self.write(&format!("(err  :{} {}{})", tag, payload, http));
             }
         }
+        if store_errors {
+            self.newline();
+            self.write("(err  :store-unavailable {} :http 503)");
+        }
 
         self.indent -= 2;
         self.append("))");
@@ -430,14 +435,6 @@ impl PctEmitter {
             .collect()
     }
 
-    fn derive_store_name(&self, spec: &SpecDoc) -> String {
-        if let Some(dt) = spec.domain_types.first() {
-            format!("{}-store", dt.name.to_lowercase())
-        } else {
-            "data-store".into()
-        }
-    }
-
     fn label_to_tag(&self, label: &str) -> String {
         label
             .to_lowercase()
@@ -480,6 +477,105 @@ impl PctEmitter {
     }
 }
 
+/// The `.pct` type expression for a spec field type. Timestamps are epoch `Int`s.
+pub(super) fn field_type_to_pct(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::StringType => "String".into(),
+        FieldType::UuidType => "UUID".into(),
+        FieldType::IntType | FieldType::DateTime => "Int".into(),
+        FieldType::FloatType => "Float".into(),
+        FieldType::DecimalType => "Decimal".into(),
+        FieldType::BoolType => "Bool".into(),
+        FieldType::Enum(values) => {
+            let kws: Vec<String> = values.iter().map(|v| format!(":{}", v)).collect();
+            format!("(enum {})", kws.join(" "))
+        }
+        FieldType::List(inner) => format!("(list {})", field_type_to_pct(inner)),
+        FieldType::Reference(target) => format!("(ref {})", target),
+        FieldType::Optional(inner) => format!("(optional {})", field_type_to_pct(inner)),
+        FieldType::Unknown(s) if s.is_empty() => "String".into(),
+        FieldType::Unknown(s) => s.clone(),
+    }
+}
+
+/// Render a default as a `.pct` literal: enum values become keywords,
+/// numbers and booleans stay bare, anything else is a string.
+fn default_to_pct(value: &str, field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Optional(inner) => default_to_pct(value, inner),
+        FieldType::Enum(_) => format!(":{}", value.to_lowercase().replace(' ', "-")),
+        FieldType::IntType | FieldType::DateTime if value.parse::<i64>().is_ok() => value.into(),
+        FieldType::FloatType | FieldType::DecimalType if value.parse::<f64>().is_ok() => value.into(),
+        FieldType::BoolType if value == "true" || value == "false" => value.into(),
+        _ => format!("\"{}\"", value.replace('"', "\\\"")),
+    }
+}
+
+/// How an endpoint touches its store.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write,
+    None,
+}
+
+fn endpoint_access(ep: &Endpoint) -> Access {
+    let is_write = ep.constraints.contains(&Constraint::Write)
+        || ep.input.source == InputSource::Body;
+    let is_read_only = ep.constraints.contains(&Constraint::ReadOnly)
+        || (ep.input.source == InputSource::Url
+            && !ep.constraints.contains(&Constraint::Write));
+
+    if is_write {
+        Access::Write
+    } else if is_read_only {
+        Access::Read
+    } else {
+        Access::None
+    }
+}
+
+/// The endpoint's declared or inferred type, else the spec's first domain type.
+fn target_type<'a>(ep: &Endpoint, spec: &'a SpecDoc) -> Option<&'a DomainType> {
+    match &ep.target_type {
+        Some(name) => spec.domain_types.iter().find(|dt| &dt.name == name),
+        None => spec.domain_types.first(),
+    }
+}
+
+/// `StockEntry` → `stock-entry-store`.
+fn store_name(type_name: &str) -> String {
+    format!("{}-store", to_kebab(type_name))
+}
+
+/// Single-type specs keep the plain `db-read`/`db-write` names; with several
+/// types each store gets its own pair (`product-read`, `product-write`, ...).
+fn effect_set_names(spec: &SpecDoc, type_name: &str) -> (String, String) {
+    if spec.domain_types.len() <= 1 {
+        ("db-read".into(), "db-write".into())
+    } else {
+        let base = to_kebab(type_name);
+        (format!("{}-read", base), format!("{}-write", base))
+    }
+}
+
+fn to_kebab(name: &str) -> String {
+    let mut result = String::new();
+    for (i, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                result.push('-');
+            }
+            result.extend(ch.to_lowercase());
+        } else if ch == '_' || ch == ' ' {
+            result.push('-');
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
 fn current_date() -> String {
     // Return a fixed format — in production this would use chrono or similar
     "2026-01-01T00:00:00Z".to_string()
@@ -503,6 +599,10 @@ mod tests {
                         field_type: FieldType::UuidType,
                         min_len: None,
                         max_len: None,
+                        min_value: None,
+                        max_value: None,
+                        scale: None,
+                        default: None,
                         format: None,
                         unique: false,
                         auto_generated: true,
@@ -514,6 +614,10 @@ mod tests {
                         field_type: FieldType::StringType,
                         min_len: Some(1),
                         max_len: Some(200),
+                        min_value: None,
+                        max_value: None,
+                        scale: None,
+                        default: None,
                         format: None,
                         unique: false,
                         auto_generated: false,
@@ -525,6 +629,10 @@ mod tests {
                         field_type: FieldType::StringType,
                         min_len: None,
                         max_len: None,
+                        min_value: None,
+                        max_value: None,
+                        scale: None,
+                        default: None,
                         format: Some("email".into()),
                         unique: true,
                         auto_generated: false,
@@ -535,6 +643,7 @@ mod tests {
             endpoints: vec![Endpoint {
                 name: "get-user".into(),
                 description: "Returns a user by ID".into(),
+                target_type: Some("User".into()),
                 input: InputSpec {
                     description: "user id (from URL)".into(),
                     source: InputSource::Url,
@@ -563,6 +672,7 @@ mod tests {
             traceability: Traceability {
                 known_dependencies: vec!["api-router".into(), "admin-panel".into()],
             },
+            warnings: Vec::new(),
         }
     }
 
@@ -638,6 +748,7 @@ mod tests {
         spec.endpoints.push(Endpoint {
             name: "create-user".into(),
             description: "Creates a new user".into(),
+            target_type: None,
             input: InputSpec {
                 description: "user data (from body)".into(),
                 source: InputSource::Body,
@@ -674,6 +785,95 @@ mod tests {
         assert!(output.contains("(insert! user-store (build User input))"));
     }
 
+    #[test]
+    fn test_emit_multiple_types_and_stores() {
+        let mut spec = make_simple_spec();
+        spec.domain_types.push(DomainType {
+            name: "StockEntry".into(),
+            fields: vec![FieldSpec {
+                name: "warehouse".into(),
+                required: true,
+                field_type: FieldType::StringType,
+                min_len: None,
+                max_len: None,
+                min_value: None,
+                max_value: None,
+                scale: None,
+                default: None,
+                format: None,
+                unique: true,
+                auto_generated: false,
+                immutable: false,
+            }],
+        });
+        spec.endpoints.push(Endpoint {
+            name: "add-stock".into(),
+            description: "Adds a stock entry".into(),
+            target_type: Some("StockEntry".into()),
+            input: InputSpec {
+                description: "stock entry data (from body)".into(),
+                source: InputSource::Body,
+                fields: Vec::new(),
+            },
+            outputs: vec![OutputSpec {
+                label: "created".into(),
+                description: "the entry (201)".into(),
+                http_status: Some(201),
+                is_success: true,
+            }],
+            constraints: Vec::new(),
+        });
+
+        let output = PctEmitter::new().emit(&spec);
+        assert!(output.contains("(field warehouse String :unique-within stock-entry-store)"));
+        assert!(output.contains("(effect-set user-read    [:reads  user-store])"));
+        assert!(output.contains(
+            "(effect-set stock-entry-write   [:writes stock-entry-store :reads stock-entry-store])"
+        ));
+        assert!(!output.contains("user-write"));
+        assert!(output.contains(":effects    [user-read http-respond]"));
+        assert!(output.contains(":effects    [stock-entry-write http-respond]"));
+        assert!(output.contains("(param input {:warehouse String}"));
+        assert!(output.contains("(ok   StockEntry :http 201  :serialize :json)"));
+        assert!(output.contains("(insert! stock-entry-store (build StockEntry input))"));
+        assert!(output.contains("(match (query user-store {:id uuid})"));
+    }
+
+    #[test]
+    fn test_emit_rich_field_types() {
+        let mut spec = make_simple_spec();
+        let fields = &mut spec.domain_types[0].fields;
+        fields.push(crate::generate::spec_parser::parse_field_descriptor(
+            "role",
+            "one of: admin, member, guest, defaults to member",
+        ));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor("tags", "list of tags"));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor(
+            "manager",
+            "optional, reference to User",
+        ));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor(
+            "score",
+            "between 0 and 100, defaults to 50",
+        ));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor("joined-at", "timestamp"));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor(
+            "price",
+            "decimal, 2 places, between 0.01 and 1000",
+        ));
+        fields.push(crate::generate::spec_parser::parse_field_descriptor("rate", "float"));
+
+        let output = PctEmitter::new().emit(&spec);
+        assert!(output.contains("(field role (enum :admin :member :guest) :default :member)"));
+        assert!(output.contains("(field tags (list String))"));
+        assert!(output.contains("(field manager (optional (ref User)))"));
+        assert!(output.contains("(field score Int :min 0 :max 100 :default 50)"));
+        assert!(output.contains("(field joined-at Int)"));
+        assert!(output.contains("(>= score 0) (<= score 100)"));
+        assert!(output.contains("(field price Decimal :min 0.01 :max 1000 :scale 2)"));
+        assert!(output.contains("(field rate Float)"));
+    }
+
     #[test]
     fn test_emit_module_closes() {
         let spec = make_simple_spec();
@@ -1,3 +1,5 @@
+use crate::ast::Number;
+
 /// Typed specification document parsed from YAML.
 #[derive(Debug, Clone)]
 pub struct SpecDoc {
@@ -8,6 +10,8 @@ pub struct SpecDoc {
     pub endpoints: Vec<Endpoint>,
     pub quality: Vec<QualityRule>,
     pub traceability: Traceability,
+    /// Descriptor phrases that could not be interpreted.
+    pub warnings: Vec<String>,
 }
 
 /// A domain type with its fields.
@@ -25,6 +29,13 @@ pub struct FieldSpec {
     pub field_type: FieldType,
     pub min_len: Option<usize>,
     pub max_len: Option<usize>,
+    /// Numeric range, e.g. "between 0 and 100".
+    pub min_value: Option<Number>,
+    pub max_value: Option<Number>,
+    /// Decimal places, e.g. "2 places".
+    pub scale: Option<u32>,
+    /// Default value as written, e.g. "defaults to member".
+    pub default: Option<String>,
     pub format: Option<String>,
     pub unique: bool,
     pub auto_generated: bool,
@@ -37,7 +48,21 @@ pub enum FieldType {
     StringType,
     UuidType,
     IntType,
+    /// "float" / "double"
+    FloatType,
+    /// "decimal" — fixed-point, for money
+    DecimalType,
     BoolType,
+    /// "timestamp" / "datetime"
+    DateTime,
+    /// "one of: admin, member, guest"
+    Enum(Vec<String>),
+    /// "list of tags"
+    List(Box<FieldType>),
+    /// "reference to User"
+    Reference(String),
+    /// "optional"
+    Optional(Box<FieldType>),
     Unknown(String),
 }
 
@@ -46,6 +71,8 @@ pub enum FieldType {
 pub struct Endpoint {
     pub name: String,
     pub description: String,
+    /// Domain type the endpoint operates on, declared via `type:` or inferred.
+    pub target_type: Option<String>,
     pub input: InputSpec,
     pub outputs: Vec<OutputSpec>,
     pub constraints: Vec<Constraint>,
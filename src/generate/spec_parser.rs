@@ -1,5 +1,6 @@
 use super::yaml_ast::YamlValue;
 use super::spec_ast::*;
+use crate::ast::Number;
 
 /// Parse a YamlValue (top-level mapping) into a typed SpecDoc.
 pub fn parse_spec(yaml: &YamlValue) -> Result<SpecDoc, SpecParseError> {
@@ -14,8 +15,10 @@ pub fn parse_spec(yaml: &YamlValue) -> Result<SpecDoc, SpecParseError> {
     let owner = get_scalar(yaml, "owner")
         .unwrap_or_default();
 
-    let domain_types = parse_domain(yaml)?;
-    let endpoints = parse_endpoints(yaml)?;
+    let mut warnings = Vec::new();
+    let domain_types = parse_domain(yaml, &mut warnings)?;
+    check_references(&domain_types, &mut warnings);
+    let endpoints = parse_endpoints(yaml, &domain_types)?;
     let quality = parse_quality(yaml);
     let traceability = parse_traceability(yaml);
 
@@ -30,6 +33,7 @@ pub fn parse_spec(yaml: &YamlValue) -> Result<SpecDoc, SpecParseError> {
         endpoints,
         quality,
         traceability,
+        warnings,
     })
 }
 
@@ -46,7 +50,10 @@ fn get_scalar(yaml: &YamlValue, key: &str) -> Option<String> {
     yaml.get(key)?.as_scalar().map(|s| s.to_string())
 }
 
-fn parse_domain(yaml: &YamlValue) -> Result<Vec<DomainType>, SpecParseError> {
+fn parse_domain(
+    yaml: &YamlValue,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<DomainType>, SpecParseError> {
     let domain = match yaml.get("domain") {
         Some(d) => d,
         None => return Ok(Vec::new()),
@@ -58,7 +65,7 @@ fn parse_domain(yaml: &YamlValue) -> Result<Vec<DomainType>, SpecParseError> {
 
     let mut types = Vec::new();
     for (type_name, type_val) in pairs {
-        let fields = parse_fields(type_val)?;
+        let fields = parse_fields(type_val, warnings)?;
         types.push(DomainType {
             name: type_name.clone(),
             fields,
@@ -67,7 +74,10 @@ fn parse_domain(yaml: &YamlValue) -> Result<Vec<DomainType>, SpecParseError> {
     Ok(types)
 }
 
-fn parse_fields(type_val: &YamlValue) -> Result<Vec<FieldSpec>, SpecParseError> {
+fn parse_fields(
+    type_val: &YamlValue,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<FieldSpec>, SpecParseError> {
     let fields_val = match type_val.get("fields") {
         Some(f) => f,
         None => return Ok(Vec::new()),
@@ -89,13 +99,46 @@ fn parse_fields(type_val: &YamlValue) -> Result<Vec<FieldSpec>, SpecParseError>
 
         let (field_name, descriptor_val) = &mapping[0];
         let descriptor = descriptor_val.as_scalar().unwrap_or("");
-        fields.push(parse_field_descriptor(field_name, descriptor));
+        fields.push(parse_field_descriptor_checked(field_name, descriptor, warnings));
     }
     Ok(fields)
 }
 
+/// Warn about "reference to X" where X is not a domain type of this spec.
+fn check_references(domain_types: &[DomainType], warnings: &mut Vec<String>) {
+    fn referenced(t: &FieldType) -> Option<&str> {
+        match t {
+            FieldType::Reference(name) => Some(name),
+            FieldType::List(inner) | FieldType::Optional(inner) => referenced(inner),
+            _ => None,
+        }
+    }
+
+    for dt in domain_types {
+        for field in &dt.fields {
+            if let Some(target) = referenced(&field.field_type) {
+                if !domain_types.iter().any(|d| d.name == target) {
+                    warnings.push(format!(
+                        "field '{}.{}': reference to unknown type '{}'",
+                        dt.name, field.name, target
+                    ));
+                }
+            }
+        }
+    }
+}
+
 /// Parse a natural-language field descriptor like "required, string, 1-200 chars"
 pub fn parse_field_descriptor(name: &str, descriptor: &str) -> FieldSpec {
+    parse_field_descriptor_checked(name, descriptor, &mut Vec::new())
+}
+
+/// Like `parse_field_descriptor`, but reports phrases it could not interpret.
+pub fn parse_field_descriptor_checked(
+    name: &str,
+    descriptor: &str,
+    warnings: &mut Vec<String>,
+) -> FieldSpec {
     let parts: Vec<&str> = descriptor.split(',').map(|s| s.trim()).collect();
     let lower_parts: Vec<String> = parts.iter().map(|s| s.to_lowercase()).collect();
 
@@ -105,24 +148,26 @@ pub fn parse_field_descriptor(name: &str, descriptor: &str) -> FieldSpec {
         field_type: FieldType::Unknown(String::new()),
         min_len: None,
         max_len: None,
+        min_value: None,
+        max_value: None,
+        scale: None,
+        default: None,
         format: None,
         unique: false,
         auto_generated: false,
         immutable: false,
     };
+    let mut optional = false;
 
-    for (i, part) in lower_parts.iter().enumerate() {
-        let part = part.as_str();
+    let mut i = 0;
+    while i < lower_parts.len() {
+        let part = lower_parts[i].as_str();
         if part == "required" {
             spec.required = true;
-        } else if part == "string" {
-            spec.field_type = FieldType::StringType;
-        } else if part == "uuid" {
-            spec.field_type = FieldType::UuidType;
-        } else if part == "int" || part == "integer" {
-            spec.field_type = FieldType::IntType;
-        } else if part == "bool" || part == "boolean" {
-            spec.field_type = FieldType::BoolType;
+        } else if part == "optional" {
+            optional = true;
+        } else if let Some(t) = scalar_type(part) {
+            spec.field_type = t;
         } else if part == "unique" {
             spec.unique = true;
         } else if part == "auto-generated" {
@@ -134,12 +179,58 @@ pub fn parse_field_descriptor(name: &str, descriptor: &str) -> FieldSpec {
         } else if part.contains("chars") || part.contains("len") {
             // Parse "1-200 chars" or "min 1 max 200"
             parse_length_constraint(parts[i], &mut spec);
-        } else if !part.is_empty() {
-            // Try to infer type from unrecognized part
-            if spec.field_type == FieldType::Unknown(String::new()) {
-                spec.field_type = FieldType::Unknown(parts[i].to_string());
+        } else if let Some(rest) = part.strip_prefix("one of") {
+            // "one of: admin, member, guest" — the values continue over the following parts
+            let mut values = Vec::new();
+            push_enum_values(rest.trim_start_matches(':'), &mut values);
+            while i + 1 < lower_parts.len() && is_enum_continuation(&lower_parts[i + 1]) {
+                i += 1;
+                push_enum_values(&lower_parts[i], &mut values);
+            }
+            spec.field_type = FieldType::Enum(values);
+        } else if part.starts_with("list of ") {
+            let element = parts[i]["list of ".len()..].trim();
+            spec.field_type = FieldType::List(Box::new(list_element_type(element)));
+        } else if let Some(target) = ["reference to ", "references ", "ref to "]
+            .iter()
+            .find(|p| part.starts_with(*p))
+            .map(|p| parts[i][p.len()..].trim())
+        {
+            spec.field_type = FieldType::Reference(target.to_string());
+        } else if part.starts_with("between ") {
+            let nums = parse_numbers(part);
+            if let [a, b] = nums[..] {
+                let (low, high) = if a.as_f64() <= b.as_f64() { (a, b) } else { (b, a) };
+                spec.min_value = Some(low);
+                spec.max_value = Some(high);
+            } else {
+                warnings.push(unrecognized(name, parts[i]));
             }
+        } else if part.starts_with("at least ") || part.starts_with("at most ") {
+            match parse_numbers(part).as_slice() {
+                [n] if part.starts_with("at least") => spec.min_value = Some(*n),
+                [n] => spec.max_value = Some(*n),
+                _ => warnings.push(unrecognized(name, parts[i])),
+            }
+        } else if part.ends_with(" places") || part.ends_with(" decimal places") {
+            // "2 places", "2 decimal places"
+            match parse_numbers(part).as_slice() {
+                [Number::Int(n)] if *n >= 0 => spec.scale = Some(*n as u32),
+                _ => warnings.push(unrecognized(name, parts[i])),
+            }
+        } else if let Some(value) = ["defaults to ", "default: ", "default "]
+            .iter()
+            .find(|p| part.starts_with(*p))
+            .map(|p| parts[i][p.len()..].trim())
+        {
+            spec.default = Some(value.to_string());
+        } else if is_type_name(parts[i]) && spec.field_type == FieldType::Unknown(String::new()) {
+            // A bare capitalized word names a type defined elsewhere ("Money")
+            spec.field_type = FieldType::Unknown(parts[i].to_string());
+        } else if !part.is_empty() {
+            warnings.push(unrecognized(name, parts[i]));
         }
+        i += 1;
     }
 
     // If we have auto-generated + immutable but no type, default to UUID
@@ -147,9 +238,97 @@ pub fn parse_field_descriptor(name: &str, descriptor: &str) -> FieldSpec {
         spec.field_type = FieldType::UuidType;
     }
 
+    // Decimal places imply a decimal, and a numeric range a number: fractional bounds a float
+    if spec.field_type == FieldType::Unknown(String::new()) {
+        let bounds = [spec.min_value, spec.max_value];
+        if spec.scale.is_some() {
+            spec.field_type = FieldType::DecimalType;
+        } else if bounds.iter().flatten().any(|b| matches!(b, Number::Float(_))) {
+            spec.field_type = FieldType::FloatType;
+        } else if bounds.iter().any(|b| b.is_some()) {
+            spec.field_type = FieldType::IntType;
+        }
+    }
+
+    if optional {
+        spec.required = false;
+        spec.field_type = FieldType::Optional(Box::new(spec.field_type));
+    }
+
     spec
 }
 
+fn unrecognized(field: &str, phrase: &str) -> String {
+    format!("field '{}': unrecognized descriptor '{}'", field, phrase)
+}
+
+fn scalar_type(word: &str) -> Option<FieldType> {
+    match word {
+        "string" | "text" => Some(FieldType::StringType),
+        "uuid" => Some(FieldType::UuidType),
+        "int" | "integer" | "number" => Some(FieldType::IntType),
+        "float" | "double" => Some(FieldType::FloatType),
+        "decimal" => Some(FieldType::DecimalType),
+        "bool" | "boolean" => Some(FieldType::BoolType),
+        "timestamp" | "datetime" | "date-time" => Some(FieldType::DateTime),
+        _ => None,
+    }
+}
+
+/// "tags" → String, "integers" → Int, "User" → User.
+fn list_element_type(element: &str) -> FieldType {
+    let lower = element.to_lowercase();
+    let singular = lower.strip_suffix('s').unwrap_or(&lower);
+    if let Some(t) = scalar_type(&lower).or_else(|| scalar_type(singular)) {
+        t
+    } else if is_type_name(element) {
+        FieldType::Unknown(element.to_string())
+    } else {
+        FieldType::StringType
+    }
+}
+
+fn is_type_name(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_uppercase())
+        && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Enum values may also be joined with "or": "member or guest".
+fn push_enum_values(part: &str, values: &mut Vec<String>) {
+    for value in part.split(" or ") {
+        let value = value
+            .trim()
+            .trim_matches(|c| matches!(c, '[' | ']' | '"' | '\''))
+            .replace(' ', "-");
+        if !value.is_empty() {
+            values.push(value);
+        }
+    }
+}
+
+fn is_enum_continuation(part: &str) -> bool {
+    part.split(" or ").all(|value| {
+        let value = value.trim().trim_matches(|c| matches!(c, '[' | ']' | '"' | '\''));
+        !value.is_empty()
+            && !value.contains(char::is_whitespace)
+            && scalar_type(value).is_none()
+            && !matches!(
+                value,
+                "required" | "optional" | "unique" | "immutable" | "auto-generated"
+            )
+    })
+}
+
+/// Whole and fractional numbers in a phrase: "between 0.01 and 100" → [0.01, 100].
+fn parse_numbers(part: &str) -> Vec<Number> {
+    part.split_whitespace()
+        .filter_map(|w| {
+            let w = w.trim_end_matches(['.', ';']);
+            w.parse().map(Number::Int).or_else(|_| w.parse().map(Number::Float)).ok()
+        })
+        .collect()
+}
+
 fn parse_length_constraint(part: &str, spec: &mut FieldSpec) {
     // Match patterns like "1-200 chars", "max 200", "min 1"
     let nums: Vec<usize> = part
@@ -170,7 +349,10 @@ fn parse_length_constraint(part: &str, spec: &mut FieldSpec) {
     }
 }
 
-fn parse_endpoints(yaml: &YamlValue) -> Result<Vec<Endpoint>, SpecParseError> {
+fn parse_endpoints(
+    yaml: &YamlValue,
+    domain_types: &[DomainType],
+) -> Result<Vec<Endpoint>, SpecParseError> {
     let endpoints = match yaml.get("endpoints") {
         Some(e) => e,
         None => return Ok(Vec::new()),
@@ -192,9 +374,23 @@ fn parse_endpoints(yaml: &YamlValue) -> Result<Vec<Endpoint>, SpecParseError> {
         let outputs = parse_outputs(ep_val)?;
         let constraints = parse_constraints(ep_val);
 
+        let target_type = match get_scalar(ep_val, "type") {
+            Some(declared) => {
+                let dt = find_domain_type(&declared, domain_types).ok_or_else(|| {
+                    SpecParseError(format!(
+                        "Endpoint '{}' targets unknown type '{}'",
+                        ep_name, declared
+                    ))
+                })?;
+                Some(dt.name.clone())
+            }
+            None => infer_target_type(ep_name, &input_str, domain_types),
+        };
+
         result.push(Endpoint {
             name: ep_name.clone(),
             description,
+            target_type,
             input,
             outputs,
             constraints,
@@ -203,6 +399,59 @@ fn parse_endpoints(yaml: &YamlValue) -> Result<Vec<Endpoint>, SpecParseError> {
     Ok(result)
 }
 
+/// Find a domain type by name, ignoring case and word separators ("stock entry" → StockEntry).
+fn find_domain_type<'a>(name: &str, domain_types: &'a [DomainType]) -> Option<&'a DomainType> {
+    let wanted = normalize_type_name(name);
+    domain_types
+        .iter()
+        .find(|dt| normalize_type_name(&dt.name) == wanted)
+}
+
+fn normalize_type_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Infer the target type from the input description ("reservation data (from body)"),
+/// falling back to the endpoint name ("get-stock-entry"). Longest match wins.
+fn infer_target_type(ep_name: &str, input: &str, domain_types: &[DomainType]) -> Option<String> {
+    let word_lists = [
+        input
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>(),
+        ep_name
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>(),
+    ];
+
+    for words in &word_lists {
+        let mut best: Option<&DomainType> = None;
+        for dt in domain_types {
+            let wanted = normalize_type_name(&dt.name);
+            let found = (0..words.len()).any(|start| {
+                let mut joined = String::new();
+                words[start..].iter().any(|w| {
+                    joined.push_str(w);
+                    joined == wanted || joined.strip_suffix('s') == Some(wanted.as_str())
+                })
+            });
+            if found && best.is_none_or(|b| b.name.len() < dt.name.len()) {
+                best = Some(dt);
+            }
+        }
+        if let Some(dt) = best {
+            return Some(dt.name.clone());
+        }
+    }
+    None
+}
+
 fn parse_input_spec(input_str: &str) -> InputSpec {
     let lower = input_str.to_lowercase();
     let source = if lower.contains("url") || lower.contains("path") {
@@ -232,16 +481,24 @@ fn parse_outputs(ep_val: &YamlValue) -> Result<Vec<OutputSpec>, SpecParseError>
 
     let mut outputs = Vec::new();
     for item in items {
-        let mapping = item.as_mapping().ok_or_else(|| {
-            SpecParseError("Each output must be a key: descriptor mapping".into())
-        })?;
-
-        if mapping.is_empty() {
-            continue;
-        }
-
-        let (label, desc_val) = &mapping[0];
-        let description = desc_val.as_scalar().unwrap_or("").to_string();
+        // `- label: description` or a bare label, e.g. from `outputs: [ok (200), not found (404)]`
+        let (label, description) = match item {
+            YamlValue::Scalar(s) => {
+                let label = strip_http_status(s);
+                let label = label.split_once(": ").map_or(label, |(head, _)| head);
+                (label.trim().to_string(), s.clone())
+            }
+            YamlValue::Mapping(mapping) if mapping.is_empty() => continue,
+            YamlValue::Mapping(mapping) => {
+                let (label, desc_val) = &mapping[0];
+                (label.clone(), desc_val.as_scalar().unwrap_or("").to_string())
+            }
+            YamlValue::Sequence(_) => {
+                return Err(SpecParseError(
+                    "Each output must be a key: descriptor mapping".into(),
+                ))
+            }
+        };
 
         // Extract HTTP status from description like "the user found (200)"
         let http_status = extract_http_status(&description);
@@ -251,7 +508,7 @@ fn parse_outputs(ep_val: &YamlValue) -> Result<Vec<OutputSpec>, SpecParseError>
             || label.to_lowercase().contains("created");
 
         outputs.push(OutputSpec {
-            label: label.clone(),
+            label,
             description,
             http_status,
             is_success,
@@ -260,6 +517,14 @@ fn parse_outputs(ep_val: &YamlValue) -> Result<Vec<OutputSpec>, SpecParseError>
     Ok(outputs)
 }
 
+/// A bare output without its trailing status: `not found (404)` → `not found`
+fn strip_http_status(label: &str) -> &str {
+    match label.trim_end().strip_suffix(')').and_then(|rest| rest.rsplit_once('(')) {
+        Some((head, code)) if code.trim().parse::<u16>().is_ok() => head.trim_end(),
+        _ => label,
+    }
+}
+
 fn extract_http_status(desc: &str) -> Option<u16> {
     // Look for (NNN) pattern at end of description
     if let Some(start) = desc.rfind('(') {
@@ -357,6 +622,17 @@ fn parse_traceability(yaml: &YamlValue) -> Traceability {
         }
     };
 
+    // Either a comma-separated scalar or a sequence
+    if let Some(items) = trace.get("known dependencies").and_then(|d| d.as_sequence()) {
+        let known_dependencies = items
+            .iter()
+            .filter_map(|item| item.as_scalar())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect();
+        return Traceability { known_dependencies };
+    }
+
     let deps_str = get_scalar(trace, "known dependencies")
         .unwrap_or_default();
 
@@ -407,6 +683,78 @@ mod tests {
         assert_eq!(field.field_type, FieldType::UuidType);
     }
 
+    #[test]
+    fn test_parse_field_enum_with_default() {
+        let field = parse_field_descriptor("role", "required, one of: admin, member or guest, defaults to member");
+        assert_eq!(
+            field.field_type,
+            FieldType::Enum(vec!["admin".into(), "member".into(), "guest".into()])
+        );
+        assert_eq!(field.default, Some("member".into()));
+        assert!(field.required);
+    }
+
+    #[test]
+    fn test_parse_field_list_reference_and_timestamp() {
+        let tags = parse_field_descriptor("tags", "list of tags");
+        assert_eq!(tags.field_type, FieldType::List(Box::new(FieldType::StringType)));
+
+        let owner = parse_field_descriptor("owner", "required, reference to User");
+        assert_eq!(owner.field_type, FieldType::Reference("User".into()));
+
+        let created = parse_field_descriptor("created-at", "timestamp, auto-generated");
+        assert_eq!(created.field_type, FieldType::DateTime);
+    }
+
+    #[test]
+    fn test_parse_field_range_and_optional() {
+        let score = parse_field_descriptor("score", "optional, between 0 and 100");
+        assert_eq!(score.min_value, Some(Number::Int(0)));
+        assert_eq!(score.max_value, Some(Number::Int(100)));
+        assert!(!score.required);
+        assert_eq!(score.field_type, FieldType::Optional(Box::new(FieldType::IntType)));
+    }
+
+    #[test]
+    fn test_parse_field_decimal_and_float() {
+        let price = parse_field_descriptor("price", "required, decimal, 2 places, at least 0.01");
+        assert_eq!(price.field_type, FieldType::DecimalType);
+        assert_eq!(price.scale, Some(2));
+        assert_eq!(price.min_value, Some(Number::Float(0.01)));
+
+        let rate = parse_field_descriptor("rate", "between 0.5 and 0");
+        assert_eq!(rate.field_type, FieldType::FloatType);
+        assert_eq!(rate.min_value, Some(Number::Int(0)));
+        assert_eq!(rate.max_value, Some(Number::Float(0.5)));
+    }
+
+    #[test]
+    fn test_parse_field_unrecognized_phrases_warn() {
+        let mut warnings = Vec::new();
+        let field = parse_field_descriptor_checked(
+            "nickname",
+            "sparkly, Money",
+            &mut warnings,
+        );
+        assert_eq!(field.field_type, FieldType::Unknown("Money".into()));
+        assert_eq!(warnings, vec!["field 'nickname': unrecognized descriptor 'sparkly'"]);
+
+        let yaml = parse_yaml("\
+spec: SPEC-001
+title: test
+owner: team
+domain:
+  Post:
+    fields:
+      - author: reference to Author
+");
+        let spec = parse_spec(&yaml).unwrap();
+        assert_eq!(
+            spec.warnings,
+            vec!["field 'Post.author': reference to unknown type 'Author'"]
+        );
+    }
+
     #[test]
     fn test_parse_minimal_spec() {
         let yaml = parse_yaml("\
@@ -514,6 +862,93 @@ traceability:
         assert_eq!(spec.traceability.known_dependencies, vec!["api-router", "admin-panel"]);
     }
 
+    #[test]
+    fn test_parse_flow_and_block_syntax() {
+        let yaml = parse_yaml("\
+spec: SPEC-001
+title: test
+owner: team
+endpoints:
+  get-user:
+    description: |
+      Returns a user by ID.
+      Never touches the write path.
+    input: user id (from URL)
+    outputs: [success (200), \"not found: unknown id (404)\"]
+    constraints: [read-only, {max response time: 50ms}]
+traceability:
+  known dependencies: [api-router, admin-panel]
+");
+        let spec = parse_spec(&yaml).unwrap();
+        let ep = &spec.endpoints[0];
+        assert!(ep.description.starts_with("Returns a user by ID.\nNever"));
+        assert_eq!(ep.outputs.len(), 2);
+        assert_eq!(ep.outputs[0].http_status, Some(200));
+        assert!(ep.outputs[0].is_success);
+        assert_eq!(ep.outputs[1].http_status, Some(404));
+        assert_eq!(ep.outputs[0].label, "success");
+        assert_eq!(ep.outputs[1].label, "not found");
+        assert_eq!(
+            ep.constraints,
+            vec![Constraint::ReadOnly, Constraint::MaxResponseTime("50ms".into())]
+        );
+        assert_eq!(spec.traceability.known_dependencies, vec!["api-router", "admin-panel"]);
+    }
+
+    #[test]
+    fn test_endpoint_target_type_declared_and_inferred() {
+        let yaml = parse_yaml("\
+spec: SPEC-001
+title: test
+owner: team
+domain:
+  Product:
+    fields:
+      - id: auto-generated, immutable
+  StockEntry:
+    fields:
+      - id: auto-generated, immutable
+  Reservation:
+    fields:
+      - id: auto-generated, immutable
+endpoints:
+  reserve:
+    input: reservation data (from body)
+  get-stock-entry:
+    input: id (from URL)
+  restock:
+    type: stock entry
+    input: quantities (from body)
+  ping:
+    input: nothing
+");
+        let spec = parse_spec(&yaml).unwrap();
+        let targets: Vec<Option<&str>> = spec
+            .endpoints
+            .iter()
+            .map(|ep| ep.target_type.as_deref())
+            .collect();
+        assert_eq!(
+            targets,
+            vec![Some("Reservation"), Some("StockEntry"), Some("StockEntry"), None]
+        );
+    }
+
+    #[test]
+    fn test_endpoint_unknown_declared_type_is_error() {
+        let yaml = parse_yaml("\
+spec: SPEC-001
+title: test
+owner: team
+endpoints:
+  get-order:
+    type: Order
+    input: order id (from URL)
+");
+        let err = parse_spec(&yaml).unwrap_err();
+        assert!(err.0.contains("unknown type 'Order'"));
+    }
+
     #[test]
     fn test_extract_http_status_numbers() {
         assert_eq!(extract_http_status("the user found (200)"), Some(200));
@@ -0,0 +1,352 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::DurationUnit;
+
+use super::pct_emitter::field_type_to_pct;
+use super::spec_ast::*;
+
+/// Check a (possibly hand-edited) module against the spec it was generated from.
+/// Every place where the module no longer satisfies the spec is reported as drift.
+pub fn verify_spec(spec: &SpecDoc, module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for dt in &spec.domain_types {
+        verify_type(dt, module, &mut diagnostics);
+    }
+
+    for ep in &spec.endpoints {
+        match module.functions.iter().find(|f| f.name == ep.name) {
+            Some(func) => verify_endpoint(ep, func, &mut diagnostics),
+            None => diagnostics.push(Diagnostic::error(
+                format!("spec endpoint '{}' has no matching fn", ep.name),
+                None,
+            )),
+        }
+    }
+
+    if spec.quality.contains(&QualityRule::AllFunctionsTotal) {
+        for func in module.functions.iter().filter(|f| !f.total) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "fn '{}' is not :total, but the spec requires all functions to be total",
+                    func.name
+                ),
+                Some(func.span.clone()),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+fn verify_type(dt: &DomainType, module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    let type_def = match module.types.iter().find(|t| t.name == dt.name) {
+        Some(t) => t,
+        None => {
+            diagnostics.push(Diagnostic::error(
+                format!("spec type '{}' is not defined in the module", dt.name),
+                None,
+            ));
+            return;
+        }
+    };
+
+    for field in &dt.fields {
+        match type_def.fields.iter().find(|f| f.name == field.name) {
+            Some(def) => verify_field(&dt.name, field, def, diagnostics),
+            None => diagnostics.push(Diagnostic::error(
+                format!("field '{}.{}' from the spec is missing", dt.name, field.name),
+                Some(type_def.span.clone()),
+            )),
+        }
+    }
+}
+
+fn verify_field(type_name: &str, spec: &FieldSpec, def: &FieldDef, diagnostics: &mut Vec<Diagnostic>) {
+    let mut looser = |what: String| {
+        diagnostics.push(Diagnostic::error(
+            format!("field '{}.{}' is looser than the spec: {}", type_name, def.name, what),
+            Some(def.span.clone()),
+        ));
+    };
+
+    if spec.required && matches!(def.type_expr, TypeExpr::Optional(_)) {
+        looser("spec requires it to be required".into());
+    } else if !satisfies(&spec.field_type, &def.type_expr) {
+        looser(format!("spec says {}", field_type_to_pct(&spec.field_type)));
+    }
+
+    if let Some(min) = spec.min_len {
+        if def.min_len.is_none_or(|m| m < min as i64) {
+            looser(format!("spec requires :min-len {}", min));
+        }
+    }
+    if let Some(max) = spec.max_len {
+        if def.max_len.is_none_or(|m| m > max as i64) {
+            looser(format!("spec requires :max-len {}", max));
+        }
+    }
+    if let Some(min) = spec.min_value {
+        if def.min.is_none_or(|m| m.as_f64() < min.as_f64()) {
+            looser(format!("spec requires :min {}", min));
+        }
+    }
+    if let Some(max) = spec.max_value {
+        if def.max.is_none_or(|m| m.as_f64() > max.as_f64()) {
+            looser(format!("spec requires :max {}", max));
+        }
+    }
+    if let Some(scale) = spec.scale {
+        if def.scale.is_none_or(|s| s > scale) {
+            looser(format!("spec requires :scale {}", scale));
+        }
+    }
+    if let Some(ref format) = spec.format {
+        if def.format.as_deref() != Some(format.as_str()) {
+            looser(format!("spec requires :format :{}", format));
+        }
+    }
+    if spec.unique && def.unique_within.is_none() {
+        looser("spec requires it to be unique".into());
+    }
+    if spec.immutable && !def.immutable {
+        looser("spec requires it to be immutable".into());
+    }
+}
+
+fn verify_endpoint(ep: &Endpoint, func: &FnDef, diagnostics: &mut Vec<Diagnostic>) {
+    for output in &ep.outputs {
+        let status = match output.http_status {
+            Some(s) => s as i64,
+            None => continue,
+        };
+        let declared = func.returns.variants.iter().any(|v| match &v.kind {
+            VariantKind::Ok { http_status, .. } | VariantKind::Err { http_status, .. } => {
+                *http_status == Some(status)
+            }
+        });
+        if !declared {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "fn '{}' has no returns variant with :http {} (spec output '{}')",
+                    func.name, status, output.label
+                ),
+                Some(func.returns.span.clone()),
+            ));
+        }
+    }
+
+    for constraint in &ep.constraints {
+        if let Constraint::MaxResponseTime(limit) = constraint {
+            let limit_ms = match parse_duration_ms(limit) {
+                Some(ms) => ms,
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        format!("cannot read spec max response time '{}' for '{}'", limit, ep.name),
+                        None,
+                    ));
+                    continue;
+                }
+            };
+            match &func.latency_budget {
+                None => diagnostics.push(Diagnostic::warning(
+                    format!(
+                        "fn '{}' has no :latency-budget, but the spec allows at most {}",
+                        func.name, limit
+                    ),
+                    Some(func.span.clone()),
+                )),
+                Some(budget) if duration_ms(budget) > limit_ms => {
+                    diagnostics.push(Diagnostic::error(
+                        format!(
+                            "fn '{}' has :latency-budget {}, above the spec's max response time {}",
+                            func.name, budget, limit
+                        ),
+                        Some(func.span.clone()),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+/// Whether a module type is at least as strict as the spec type: the same scalar,
+/// list element and ref target, and no enum values beyond the spec's.
+fn satisfies(spec: &FieldType, def: &TypeExpr) -> bool {
+    match (spec, def) {
+        (FieldType::Optional(inner), def) => satisfies(inner, def.non_optional()),
+        (_, TypeExpr::Optional(_)) => false,
+        (FieldType::Enum(values), TypeExpr::Enum(module_values)) => {
+            module_values.iter().all(|v| values.contains(v))
+        }
+        (FieldType::List(inner), TypeExpr::List(element)) => satisfies(inner, element),
+        (FieldType::Reference(target), TypeExpr::Ref(module_target)) => target == module_target,
+        (FieldType::Enum(_) | FieldType::List(_) | FieldType::Reference(_), _) => false,
+        (FieldType::Unknown(_), _) => true,
+        (scalar, def) => {
+            matches!(def, TypeExpr::Named(name) if Some(name.as_str()) == scalar_type_name(scalar))
+        }
+    }
+}
+
+/// The `.pct` type name for spec types that map to a plain named type.
+fn scalar_type_name(field_type: &FieldType) -> Option<&'static str> {
+    match field_type {
+        FieldType::StringType => Some("String"),
+        FieldType::UuidType => Some("UUID"),
+        FieldType::IntType | FieldType::DateTime => Some("Int"),
+        FieldType::FloatType => Some("Float"),
+        FieldType::DecimalType => Some("Decimal"),
+        FieldType::BoolType => Some("Bool"),
+        _ => None,
+    }
+}
+
+fn duration_ms(d: &Duration) -> u64 {
+    match d.unit {
+        DurationUnit::Ms => d.value,
+        DurationUnit::S => d.value * 1_000,
+        DurationUnit::M => d.value * 60_000,
+        DurationUnit::H => d.value * 3_600_000,
+    }
+}
+
+/// "50ms", "2s", "1 m" → milliseconds.
+fn parse_duration_ms(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let value: u64 = s[..digits_end].parse().ok()?;
+    let factor = match s[digits_end..].trim() {
+        "ms" => 1,
+        "s" | "" => 1_000,
+        "m" | "min" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    Some(value * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::DiagnosticKind;
+    use crate::generate::pct_emitter::PctEmitter;
+    use crate::generate::spec_parser::parse_spec;
+    use crate::generate::yaml_parser::YamlParser;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    const SPEC: &str = "\
+spec: SPEC-001
+title: \"User service\"
+owner: team
+domain:
+  User:
+    fields:
+      - id: auto-generated, immutable
+      - name: required, string, 1-200 chars
+      - email: required, email format, unique
+      - role: required, one of: admin, member or guest
+      - tags: list of strings
+      - manager: optional, reference to User
+endpoints:
+  get-user:
+    input: user id (from URL)
+    outputs:
+      - success: the user (200)
+      - not found: no such user (404)
+    constraints:
+      - max response time: 50ms
+      - read-only
+quality:
+  - all functions must be total
+";
+
+    fn spec() -> SpecDoc {
+        parse_spec(&YamlParser::new(SPEC).parse().unwrap()).unwrap()
+    }
+
+    fn lower(source: &str) -> Module {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    fn errors(diags: &[Diagnostic]) -> Vec<String> {
+        diags
+            .iter()
+            .filter(|d| d.kind == DiagnosticKind::Error)
+            .map(|d| d.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_generated_module_satisfies_spec() {
+        let spec = spec();
+        let module = lower(&PctEmitter::new().emit(&spec));
+        let diags = verify_spec(&spec, &module);
+        assert!(diags.is_empty(), "unexpected drift: {:?}", diags);
+    }
+
+    #[test]
+    fn test_edited_module_drift_is_reported() {
+        let spec = spec();
+        let edited = PctEmitter::new()
+            .emit(&spec)
+            .replace(":latency-budget 50ms", ":latency-budget 2s")
+            .replace(":max-len 200", ":max-len 500")
+            .replace(" :unique-within user-store", "")
+            .replace("(err  :not-found {:id id} :http 404)", "(err  :not-found {:id id} :http 410)")
+            .replace(":total      true", ":total      false");
+        let module = lower(&edited);
+
+        let errs = errors(&verify_spec(&spec, &module));
+        assert_eq!(errs.len(), 5, "{:?}", errs);
+        assert!(errs.iter().any(|e| e.contains("above the spec's max response time 50ms")));
+        assert!(errs.iter().any(|e| e.contains("'User.name'") && e.contains(":max-len 200")));
+        assert!(errs.iter().any(|e| e.contains("'User.email'") && e.contains("unique")));
+        assert!(errs.iter().any(|e| e.contains(":http 404")));
+        assert!(errs.iter().any(|e| e.contains("is not :total")));
+    }
+
+    #[test]
+    fn test_type_drift_is_reported() {
+        let spec = spec();
+        let pct = PctEmitter::new().emit(&spec);
+        let drift = |from: &str, to: &str| {
+            assert!(pct.contains(from), "{} not in {}", from, pct);
+            errors(&verify_spec(&spec, &lower(&pct.replacen(from, to, 1))))
+        };
+
+        let errs = drift("(enum :admin :member :guest)", "String");
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        assert!(errs[0].contains("'User.role'") && errs[0].contains("spec says (enum :admin :member :guest)"));
+        let errs = drift("(enum :admin :member :guest)", "(enum :admin :member :owner)");
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        assert!(drift("(enum :admin :member :guest)", "(enum :admin :member)").is_empty());
+
+        let errs = drift("(list String)", "(list Int)");
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        assert!(errs[0].contains("'User.tags'") && errs[0].contains("spec says (list String)"));
+
+        let errs = drift("(ref User)", "UUID");
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        assert!(errs[0].contains("'User.manager'") && errs[0].contains("spec says (optional (ref User))"));
+        assert!(drift("(optional (ref User))", "(ref User)").is_empty());
+
+        let errs = drift("name String", "name (optional String)");
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        assert!(errs[0].contains("'User.name'") && errs[0].contains("spec requires it to be required"));
+    }
+
+    #[test]
+    fn test_missing_endpoint_fn_and_type() {
+        let spec = spec();
+        let module = lower("(module user-service :version 1)");
+        let errs = errors(&verify_spec(&spec, &module));
+        assert!(errs.contains(&"spec endpoint 'get-user' has no matching fn".to_string()));
+        assert!(errs.contains(&"spec type 'User' is not defined in the module".to_string()));
+    }
+}
@@ -1,17 +1,26 @@
+use std::collections::HashMap;
+
 use super::yaml_ast::YamlValue;
 
-/// Hand-written indentation-based YAML subset parser.
+/// Hand-written indentation-based YAML parser.
 ///
 /// Supports:
-/// - Key-value mappings (indentation-based nesting)
-/// - Sequences (`- item`)
-/// - Scalars (plain + double-quoted strings)
-/// - Comments (`#`)
+/// - Key-value mappings (indentation-based nesting), including quoted keys
+/// - Sequences (`- item`), nested or at the same indent as their key
+/// - Flow collections (`[a, b]`, `{k: v}`), which may span several lines
+/// - Scalars (plain, single- and double-quoted, multi-line plain)
+/// - Literal (`|`) and folded (`>`) block scalars with chomping indicators
+/// - Anchors (`&name`), aliases (`*name`) and merge keys (`<<: *name`)
+/// - Multi-document streams separated by `---`
+/// - Comments (`#`), on their own line or trailing a value
 ///
-/// Does NOT support: anchors, tags, flow collections, multi-doc, block scalars.
+/// Tags (`!!str`, `!custom`) are accepted and ignored; every scalar stays a string.
+/// Does NOT support: complex keys (`? key`), multi-line quoted scalars.
 pub struct YamlParser {
     lines: Vec<String>,
     pos: usize,
+    end: usize,
+    anchors: HashMap<String, YamlValue>,
 }
 
 #[derive(Debug)]
@@ -26,47 +35,124 @@ impl std::fmt::Display for YamlParseError {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chomping {
+    Clip,
+    Strip,
+    Keep,
+}
+
+/// Header of a `|` / `>` block scalar.
+struct BlockHeader {
+    folded: bool,
+    chomping: Chomping,
+    indent: Option<usize>,
+}
+
 impl YamlParser {
     pub fn new(input: &str) -> Self {
         let lines: Vec<String> = input.lines().map(|l| l.to_string()).collect();
-        YamlParser { lines, pos: 0 }
+        let end = lines.len();
+        YamlParser {
+            lines,
+            pos: 0,
+            end,
+            anchors: HashMap::new(),
+        }
     }
 
+    /// Parse the first document of the stream (an empty mapping if there is none).
     pub fn parse(&mut self) -> Result<YamlValue, YamlParseError> {
-        self.skip_blank_and_comments();
-        if self.pos >= self.lines.len() {
+        let mut docs = self.parse_all()?;
+        if docs.is_empty() {
             return Ok(YamlValue::Mapping(Vec::new()));
         }
-        let indent = self.current_indent();
-        self.parse_value_at_indent(indent)
+        Ok(docs.swap_remove(0))
+    }
+
+    /// Parse every `---`-separated document of the stream. Empty documents are skipped.
+    pub fn parse_all(&mut self) -> Result<Vec<YamlValue>, YamlParseError> {
+        // Sequence entries are parsed by rewriting lines in place; keep the source intact
+        let original = self.lines.clone();
+        let result = self.parse_documents();
+        self.lines = original;
+        result
+    }
+
+    fn parse_documents(&mut self) -> Result<Vec<YamlValue>, YamlParseError> {
+        let mut docs = Vec::new();
+        for (start, end) in self.document_ranges() {
+            self.pos = start;
+            self.end = end;
+            self.anchors.clear();
+
+            self.skip_blank_and_comments();
+            if self.pos >= self.end {
+                continue;
+            }
+            let indent = self.current_indent();
+            let value = self.parse_value_at_indent(indent)?;
+
+            self.skip_blank_and_comments();
+            if self.pos < self.end {
+                return Err(self.error(self.pos, "unexpected content after the document root"));
+            }
+            docs.push(value);
+        }
+        Ok(docs)
+    }
+
+    /// Split the stream into `[start, end)` line ranges, one per document.
+    /// Document markers and directives are blanked so they read as empty lines.
+    fn document_ranges(&mut self) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for i in 0..self.lines.len() {
+            let line = &self.lines[i];
+            if line == "---" || line.starts_with("--- ") || line.starts_with("---\t") {
+                ranges.push((start, i));
+                // Content after the marker (`--- text`) stays on this line
+                self.lines[i] = format!("   {}", &line[3..]);
+                start = i;
+            } else if line == "..." || line.starts_with("... ") {
+                ranges.push((start, i));
+                self.lines[i] = String::new();
+                start = i + 1;
+            } else if line.starts_with('%') {
+                self.lines[i] = String::new();
+            }
+        }
+        ranges.push((start, self.lines.len()));
+        ranges
     }
 
     fn parse_value_at_indent(&mut self, indent: usize) -> Result<YamlValue, YamlParseError> {
         self.skip_blank_and_comments();
-        if self.pos >= self.lines.len() {
+        if self.pos >= self.end {
             return Ok(YamlValue::Scalar(String::new()));
         }
 
         let line = self.current_line_trimmed();
 
-        if line.starts_with("- ") || line == "-" {
+        if is_sequence_entry(&line) {
             self.parse_sequence(indent)
-        } else if line.contains(": ") || line.ends_with(':') {
+        } else if split_key_value(&line).is_some() {
             self.parse_mapping(indent)
         } else {
-            // Plain scalar on its own line
-            let val = self.parse_scalar_value(&line);
+            // Standalone node on its own line: scalar, flow collection, alias, ...
+            let line_no = self.pos;
             self.pos += 1;
-            Ok(val)
+            self.parse_inline_value(&line, indent, line_no, false)
         }
     }
 
     fn parse_mapping(&mut self, indent: usize) -> Result<YamlValue, YamlParseError> {
         let mut pairs = Vec::new();
+        let mut merges = Vec::new();
 
-        while self.pos < self.lines.len() {
+        while self.pos < self.end {
             self.skip_blank_and_comments();
-            if self.pos >= self.lines.len() {
+            if self.pos >= self.end {
                 break;
             }
 
@@ -75,46 +161,47 @@ impl YamlParser {
                 break;
             }
             if cur_indent > indent {
-                break;
+                return Err(self.error(self.pos, "unexpected indentation"));
             }
 
             let line = self.current_line_trimmed();
-            // Must be a key: value or key:
-            if let Some((key, rest)) = self.split_key_value(&line) {
-                self.pos += 1;
+            let (key, rest) = match split_key_value(&line) {
+                Some(kv) => kv,
+                None if is_sequence_entry(&line) => {
+                    return Err(self.error(self.pos, "sequence entry inside a mapping"));
+                }
+                None => {
+                    return Err(self.error(
+                        self.pos,
+                        format!("expected 'key: value', found '{}'", line),
+                    ));
+                }
+            };
 
-                let value = if rest.is_empty() {
-                    // Value is on subsequent indented lines
-                    self.skip_blank_and_comments();
-                    if self.pos < self.lines.len() {
-                        let child_indent = self.current_indent();
-                        if child_indent > indent {
-                            self.parse_value_at_indent(child_indent)?
-                        } else {
-                            YamlValue::Scalar(String::new())
-                        }
-                    } else {
-                        YamlValue::Scalar(String::new())
-                    }
-                } else {
-                    self.parse_scalar_value(&rest)
-                };
+            let line_no = self.pos;
+            self.pos += 1;
+            let value = self.parse_inline_value(&rest, indent, line_no, true)?;
 
-                pairs.push((key, value));
+            if key == "<<" {
+                merges.push((line_no, value));
             } else {
-                break;
+                pairs.push((key, value));
             }
         }
 
+        for (line_no, value) in merges {
+            merge_into(&mut pairs, value).map_err(|msg| self.error(line_no, msg))?;
+        }
+
         Ok(YamlValue::Mapping(pairs))
     }
 
     fn parse_sequence(&mut self, indent: usize) -> Result<YamlValue, YamlParseError> {
         let mut items = Vec::new();
 
-        while self.pos < self.lines.len() {
+        while self.pos < self.end {
             self.skip_blank_and_comments();
-            if self.pos >= self.lines.len() {
+            if self.pos >= self.end {
                 break;
             }
 
@@ -123,150 +210,385 @@ impl YamlParser {
                 break;
             }
             if cur_indent > indent {
-                break;
+                return Err(self.error(self.pos, "unexpected indentation"));
             }
 
             let line = self.current_line_trimmed();
-            if !line.starts_with("- ") && line != "-" {
+            if !is_sequence_entry(&line) {
                 break;
             }
 
-            let after_dash = if line == "-" {
-                String::new()
+            let after_dash = line[1..].trim_start().to_string();
+            let line_no = self.pos;
+
+            if after_dash.is_empty() {
+                // Block sequence item — value on next indented lines
+                self.pos += 1;
+                items.push(self.parse_block_node(indent, false)?);
+            } else if is_sequence_entry(&after_dash) || split_key_value(&after_dash).is_some() {
+                // `- key: value` or `- - item`: re-read the rest of the line as a node
+                // starting at the column after the dash, so continuation lines line up
+                let raw = &self.lines[self.pos][cur_indent + 1..];
+                let item_indent = cur_indent + 1 + (raw.len() - raw.trim_start().len());
+                self.lines[self.pos] = format!("{}{}", " ".repeat(item_indent), after_dash);
+                items.push(self.parse_value_at_indent(item_indent)?);
             } else {
-                line[2..].to_string()
-            };
+                self.pos += 1;
+                items.push(self.parse_inline_value(&after_dash, indent, line_no, false)?);
+            }
+        }
+
+        Ok(YamlValue::Sequence(items))
+    }
+
+    /// Parse the value written after `key:` or `- ` on line `line_no`.
+    /// Anything continuing on later lines must be indented past `parent_indent`;
+    /// mapping values may also be a sequence at the parent's own indent.
+    fn parse_inline_value(
+        &mut self,
+        text: &str,
+        parent_indent: usize,
+        line_no: usize,
+        in_mapping: bool,
+    ) -> Result<YamlValue, YamlParseError> {
+        let (anchor, rest) = take_properties(text).map_err(|msg| self.error(line_no, msg))?;
+
+        let value = if rest.is_empty() {
+            self.parse_block_node(parent_indent, in_mapping)?
+        } else if let Some(name) = rest.strip_prefix('*') {
+            match self.anchors.get(name) {
+                Some(v) => v.clone(),
+                None => return Err(self.error(line_no, format!("unknown alias '*{}'", name))),
+            }
+        } else if let Some(header) = parse_block_header(rest) {
+            self.parse_block_scalar(&header, parent_indent)
+        } else if rest.starts_with('[') || rest.starts_with('{') {
+            self.parse_flow(rest, line_no)?
+        } else if rest.starts_with('"') || rest.starts_with('\'') {
+            let chars: Vec<char> = rest.chars().collect();
+            let mut i = 0;
+            let s = parse_quoted(&chars, &mut i)
+                .ok_or_else(|| self.error(line_no, "unterminated quoted string"))?;
+            if chars[i..].iter().any(|c| !c.is_whitespace()) {
+                return Err(self.error(line_no, "unexpected text after quoted string"));
+            }
+            YamlValue::Scalar(s)
+        } else {
+            self.parse_plain_scalar(rest, parent_indent)
+        };
 
+        if let Some(name) = anchor {
+            self.anchors.insert(name, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Parse a node that starts on the line after its key or dash.
+    fn parse_block_node(
+        &mut self,
+        parent_indent: usize,
+        in_mapping: bool,
+    ) -> Result<YamlValue, YamlParseError> {
+        self.skip_blank_and_comments();
+        if self.pos < self.end {
+            let child_indent = self.current_indent();
+            if child_indent > parent_indent {
+                return self.parse_value_at_indent(child_indent);
+            }
+            if in_mapping
+                && child_indent == parent_indent
+                && is_sequence_entry(&self.current_line_trimmed())
+            {
+                return self.parse_sequence(child_indent);
+            }
+        }
+        Ok(YamlValue::Scalar(String::new()))
+    }
+
+    /// A plain scalar, folding more-indented continuation lines into it.
+    fn parse_plain_scalar(&mut self, first: &str, parent_indent: usize) -> YamlValue {
+        let mut value = first.trim().to_string();
+        while self.pos < self.end {
+            let raw = &self.lines[self.pos];
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || indent_of(raw) <= parent_indent {
+                break;
+            }
+            // `key: value` can't continue a plain scalar; leave it to be reported
+            let continuation = strip_comment(trimmed);
+            if split_key_value(continuation).is_some() {
+                break;
+            }
+            value.push(' ');
+            value.push_str(continuation);
             self.pos += 1;
+        }
+        YamlValue::Scalar(value)
+    }
 
-            if after_dash.is_empty() {
-                // Block sequence item — value on next indented lines
-                self.skip_blank_and_comments();
-                if self.pos < self.lines.len() {
-                    let child_indent = self.current_indent();
-                    if child_indent > indent {
-                        items.push(self.parse_value_at_indent(child_indent)?);
-                    } else {
-                        items.push(YamlValue::Scalar(String::new()));
+    fn parse_block_scalar(&mut self, header: &BlockHeader, parent_indent: usize) -> YamlValue {
+        let mut block_indent = header.indent.map(|n| parent_indent + n);
+        let mut lines: Vec<String> = Vec::new();
+
+        while self.pos < self.end {
+            let raw = &self.lines[self.pos];
+            if raw.trim().is_empty() {
+                lines.push(String::new());
+                self.pos += 1;
+                continue;
+            }
+            let indent = indent_of(raw);
+            if indent <= parent_indent {
+                break;
+            }
+            let bi = *block_indent.get_or_insert(indent);
+            if indent < bi {
+                break;
+            }
+            lines.push(raw[bi..].to_string());
+            self.pos += 1;
+        }
+
+        let trailing = lines.iter().rev().take_while(|l| l.is_empty()).count();
+        let content = &lines[..lines.len() - trailing];
+
+        let mut text = if header.folded {
+            fold_lines(content)
+        } else {
+            content.join("\n")
+        };
+        if !content.is_empty() {
+            match header.chomping {
+                Chomping::Strip => {}
+                Chomping::Clip => text.push('\n'),
+                Chomping::Keep => text.push_str(&"\n".repeat(trailing + 1)),
+            }
+        }
+        YamlValue::Scalar(text)
+    }
+
+    fn parse_flow(&mut self, first: &str, line_no: usize) -> Result<YamlValue, YamlParseError> {
+        let mut source = first.to_string();
+        while flow_depth(&source) > 0 {
+            if self.pos >= self.end {
+                return Err(self.error(line_no, "unterminated flow collection"));
+            }
+            source.push(' ');
+            source.push_str(strip_comment(self.lines[self.pos].trim()));
+            self.pos += 1;
+        }
+
+        let chars: Vec<char> = source.chars().collect();
+        let mut i = 0;
+        let value = self.parse_flow_node(&chars, &mut i, line_no)?;
+        skip_spaces(&chars, &mut i);
+        if i < chars.len() {
+            return Err(self.error(line_no, "unexpected text after flow collection"));
+        }
+        Ok(value)
+    }
+
+    fn parse_flow_node(
+        &mut self,
+        chars: &[char],
+        i: &mut usize,
+        line_no: usize,
+    ) -> Result<YamlValue, YamlParseError> {
+        skip_spaces(chars, i);
+
+        let mut anchor = None;
+        loop {
+            match chars.get(*i) {
+                Some('&') => {
+                    *i += 1;
+                    let name = read_flow_token(chars, i);
+                    if name.is_empty() {
+                        return Err(self.error(line_no, "anchor without a name"));
                     }
-                } else {
-                    items.push(YamlValue::Scalar(String::new()));
+                    anchor = Some(name);
                 }
-            } else if after_dash.contains(": ") || after_dash.ends_with(':') {
-                // Inline mapping item in sequence: `- key: value`
-                // The content after "- " might be a single key: value or the start of a mapping
-                let item_indent = indent + 2; // "- " is 2 chars
-                let mut pairs = Vec::new();
-
-                // Parse the first key-value from after_dash
-                if let Some((key, rest)) = self.split_key_value(&after_dash) {
-                    let value = if rest.is_empty() {
-                        self.skip_blank_and_comments();
-                        if self.pos < self.lines.len() {
-                            let child_indent = self.current_indent();
-                            if child_indent > indent {
-                                self.parse_value_at_indent(child_indent)?
-                            } else {
-                                YamlValue::Scalar(String::new())
-                            }
-                        } else {
-                            YamlValue::Scalar(String::new())
-                        }
-                    } else {
-                        self.parse_scalar_value(&rest)
-                    };
-                    pairs.push((key, value));
+                Some('!') => {
+                    read_flow_token(chars, i);
                 }
+                _ => break,
+            }
+            skip_spaces(chars, i);
+        }
 
-                // Parse continuation keys at item_indent
-                while self.pos < self.lines.len() {
-                    self.skip_blank_and_comments();
-                    if self.pos >= self.lines.len() {
-                        break;
-                    }
-                    let ci = self.current_indent();
-                    if ci < item_indent {
-                        break;
+        let value = match chars.get(*i) {
+            Some('[') => {
+                *i += 1;
+                self.parse_flow_sequence(chars, i, line_no)?
+            }
+            Some('{') => {
+                *i += 1;
+                self.parse_flow_mapping(chars, i, line_no)?
+            }
+            Some('*') => {
+                *i += 1;
+                let name = read_flow_token(chars, i);
+                match self.anchors.get(&name) {
+                    Some(v) => v.clone(),
+                    None => {
+                        return Err(self.error(line_no, format!("unknown alias '*{}'", name)))
                     }
-                    if ci > item_indent {
+                }
+            }
+            Some('"') | Some('\'') => {
+                let s = parse_quoted(chars, i)
+                    .ok_or_else(|| self.error(line_no, "unterminated quoted string"))?;
+                YamlValue::Scalar(s)
+            }
+            _ => {
+                let start = *i;
+                while let Some(&c) = chars.get(*i) {
+                    if matches!(c, ',' | '[' | ']' | '{' | '}') {
                         break;
                     }
-                    let l = self.current_line_trimmed();
-                    if let Some((key, rest)) = self.split_key_value(&l) {
-                        self.pos += 1;
-                        let value = if rest.is_empty() {
-                            self.skip_blank_and_comments();
-                            if self.pos < self.lines.len() {
-                                let child_indent = self.current_indent();
-                                if child_indent > item_indent {
-                                    self.parse_value_at_indent(child_indent)?
-                                } else {
-                                    YamlValue::Scalar(String::new())
-                                }
-                            } else {
-                                YamlValue::Scalar(String::new())
-                            }
-                        } else {
-                            self.parse_scalar_value(&rest)
-                        };
-                        pairs.push((key, value));
-                    } else {
+                    if c == ':' && is_flow_value_break(chars.get(*i + 1)) {
                         break;
                     }
+                    *i += 1;
                 }
+                let s: String = chars[start..*i].iter().collect();
+                YamlValue::Scalar(s.trim().to_string())
+            }
+        };
 
-                if pairs.len() == 1 && pairs[0].1 == YamlValue::Scalar(String::new()) {
-                    // Just "- key:" with no value, treat as scalar
-                    items.push(YamlValue::Scalar(after_dash.to_string()));
-                } else {
-                    items.push(YamlValue::Mapping(pairs));
+        if let Some(name) = anchor {
+            self.anchors.insert(name, value.clone());
+        }
+        Ok(value)
+    }
+
+    fn parse_flow_sequence(
+        &mut self,
+        chars: &[char],
+        i: &mut usize,
+        line_no: usize,
+    ) -> Result<YamlValue, YamlParseError> {
+        let mut items = Vec::new();
+        loop {
+            skip_spaces(chars, i);
+            match chars.get(*i) {
+                Some(']') => {
+                    *i += 1;
+                    break;
+                }
+                None => return Err(self.error(line_no, "unterminated flow sequence")),
+                _ => {}
+            }
+
+            let mut item = self.parse_flow_node(chars, i, line_no)?;
+            skip_spaces(chars, i);
+            if chars.get(*i) == Some(&':') {
+                // Single-pair mapping inside a sequence: `[name: Alice]`
+                *i += 1;
+                let key = flow_key(item).map_err(|msg| self.error(line_no, msg))?;
+                let value = self.parse_flow_value(chars, i, line_no)?;
+                item = YamlValue::Mapping(vec![(key, value)]);
+                skip_spaces(chars, i);
+            }
+            items.push(item);
+
+            match chars.get(*i) {
+                Some(',') => *i += 1,
+                Some(']') => {
+                    *i += 1;
+                    break;
+                }
+                _ => return Err(self.error(line_no, "expected ',' or ']' in flow sequence")),
+            }
+        }
+        Ok(YamlValue::Sequence(items))
+    }
+
+    fn parse_flow_mapping(
+        &mut self,
+        chars: &[char],
+        i: &mut usize,
+        line_no: usize,
+    ) -> Result<YamlValue, YamlParseError> {
+        let mut pairs = Vec::new();
+        let mut merges = Vec::new();
+        loop {
+            skip_spaces(chars, i);
+            match chars.get(*i) {
+                Some('}') => {
+                    *i += 1;
+                    break;
                 }
+                None => return Err(self.error(line_no, "unterminated flow mapping")),
+                _ => {}
+            }
+
+            let key_node = self.parse_flow_node(chars, i, line_no)?;
+            let key = flow_key(key_node).map_err(|msg| self.error(line_no, msg))?;
+            skip_spaces(chars, i);
+            let value = if chars.get(*i) == Some(&':') {
+                *i += 1;
+                self.parse_flow_value(chars, i, line_no)?
+            } else {
+                YamlValue::Scalar(String::new())
+            };
+
+            if key == "<<" {
+                merges.push(value);
             } else {
-                items.push(self.parse_scalar_value(&after_dash));
+                pairs.push((key, value));
+            }
+
+            skip_spaces(chars, i);
+            match chars.get(*i) {
+                Some(',') => *i += 1,
+                Some('}') => {
+                    *i += 1;
+                    break;
+                }
+                _ => return Err(self.error(line_no, "expected ',' or '}' in flow mapping")),
             }
         }
 
-        Ok(YamlValue::Sequence(items))
+        for value in merges {
+            merge_into(&mut pairs, value).map_err(|msg| self.error(line_no, msg))?;
+        }
+        Ok(YamlValue::Mapping(pairs))
     }
 
-    fn split_key_value(&self, line: &str) -> Option<(String, String)> {
-        // Find the first ": " or trailing ":"
-        if let Some(idx) = line.find(": ") {
-            let key = line[..idx].trim().to_string();
-            let val = line[idx + 2..].trim().to_string();
-            Some((key, val))
-        } else if line.ends_with(':') {
-            let key = line[..line.len() - 1].trim().to_string();
-            Some((key, String::new()))
-        } else {
-            None
+    /// The value after a `:` in a flow collection; missing values are empty scalars.
+    fn parse_flow_value(
+        &mut self,
+        chars: &[char],
+        i: &mut usize,
+        line_no: usize,
+    ) -> Result<YamlValue, YamlParseError> {
+        skip_spaces(chars, i);
+        match chars.get(*i) {
+            Some(',') | Some(']') | Some('}') | None => Ok(YamlValue::Scalar(String::new())),
+            _ => self.parse_flow_node(chars, i, line_no),
         }
     }
 
-    fn parse_scalar_value(&self, s: &str) -> YamlValue {
-        let trimmed = s.trim();
-        if trimmed.starts_with('"') && trimmed.ends_with('"') && trimmed.len() >= 2 {
-            // Quoted string — strip quotes
-            YamlValue::Scalar(trimmed[1..trimmed.len() - 1].to_string())
-        } else {
-            YamlValue::Scalar(trimmed.to_string())
+    fn error(&self, line: usize, message: impl Into<String>) -> YamlParseError {
+        YamlParseError {
+            line,
+            message: message.into(),
         }
     }
 
     fn current_line_trimmed(&self) -> String {
-        self.lines[self.pos].trim().to_string()
+        strip_comment(self.lines[self.pos].trim()).to_string()
     }
 
     fn current_indent(&self) -> usize {
-        if self.pos >= self.lines.len() {
+        if self.pos >= self.end {
             return 0;
         }
-        let line = &self.lines[self.pos];
-        line.len() - line.trim_start().len()
+        indent_of(&self.lines[self.pos])
     }
 
     fn skip_blank_and_comments(&mut self) {
-        while self.pos < self.lines.len() {
+        while self.pos < self.end {
             let trimmed = self.lines[self.pos].trim();
             if trimmed.is_empty() || trimmed.starts_with('#') {
                 self.pos += 1;
@@ -277,6 +599,269 @@ impl YamlParser {
     }
 }
 
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_sequence_entry(line: &str) -> bool {
+    line == "-" || line.starts_with("- ") || line.starts_with("-\t")
+}
+
+/// Split `key: value` / `key:` / `"quoted: key": value` into key and raw value text.
+fn split_key_value(line: &str) -> Option<(String, String)> {
+    if line.starts_with('"') || line.starts_with('\'') {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        let key = parse_quoted(&chars, &mut i)?;
+        let rest: String = chars[i..].iter().collect();
+        let after = rest.trim_start().strip_prefix(':')?;
+        if !after.is_empty() && !after.starts_with([' ', '\t']) {
+            return None;
+        }
+        return Some((key, after.trim().to_string()));
+    }
+
+    // Flow collections, aliases, anchors, tags and block indicators are never plain keys
+    if line.starts_with(['[', '{', '*', '&', '!', '|', '>']) {
+        return None;
+    }
+
+    for (idx, ch) in line.char_indices() {
+        if ch == ':' {
+            let after = &line[idx + 1..];
+            if after.is_empty() || after.starts_with([' ', '\t']) {
+                return Some((line[..idx].trim().to_string(), after.trim().to_string()));
+            }
+        }
+    }
+    None
+}
+
+/// Strip leading `&anchor` and `!tag` properties, returning the anchor name.
+fn take_properties(text: &str) -> Result<(Option<String>, &str), String> {
+    let mut anchor = None;
+    let mut rest = text.trim();
+    loop {
+        if let Some(after) = rest.strip_prefix('&') {
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            if end == 0 {
+                return Err("anchor without a name".into());
+            }
+            anchor = Some(after[..end].to_string());
+            rest = after[end..].trim_start();
+        } else if rest.starts_with('!') {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        } else {
+            return Ok((anchor, rest));
+        }
+    }
+}
+
+fn parse_block_header(text: &str) -> Option<BlockHeader> {
+    let mut chars = text.chars();
+    let folded = match chars.next()? {
+        '|' => false,
+        '>' => true,
+        _ => return None,
+    };
+    let mut chomping = Chomping::Clip;
+    let mut indent = None;
+    for c in chars {
+        match c {
+            '-' if chomping == Chomping::Clip => chomping = Chomping::Strip,
+            '+' if chomping == Chomping::Clip => chomping = Chomping::Keep,
+            '1'..='9' if indent.is_none() => indent = c.to_digit(10).map(|d| d as usize),
+            _ => return None,
+        }
+    }
+    Some(BlockHeader {
+        folded,
+        chomping,
+        indent,
+    })
+}
+
+/// Fold block scalar lines: single line breaks become spaces, blank lines
+/// become newlines, and more-indented lines keep their line breaks.
+fn fold_lines(lines: &[String]) -> String {
+    let mut out = String::new();
+    let mut started = false;
+    let mut prev_normal = false;
+    let mut blanks = 0;
+
+    for line in lines {
+        if line.is_empty() {
+            blanks += 1;
+            continue;
+        }
+        let more_indented = line.starts_with([' ', '\t']);
+        if !started {
+            out.push_str(&"\n".repeat(blanks));
+        } else if prev_normal && !more_indented {
+            if blanks == 0 {
+                out.push(' ');
+            } else {
+                out.push_str(&"\n".repeat(blanks));
+            }
+        } else {
+            out.push_str(&"\n".repeat(blanks + 1));
+        }
+        out.push_str(line);
+        started = true;
+        prev_normal = !more_indented;
+        blanks = 0;
+    }
+    out
+}
+
+/// Parse a single- or double-quoted string starting at `chars[*i]`.
+fn parse_quoted(chars: &[char], i: &mut usize) -> Option<String> {
+    let quote = *chars.get(*i)?;
+    *i += 1;
+    let mut out = String::new();
+    while let Some(&c) = chars.get(*i) {
+        *i += 1;
+        if c == quote {
+            if quote == '\'' && chars.get(*i) == Some(&'\'') {
+                out.push('\'');
+                *i += 1;
+                continue;
+            }
+            return Some(out);
+        }
+        if c == '\\' && quote == '"' {
+            let escaped = *chars.get(*i)?;
+            *i += 1;
+            match escaped {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                '0' => out.push('\0'),
+                'x' | 'u' | 'U' => {
+                    let len = match escaped {
+                        'x' => 2,
+                        'u' => 4,
+                        _ => 8,
+                    };
+                    let hex: String = chars.get(*i..*i + len)?.iter().collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    *i += len;
+                }
+                other => out.push(other),
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    None
+}
+
+/// Characters outside quoted strings, paired with the character before them.
+/// A quote only opens a string at the start of a token, so `doesn't` stays plain.
+fn unquoted_chars(s: &str) -> Vec<(usize, char, char)> {
+    let mut out = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (idx, c) in s.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => {
+                if (c == '"' || c == '\'') && matches!(prev, ' ' | '\t' | '[' | '{' | ',' | ':') {
+                    quote = Some(c);
+                } else {
+                    out.push((idx, c, prev));
+                }
+            }
+        }
+        prev = c;
+    }
+    out
+}
+
+/// Remove a trailing `# comment` (a `#` at the start or after whitespace, outside quotes).
+fn strip_comment(s: &str) -> &str {
+    for (idx, c, prev) in unquoted_chars(s) {
+        if c == '#' && prev.is_whitespace() {
+            return s[..idx].trim_end();
+        }
+    }
+    s
+}
+
+/// Net bracket depth of a flow collection fragment.
+fn flow_depth(s: &str) -> i32 {
+    unquoted_chars(s).iter().fold(0, |depth, &(_, c, _)| match c {
+        '[' | '{' => depth + 1,
+        ']' | '}' => depth - 1,
+        _ => depth,
+    })
+}
+
+fn skip_spaces(chars: &[char], i: &mut usize) {
+    while chars.get(*i).is_some_and(|c| c.is_whitespace()) {
+        *i += 1;
+    }
+}
+
+/// Read an anchor, alias or tag name inside a flow collection.
+fn read_flow_token(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while let Some(&c) = chars.get(*i) {
+        if c.is_whitespace() || matches!(c, ',' | '[' | ']' | '{' | '}') {
+            break;
+        }
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+fn is_flow_value_break(next: Option<&char>) -> bool {
+    match next {
+        None => true,
+        Some(c) => c.is_whitespace() || matches!(c, ',' | ']' | '}'),
+    }
+}
+
+fn flow_key(node: YamlValue) -> Result<String, String> {
+    match node {
+        YamlValue::Scalar(s) => Ok(s),
+        _ => Err("mapping keys must be scalars".into()),
+    }
+}
+
+/// Apply a `<<` merge: keys already present win over merged ones.
+fn merge_into(pairs: &mut Vec<(String, YamlValue)>, value: YamlValue) -> Result<(), String> {
+    let sources = match value {
+        YamlValue::Mapping(m) => vec![m],
+        YamlValue::Sequence(items) => items
+            .into_iter()
+            .map(|item| match item {
+                YamlValue::Mapping(m) => Ok(m),
+                _ => Err("'<<' expects a mapping or a list of mappings".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err("'<<' expects a mapping or a list of mappings".into()),
+    };
+    for source in sources {
+        for (k, v) in source {
+            if !pairs.iter().any(|(existing, _)| *existing == k) {
+                pairs.push((k, v));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -418,16 +1003,15 @@ mod tests {
 
     #[test]
     fn test_inline_comment_handling() {
-        // Comments at end of value lines are treated as part of the scalar
-        // (our minimal parser doesn't strip inline comments to keep things simple
-        //  and avoid breaking quoted strings with # in them)
-        let input = "key: value\n";
+        // A `#` after whitespace starts a comment; inside quotes it is kept
+        let input = "key: value # trailing\nother: \"a # b\"\n";
         let mut parser = YamlParser::new(input);
         let result = parser.parse().unwrap();
         assert_eq!(
             result,
             YamlValue::Mapping(vec![
                 ("key".into(), YamlValue::Scalar("value".into())),
+                ("other".into(), YamlValue::Scalar("a # b".into())),
             ])
         );
     }
@@ -475,4 +1059,134 @@ quality:
         let quality = result.get("quality").unwrap().as_sequence().unwrap();
         assert_eq!(quality[0], YamlValue::Scalar("all functions must be total".into()));
     }
+
+    #[test]
+    fn test_flow_sequence() {
+        let input = "outputs: [success, not found, \"a, b\"]\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        assert_eq!(
+            result.get("outputs").unwrap(),
+            &YamlValue::Sequence(vec![
+                YamlValue::Scalar("success".into()),
+                YamlValue::Scalar("not found".into()),
+                YamlValue::Scalar("a, b".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_flow_mapping_nested_and_multiline() {
+        let input = "user: {name: Alice, tags: [a, b],\n  address: {city: Lisbon}}\nnext: 1\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        let user = result.get("user").unwrap();
+        assert_eq!(user.get("name").unwrap(), &YamlValue::Scalar("Alice".into()));
+        assert_eq!(user.get("tags").unwrap().as_sequence().unwrap().len(), 2);
+        assert_eq!(
+            user.get("address").unwrap().get("city").unwrap(),
+            &YamlValue::Scalar("Lisbon".into())
+        );
+        assert_eq!(result.get("next").unwrap(), &YamlValue::Scalar("1".into()));
+    }
+
+    #[test]
+    fn test_literal_block_scalar() {
+        let input = "description: |\n  Line one\n    indented\n\n  Line three\nnext: x\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        assert_eq!(
+            result.get("description").unwrap(),
+            &YamlValue::Scalar("Line one\n  indented\n\nLine three\n".into())
+        );
+        assert_eq!(result.get("next").unwrap(), &YamlValue::Scalar("x".into()));
+    }
+
+    #[test]
+    fn test_folded_block_scalar_with_chomping() {
+        let input = "a: >\n  folded\n  text\n\n  para\nb: |-\n  stripped\n\nc: >+\n  kept\n\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        assert_eq!(result.get("a").unwrap(), &YamlValue::Scalar("folded text\npara\n".into()));
+        assert_eq!(result.get("b").unwrap(), &YamlValue::Scalar("stripped".into()));
+        assert_eq!(result.get("c").unwrap(), &YamlValue::Scalar("kept\n\n".into()));
+    }
+
+    #[test]
+    fn test_anchors_aliases_and_merge() {
+        let input = "\
+base: &base
+  type: string
+  required: yes
+name:
+  <<: *base
+  required: no
+tags: &tags [a, b]
+again: *tags
+";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        let name = result.get("name").unwrap();
+        assert_eq!(name.get("type").unwrap(), &YamlValue::Scalar("string".into()));
+        assert_eq!(name.get("required").unwrap(), &YamlValue::Scalar("no".into()));
+        assert_eq!(result.get("again"), result.get("tags"));
+    }
+
+    #[test]
+    fn test_unknown_alias_reports_line() {
+        let input = "a: 1\nb: *missing\n";
+        let err = YamlParser::new(input).parse().unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("missing"));
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_quoted_keys_with_colons() {
+        let input = "\"GET /users/:id\": get-user\n'a: b': \"it's \\\"quoted\\\"\"\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        assert_eq!(
+            result.get("GET /users/:id").unwrap(),
+            &YamlValue::Scalar("get-user".into())
+        );
+        assert_eq!(
+            result.get("a: b").unwrap(),
+            &YamlValue::Scalar("it's \"quoted\"".into())
+        );
+    }
+
+    #[test]
+    fn test_multi_document_stream() {
+        let input = "---\nspec: one\n---\nspec: two\n...\n";
+        let mut parser = YamlParser::new(input);
+        let docs = parser.parse_all().unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[1].get("spec").unwrap(), &YamlValue::Scalar("two".into()));
+        assert_eq!(parser.parse().unwrap(), docs[0]);
+    }
+
+    #[test]
+    fn test_sequence_at_key_indent() {
+        let input = "items:\n- alpha\n- beta: 1\n  gamma: 2\nnext: x\n";
+        let mut parser = YamlParser::new(input);
+        let result = parser.parse().unwrap();
+        let items = result.get("items").unwrap().as_sequence().unwrap();
+        assert_eq!(items[0], YamlValue::Scalar("alpha".into()));
+        assert_eq!(items[1].get("gamma").unwrap(), &YamlValue::Scalar("2".into()));
+        assert_eq!(result.get("next").unwrap(), &YamlValue::Scalar("x".into()));
+    }
+
+    #[test]
+    fn test_errors_carry_line_numbers() {
+        let err = YamlParser::new("a: 1\n    b: 2\n").parse().unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = YamlParser::new("a: 1\nb: [x, y\n\n").parse().unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("unterminated"));
+
+        let err = YamlParser::new("a: 1\nb: \"open\n").parse().unwrap_err();
+        assert_eq!(err.line, 1);
+    }
 }
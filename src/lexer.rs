@@ -1,3 +1,5 @@
+use crate::ast::Number;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Span {
     pub start: usize,
@@ -22,6 +24,7 @@ pub enum TokenKind {
     Keyword(String),     // without the leading colon
     StringLit(String),
     IntLit(i64),
+    FloatLit(f64),
     BoolLit(bool),
     DurationLit(u64, DurationUnit),
     RegexLit(String),
@@ -122,7 +125,7 @@ impl<'a> Lexer<'a> {
             b',' => { self.pos += 1; Ok(Token::new(TokenKind::Comma, Span::new(start, self.pos))) }
             b'#' => self.lex_hash(),
             _ if ch.is_ascii_digit() => self.lex_number_or_duration(),
-            _ if ch == b'-' && self.peek_next().map_or(false, |c| c.is_ascii_digit()) => self.lex_number_or_duration(),
+            _ if ch == b'-' && self.peek_next().is_some_and(|c| c.is_ascii_digit()) => self.lex_number_or_duration(),
             _ if is_symbol_start(ch) => self.lex_symbol(),
             _ => {
                 // Try UTF-8 character for better error message
@@ -246,6 +249,25 @@ impl<'a> Lexer<'a> {
             return Err(format!("expected digit after '-' at byte {}", start));
         }
 
+        // Fractional part: `3.14`, `-0.5`
+        if self.bytes.get(self.pos) == Some(&b'.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_digit() {
+                self.pos += 1;
+            }
+            let full_str = &self.source[start..self.pos];
+            let value: f64 = full_str.parse().map_err(|e| format!("invalid float '{}': {}", full_str, e))?;
+            // Decimals are built from the value: it must hold exactly the digits written
+            let written = written_parts(full_str);
+            if written.is_none() || Number::Float(value).decimal_parts() != written {
+                return Err(format!(
+                    "float literal '{}' at byte {} has more digits than a 64-bit number holds exactly",
+                    full_str, start
+                ));
+            }
+            return Ok(Token::new(TokenKind::FloatLit(value), Span::new(start, self.pos)));
+        }
+
         let num_str = &self.source[num_start..self.pos];
 
         // Check for duration suffix
@@ -260,17 +282,17 @@ impl<'a> Lexer<'a> {
             if let Some(&ch) = self.bytes.get(self.pos) {
                 let next_after = self.bytes.get(self.pos + 1).copied();
                 match ch {
-                    b's' if !next_after.map_or(false, is_symbol_cont) => {
+                    b's' if !next_after.is_some_and(is_symbol_cont) => {
                         let value: u64 = num_str.parse().map_err(|e| format!("invalid duration number: {}", e))?;
                         self.pos += 1;
                         return Ok(Token::new(TokenKind::DurationLit(value, DurationUnit::S), Span::new(start, self.pos)));
                     }
-                    b'h' if !next_after.map_or(false, is_symbol_cont) => {
+                    b'h' if !next_after.is_some_and(is_symbol_cont) => {
                         let value: u64 = num_str.parse().map_err(|e| format!("invalid duration number: {}", e))?;
                         self.pos += 1;
                         return Ok(Token::new(TokenKind::DurationLit(value, DurationUnit::H), Span::new(start, self.pos)));
                     }
-                    b'm' if !next_after.map_or(false, is_symbol_cont) => {
+                    b'm' if !next_after.is_some_and(is_symbol_cont) => {
                         let value: u64 = num_str.parse().map_err(|e| format!("invalid duration number: {}", e))?;
                         self.pos += 1;
                         return Ok(Token::new(TokenKind::DurationLit(value, DurationUnit::M), Span::new(start, self.pos)));
@@ -303,6 +325,14 @@ impl<'a> Lexer<'a> {
     }
 }
 
+/// A float literal's digits as fixed-point units and scale, without trailing zeros:
+/// `19.90` is (199, 1). `None` when they do not fit in an `i64`.
+fn written_parts(text: &str) -> Option<(i64, u32)> {
+    let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+    let frac = frac.trim_end_matches('0');
+    Some((format!("{}{}", whole, frac).parse().ok()?, frac.len() as u32))
+}
+
 fn is_symbol_start(ch: u8) -> bool {
     ch.is_ascii_alphabetic()
         || matches!(ch, b'_' | b'-' | b'+' | b'*' | b'/' | b'!' | b'?' | b'>' | b'<' | b'=' | b'.')
@@ -385,6 +415,27 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_floats() {
+        assert_eq!(lex("2.5 -0.5 19.99"), vec![
+            TokenKind::FloatLit(2.5),
+            TokenKind::FloatLit(-0.5),
+            TokenKind::FloatLit(19.99),
+        ]);
+        // A trailing dot is not part of the number
+        assert_eq!(lex("(. x 1)"), vec![
+            TokenKind::LParen,
+            TokenKind::Symbol(".".into()),
+            TokenKind::Symbol("x".into()),
+            TokenKind::IntLit(1),
+            TokenKind::RParen,
+        ]);
+        // Trailing zeros are fine; digits a float cannot hold, or too many for 64 bits, are not
+        assert_eq!(lex("19.90 100.0"), vec![TokenKind::FloatLit(19.9), TokenKind::FloatLit(100.0)]);
+        assert!(Lexer::new("12345678901234567.89").tokenize().unwrap_err().contains("more digits"));
+        assert!(Lexer::new("99999999999999999999.5").tokenize().is_err());
+    }
+
     #[test]
     fn test_booleans() {
         assert_eq!(lex("true false"), vec![
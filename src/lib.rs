@@ -7,3 +7,5 @@ pub mod codegen;
 pub mod diagnostics;
 pub mod generate;
 pub mod scaffold;
+pub mod schema;
+pub mod naming;
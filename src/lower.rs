@@ -7,6 +7,12 @@ pub struct Lowerer {
     pub diagnostics: Vec<Diagnostic>,
 }
 
+impl Default for Lowerer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Lowerer {
     pub fn new() -> Self {
         Lowerer {
@@ -17,7 +23,7 @@ impl Lowerer {
     pub fn lower_module(&mut self, sexpr: &SExpr) -> Result<Module, String> {
         let items = sexpr
             .as_list()
-            .ok_or_else(|| format!("expected module to be a list"))?;
+            .ok_or_else(|| "expected module to be a list".to_string())?;
         if items.is_empty() || items[0].as_symbol() != Some("module") {
             return Err("expected (module ...)".to_string());
         }
@@ -34,6 +40,8 @@ impl Lowerer {
         let mut types = Vec::new();
         let mut effect_sets = Vec::new();
         let mut functions = Vec::new();
+        let mut helpers = Vec::new();
+        let mut externs = Vec::new();
         let mut extra_meta = Vec::new();
 
         let mut i = 2;
@@ -70,6 +78,8 @@ impl Lowerer {
                         "type" => types.push(self.lower_type_def(&items[i])?),
                         "effect-set" => effect_sets.push(self.lower_effect_set(&items[i])?),
                         "fn" => functions.push(self.lower_fn_def(&items[i])?),
+                        "defn" => helpers.push(self.lower_helper_def(&items[i])?),
+                        "extern" => externs.push(self.lower_extern_def(&items[i])?),
                         _ => {
                             self.diagnostics.push(Diagnostic::warning(
                                 format!("unknown top-level form '{}'", head),
@@ -93,6 +103,8 @@ impl Lowerer {
             types,
             effect_sets,
             functions,
+            helpers,
+            externs,
             extra_meta,
             span: sexpr.span.clone(),
         })
@@ -170,11 +182,34 @@ impl Lowerer {
     fn lower_type_def(&mut self, sexpr: &SExpr) -> Result<TypeDef, String> {
         let items = sexpr.as_list().ok_or("expected type to be a list")?;
         // (type Name :invariants [...] (field ...) ...)
-        let name = items
-            .get(1)
-            .and_then(|s| s.as_symbol())
-            .ok_or("expected type name")?
-            .to_string();
+        // (type (Name T ...) (field ...) ...) declares a generic type
+        let (name, type_params) = match items.get(1).and_then(|s| s.as_list()) {
+            Some(head) => {
+                let name = head
+                    .first()
+                    .and_then(|s| s.as_symbol())
+                    .ok_or("expected type name")?
+                    .to_string();
+                let mut params = Vec::new();
+                for param in &head[1..] {
+                    params.push(
+                        param
+                            .as_symbol()
+                            .ok_or("expected type parameter name")?
+                            .to_string(),
+                    );
+                }
+                (name, params)
+            }
+            None => (
+                items
+                    .get(1)
+                    .and_then(|s| s.as_symbol())
+                    .ok_or("expected type name")?
+                    .to_string(),
+                Vec::new(),
+            ),
+        };
 
         let mut invariants = Vec::new();
         let mut fields = Vec::new();
@@ -215,6 +250,7 @@ impl Lowerer {
 
         Ok(TypeDef {
             name,
+            type_params,
             invariants,
             fields,
             extra_meta,
@@ -239,6 +275,10 @@ impl Lowerer {
         let mut generated = false;
         let mut min_len = None;
         let mut max_len = None;
+        let mut min = None;
+        let mut max = None;
+        let mut scale = None;
+        let mut default = None;
         let mut format = None;
         let mut unique_within = None;
         let mut extra_meta = Vec::new();
@@ -265,6 +305,26 @@ impl Lowerer {
                         max_len = items.get(i).and_then(|s| s.as_int());
                         i += 1;
                     }
+                    "min" => {
+                        i += 1;
+                        min = items.get(i).and_then(|s| s.as_number());
+                        i += 1;
+                    }
+                    "max" => {
+                        i += 1;
+                        max = items.get(i).and_then(|s| s.as_number());
+                        i += 1;
+                    }
+                    "scale" => {
+                        i += 1;
+                        scale = items.get(i).and_then(|s| s.as_int()).and_then(|n| u32::try_from(n).ok());
+                        i += 1;
+                    }
+                    "default" => {
+                        i += 1;
+                        default = items.get(i).map(|s| self.sexpr_to_meta(s));
+                        i += 1;
+                    }
                     "format" => {
                         i += 1;
                         format = items.get(i).and_then(|s| s.as_keyword()).map(|s| s.to_string());
@@ -277,8 +337,7 @@ impl Lowerer {
                         i += 1;
                     }
                     other => {
-                        // Check if the next item is a value or another keyword (flag keyword)
-                        if i + 1 < items.len() && items[i + 1].as_keyword().is_none() {
+                        if !is_flag("field", items, i) {
                             i += 1;
                             extra_meta.push((other.to_string(), self.sexpr_to_meta(&items[i])));
                             i += 1;
@@ -301,6 +360,10 @@ impl Lowerer {
             generated,
             min_len,
             max_len,
+            min,
+            max,
+            scale,
+            default,
             format,
             unique_within,
             extra_meta,
@@ -350,13 +413,56 @@ impl Lowerer {
                             }
                             Ok(TypeExpr::Enum(names))
                         }
+                        "optional" => {
+                            let inner = items
+                                .get(1)
+                                .map(|s| self.lower_type_expr(s))
+                                .ok_or("expected optional type")??;
+                            Ok(TypeExpr::Optional(Box::new(inner)))
+                        }
+                        "ref" => items
+                            .get(1)
+                            .and_then(|s| s.as_symbol())
+                            .map(|name| TypeExpr::Ref(name.to_string()))
+                            .ok_or_else(|| "expected a type name in ref".to_string()),
+                        "map" => {
+                            let key = items
+                                .get(1)
+                                .map(|s| self.lower_type_expr(s))
+                                .ok_or("expected map key type")??;
+                            let value = items
+                                .get(2)
+                                .map(|s| self.lower_type_expr(s))
+                                .ok_or("expected map value type")??;
+                            Ok(TypeExpr::MapOf(Box::new(key), Box::new(value)))
+                        }
+                        "result" => {
+                            let ok = items
+                                .get(1)
+                                .map(|s| self.lower_type_expr(s))
+                                .ok_or("expected result ok type")??;
+                            let err = items
+                                .get(2)
+                                .map(|s| self.lower_type_expr(s))
+                                .ok_or("expected result err type")??;
+                            Ok(TypeExpr::Result(Box::new(ok), Box::new(err)))
+                        }
+                        // (Page User): type names are capitalized, unlike payload
+                        // expressions such as (. input email)
+                        _ if items.len() > 1 && head.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                            let mut args = Vec::new();
+                            for item in &items[1..] {
+                                args.push(self.lower_type_expr(item)?);
+                            }
+                            Ok(TypeExpr::Generic(head.to_string(), args))
+                        }
                         _ => Ok(TypeExpr::Named(head.to_string())),
                     }
                 } else {
                     Err("expected type expression".to_string())
                 }
             }
-            _ => Err(format!("unexpected type expression")),
+            _ => Err("unexpected type expression".to_string()),
         }
     }
 
@@ -419,6 +525,7 @@ impl Lowerer {
         let mut latency_budget = None;
         let mut called_by = Vec::new();
         let mut idempotency_key = None;
+        let mut on_store_error = None;
         let mut params = Vec::new();
         let mut returns = None;
         let mut body = None;
@@ -469,6 +576,15 @@ impl Lowerer {
                         i += 1;
                         idempotency_key = Some(self.lower_expr(&items[i])?);
                     }
+                    "on-store-error" => {
+                        i += 1;
+                        on_store_error = Some(
+                            items[i]
+                                .as_keyword()
+                                .ok_or("expected :on-store-error to name an err variant, e.g. :internal")?
+                                .to_string(),
+                        );
+                    }
                     other => {
                         i += 1;
                         if i < items.len() {
@@ -505,6 +621,7 @@ impl Lowerer {
             latency_budget,
             called_by,
             idempotency_key,
+            on_store_error,
             params,
             returns,
             body,
@@ -513,6 +630,92 @@ impl Lowerer {
         })
     }
 
+    fn lower_helper_def(&mut self, sexpr: &SExpr) -> Result<HelperDef, String> {
+        let items = sexpr.as_list().ok_or("expected defn to be a list")?;
+        // (defn name [param Type ...] ReturnType body)
+        if items.len() != 5 {
+            return Err("defn requires a name, a parameter vector, a return type and a body".to_string());
+        }
+        let name = items[1].as_symbol().ok_or("expected helper name")?.to_string();
+        let param_items = items[2]
+            .as_vector()
+            .ok_or("expected defn parameters to be a vector of name-type pairs")?;
+        if param_items.len() % 2 != 0 {
+            return Err(format!("defn '{}' parameters must be name-type pairs", name));
+        }
+        let mut params = Vec::new();
+        for pair in param_items.chunks(2) {
+            params.push(ParamDef {
+                name: pair[0].as_symbol().ok_or("expected parameter name")?.to_string(),
+                type_expr: self.lower_type_expr(&pair[1])?,
+                source: None,
+                content_type: None,
+                validated_at: None,
+                extra_meta: Vec::new(),
+                span: pair[0].span.clone(),
+            });
+        }
+        let returns = self.lower_type_expr(&items[3])?;
+        let body = self.lower_expr(&items[4])?;
+
+        Ok(HelperDef {
+            name,
+            params,
+            returns,
+            body,
+            span: sexpr.span.clone(),
+        })
+    }
+
+    fn lower_extern_def(&mut self, sexpr: &SExpr) -> Result<ExternDef, String> {
+        let items = sexpr.as_list().ok_or("expected extern to be a list")?;
+        // (extern name [ParamType ...] ReturnType :effects [effect-set ...])
+        if items.len() < 4 {
+            return Err("extern requires a name, a vector of parameter types and a return type".to_string());
+        }
+        let name = items[1].as_symbol().ok_or("expected extern name")?.to_string();
+        let param_items = items[2]
+            .as_vector()
+            .ok_or("expected extern parameter types to be a vector")?;
+        let mut params = Vec::new();
+        for item in param_items {
+            params.push(self.lower_type_expr(item)?);
+        }
+        let returns = self.lower_type_expr(&items[3])?;
+
+        let mut effects = Vec::new();
+        let mut i = 4;
+        while i < items.len() {
+            match items[i].as_keyword() {
+                Some("effects") => {
+                    i += 1;
+                    let effect_items = items
+                        .get(i)
+                        .and_then(|s| s.as_vector())
+                        .ok_or("expected :effects to be a vector")?;
+                    for item in effect_items {
+                        effects.push(item.as_symbol().ok_or("expected effect set name")?.to_string());
+                    }
+                }
+                _ => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        format!("extern '{}': unexpected form, expected :effects [...]", name),
+                        Some(items[i].span.clone()),
+                    ));
+                }
+            }
+            i += 1;
+        }
+
+        Ok(ExternDef {
+            name,
+            params,
+            returns,
+            effects,
+            span: sexpr.span.clone(),
+        })
+    }
+
     fn lower_param_def(&mut self, sexpr: &SExpr) -> Result<ParamDef, String> {
         let items = sexpr.as_list().ok_or("expected param to be a list")?;
         // (param name TypeExpr :keyword value ...)
@@ -733,6 +936,9 @@ impl Lowerer {
             SExprKind::Atom(AtomKind::IntLit(n)) => {
                 Ok(Expr::IntLit(*n, sexpr.span.clone()))
             }
+            SExprKind::Atom(AtomKind::FloatLit(n)) => {
+                Ok(Expr::FloatLit(*n, sexpr.span.clone()))
+            }
             SExprKind::Atom(AtomKind::BoolLit(b)) => {
                 Ok(Expr::BoolLit(*b, sexpr.span.clone()))
             }
@@ -761,6 +967,8 @@ impl Lowerer {
                     "let" => self.lower_let(items, &sexpr.span),
                     "match" => self.lower_match(items, &sexpr.span),
                     "if" => self.lower_if(items, &sexpr.span),
+                    "fn" => self.lower_lambda(items, &sexpr.span),
+                    "for-each" => self.lower_for_each(items, &sexpr.span),
                     "." => {
                         if items.len() != 3 {
                             return Err("expected (. expr field)".to_string());
@@ -815,7 +1023,14 @@ impl Lowerer {
                     }
                 }
             }
-            _ => Err(format!("unexpected expression form")),
+            SExprKind::Vector(items) => {
+                let mut elements = Vec::new();
+                for item in items {
+                    elements.push(self.lower_expr(item)?);
+                }
+                Ok(Expr::ListLit(elements, sexpr.span.clone()))
+            }
+            _ => Err("unexpected expression form".to_string()),
         }
     }
 
@@ -849,6 +1064,50 @@ impl Lowerer {
         })
     }
 
+    fn lower_lambda(&mut self, items: &[SExpr], span: &Span) -> Result<Expr, String> {
+        // (fn [x y] body)
+        if items.len() != 3 {
+            return Err("fn requires a parameter vector and a body".to_string());
+        }
+        let param_items = items[1]
+            .as_vector()
+            .ok_or("expected fn parameters to be a vector")?;
+        let mut params = Vec::new();
+        for item in param_items {
+            params.push(item.as_symbol().ok_or("expected fn parameter name")?.to_string());
+        }
+        let body = self.lower_expr(&items[2])?;
+
+        Ok(Expr::Lambda {
+            params,
+            body: Box::new(body),
+            span: span.clone(),
+        })
+    }
+
+    fn lower_for_each(&mut self, items: &[SExpr], span: &Span) -> Result<Expr, String> {
+        // (for-each [x xs] body)
+        if items.len() != 3 {
+            return Err("for-each requires a binding and a body".to_string());
+        }
+        let binding = items[1]
+            .as_vector()
+            .ok_or("expected for-each binding to be a vector")?;
+        let [var, list] = binding else {
+            return Err("expected for-each binding [name list]".to_string());
+        };
+        let var = var.as_symbol().ok_or("expected for-each binding name")?.to_string();
+        let list = self.lower_expr(list)?;
+        let body = self.lower_expr(&items[2])?;
+
+        Ok(Expr::ForEach {
+            var,
+            list: Box::new(list),
+            body: Box::new(body),
+            span: span.clone(),
+        })
+    }
+
     fn lower_match(&mut self, items: &[SExpr], span: &Span) -> Result<Expr, String> {
         // (match expr pattern1 body1 pattern2 body2 ...)
         if items.len() < 4 {
@@ -932,6 +1191,7 @@ impl Lowerer {
         match &sexpr.kind {
             SExprKind::Atom(AtomKind::StringLit(s)) => MetaValue::String(s.clone()),
             SExprKind::Atom(AtomKind::IntLit(n)) => MetaValue::Int(*n),
+            SExprKind::Atom(AtomKind::FloatLit(n)) => MetaValue::Float(*n),
             SExprKind::Atom(AtomKind::BoolLit(b)) => MetaValue::Bool(*b),
             SExprKind::Atom(AtomKind::Symbol(s)) => MetaValue::Symbol(s.clone()),
             SExprKind::Atom(AtomKind::Keyword(s)) => MetaValue::Keyword(s.clone()),
@@ -965,6 +1225,23 @@ impl Lowerer {
     }
 }
 
+/// Whether the keyword at `items[i]` of a `(form ...)` stands alone instead of taking
+/// the next item as its value. Only fields have flags: `:immutable`, `:generated`, and any
+/// keyword of no meaning of its own that is followed by another keyword or by nothing.
+/// Everywhere else a keyword takes the next item, even a keyword (`:on-store-error :internal`).
+pub(crate) fn is_flag(form: &str, items: &[SExpr], i: usize) -> bool {
+    if form != "field" {
+        return false;
+    }
+    match items[i].as_keyword() {
+        Some("immutable" | "generated") => true,
+        Some("min-len" | "max-len" | "min" | "max" | "scale" | "default" | "format" | "unique-within") => {
+            false
+        }
+        _ => items.get(i + 1).is_none_or(|next| next.as_keyword().is_some()),
+    }
+}
+
 /// Format an SExpr back to a string (for invariants, etc.)
 fn format_sexpr(sexpr: &SExpr) -> String {
     match &sexpr.kind {
@@ -972,6 +1249,7 @@ fn format_sexpr(sexpr: &SExpr) -> String {
         SExprKind::Atom(AtomKind::Keyword(s)) => format!(":{}", s),
         SExprKind::Atom(AtomKind::StringLit(s)) => format!("\"{}\"", s),
         SExprKind::Atom(AtomKind::IntLit(n)) => n.to_string(),
+        SExprKind::Atom(AtomKind::FloatLit(n)) => format!("{:?}", n),
         SExprKind::Atom(AtomKind::BoolLit(b)) => b.to_string(),
         SExprKind::Atom(AtomKind::DurationLit(v, u)) => format!("{}{}", v, u),
         SExprKind::Atom(AtomKind::RegexLit(r)) => format!("#/{}/", r),
@@ -1031,6 +1309,63 @@ mod tests {
         assert_eq!(t.fields[1].min_len, Some(1));
     }
 
+    #[test]
+    fn test_lower_generic_type_def() {
+        let module = parse_and_lower(
+            "(module test :version 1 (type (Page T) (field items (list T)) (field counts (map String Int)) (field inner (Page T))))"
+        );
+        let t = &module.types[0];
+        assert_eq!(t.name, "Page");
+        assert_eq!(t.type_params, vec!["T".to_string()]);
+        assert!(matches!(&t.fields[1].type_expr, TypeExpr::MapOf(_, v) if matches!(&**v, TypeExpr::Named(n) if n == "Int")));
+        assert!(matches!(&t.fields[2].type_expr, TypeExpr::Generic(n, args) if n == "Page" && args.len() == 1));
+    }
+
+    #[test]
+    fn test_lower_collection_operations() {
+        let module = parse_and_lower(
+            "(module test :version 1 (fn f (param xs (list Int)) (returns (union (ok Int))) (for-each [x (map (fn [y] (* y 2)) [1 2 xs])] (ok x))))"
+        );
+        let Expr::ForEach { var, list, .. } = &module.functions[0].body else {
+            panic!("expected for-each");
+        };
+        assert_eq!(var, "x");
+        let Expr::Call { name, args, .. } = list.as_ref() else {
+            panic!("expected map call");
+        };
+        assert_eq!(name, "map");
+        assert!(matches!(&args[0], Expr::Lambda { params, .. } if params == &["y".to_string()]));
+        assert!(matches!(&args[1], Expr::ListLit(items, _) if items.len() == 3));
+    }
+
+    #[test]
+    fn test_lower_helper_def() {
+        let module = parse_and_lower(
+            "(module test :version 1 (defn line-total [qty Int prices (list Int)] Int (* qty 2)))"
+        );
+        let helper = &module.helpers[0];
+        assert_eq!(helper.name, "line-total");
+        assert_eq!(helper.params.len(), 2);
+        assert_eq!(helper.params[1].name, "prices");
+        assert!(matches!(&helper.params[1].type_expr, TypeExpr::List(_)));
+        assert!(matches!(&helper.returns, TypeExpr::Named(n) if n == "Int"));
+        assert!(module.functions.is_empty());
+    }
+
+    #[test]
+    fn test_lower_extern_def() {
+        let module = parse_and_lower(
+            "(module test :version 1 (extern send-sms [String String] (result Unit String) :effects [sms]))"
+        );
+        let ext = &module.externs[0];
+        assert_eq!(ext.name, "send-sms");
+        assert_eq!(ext.params.len(), 2);
+        assert!(matches!(&ext.returns, TypeExpr::Result(ok, err)
+            if matches!(ok.as_ref(), TypeExpr::Named(n) if n == "Unit")
+                && matches!(err.as_ref(), TypeExpr::Named(n) if n == "String")));
+        assert_eq!(ext.effects, vec!["sms".to_string()]);
+    }
+
     #[test]
     fn test_lower_effect_set() {
         let module =
@@ -1,33 +1,51 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 
+use pact_lang::codegen::crate_output::{self, Backend};
 use pact_lang::codegen::rust::RustCodegen;
 use pact_lang::codegen::rust_v2::RustV2Codegen;
+use pact_lang::codegen::typescript::TypeScriptCodegen;
+use pact_lang::ast::Module;
 use pact_lang::diagnostics::{self, DiagnosticKind};
 use pact_lang::generate::yaml_parser::YamlParser;
 use pact_lang::generate::spec_parser;
 use pact_lang::generate::pct_emitter::PctEmitter;
+use pact_lang::generate::verify;
+use pact_lang::generate::merge;
 use pact_lang::lexer::Lexer;
 use pact_lang::lower::Lowerer;
 use pact_lang::parser::Parser;
+use pact_lang::scaffold::ScaffoldOptions;
+use pact_lang::schema::{self, Dialect};
 use pact_lang::semantic;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
     if args.len() < 2 || args[1] == "--help" || args[1] == "-h" {
-        eprintln!("Usage: pact compile <input.pct> [-o <output-dir>] [--runtime]");
-        eprintln!("");
+        eprintln!("Usage: pact compile <input.pct>... [-o <output-dir>] [--runtime] [--crate] [--ts]");
+        eprintln!();
         eprintln!("Commands:");
         eprintln!("  compile    Parse, analyze, and generate Rust code from a Pact file");
         eprintln!("  generate   Generate a .pct file from a YAML spec");
+        eprintln!("             --update <existing.pct> [--base <generated.pct>] merges into hand edits");
+        eprintln!("  verify-spec <spec.yaml> <module.pct>");
+        eprintln!("             Report where a module has drifted from its YAML spec");
         eprintln!("  scaffold   Generate an Axum web project from a Pact file");
+        eprintln!("             --store sqlite backs stores with generated SQLite stores");
+        eprintln!("  schema     Emit SQL tables for the module's stores [--dialect sqlite|postgres]");
+        eprintln!("             --from <previous.pct> emits a migration from the parent version");
         eprintln!("  check      Parse and analyze without generating code");
+        eprintln!("             --suggest-effects prints the minimal :effects each function needs");
         eprintln!("  parse      Parse only (show CST)");
-        eprintln!("");
+        eprintln!();
         eprintln!("Flags:");
         eprintln!("  --runtime  Generate code targeting pact-runtime crate");
+        eprintln!("  --async    With --runtime (and for scaffold): async domain functions");
+        eprintln!("  --crate    Write a library crate (Cargo.toml, src/lib.rs, pact.lock) and");
+        eprintln!("             recompile only modules whose source or compiler changed");
+        eprintln!("  --ts       Generate TypeScript types, validators and an API client instead");
         process::exit(if args.len() < 2 { 1 } else { 0 });
     }
 
@@ -35,7 +53,9 @@ fn main() {
     match command.as_str() {
         "compile" => cmd_compile(&args[2..]),
         "generate" => cmd_generate(&args[2..]),
+        "verify-spec" => cmd_verify_spec(&args[2..]),
         "scaffold" => cmd_scaffold(&args[2..]),
+        "schema" => cmd_schema(&args[2..]),
         "check" => cmd_check(&args[2..]),
         "parse" => cmd_parse(&args[2..]),
         _ => {
@@ -47,11 +67,94 @@ fn main() {
 }
 
 fn cmd_compile(args: &[String]) {
-    let (input_path, output_dir, use_runtime) = parse_compile_args(args);
-    let source = read_source(&input_path);
+    let args = parse_compile_args(args);
+    if args.use_async && !args.use_runtime {
+        eprintln!("--async requires --runtime");
+        process::exit(1);
+    }
+    if args.use_ts && (args.use_runtime || args.use_crate) {
+        eprintln!("--ts cannot be combined with --runtime or --crate");
+        process::exit(1);
+    }
+    let output_dir = args.output.unwrap_or_else(|| PathBuf::from("output"));
+
+    if args.use_crate {
+        let backend = match (args.use_runtime, args.use_async) {
+            (false, _) => Backend::V1,
+            (true, false) => Backend::Runtime,
+            (true, true) => Backend::RuntimeAsync,
+        };
+        let report = crate_output::write_crate(&output_dir, &args.inputs, backend, |path, source| {
+            eprintln!("Compiling {}", path.display());
+            Ok(compile_to_rust(source, args.use_runtime, args.use_async))
+        });
+        match report {
+            Ok(report) => {
+                for path in &report.written {
+                    eprintln!("  Wrote {}", path.display());
+                }
+                for warning in &report.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                eprintln!(
+                    "Crate at {}: {} file(s) written, {} unchanged",
+                    output_dir.display(),
+                    report.written.len(),
+                    report.unchanged.len()
+                );
+            }
+            Err(e) => {
+                eprintln!("Crate error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
 
+    if args.inputs.len() > 1 {
+        eprintln!("Compiling several modules requires --crate");
+        process::exit(1);
+    }
+    let source = read_source(&args.inputs[0]);
+    let (module_name, code, extension) = if args.use_ts {
+        let module = load_module(&source);
+        (module.name.clone(), TypeScriptCodegen::new().generate(&module), "ts")
+    } else {
+        let (name, code) = compile_to_rust(&source, args.use_runtime, args.use_async);
+        (name, code, "rs")
+    };
+
+    // Write output
+    fs::create_dir_all(&output_dir).unwrap_or_else(|e| {
+        eprintln!("Failed to create output directory: {}", e);
+        process::exit(1);
+    });
+
+    let output_file = output_dir.join(format!("{}.{}", module_name, extension));
+    fs::write(&output_file, &code).unwrap_or_else(|e| {
+        eprintln!("Failed to write output: {}", e);
+        process::exit(1);
+    });
+
+    eprintln!("Generated {} ({} bytes)", output_file.display(), code.len());
+}
+
+/// Lex, parse, lower, analyze and generate Rust for one module, exiting on errors.
+/// Returns the Rust module name and its code.
+fn compile_to_rust(source: &str, use_runtime: bool, use_async: bool) -> (String, String) {
+    let module = load_module(source);
+    let rust_code = if use_runtime {
+        RustV2Codegen::new().with_async(use_async).generate(&module)
+    } else {
+        RustCodegen::new().generate(&module)
+    };
+    (module.name.replace('-', "_"), rust_code)
+}
+
+/// Lex, parse, lower and analyze one module, exiting on errors.
+fn load_module(source: &str) -> Module {
     // Lex
-    let mut lexer = Lexer::new(&source);
+    let mut lexer = Lexer::new(source);
     let tokens = match lexer.tokenize() {
         Ok(t) => t,
         Err(e) => {
@@ -87,14 +190,14 @@ fn cmd_compile(args: &[String]) {
 
     // Print lowering warnings
     if !lowerer.diagnostics.is_empty() {
-        let formatted = diagnostics::format_diagnostics(&source, &lowerer.diagnostics);
+        let formatted = diagnostics::format_diagnostics(source, &lowerer.diagnostics);
         eprint!("{}", formatted);
     }
 
     // Semantic analysis
     let diags = semantic::analyze(&module);
     if !diags.is_empty() {
-        let formatted = diagnostics::format_diagnostics(&source, &diags);
+        let formatted = diagnostics::format_diagnostics(source, &diags);
         eprint!("{}", formatted);
 
         let error_count = diags.iter().filter(|d| d.kind == DiagnosticKind::Error).count();
@@ -104,27 +207,7 @@ fn cmd_compile(args: &[String]) {
         }
     }
 
-    // Code generation
-    let rust_code = if use_runtime {
-        RustV2Codegen::new().generate(&module)
-    } else {
-        RustCodegen::new().generate(&module)
-    };
-
-    // Write output
-    let output_dir = output_dir.unwrap_or_else(|| PathBuf::from("output"));
-    fs::create_dir_all(&output_dir).unwrap_or_else(|e| {
-        eprintln!("Failed to create output directory: {}", e);
-        process::exit(1);
-    });
-
-    let output_file = output_dir.join(format!("{}.rs", module.name.replace('-', "_")));
-    fs::write(&output_file, &rust_code).unwrap_or_else(|e| {
-        eprintln!("Failed to write output: {}", e);
-        process::exit(1);
-    });
-
-    eprintln!("Generated {} ({} bytes)", output_file.display(), rust_code.len());
+    module
 }
 
 fn cmd_scaffold(args: &[String]) {
@@ -186,7 +269,23 @@ fn cmd_scaffold(args: &[String]) {
     }
 
     // Scaffold
-    let scaffold_output = pact_lang::scaffold::scaffold(&module);
+    let store = args
+        .iter()
+        .position(|a| a == "--store")
+        .and_then(|i| args.get(i + 1))
+        .map_or("memory", |s| s.as_str());
+    let options = ScaffoldOptions {
+        async_fns: args.iter().any(|a| a == "--async"),
+        sqlite: match store {
+            "memory" => false,
+            "sqlite" => true,
+            other => {
+                eprintln!("Unknown store '{}' (expected memory or sqlite)", other);
+                process::exit(1);
+            }
+        },
+    };
+    let scaffold_output = pact_lang::scaffold::scaffold_with(&module, &options);
 
     // Write output
     let output_dir = output_dir.unwrap_or_else(|| {
@@ -205,8 +304,8 @@ fn cmd_scaffold(args: &[String]) {
     }
 
     eprintln!("Done! Next steps:");
-    eprintln!("  1. Generate domain code: pact compile --runtime {} -o {}/src/generated/",
-        input_path.display(), output_dir.display());
+    eprintln!("  1. Generate domain code: pact compile --runtime{} {} -o {}/src/generated/",
+        if options.async_fns { " --async" } else { "" }, input_path.display(), output_dir.display());
     eprintln!("  2. Build: cd {} && cargo build", output_dir.display());
     eprintln!("  3. Run:   cd {} && cargo run", output_dir.display());
 }
@@ -261,6 +360,10 @@ fn cmd_check(args: &[String]) {
     let error_count = diags.iter().filter(|d| d.kind == DiagnosticKind::Error).count();
     let warning_count = diags.iter().filter(|d| d.kind == DiagnosticKind::Warning).count();
 
+    if args.iter().any(|a| a == "--suggest-effects") {
+        print_effect_suggestions(&module);
+    }
+
     eprintln!(
         "Module '{}' v{}: {} error(s), {} warning(s)",
         module.name,
@@ -274,6 +377,21 @@ fn cmd_check(args: &[String]) {
     }
 }
 
+/// One line per function: the `:effects` it needs, then what it declares without needing
+/// and what no effect set grants.
+fn print_effect_suggestions(module: &Module) {
+    for suggestion in semantic::effects::suggest_effects(module) {
+        let mut line = format!("{}: :effects [{}]", suggestion.function, suggestion.effects.join(" "));
+        if !suggestion.over_declared.is_empty() {
+            line.push_str(&format!("  ; over-declared: {}", suggestion.over_declared.join(", ")));
+        }
+        for (kind, target) in &suggestion.ungranted {
+            line.push_str(&format!("  ; no effect set grants {:?} on '{}'", kind, target));
+        }
+        println!("{}", line);
+    }
+}
+
 fn cmd_parse(args: &[String]) {
     let (input_path, _) = parse_args(args);
     let source = read_source(&input_path);
@@ -304,7 +422,7 @@ fn cmd_parse(args: &[String]) {
 }
 
 fn cmd_generate(args: &[String]) {
-    let (input_path, output_path) = parse_args(args);
+    let (input_path, output_path, update_path, base_path) = parse_generate_args(args);
     let source = read_source(&input_path);
 
     // Parse YAML
@@ -325,6 +443,9 @@ fn cmd_generate(args: &[String]) {
             process::exit(1);
         }
     };
+    for warning in &spec.warnings {
+        eprintln!("warning: {}", warning);
+    }
 
     // Emit .pct
     let pct_source = PctEmitter::new().emit(&spec);
@@ -368,6 +489,12 @@ fn cmd_generate(args: &[String]) {
         }
     }
 
+    if let Some(update_path) = update_path {
+        let output_file = output_path.unwrap_or_else(|| update_path.clone());
+        update_module(&update_path, base_path, &output_file, &pct_source);
+        return;
+    }
+
     // Write output
     let output_file = output_path.unwrap_or_else(|| {
         let stem = input_path
@@ -383,6 +510,7 @@ fn cmd_generate(args: &[String]) {
         eprintln!("Failed to write output: {}", e);
         process::exit(1);
     });
+    write_generated_base(&output_file, &pct_source);
 
     eprintln!(
         "Generated {} ({} bytes) from spec '{}'",
@@ -392,10 +520,249 @@ fn cmd_generate(args: &[String]) {
     );
 }
 
-fn parse_compile_args(args: &[String]) -> (PathBuf, Option<PathBuf>, bool) {
+/// Three-way merge of a freshly generated module into a hand-edited one.
+fn update_module(existing: &PathBuf, base_path: Option<PathBuf>, output_file: &PathBuf, generated: &str) {
+    let user_source = read_source(existing);
+    let base_path = base_path.unwrap_or_else(|| generated_base_path(existing));
+    let base_source = fs::read_to_string(&base_path).ok();
+    if base_source.is_none() {
+        eprintln!(
+            "warning: no previously generated base at '{}'; keeping hand-written bodies and taking everything else from the spec",
+            base_path.display()
+        );
+    }
+
+    let result = match merge::merge_modules(base_source.as_deref(), &user_source, generated) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Cannot merge into '{}': {}", existing.display(), e);
+            process::exit(1);
+        }
+    };
+
+    // The merged module must still lower
+    let lowered = Lexer::new(&result.source)
+        .tokenize()
+        .and_then(|tokens| Parser::new(tokens).parse_program())
+        .and_then(|sexprs| match sexprs.first() {
+            Some(s) => Lowerer::new().lower_module(s),
+            None => Err("no top-level expressions".into()),
+        });
+    if let Err(e) = lowered {
+        eprintln!("Merged .pct does not lower: {}", e);
+        eprintln!("--- merged source ---");
+        eprintln!("{}", result.source);
+        process::exit(1);
+    }
+
+    fs::write(output_file, &result.source).unwrap_or_else(|e| {
+        eprintln!("Failed to write output: {}", e);
+        process::exit(1);
+    });
+    write_generated_base(output_file, generated);
+
+    if !result.diagnostics.is_empty() {
+        eprint!("{}", diagnostics::format_diagnostics(&result.source, &result.diagnostics));
+    }
+    for change in &result.changes {
+        eprintln!("  {}", change);
+    }
+    let conflicts = result.diagnostics.iter().filter(|d| d.kind == DiagnosticKind::Error).count();
+    eprintln!(
+        "Updated {} ({} change(s), {} conflict(s))",
+        output_file.display(),
+        result.changes.len(),
+        conflicts
+    );
+    if conflicts > 0 {
+        process::exit(1);
+    }
+}
+
+/// Where `generate` keeps the untouched output for later `--update` merges.
+fn generated_base_path(pct: &Path) -> PathBuf {
+    let mut name = pct.as_os_str().to_owned();
+    name.push(".base");
+    PathBuf::from(name)
+}
+
+fn write_generated_base(output_file: &Path, source: &str) {
+    let base = generated_base_path(output_file);
+    if let Err(e) = fs::write(&base, source) {
+        eprintln!("warning: failed to write '{}': {}", base.display(), e);
+    }
+}
+
+fn cmd_verify_spec(args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("Usage: pact verify-spec <spec.yaml> <module.pct>");
+        process::exit(1);
+    }
+    let spec_path = PathBuf::from(&args[0]);
+    let pct_path = PathBuf::from(&args[1]);
+
+    // Spec side
+    let spec_source = read_source(&spec_path);
+    let yaml = match YamlParser::new(&spec_source).parse() {
+        Ok(y) => y,
+        Err(e) => {
+            eprintln!("YAML parse error: {}", e);
+            process::exit(1);
+        }
+    };
+    let spec = match spec_parser::parse_spec(&yaml) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Spec parse error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    // Module side
+    let source = read_source(&pct_path);
+    let mut lexer = Lexer::new(&source);
+    let tokens = match lexer.tokenize() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Lexer error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut parser = Parser::new(tokens);
+    let sexprs = match parser.parse_program() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if sexprs.is_empty() {
+        eprintln!("No top-level expressions found");
+        process::exit(1);
+    }
+
+    let mut lowerer = Lowerer::new();
+    let module = match lowerer.lower_module(&sexprs[0]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Lowering error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let diags = verify::verify_spec(&spec, &module);
+    if !diags.is_empty() {
+        let formatted = diagnostics::format_diagnostics(&source, &diags);
+        eprint!("{}", formatted);
+    }
+
+    let error_count = diags.iter().filter(|d| d.kind == DiagnosticKind::Error).count();
+    let warning_count = diags.iter().filter(|d| d.kind == DiagnosticKind::Warning).count();
+
+    eprintln!(
+        "Spec '{}' vs module '{}': {} error(s), {} warning(s)",
+        spec.spec_id, module.name, error_count, warning_count
+    );
+
+    if error_count > 0 {
+        process::exit(1);
+    }
+}
+
+fn cmd_schema(args: &[String]) {
     let mut input: Option<PathBuf> = None;
+    let mut from = None;
+    let mut output = None;
+    let mut dialect = Dialect::Sqlite;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--dialect" => {
+                i += 1;
+                if i < args.len() {
+                    dialect = Dialect::parse(&args[i]).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        process::exit(1);
+                    });
+                }
+            }
+            "--from" => {
+                i += 1;
+                if i < args.len() {
+                    from = Some(PathBuf::from(&args[i]));
+                }
+            }
+            "-o" | "--output" => {
+                i += 1;
+                if i < args.len() {
+                    output = Some(PathBuf::from(&args[i]));
+                }
+            }
+            _ => {
+                if input.is_none() {
+                    input = Some(PathBuf::from(&args[i]));
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let input = input.unwrap_or_else(|| {
+        eprintln!("Expected input file path");
+        process::exit(1);
+    });
+    let module = load_module(&read_source(&input));
+
+    let sql = match from {
+        Some(old_path) => {
+            let old = load_module(&read_source(&old_path));
+            match schema::migration::diff(&old, &module, dialect) {
+                Ok(migration) => {
+                    for warning in &migration.warnings {
+                        eprintln!("warning: {}", warning);
+                    }
+                    migration.sql
+                }
+                Err(e) => {
+                    eprintln!("Migration error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => schema::render(&module, dialect),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(&path, &sql).unwrap_or_else(|e| {
+                eprintln!("Failed to write output: {}", e);
+                process::exit(1);
+            });
+            eprintln!("Generated {} ({} bytes)", path.display(), sql.len());
+        }
+        None => print!("{}", sql),
+    }
+}
+
+struct CompileArgs {
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    use_runtime: bool,
+    use_async: bool,
+    use_crate: bool,
+    use_ts: bool,
+}
+
+fn parse_compile_args(args: &[String]) -> CompileArgs {
+    let mut inputs = Vec::new();
     let mut output = None;
     let mut use_runtime = false;
+    let mut use_async = false;
+    let mut use_crate = false;
+    let mut use_ts = false;
 
     let mut i = 0;
     while i < args.len() {
@@ -409,6 +776,58 @@ fn parse_compile_args(args: &[String]) -> (PathBuf, Option<PathBuf>, bool) {
             "--runtime" => {
                 use_runtime = true;
             }
+            "--async" => {
+                use_async = true;
+            }
+            "--crate" => {
+                use_crate = true;
+            }
+            "--ts" => {
+                use_ts = true;
+            }
+            _ => {
+                inputs.push(PathBuf::from(&args[i]));
+            }
+        }
+        i += 1;
+    }
+
+    if inputs.is_empty() {
+        eprintln!("Expected input file path");
+        process::exit(1);
+    }
+
+    CompileArgs {
+        inputs,
+        output,
+        use_runtime,
+        use_async,
+        use_crate,
+        use_ts,
+    }
+}
+
+fn parse_generate_args(args: &[String]) -> (PathBuf, Option<PathBuf>, Option<PathBuf>, Option<PathBuf>) {
+    let mut input: Option<PathBuf> = None;
+    let mut output = None;
+    let mut update = None;
+    let mut base = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-o" | "--output" | "--update" | "--base" => {
+                let flag = args[i].as_str();
+                i += 1;
+                if i < args.len() {
+                    let path = Some(PathBuf::from(&args[i]));
+                    match flag {
+                        "--update" => update = path,
+                        "--base" => base = path,
+                        _ => output = path,
+                    }
+                }
+            }
             _ => {
                 if input.is_none() {
                     input = Some(PathBuf::from(&args[i]));
@@ -423,7 +842,7 @@ fn parse_compile_args(args: &[String]) -> (PathBuf, Option<PathBuf>, bool) {
         process::exit(1);
     });
 
-    (input, output, use_runtime)
+    (input, output, update, base)
 }
 
 fn parse_args(args: &[String]) -> (PathBuf, Option<PathBuf>) {
@@ -0,0 +1,91 @@
+//! Names every backend derives from Pact identifiers, and the type each store holds.
+
+use crate::ast::{Module, TypeDef};
+
+/// Convert a kebab-case name to snake_case, stripping ? and ! suffixes
+pub(crate) fn to_snake(name: &str) -> String {
+    name.replace(['-', '/'], "_").replace(['?', '!'], "")
+}
+
+/// Convert a kebab-case name to PascalCase, stripping ? and ! suffixes.
+/// Names already in PascalCase (`StockEntry`) are left as they are.
+pub(crate) fn to_pascal(name: &str) -> String {
+    name.split(['-', '_', '/'])
+        .map(|part| {
+            let part = part.replace(['?', '!'], "");
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Convert a snake_case or kebab-case name to camelCase
+pub(crate) fn to_camel(name: &str) -> String {
+    let pascal = to_pascal(name);
+    let mut chars = pascal.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_lowercase().to_string() + chars.as_str(),
+    }
+}
+
+/// Convert a kebab-case name to words for people: `not-found` → "Not Found"
+pub(crate) fn to_title(name: &str) -> String {
+    name.split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The module type a store holds: the type with a field `:unique-within` the store, or
+/// else the type named after it (`user-store` holds `User`). A generic type has no one
+/// item type, so stores never hold one.
+pub(crate) fn stored_type<'a>(store: &str, module: &'a Module) -> Option<&'a TypeDef> {
+    let stored = || module.types.iter().filter(|t| t.type_params.is_empty());
+    stored()
+        .find(|t| t.fields.iter().any(|f| f.unique_within.as_deref() == Some(store)))
+        .or_else(|| stored().find(|t| t.name == store_base_name(store)))
+}
+
+/// The name of the type a store holds: its module type when it has one, or else the
+/// name of a type defined elsewhere, after the store (`audit-store` → `Audit`)
+pub(crate) fn store_type_name(store: &str, module: &Module) -> String {
+    stored_type(store, module).map_or_else(|| store_base_name(store), |t| t.name.clone())
+}
+
+fn store_base_name(store: &str) -> String {
+    to_pascal(store.strip_suffix("-store").or_else(|| store.strip_suffix("_store")).unwrap_or(store))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_stores_hold_their_unique_type_first() {
+        let input = r#"(module inventory :version 1
+            (type StockEntry (field sku String :unique-within stock-store))
+            (type Stock (field id UUID))
+            (type User (field id UUID)))"#;
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        assert_eq!(store_type_name("stock-store", &module), "StockEntry");
+        assert_eq!(store_type_name("user-store", &module), "User");
+        assert_eq!(store_type_name("audit-store", &module), "Audit");
+        assert!(stored_type("audit-store", &module).is_none());
+        assert_eq!(to_pascal("stock-entry"), to_pascal("StockEntry"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ast::Number;
 use crate::lexer::{Token, TokenKind, Span};
 
 /// Concrete Syntax Tree node — generic S-expression structure.
@@ -26,6 +27,7 @@ pub enum AtomKind {
     Keyword(String),
     StringLit(String),
     IntLit(i64),
+    FloatLit(f64),
     BoolLit(bool),
     DurationLit(u64, crate::lexer::DurationUnit),
     RegexLit(String),
@@ -64,6 +66,15 @@ impl SExpr {
         }
     }
 
+    /// An integer or float literal, as used by `:min` and `:max`
+    pub fn as_number(&self) -> Option<Number> {
+        match &self.kind {
+            SExprKind::Atom(AtomKind::IntLit(n)) => Some(Number::Int(*n)),
+            SExprKind::Atom(AtomKind::FloatLit(n)) => Some(Number::Float(*n)),
+            _ => None,
+        }
+    }
+
     pub fn as_bool(&self) -> Option<bool> {
         match &self.kind {
             SExprKind::Atom(AtomKind::BoolLit(b)) => Some(*b),
@@ -181,6 +192,7 @@ impl Parser {
             TokenKind::Keyword(s) => AtomKind::Keyword(s),
             TokenKind::StringLit(s) => AtomKind::StringLit(s),
             TokenKind::IntLit(n) => AtomKind::IntLit(n),
+            TokenKind::FloatLit(n) => AtomKind::FloatLit(n),
             TokenKind::BoolLit(b) => AtomKind::BoolLit(b),
             TokenKind::DurationLit(n, u) => AtomKind::DurationLit(n, u),
             TokenKind::RegexLit(s) => AtomKind::RegexLit(s),
@@ -3,6 +3,11 @@ use super::route_analysis::RouteTable;
 /// Generate Cargo.toml for the scaffolded web project
 pub fn emit(table: &RouteTable) -> String {
     let package_name = table.module_name.replace('_', "-");
+    let sqlite = if table.sqlite_stores.is_empty() {
+        ""
+    } else {
+        "rusqlite = { version = \"0.32\", features = [\"bundled\"] }\n"
+    };
 
     format!(
         r#"[package]
@@ -17,8 +22,9 @@ tokio = {{ version = "1", features = ["full"] }}
 serde = {{ version = "1", features = ["derive"] }}
 serde_json = "1"
 uuid = {{ version = "1", features = ["v4"] }}
-"#,
+{sqlite}"#,
         name = package_name,
+        sqlite = sqlite,
     )
 }
 
@@ -37,6 +43,8 @@ mod tests {
                 needs_mut: true,
             }],
             routes: vec![],
+            async_fns: false,
+            sqlite_stores: vec![],
         }
     }
 
@@ -55,5 +63,14 @@ mod tests {
         assert!(output.contains("serde"));
         assert!(output.contains("serde_json"));
         assert!(output.contains("uuid"));
+        assert!(!output.contains("rusqlite"));
+    }
+
+    #[test]
+    fn test_cargo_emitter_sqlite_dependency() {
+        let mut table = test_table();
+        table.sqlite_stores = vec!["User".to_string()];
+        let output = emit(&table);
+        assert!(output.ends_with("rusqlite = { version = \"0.32\", features = [\"bundled\"] }\n"));
     }
 }
@@ -1,4 +1,5 @@
 use super::route_analysis::*;
+use crate::naming::to_title;
 
 struct Emitter {
     output: String,
@@ -95,12 +96,43 @@ fn emit_form_struct(e: &mut Emitter, route: &Route) {
     e.line(&format!("pub struct {} {{", struct_name));
     e.indent += 1;
     for field in &route.form_fields {
-        e.line(&format!("pub {}: String,", field.name));
+        // Fields a form may leave blank arrive as empty strings or not at all
+        if field.required {
+            e.line(&format!("pub {}: {},", field.name, field.enum_type.as_deref().unwrap_or("String")));
+        } else {
+            e.line("#[serde(default)]");
+            e.line(&format!("pub {}: Option<String>,", field.name));
+        }
     }
     e.indent -= 1;
     e.line("}");
 }
 
+/// Call a domain function with the route's store. Writes (`input`) take the store
+/// mutably. Async functions get a clone of the shared store handle, which locks per
+/// store call, so the handler never holds a lock across an `.await`.
+fn emit_domain_call(e: &mut Emitter, table: &RouteTable, sf: &str, fn_name: &str, arg: &str) {
+    let writes = !arg.starts_with('&');
+    match (table.async_fns, writes) {
+        (true, true) => {
+            e.line(&format!("let mut store = state.{}.clone();", sf));
+            e.line(&format!("let result = {}(&mut store, {}).await;", fn_name, arg));
+        }
+        (true, false) => {
+            e.line(&format!("let store = state.{}.clone();", sf));
+            e.line(&format!("let result = {}(&store, {}).await;", fn_name, arg));
+        }
+        (false, true) => {
+            e.line(&format!("let mut store = state.{}.lock().unwrap();", sf));
+            e.line(&format!("let result = {}(&mut *store, {});", fn_name, arg));
+        }
+        (false, false) => {
+            e.line(&format!("let store = state.{}.lock().unwrap();", sf));
+            e.line(&format!("let result = {}(&*store, {});", fn_name, arg));
+        }
+    }
+}
+
 fn store_field(store_type: &str) -> String {
     format!("{}_store", store_type.to_lowercase())
 }
@@ -108,7 +140,7 @@ fn store_field(store_type: &str) -> String {
 fn emit_list_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     let sf = store_field(&route.store_type);
     let plural = route.path.trim_start_matches('/');
-    let title = to_title_case(plural);
+    let title = to_title(plural);
     let display_fields = get_display_fields(route, table);
 
     e.line(&format!("pub async fn {}(State(state): State<AppState>) -> Html<String> {{", route.handler_name));
@@ -147,7 +179,7 @@ fn emit_list_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     // Build headers
     let mut headers = vec!["\"ID\"".to_string()];
     for field in &display_fields {
-        headers.push(format!("\"{}\"", to_title_case(field)));
+        headers.push(format!("\"{}\"", to_title(field)));
     }
     headers.push("\"Actions\"".to_string());
 
@@ -174,7 +206,7 @@ fn emit_list_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
 }
 
 fn emit_new_form_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
-    let type_title = to_title_case(&route.store_type.to_lowercase());
+    let type_title = to_title(&route.store_type.to_lowercase());
     let plural = route.store_type.to_lowercase() + "s";
     let fields = form_field_entries(&route.form_fields);
 
@@ -189,10 +221,10 @@ fn emit_new_form_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line("}");
 }
 
-fn emit_create_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
+fn emit_create_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     let sf = store_field(&route.store_type);
     let form_struct = format!("Create{}Form", route.store_type);
-    let type_title = to_title_case(&route.store_type.to_lowercase());
+    let type_title = to_title(&route.store_type.to_lowercase());
     let plural = route.store_type.to_lowercase() + "s";
 
     let fn_route = match &route.function {
@@ -219,15 +251,21 @@ fn emit_create_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line(&format!("let input = {} {{", input_struct));
     e.indent += 1;
     for field in &route.form_fields {
-        e.line(&format!("{name}: form.{name},", name = field.name));
+        if field.required {
+            e.line(&format!("{name}: form.{name},", name = field.name));
+        } else {
+            e.line(&format!(
+                "{name}: form.{name}.filter(|v| !v.is_empty()).and_then(|v| v.parse().ok()),",
+                name = field.name
+            ));
+        }
     }
     e.indent -= 1;
     e.line("};");
     e.blank();
 
     // Call domain function
-    e.line(&format!("let mut store = state.{}.lock().unwrap();", sf));
-    e.line(&format!("let result = {}(&mut *store, input);", fn_route.fn_name));
+    emit_domain_call(e, table, &sf, &fn_route.fn_name, "input");
     e.blank();
 
     // Match on result
@@ -278,9 +316,9 @@ fn emit_create_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line("}");
 }
 
-fn emit_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
+fn emit_show_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     let sf = store_field(&route.store_type);
-    let type_title = to_title_case(&route.store_type.to_lowercase());
+    let type_title = to_title(&route.store_type.to_lowercase());
     let plural = route.store_type.to_lowercase() + "s";
 
     let fn_route = match &route.function {
@@ -296,8 +334,7 @@ fn emit_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line(") -> impl IntoResponse {");
     e.indent += 1;
 
-    e.line(&format!("let store = state.{}.lock().unwrap();", sf));
-    e.line(&format!("let result = {}(&*store, &id);", fn_route.fn_name));
+    emit_domain_call(e, table, &sf, &fn_route.fn_name, "&id");
     e.blank();
 
     e.line("match result {");
@@ -308,9 +345,10 @@ fn emit_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
             let singular = route.store_type.to_lowercase();
             e.line(&format!("{}::Ok({}) => {{", fn_route.result_enum, singular));
             e.indent += 1;
+            let (ref_cells, ref_values) = ref_link_cells(route, &singular);
             e.line(&format!(
-                "let body = format!(r#\"<h1 class=\"text-2xl font-bold mb-6\">{} Details</h1><div class=\"bg-white shadow rounded-lg p-6\"><dl class=\"grid grid-cols-2 gap-4\"><dt class=\"text-sm font-medium text-gray-500\">ID</dt><dd class=\"text-sm text-gray-900\">{{}}</dd></dl><div class=\"mt-6 flex space-x-4\"><a href=\"/\" class=\"text-indigo-600 hover:underline\">Back to list</a><form method=\"POST\" action=\"/{}/{{}}/delete\" class=\"inline\"><button type=\"submit\" class=\"text-red-600 hover:underline\">Delete</button></form></div></div>\"#, {}.id, {}.id);",
-                type_title, plural, singular, singular,
+                "let body = format!(r#\"<h1 class=\"text-2xl font-bold mb-6\">{} Details</h1><div class=\"bg-white shadow rounded-lg p-6\"><dl class=\"grid grid-cols-2 gap-4\"><dt class=\"text-sm font-medium text-gray-500\">ID</dt><dd class=\"text-sm text-gray-900\">{{}}</dd>{}</dl><div class=\"mt-6 flex space-x-4\"><a href=\"/\" class=\"text-indigo-600 hover:underline\">Back to list</a><form method=\"POST\" action=\"/{}/{{}}/delete\" class=\"inline\"><button type=\"submit\" class=\"text-red-600 hover:underline\">Delete</button></form></div></div>\"#, {}.id, {}{}.id);",
+                type_title, ref_cells, plural, singular, ref_values, singular,
             ));
             e.line(&format!("Html(html_page(\"{} Details\", &body)).into_response()", type_title));
             e.indent -= 1;
@@ -320,7 +358,7 @@ fn emit_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
             let status_code = http_status_to_axum(variant.http_status);
             let pattern = variant_pattern(&fn_route.result_enum, variant);
             let error_detail = variant_error_detail(variant);
-            let vtitle = variant_to_title(tag);
+            let vtitle = to_title(tag);
 
             e.line(&format!("{} => {{", pattern));
             e.indent += 1;
@@ -382,7 +420,7 @@ fn emit_api_list_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line("}");
 }
 
-fn emit_api_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
+fn emit_api_show_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     let api_handler = match &route.api_handler_name {
         Some(h) => h.clone(),
         None => return,
@@ -402,8 +440,7 @@ fn emit_api_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line(") -> impl IntoResponse {");
     e.indent += 1;
 
-    e.line(&format!("let store = state.{}.lock().unwrap();", sf));
-    e.line(&format!("let result = {}(&*store, &id);", fn_route.fn_name));
+    emit_domain_call(e, table, &sf, &fn_route.fn_name, "&id");
     e.blank();
 
     e.line("match result {");
@@ -439,7 +476,7 @@ fn emit_api_show_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
     e.line("}");
 }
 
-fn emit_api_create_handler(e: &mut Emitter, route: &Route, _table: &RouteTable) {
+fn emit_api_create_handler(e: &mut Emitter, route: &Route, table: &RouteTable) {
     let api_handler = match &route.api_handler_name {
         Some(h) => h.clone(),
         None => return,
@@ -464,8 +501,7 @@ fn emit_api_create_handler(e: &mut Emitter, route: &Route, _table: &RouteTable)
     e.line(") -> impl IntoResponse {");
     e.indent += 1;
 
-    e.line(&format!("let mut store = state.{}.lock().unwrap();", sf));
-    e.line(&format!("let result = {}(&mut *store, input);", fn_route.fn_name));
+    emit_domain_call(e, table, &sf, &fn_route.fn_name, "input");
     e.blank();
 
     e.line("match result {");
@@ -525,13 +561,13 @@ fn variant_error_msg(variant: &RouteVariant) -> String {
     let tag = variant.tag.as_deref().unwrap_or("error");
     match &variant.payload_kind {
         PayloadKind::Map(fields) if fields.len() == 1 => {
-            format!("format!(\"{}: {{}}\", {})", tag_to_message(tag), fields[0].0)
+            format!("format!(\"{}: {{}}\", {})", to_title(tag), fields[0].0)
         }
         PayloadKind::List(_) => {
             "errors.iter().map(|e| format!(\"{}: {}\", e.field, e.message)).collect::<Vec<_>>().join(\", \")".to_string()
         }
         _ => {
-            format!("\"{}\".to_string()", tag_to_message(tag))
+            format!("\"{}\".to_string()", to_title(tag))
         }
     }
 }
@@ -543,7 +579,7 @@ fn variant_error_detail(variant: &RouteVariant) -> String {
             format!("format!(\"{{}}\", {})", fields[0].0)
         }
         _ => {
-            format!("\"{}\"", tag_to_message(tag))
+            format!("\"{}\"", to_title(tag))
         }
     }
 }
@@ -582,9 +618,44 @@ fn get_display_fields(route: &Route, table: &RouteTable) -> Vec<String> {
     vec![]
 }
 
+/// The `<dt>`/`<dd>` cells of a show page's `(ref T)` fields, and the format arguments
+/// filling them: a link to the referenced record when `T` has a show page.
+fn ref_link_cells(route: &Route, singular: &str) -> (String, String) {
+    let mut cells = String::new();
+    let mut values = String::new();
+    for link in &route.ref_links {
+        cells.push_str(&format!(
+            "<dt class=\"text-sm font-medium text-gray-500\">{}</dt><dd class=\"text-sm text-gray-900\">{{}}</dd>",
+            link.label
+        ));
+        let render = |id: &str| match &link.target_path {
+            Some(path) => format!(
+                "format!(r#\"<a href=\"{}/{{0}}\" class=\"text-indigo-600 hover:underline\">{{0}}</a>\"#, {})",
+                path, id
+            ),
+            None => format!("{}.to_string()", id),
+        };
+        let value = format!("{}.{}", singular, link.name);
+        if link.optional {
+            values.push_str(&format!("{}.map(|id| {}).unwrap_or_default(), ", value, render("id")));
+        } else {
+            values.push_str(&format!("{}, ", render(&value)));
+        }
+    }
+    (cells, values)
+}
+
 fn form_field_entries(fields: &[FormField]) -> String {
     fields.iter().map(|f| {
-        format!("(\"{}\", \"{}\", \"{}\")", f.name, f.label, f.input_type)
+        let options: Vec<String> = f.options.iter().map(|o| format!("\"{}\"", o)).collect();
+        format!(
+            "(\"{}\", \"{}\", \"{}\", {}, &[{}])",
+            f.name,
+            f.label,
+            f.input_type,
+            f.required,
+            options.join(", ")
+        )
     }).collect::<Vec<_>>().join(", ")
 }
 
@@ -601,42 +672,6 @@ fn http_status_to_axum(status: u16) -> String {
     }
 }
 
-fn tag_to_message(tag: &str) -> String {
-    tag.split('-')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + chars.as_str()
-                }
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
-fn variant_to_title(tag: &str) -> String {
-    tag_to_message(tag)
-}
-
-fn to_title_case(name: &str) -> String {
-    name.split(|c: char| c == '-' || c == '_')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + chars.as_str()
-                }
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -724,4 +759,14 @@ mod tests {
         assert!(output.contains("axum::Json(serde_json::to_value("));
         assert!(output.contains("serde_json::json!"));
     }
+
+    #[test]
+    fn test_async_handlers_await_without_holding_lock() {
+        let mut table = analyze_example();
+        table.async_fns = true;
+        let output = emit(&table);
+        assert!(output.contains("let mut store = state.user_store.clone();\n    let result = create_user(&mut store, input).await;"));
+        assert!(output.contains("let result = get_user_by_id(&store, &id).await;"));
+        assert!(!output.contains("(&mut *store, input)"));
+    }
 }
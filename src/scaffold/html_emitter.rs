@@ -1,9 +1,10 @@
 use super::route_analysis::RouteTable;
+use crate::naming::to_title;
 
 /// Generate the html.rs helper file (static Tailwind utilities).
 /// This is mostly identical to pact-web/src/html.rs but parameterized with the app title.
 pub fn emit(table: &RouteTable) -> String {
-    let app_title = to_title_case(&table.module_name);
+    let app_title = to_title(&table.module_name);
     let nav_links = build_nav_links(table);
 
     let mut out = String::new();
@@ -110,20 +111,45 @@ pub fn emit(table: &RouteTable) -> String {
     // html_form function
     out.push_str(concat!(
         "/// Render a Tailwind-styled form\n",
-        "pub fn html_form(action: &str, fields: &[(&str, &str, &str)]) -> String {\n",
+        "/// Fields are (name, label, input type, required, options); \"select\" fields offer their\n",
+        "/// options, with a blank first choice when the field may be left out.\n",
+        "pub fn html_form(action: &str, fields: &[(&str, &str, &str, bool, &[&str])]) -> String {\n",
         "    let field_html: String = fields\n",
         "        .iter()\n",
-        "        .map(|(name, label, input_type)| {\n",
+        "        .map(|(name, label, input_type, required, options)| {\n",
+        "            let class = \"block w-full rounded-md border-gray-300 shadow-sm focus:border-indigo-500 focus:ring-indigo-500 sm:text-sm px-3 py-2 border\";\n",
+        "            let required = if *required { \" required\" } else { \"\" };\n",
+        "            let input = if *input_type == \"select\" {\n",
+        "                let blank = if required.is_empty() { r#\"<option value=\"\"></option>\"# } else { \"\" };\n",
+        "                let options: String = options\n",
+        "                    .iter()\n",
+        "                    .map(|option| format!(r#\"<option value=\"{option}\">{option}</option>\"#, option = option))\n",
+        "                    .collect();\n",
+        "                format!(\n",
+        "                    r#\"<select name=\"{name}\" id=\"{name}\" class=\"{class}\"{required}>{blank}{options}</select>\"#,\n",
+        "                    name = name,\n",
+        "                    class = class,\n",
+        "                    required = required,\n",
+        "                    blank = blank,\n",
+        "                    options = options,\n",
+        "                )\n",
+        "            } else {\n",
+        "                format!(\n",
+        "                    r#\"<input type=\"{input_type}\" name=\"{name}\" id=\"{name}\" class=\"{class}\"{required}>\"#,\n",
+        "                    input_type = input_type,\n",
+        "                    name = name,\n",
+        "                    class = class,\n",
+        "                    required = required,\n",
+        "                )\n",
+        "            };\n",
         "            format!(\n",
         "                r#\"<div class=\"mb-4\">\n",
         "    <label for=\"{name}\" class=\"block text-sm font-medium text-gray-700 mb-1\">{label}</label>\n",
-        "    <input type=\"{input_type}\" name=\"{name}\" id=\"{name}\"\n",
-        "        class=\"block w-full rounded-md border-gray-300 shadow-sm focus:border-indigo-500 focus:ring-indigo-500 sm:text-sm px-3 py-2 border\"\n",
-        "        required>\n",
+        "    {input}\n",
         "</div>\"#,\n",
         "                name = name,\n",
         "                label = label,\n",
-        "                input_type = input_type,\n",
+        "                input = input,\n",
         "            )\n",
         "        })\n",
         "        .collect();\n",
@@ -172,29 +198,13 @@ pub fn emit(table: &RouteTable) -> String {
 fn build_nav_links(table: &RouteTable) -> String {
     let mut links = Vec::new();
     for store in &table.store_types {
-        let title = to_title_case(&store.plural);
+        let title = to_title(&store.plural);
         links.push(format!("(\"/\", \"{}\")", title));
-        links.push(format!("(\"/{}/new\", \"New {}\")", store.plural, to_title_case(&store.singular)));
+        links.push(format!("(\"/{}/new\", \"New {}\")", store.plural, to_title(&store.singular)));
     }
     links.join(", ")
 }
 
-fn to_title_case(name: &str) -> String {
-    name.split(|c: char| c == '-' || c == '_')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + chars.as_str()
-                }
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,6 +220,8 @@ mod tests {
                 needs_mut: true,
             }],
             routes: vec![],
+            async_fns: false,
+            sqlite_stores: vec![],
         }
     }
 
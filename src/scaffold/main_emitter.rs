@@ -1,20 +1,29 @@
 use super::route_analysis::*;
+use crate::naming::to_title;
 
 /// Generate main.rs for the scaffolded web project
 pub fn emit(table: &RouteTable) -> String {
     let mut out = String::new();
 
     // Imports
-    out.push_str("use std::sync::{Arc, Mutex};\n");
-    out.push_str("\n");
+    if !table.async_fns {
+        out.push_str("use std::sync::{Arc, Mutex};\n");
+        out.push('\n');
+    }
     out.push_str("use axum::routing::{get, post};\n");
     out.push_str("use axum::Router;\n");
-    out.push_str("use pact_runtime::prelude::InMemoryStore;\n");
-    out.push_str("\n");
+    let in_memory = table.store_types.iter().any(|s| !table.sqlite_stores.contains(&s.type_name));
+    if in_memory {
+        out.push_str("use pact_runtime::prelude::InMemoryStore;\n");
+    }
+    out.push('\n');
     out.push_str("mod generated;\n");
     out.push_str("mod handlers;\n");
     out.push_str("mod html;\n");
-    out.push_str("\n");
+    if !table.sqlite_stores.is_empty() {
+        out.push_str("mod sqlite_store;\n");
+    }
+    out.push('\n');
 
     // Use statements for generated types
     for store in &table.store_types {
@@ -23,36 +32,63 @@ pub fn emit(table: &RouteTable) -> String {
             table.module_name, store.type_name
         ));
     }
-    out.push_str("\n");
+    if table.async_fns {
+        out.push_str(&format!("use generated::{}::SharedStore;\n", table.module_name));
+    }
+    for type_name in &table.sqlite_stores {
+        out.push_str(&format!("use sqlite_store::Sqlite{}Store;\n", type_name));
+    }
+    out.push('\n');
 
     // AppState struct
     out.push_str("#[derive(Clone)]\n");
     out.push_str("pub struct AppState {\n");
     for store in &table.store_types {
         let field_name = format!("{}_store", store.singular);
-        out.push_str(&format!(
-            "    pub {}: Arc<Mutex<InMemoryStore<{}>>>,\n",
-            field_name, store.type_name
-        ));
+        let backing = if table.sqlite_stores.contains(&store.type_name) {
+            format!("Sqlite{}Store", store.type_name)
+        } else {
+            format!("InMemoryStore<{}>", store.type_name)
+        };
+        // Async handlers share a handle that locks per store call instead of a Mutex guard
+        let store_type = if table.async_fns {
+            format!("SharedStore<{}>", backing)
+        } else {
+            format!("Arc<Mutex<{}>>", backing)
+        };
+        out.push_str(&format!("    pub {}: {},\n", field_name, store_type));
     }
     out.push_str("}\n");
-    out.push_str("\n");
+    out.push('\n');
 
     // main function
     out.push_str("#[tokio::main]\n");
     out.push_str("async fn main() {\n");
 
     // State initialization
+    if !table.sqlite_stores.is_empty() {
+        out.push_str(&format!(
+            "    let db_path = std::env::var(\"DATABASE_PATH\").unwrap_or_else(|_| \"{}.db\".to_string());\n",
+            table.module_name
+        ));
+    }
     out.push_str("    let state = AppState {\n");
     for store in &table.store_types {
         let field_name = format!("{}_store", store.singular);
-        out.push_str(&format!(
-            "        {}: Arc::new(Mutex::new(InMemoryStore::new())),\n",
-            field_name
-        ));
+        let backing = if table.sqlite_stores.contains(&store.type_name) {
+            format!("Sqlite{}Store::open(&db_path).expect(\"open SQLite database\")", store.type_name)
+        } else {
+            "InMemoryStore::new()".to_string()
+        };
+        let init = if table.async_fns {
+            format!("SharedStore::new({})", backing)
+        } else {
+            format!("Arc::new(Mutex::new({}))", backing)
+        };
+        out.push_str(&format!("        {}: {},\n", field_name, init));
     }
     out.push_str("    };\n");
-    out.push_str("\n");
+    out.push('\n');
 
     // Router
     out.push_str("    let app = Router::new()\n");
@@ -121,10 +157,10 @@ pub fn emit(table: &RouteTable) -> String {
     }
 
     out.push_str("        .with_state(state);\n");
-    out.push_str("\n");
+    out.push('\n');
     out.push_str("    let listener = tokio::net::TcpListener::bind(\"0.0.0.0:3000\").await.unwrap();\n");
 
-    let app_title = to_title_case(&table.module_name);
+    let app_title = to_title(&table.module_name);
     out.push_str(&format!(
         "    eprintln!(\"{} listening on http://localhost:3000\");\n",
         app_title
@@ -135,22 +171,6 @@ pub fn emit(table: &RouteTable) -> String {
     out
 }
 
-fn to_title_case(name: &str) -> String {
-    name.split(|c: char| c == '-' || c == '_')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + chars.as_str()
-                }
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,4 +229,27 @@ mod tests {
         assert!(output.contains("axum::serve"));
         assert!(output.contains("User Service"));
     }
+
+    #[test]
+    fn test_main_async_uses_shared_store() {
+        let mut table = analyze_example();
+        table.async_fns = true;
+        let output = emit(&table);
+        assert!(output.contains("pub user_store: SharedStore<InMemoryStore<User>>,"));
+        assert!(output.contains("user_store: SharedStore::new(InMemoryStore::new()),"));
+        assert!(!output.contains("Mutex"));
+    }
+
+    #[test]
+    fn test_main_opens_sqlite_stores() {
+        let mut table = analyze_example();
+        table.sqlite_stores = vec!["User".to_string()];
+        let output = emit(&table);
+        assert!(output.contains("mod sqlite_store;\n"));
+        assert!(output.contains("use sqlite_store::SqliteUserStore;\n"));
+        assert!(output.contains("pub user_store: Arc<Mutex<SqliteUserStore>>,"));
+        assert!(output.contains("let db_path = std::env::var(\"DATABASE_PATH\").unwrap_or_else(|_| \"user_service.db\".to_string());"));
+        assert!(output.contains("user_store: Arc::new(Mutex::new(SqliteUserStore::open(&db_path).expect(\"open SQLite database\"))),"));
+        assert!(!output.contains("InMemoryStore"));
+    }
 }
@@ -3,6 +3,7 @@ pub mod html_emitter;
 pub mod cargo_emitter;
 pub mod main_emitter;
 pub mod handlers_emitter;
+pub mod sqlite_emitter;
 
 use std::fs;
 use std::path::Path;
@@ -17,15 +18,43 @@ pub struct ScaffoldOutput {
     pub html_rs: String,
     pub cargo_toml: String,
     pub generated_mod_rs: String,
+    /// `Sqlite{Type}Store`s, with `--store sqlite`
+    pub sqlite_store_rs: Option<String>,
+}
+
+/// Choices that change the shape of the scaffolded project
+#[derive(Debug, Clone, Default)]
+pub struct ScaffoldOptions {
+    /// Domain functions are generated with `--async`
+    pub async_fns: bool,
+    /// Back stores holding module types with SQLite instead of `InMemoryStore`
+    pub sqlite: bool,
 }
 
 /// Generate all scaffold files from an AST module
 pub fn scaffold(module: &Module) -> ScaffoldOutput {
-    let table = route_analysis::analyze(module);
+    scaffold_with(module, &ScaffoldOptions::default())
+}
+
+/// Like `scaffold`, for domain code generated with `--async`
+pub fn scaffold_async(module: &Module) -> ScaffoldOutput {
+    scaffold_with(module, &ScaffoldOptions { async_fns: true, ..Default::default() })
+}
+
+pub fn scaffold_with(module: &Module, options: &ScaffoldOptions) -> ScaffoldOutput {
+    let mut table = route_analysis::analyze(module);
+    table.async_fns = options.async_fns;
+    if options.sqlite {
+        table.sqlite_stores = sqlite_emitter::store_type_names(module, &table);
+    }
+    emit_scaffold(module, table)
+}
 
+fn emit_scaffold(module: &Module, table: RouteTable) -> ScaffoldOutput {
     let module_name = module.name.replace('-', "_");
 
     ScaffoldOutput {
+        sqlite_store_rs: (!table.sqlite_stores.is_empty()).then(|| sqlite_emitter::emit(module, &table)),
         main_rs: main_emitter::emit(&table),
         handlers_rs: handlers_emitter::emit(&table),
         html_rs: html_emitter::emit(&table),
@@ -66,6 +95,13 @@ pub fn write_scaffold(output: &ScaffoldOutput, output_dir: &Path) -> Result<(),
         eprintln!("  Created {}", path.display());
     }
 
+    if let Some(ref sqlite_store_rs) = output.sqlite_store_rs {
+        let path = src_dir.join("sqlite_store.rs");
+        fs::write(&path, sqlite_store_rs)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        eprintln!("  Created {}", path.display());
+    }
+
     Ok(())
 }
 
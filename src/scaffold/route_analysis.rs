@@ -1,4 +1,7 @@
 use crate::ast::*;
+use crate::naming::{self, to_pascal, to_snake, to_title};
+use crate::semantic::enums::{self, EnumDef};
+use crate::semantic::refs;
 
 /// HTTP method for a route
 #[derive(Debug, Clone, PartialEq)]
@@ -31,12 +34,23 @@ pub struct StoreInfo {
 pub struct FormField {
     pub name: String,         // "name", "email"
     pub label: String,        // "Name", "Email"
-    pub input_type: String,   // "text", "email"
+    pub input_type: String,   // "text", "email", "select"
+    pub enum_type: Option<String>, // "Role" for enum fields
+    pub options: Vec<String>, // the enum's variants, for "select"
     pub min_len: Option<i64>,
     pub max_len: Option<i64>,
     pub required: bool,
 }
 
+/// A `(ref T)` field on a show page, linking to the record it refers to
+#[derive(Debug, Clone)]
+pub struct RefLink {
+    pub name: String,                // "user_id"
+    pub label: String,               // "User Id"
+    pub optional: bool,
+    pub target_path: Option<String>, // "/users" when T has a show page
+}
+
 /// Metadata about a function-backed route
 #[derive(Debug, Clone)]
 pub struct FnRoute {
@@ -76,6 +90,7 @@ pub struct Route {
     pub function: Option<FnRoute>,
     pub store_type: String,        // "User"
     pub form_fields: Vec<FormField>,
+    pub ref_links: Vec<RefLink>,    // show routes only
 }
 
 /// The complete route table extracted from a module
@@ -84,10 +99,17 @@ pub struct RouteTable {
     pub module_name: String,
     pub store_types: Vec<StoreInfo>,
     pub routes: Vec<Route>,
+    /// Domain functions are `async` (generated with `--async`)
+    pub async_fns: bool,
+    /// Store types backed by a generated `Sqlite{Type}Store` (`--store sqlite`);
+    /// the others use `InMemoryStore`
+    pub sqlite_stores: Vec<String>,
 }
 
 /// Analyze an AST Module and produce a RouteTable
 pub fn analyze(module: &Module) -> RouteTable {
+    let enums = enums::collect_enums(module);
+    let module = &enums::name_enums(module, &enums);
     let module_name = module.name.replace('-', "_");
     let store_types = collect_store_types(module);
     let mut routes = Vec::new();
@@ -105,6 +127,7 @@ pub fn analyze(module: &Module) -> RouteTable {
             function: None,
             store_type: store.type_name.clone(),
             form_fields: vec![],
+            ref_links: vec![],
         });
 
         // GET /{plural}/new → new form
@@ -118,6 +141,7 @@ pub fn analyze(module: &Module) -> RouteTable {
             function: None,
             store_type: store.type_name.clone(),
             form_fields: vec![],
+            ref_links: vec![],
         });
 
         // POST /{plural}/{id}/delete → delete
@@ -131,12 +155,13 @@ pub fn analyze(module: &Module) -> RouteTable {
             function: None,
             store_type: store.type_name.clone(),
             form_fields: vec![],
+            ref_links: vec![],
         });
     }
 
     // For each function, generate routes based on param sources and effects
     for func in &module.functions {
-        let fn_routes = analyze_function(func, module, &store_types);
+        let fn_routes = analyze_function(func, module, &store_types, &enums);
         routes.extend(fn_routes);
     }
 
@@ -144,7 +169,32 @@ pub fn analyze(module: &Module) -> RouteTable {
     for route in &mut routes {
         if route.kind == RouteKind::NewForm {
             if let Some(typedef) = module.types.iter().find(|t| t.name == route.store_type) {
-                route.form_fields = extract_form_fields(typedef);
+                route.form_fields = extract_form_fields(typedef, &enums);
+            }
+        }
+    }
+
+    // Link the (ref T) fields of shown records to the show page of T
+    let show_paths: Vec<(String, String)> = routes
+        .iter()
+        .filter(|r| r.kind == RouteKind::Show)
+        .filter_map(|r| {
+            let store = store_types.iter().find(|s| s.type_name == r.store_type)?;
+            Some((r.store_type.clone(), format!("/{}", store.plural)))
+        })
+        .collect();
+    for route in &mut routes {
+        if route.kind == RouteKind::Show {
+            if let Some(typedef) = module.types.iter().find(|t| t.name == route.store_type) {
+                route.ref_links = refs::ref_fields(typedef)
+                    .into_iter()
+                    .map(|(field, target)| RefLink {
+                        name: to_snake(&field.name),
+                        label: to_title(&field.name),
+                        optional: matches!(field.type_expr, TypeExpr::Optional(_)),
+                        target_path: show_paths.iter().find(|(t, _)| t == target).map(|(_, p)| p.clone()),
+                    })
+                    .collect();
             }
         }
     }
@@ -153,11 +203,13 @@ pub fn analyze(module: &Module) -> RouteTable {
         module_name,
         store_types,
         routes,
+        async_fns: false,
+        sqlite_stores: Vec::new(),
     }
 }
 
 /// Analyze a single function and generate routes for it
-fn analyze_function(func: &FnDef, module: &Module, store_types: &[StoreInfo]) -> Vec<Route> {
+fn analyze_function(func: &FnDef, module: &Module, store_types: &[StoreInfo], enums: &[EnumDef]) -> Vec<Route> {
     let mut routes = Vec::new();
 
     let has_writes = func_has_writes(func, module);
@@ -209,7 +261,7 @@ fn analyze_function(func: &FnDef, module: &Module, store_types: &[StoreInfo]) ->
             // Extract form fields from the map param
             let form_fields = func.params.iter().find_map(|p| {
                 if let TypeExpr::Map(fields) = &p.type_expr {
-                    Some(extract_form_fields_from_map(fields, module))
+                    Some(extract_form_fields_from_map(fields, module, enums))
                 } else {
                     None
                 }
@@ -225,6 +277,7 @@ fn analyze_function(func: &FnDef, module: &Module, store_types: &[StoreInfo]) ->
                 function: Some(fn_route),
                 store_type: store.type_name.clone(),
                 form_fields,
+                ref_links: vec![],
             });
         }
     } else {
@@ -246,6 +299,7 @@ fn analyze_function(func: &FnDef, module: &Module, store_types: &[StoreInfo]) ->
                     function: Some(fn_route),
                     store_type: store.type_name.clone(),
                     form_fields: vec![],
+                    ref_links: vec![],
                 });
             }
         }
@@ -263,7 +317,11 @@ fn collect_store_types(module: &Module) -> Vec<StoreInfo> {
             if matches!(effect.kind, EffectKind::Sends) {
                 continue;
             }
-            let type_name = store_target_to_type(&effect.target);
+            // A store holding no type defined here has no item type to keep or show
+            let Some(type_def) = naming::stored_type(&effect.target, module) else {
+                continue;
+            };
+            let type_name = type_def.name.clone();
             let needs_mut = matches!(effect.kind, EffectKind::Writes);
 
             if let Some(existing) = stores.iter_mut().find(|s| s.type_name == type_name) {
@@ -306,7 +364,7 @@ fn find_fn_store_type<'a>(func: &FnDef, module: &Module, store_types: &'a [Store
                 if matches!(effect.kind, EffectKind::Sends) {
                     continue;
                 }
-                let type_name = store_target_to_type(&effect.target);
+                let type_name = naming::store_type_name(&effect.target, module);
                 if let Some(store) = store_types.iter().find(|s| s.type_name == type_name) {
                     return Some(store);
                 }
@@ -317,14 +375,17 @@ fn find_fn_store_type<'a>(func: &FnDef, module: &Module, store_types: &'a [Store
 }
 
 /// Extract form fields from a type definition (for new-form routes)
-fn extract_form_fields(typedef: &TypeDef) -> Vec<FormField> {
+fn extract_form_fields(typedef: &TypeDef, enums: &[EnumDef]) -> Vec<FormField> {
     typedef.fields.iter()
         .filter(|f| !f.generated && !f.immutable)
         .map(|f| {
+            let enum_def = find_enum(&f.type_expr, enums);
             let input_type = if f.format.as_deref() == Some("email") {
                 "email".to_string()
+            } else if enum_def.is_some() {
+                "select".to_string()
             } else {
-                match &f.type_expr {
+                match f.type_expr.non_optional() {
                     TypeExpr::Named(n) if n == "Int" => "number".to_string(),
                     TypeExpr::Named(n) if n == "Bool" => "checkbox".to_string(),
                     _ => "text".to_string(),
@@ -335,24 +396,30 @@ fn extract_form_fields(typedef: &TypeDef) -> Vec<FormField> {
                 name: to_snake(&f.name),
                 label: to_title(&f.name),
                 input_type,
+                enum_type: enum_def.map(|e| e.name.clone()),
+                options: enum_def.map(|e| e.variants.clone()).unwrap_or_default(),
                 min_len: f.min_len,
                 max_len: f.max_len,
-                required: true,
+                required: !matches!(f.type_expr, TypeExpr::Optional(_)) && f.default.is_none(),
             }
         })
         .collect()
 }
 
 /// Extract form fields from a Map type expression (for create routes)
-fn extract_form_fields_from_map(fields: &[(String, TypeExpr)], module: &Module) -> Vec<FormField> {
+fn extract_form_fields_from_map(fields: &[(String, TypeExpr)], module: &Module, enums: &[EnumDef]) -> Vec<FormField> {
     fields.iter().map(|(name, type_expr)| {
         // Try to find field constraints from type definitions
         let (min_len, max_len, format) = find_field_constraints(name, module);
+        let defaulted = module.types.iter().flat_map(|t| &t.fields).any(|f| f.name == *name && f.default.is_some());
+        let enum_def = find_enum(type_expr, enums);
 
         let input_type = if format.as_deref() == Some("email") {
             "email".to_string()
+        } else if enum_def.is_some() {
+            "select".to_string()
         } else {
-            match type_expr {
+            match type_expr.non_optional() {
                 TypeExpr::Named(n) if n == "Int" => "number".to_string(),
                 TypeExpr::Named(n) if n == "Bool" => "checkbox".to_string(),
                 _ => "text".to_string(),
@@ -363,13 +430,23 @@ fn extract_form_fields_from_map(fields: &[(String, TypeExpr)], module: &Module)
             name: to_snake(name),
             label: to_title(name),
             input_type,
+            enum_type: enum_def.map(|e| e.name.clone()),
+            options: enum_def.map(|e| e.variants.clone()).unwrap_or_default(),
             min_len,
             max_len,
-            required: true,
+            required: !matches!(type_expr, TypeExpr::Optional(_)) && !defaulted,
         }
     }).collect()
 }
 
+/// The enum a (named, possibly optional) field type refers to
+fn find_enum<'e>(type_expr: &TypeExpr, enums: &'e [EnumDef]) -> Option<&'e EnumDef> {
+    match type_expr.non_optional() {
+        TypeExpr::Named(n) => enums.iter().find(|e| e.name == *n),
+        _ => None,
+    }
+}
+
 /// Look up field constraints from type definitions in the module
 fn find_field_constraints(field_name: &str, module: &Module) -> (Option<i64>, Option<i64>, Option<String>) {
     for typedef in &module.types {
@@ -403,67 +480,31 @@ fn type_expr_to_rust_simple(type_expr: &TypeExpr) -> String {
             "UUID" => "Uuid".to_string(),
             "String" => "String".to_string(),
             "Int" => "i64".to_string(),
+            "Float" => "f64".to_string(),
             "Bool" => "bool".to_string(),
             other => other.to_string(),
         },
         TypeExpr::List(inner) => format!("Vec<{}>", type_expr_to_rust_simple(inner)),
+        TypeExpr::Optional(inner) => format!("Option<{}>", type_expr_to_rust_simple(inner)),
+        TypeExpr::Ref(target) => refs::id_type(target),
+        TypeExpr::MapOf(key, value) => format!(
+            "std::collections::HashMap<{}, {}>",
+            type_expr_to_rust_simple(key),
+            type_expr_to_rust_simple(value)
+        ),
+        TypeExpr::Generic(name, args) => {
+            let args: Vec<String> = args.iter().map(type_expr_to_rust_simple).collect();
+            format!("{}<{}>", name, args.join(", "))
+        }
         _ => "String".to_string(),
     }
 }
 
-/// Convert store target name to type name
-fn store_target_to_type(target: &str) -> String {
-    let name = target
-        .strip_suffix("-store")
-        .or_else(|| target.strip_suffix("_store"))
-        .unwrap_or(target);
-    to_pascal(name)
-}
-
 /// Simple pluralization (just adds "s")
 fn pluralize(s: &str) -> String {
     format!("{}s", s)
 }
 
-fn to_snake(name: &str) -> String {
-    name.replace('-', "_")
-        .replace('/', "_")
-        .replace('?', "")
-        .replace('!', "")
-}
-
-fn to_pascal(name: &str) -> String {
-    name.split(|c| c == '-' || c == '_' || c == '/')
-        .map(|part| {
-            let part = part.replace('?', "").replace('!', "");
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + &chars.as_str().to_lowercase()
-                }
-            }
-        })
-        .collect()
-}
-
-fn to_title(name: &str) -> String {
-    name.split(|c: char| c == '-' || c == '_')
-        .map(|part| {
-            let mut chars = part.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(c) => {
-                    let upper = c.to_uppercase().to_string();
-                    upper + chars.as_str()
-                }
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -519,6 +560,19 @@ mod tests {
         assert_eq!(delete.unwrap().path, "/users/{id}/delete");
     }
 
+    #[test]
+    fn test_helpers_are_not_routes() {
+        let module = parse_module(
+            "(module test :version 1
+                (type User (field id UUID :immutable :generated) (field name String))
+                (effect-set db-read [:reads user-store])
+                (defn get-name [u User] String (. u name)))"
+        );
+        let table = analyze(&module);
+        assert!(table.routes.iter().all(|r| r.function.is_none()));
+        assert!(table.routes.iter().all(|r| !r.handler_name.contains("get_name")));
+    }
+
     #[test]
     fn test_analyze_show_route() {
         let source = std::fs::read_to_string(
@@ -567,7 +621,7 @@ mod tests {
                     (field name String :min-len 1 :max-len 200)
                     (field email String :format :email)))"
         );
-        let fields = extract_form_fields(&module.types[0]);
+        let fields = extract_form_fields(&module.types[0], &[]);
         assert_eq!(fields.len(), 2);
         assert_eq!(fields[0].name, "name");
         assert_eq!(fields[0].input_type, "text");
@@ -577,6 +631,61 @@ mod tests {
         assert_eq!(fields[1].input_type, "email");
     }
 
+    #[test]
+    fn test_enum_fields_are_selects() {
+        let module = parse_module(
+            "(module test :version 1
+                (type User (field id UUID :immutable :generated) (field role (enum :admin :member)))
+                (effect-set db-read [:reads user-store])
+                (fn list-users :effects [db-read] (returns (union (ok Unit))) (ok true)))"
+        );
+        let table = analyze(&module);
+        let form = table.routes.iter().find(|r| r.kind == RouteKind::NewForm).unwrap();
+        assert_eq!(form.form_fields[0].input_type, "select");
+        assert_eq!(form.form_fields[0].enum_type.as_deref(), Some("Role"));
+        assert_eq!(form.form_fields[0].options, vec!["admin", "member"]);
+    }
+
+    #[test]
+    fn test_optional_and_defaulted_fields_are_not_required() {
+        let module = parse_module(
+            "(module test :version 1
+                (type User
+                    (field id UUID :immutable :generated)
+                    (field name String)
+                    (field bio (optional String))
+                    (field role (enum :admin :member) :default :member))
+                (effect-set db-read [:reads user-store])
+                (fn list-users :effects [db-read] (returns (union (ok Unit))) (ok true)))"
+        );
+        let table = analyze(&module);
+        let form = table.routes.iter().find(|r| r.kind == RouteKind::NewForm).unwrap();
+        let required: Vec<bool> = form.form_fields.iter().map(|f| f.required).collect();
+        assert_eq!(required, vec![true, false, false]);
+    }
+
+    #[test]
+    fn test_show_pages_link_references() {
+        let module = parse_module(
+            "(module blog :version 1
+                (type Author (field id UUID :immutable :generated))
+                (type Post (field id UUID :immutable :generated) (field author (ref Author)) (field tag (optional (ref Tag))))
+                (type Tag (field id UUID :immutable :generated))
+                (effect-set author-read [:reads author-store])
+                (effect-set post-read [:reads post-store])
+                (fn get-author :effects [author-read] (param id UUID :source http-path-param) (returns (union (ok Author))) (ok id))
+                (fn get-post :effects [post-read] (param id UUID :source http-path-param) (returns (union (ok Post))) (ok id)))"
+        );
+        let table = analyze(&module);
+        let show = table.routes.iter().find(|r| r.kind == RouteKind::Show && r.store_type == "Post").unwrap();
+        let links: Vec<(&str, bool, Option<&str>)> = show
+            .ref_links
+            .iter()
+            .map(|l| (l.name.as_str(), l.optional, l.target_path.as_deref()))
+            .collect();
+        assert_eq!(links, vec![("author", false, Some("/authors")), ("tag", true, None)]);
+    }
+
     #[test]
     fn test_route_variants() {
         let source = std::fs::read_to_string(
@@ -0,0 +1,530 @@
+use crate::ast::{FieldDef, Module, TypeDef, TypeExpr};
+use crate::schema::{self, Dialect, Table};
+use crate::semantic::refs;
+
+use super::route_analysis::RouteTable;
+
+struct Emitter {
+    output: String,
+    indent: usize,
+}
+
+impl Emitter {
+    fn new() -> Self {
+        Emitter { output: String::new(), indent: 0 }
+    }
+
+    fn line(&mut self, s: &str) {
+        if s.is_empty() {
+            self.output.push('\n');
+        } else {
+            for _ in 0..self.indent {
+                self.output.push_str("    ");
+            }
+            self.output.push_str(s);
+            self.output.push('\n');
+        }
+    }
+
+    fn blank(&mut self) {
+        self.output.push('\n');
+    }
+}
+
+/// How a field is written to and read from its column
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnKind {
+    /// UUIDs are stored as hyphenated text
+    Uuid,
+    /// String, Int, Float and Bool map directly onto SQLite values
+    Plain,
+    /// Enum variants are stored as their serialized name
+    Enum,
+    /// Lists, maps and other types are stored as JSON text
+    Json,
+}
+
+fn column_kind(type_expr: &TypeExpr) -> ColumnKind {
+    match type_expr {
+        TypeExpr::Named(n) if n == "UUID" => ColumnKind::Uuid,
+        TypeExpr::Named(n) if matches!(n.as_str(), "String" | "Int" | "Float" | "Bool") => ColumnKind::Plain,
+        TypeExpr::Enum(_) => ColumnKind::Enum,
+        // Typed ids are stored as the UUID they wrap
+        TypeExpr::Ref(_) => ColumnKind::Uuid,
+        // rusqlite maps `Option` of a plain value to a nullable column
+        TypeExpr::Optional(inner) if column_kind(inner) == ColumnKind::Plain => ColumnKind::Plain,
+        _ => ColumnKind::Json,
+    }
+}
+
+/// A store type that gets a `Sqlite{Type}Store`: its type is defined in the module,
+/// has a UUID `id` (the store is keyed by it) and is held by one of the module's stores.
+struct SqliteStore<'a> {
+    typedef: &'a TypeDef,
+    table: Table,
+}
+
+fn sqlite_stores<'a>(module: &'a Module, table: &RouteTable) -> Vec<SqliteStore<'a>> {
+    let schema = schema::build(module, Dialect::Sqlite);
+    table
+        .store_types
+        .iter()
+        .filter_map(|store| {
+            let typedef = module.types.iter().find(|t| t.name == store.type_name)?;
+            let has_id = typedef
+                .fields
+                .iter()
+                .any(|f| f.name == "id" && column_kind(&f.type_expr) == ColumnKind::Uuid);
+            let table = schema.tables.iter().find(|t| t.type_name == store.type_name)?;
+            has_id.then(|| SqliteStore { typedef, table: table.clone() })
+        })
+        .collect()
+}
+
+/// Type names that `emit` generates a SQLite store for; the rest stay in memory
+pub fn store_type_names(module: &Module, table: &RouteTable) -> Vec<String> {
+    sqlite_stores(module, table)
+        .iter()
+        .map(|s| s.typedef.name.clone())
+        .collect()
+}
+
+/// Generate sqlite_store.rs: a `Sqlite{Type}Store` implementing `Store<T>` over the table
+/// `pact schema --dialect sqlite` describes, plus a test of each against a temp-file database.
+pub fn emit(module: &Module, table: &RouteTable) -> String {
+    let stores = sqlite_stores(module, table);
+    let kinds: Vec<ColumnKind> = stores
+        .iter()
+        .flat_map(|s| s.typedef.fields.iter().map(|f| column_kind(&f.type_expr)))
+        .collect();
+    let mut e = Emitter::new();
+
+    e.line("// ============================================================");
+    e.line(&format!("// SQLite stores generated by pact scaffold --store sqlite from module: {}", module.name));
+    e.line("// ============================================================");
+    e.blank();
+    e.line("use std::path::Path;");
+    e.blank();
+    e.line("use pact_runtime::prelude::*;");
+    e.line("use rusqlite::{params, Connection, OptionalExtension, Row, ToSql};");
+    e.blank();
+    for store in &stores {
+        let mut names = vec![store.typedef.name.clone()];
+        for (_, target) in refs::ref_fields(store.typedef) {
+            let id_type = refs::id_type(target);
+            if !names.contains(&id_type) {
+                names.push(id_type);
+            }
+        }
+        let names = if names.len() == 1 { names.remove(0) } else { format!("{{{}}}", names.join(", ")) };
+        e.line(&format!("use crate::generated::{}::{};", table.module_name, names));
+    }
+    e.blank();
+
+    emit_helpers(&mut e, &kinds);
+
+    for store in &stores {
+        emit_store(&mut e, store);
+    }
+
+    emit_tests(&mut e, &stores);
+
+    e.output
+}
+
+fn emit_helpers(e: &mut Emitter, kinds: &[ColumnKind]) {
+    let conversion_failed = "rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))";
+    if kinds.contains(&ColumnKind::Uuid) {
+        e.line("fn uuid_column(row: &Row, idx: usize) -> rusqlite::Result<Uuid> {");
+        e.line("    let text: String = row.get(idx)?;");
+        e.line(&format!("    Uuid::parse_str(&text).map_err(|e| {})", conversion_failed));
+        e.line("}");
+        e.blank();
+    }
+    if kinds.contains(&ColumnKind::Enum) {
+        e.line("fn enum_text<T: serde::Serialize>(value: &T) -> String {");
+        e.line("    match serde_json::to_value(value) {");
+        e.line("        Ok(serde_json::Value::String(name)) => name,");
+        e.line("        other => panic!(\"enum did not serialize to a name: {:?}\", other),");
+        e.line("    }");
+        e.line("}");
+        e.blank();
+        e.line("fn enum_column<T: serde::de::DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {");
+        e.line("    let text: String = row.get(idx)?;");
+        e.line(&format!(
+            "    serde_json::from_value(serde_json::Value::String(text)).map_err(|e| {})",
+            conversion_failed
+        ));
+        e.line("}");
+        e.blank();
+    }
+    if kinds.contains(&ColumnKind::Json) {
+        e.line("fn json_text<T: serde::Serialize>(value: &T) -> String {");
+        e.line("    serde_json::to_string(value).expect(\"serializable column\")");
+        e.line("}");
+        e.blank();
+        e.line("fn json_column<T: serde::de::DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {");
+        e.line("    let text: String = row.get(idx)?;");
+        e.line(&format!("    serde_json::from_str(&text).map_err(|e| {})", conversion_failed));
+        e.line("}");
+        e.blank();
+    }
+    e.line("/// Map a failed write to `StoreError`: UNIQUE and PRIMARY KEY violations name the field,");
+    e.line("/// given as (column, field) pairs.");
+    e.line("fn store_error(e: rusqlite::Error, unique: &[(&str, &str)]) -> StoreError {");
+    e.indent += 1;
+    e.line("if let rusqlite::Error::SqliteFailure(err, Some(message)) = &e {");
+    e.indent += 1;
+    e.line("if err.code == rusqlite::ErrorCode::ConstraintViolation {");
+    e.indent += 1;
+    e.line("let column = message");
+    e.line("    .strip_prefix(\"UNIQUE constraint failed: \")");
+    e.line("    .and_then(|c| c.rsplit('.').next());");
+    e.line("if let Some((_, field)) = unique.iter().find(|(c, _)| Some(*c) == column) {");
+    e.line("    return StoreError::UniqueViolation { field: field.to_string() };");
+    e.line("}");
+    e.indent -= 1;
+    e.line("}");
+    e.indent -= 1;
+    e.line("}");
+    e.line("StoreError::Unavailable(e.to_string())");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+}
+
+fn emit_store(e: &mut Emitter, store: &SqliteStore) {
+    let type_name = &store.typedef.name;
+    let struct_name = format!("Sqlite{}Store", type_name);
+    let table = &store.table;
+    let fields: Vec<(&FieldDef, &schema::Column)> = store.typedef.fields.iter().zip(&table.columns).collect();
+    let columns: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+
+    e.line(&format!("/// `{}` rows in the `{}` table.", type_name, table.name));
+    e.line(&format!("pub struct {} {{", struct_name));
+    e.line("    conn: Connection,");
+    e.line("}");
+    e.blank();
+    e.line(&format!("impl {} {{", struct_name));
+    e.indent += 1;
+    e.line("const SCHEMA: &str = r#\"");
+    for line in schema::create_table_if_not_exists(table).lines() {
+        e.output.push_str(line);
+        e.output.push('\n');
+    }
+    e.line("\"#;");
+    e.line(&format!("const COLUMNS: &str = \"{}\";", columns.join(", ")));
+    e.blank();
+    e.line("/// Open (or create) the database at `path`, creating the table if it is missing.");
+    e.line("pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {");
+    e.line("    let conn = Connection::open(path)?;");
+    e.line("    conn.execute_batch(Self::SCHEMA)?;");
+    e.line(&format!("    Ok({} {{ conn }})", struct_name));
+    e.line("}");
+    e.blank();
+
+    e.line(&format!("fn from_row(row: &Row) -> rusqlite::Result<{}> {{", type_name));
+    e.indent += 1;
+    e.line(&format!("Ok({} {{", type_name));
+    e.indent += 1;
+    for (i, (field, column)) in fields.iter().enumerate() {
+        let read = match (column_kind(&field.type_expr), &field.type_expr) {
+            (ColumnKind::Uuid, TypeExpr::Ref(target)) => format!("{}(uuid_column(row, {})?)", refs::id_type(target), i),
+            (ColumnKind::Uuid, _) => format!("uuid_column(row, {})?", i),
+            (ColumnKind::Plain, _) => format!("row.get({})?", i),
+            (ColumnKind::Enum, _) => format!("enum_column(row, {})?", i),
+            (ColumnKind::Json, _) => format!("json_column(row, {})?", i),
+        };
+        e.line(&format!("{}: {},", column.name, read));
+    }
+    e.indent -= 1;
+    e.line("})");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+
+    e.line(&format!("fn find_one(&self, column: &str, value: &dyn ToSql) -> Option<{}> {{", type_name));
+    e.indent += 1;
+    e.line(&format!(
+        "let sql = format!(\"SELECT {{}} FROM {} WHERE {{}} = ?1\", Self::COLUMNS, column);",
+        table.name
+    ));
+    e.line("self.conn");
+    e.line("    .query_row(&sql, params![value], Self::from_row)");
+    e.line("    .optional()");
+    e.line("    .unwrap_or_else(|e| {");
+    e.line(&format!("        eprintln!(\"{}: {{}}\", e);", table.name));
+    e.line("        None");
+    e.line("    })");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+    e.line(&format!("fn select_all(&self) -> rusqlite::Result<Vec<{}>> {{", type_name));
+    e.line(&format!(
+        "    let mut stmt = self.conn.prepare(&format!(\"SELECT {{}} FROM {}\", Self::COLUMNS))?;",
+        table.name
+    ));
+    e.line("    let items = stmt.query_map([], Self::from_row)?.collect();");
+    e.line("    items");
+    e.line("}");
+
+    // Unique-field lookups
+    for (field, column) in fields.iter().filter(|(_, c)| c.unique) {
+        let (param, bind) = match column_kind(&field.type_expr) {
+            ColumnKind::Uuid => (format!("&{}", uuid_param_type(&field.type_expr)), "&value.to_string()".to_string()),
+            ColumnKind::Plain => (plain_param_type(&field.type_expr).to_string(), "&value".to_string()),
+            ColumnKind::Enum => ("&impl serde::Serialize".to_string(), "&enum_text(value)".to_string()),
+            ColumnKind::Json => ("&impl serde::Serialize".to_string(), "&json_text(value)".to_string()),
+        };
+        e.blank();
+        e.line(&format!("/// The row whose unique `{}` is `value`.", field.name));
+        e.line(&format!(
+            "pub fn find_by_{}(&self, value: {}) -> Option<{}> {{",
+            column.name, param, type_name
+        ));
+        e.line(&format!("    self.find_one(\"{}\", {})", column.name, bind));
+        e.line("}");
+    }
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+
+    let unique: Vec<String> = fields
+        .iter()
+        .filter(|(_, c)| c.unique || c.primary_key)
+        .map(|(f, c)| format!("(\"{}\", \"{}\")", c.name, f.name))
+        .collect();
+    let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+    let binds: Vec<String> = fields
+        .iter()
+        .map(|(f, c)| match column_kind(&f.type_expr) {
+            ColumnKind::Uuid => format!("item.{}.to_string()", c.name),
+            ColumnKind::Plain => format!("item.{}", c.name),
+            ColumnKind::Enum => format!("enum_text(&item.{})", c.name),
+            ColumnKind::Json => format!("json_text(&item.{})", c.name),
+        })
+        .collect();
+
+    e.line(&format!("impl Store<{}> for {} {{", type_name, struct_name));
+    e.indent += 1;
+    e.line(&format!("fn query_by_id(&self, id: &Uuid) -> Option<{}> {{", type_name));
+    e.line("    self.find_one(\"id\", &id.to_string())");
+    e.line("}");
+    e.blank();
+    e.line(&format!("fn insert(&mut self, item: {}) -> Result<{}, StoreError> {{", type_name, type_name));
+    e.indent += 1;
+    e.line("self.conn");
+    e.line("    .execute(");
+    e.line(&format!(
+        "        \"INSERT INTO {} ({}) VALUES ({})\",",
+        table.name,
+        columns.join(", "),
+        placeholders.join(", ")
+    ));
+    e.line(&format!("        params![{}],", binds.join(", ")));
+    e.line("    )");
+    e.line(&format!("    .map_err(|e| store_error(e, &[{}]))?;", unique.join(", ")));
+    e.line("Ok(item)");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+    e.line(&format!("fn list_all(&self) -> Vec<{}> {{", type_name));
+    e.indent += 1;
+    e.line("self.select_all().unwrap_or_else(|e| {");
+    e.line(&format!("    eprintln!(\"{}: {{}}\", e);", table.name));
+    e.line("    Vec::new()");
+    e.line("})");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+    e.line("fn delete(&mut self, id: &Uuid) -> bool {");
+    e.indent += 1;
+    e.line("self.conn");
+    e.line(&format!("    .execute(\"DELETE FROM {} WHERE id = ?1\", params![id.to_string()])", table.name));
+    e.line("    .map(|n| n > 0)");
+    e.line("    .unwrap_or_else(|e| {");
+    e.line(&format!("        eprintln!(\"{}: {{}}\", e);", table.name));
+    e.line("        false");
+    e.line("    })");
+    e.indent -= 1;
+    e.line("}");
+    e.indent -= 1;
+    e.line("}");
+    e.blank();
+}
+
+/// `Uuid`, or the typed id of a `(ref T)` field
+fn uuid_param_type(type_expr: &TypeExpr) -> String {
+    match type_expr {
+        TypeExpr::Ref(target) => refs::id_type(target),
+        _ => "Uuid".to_string(),
+    }
+}
+
+fn plain_param_type(type_expr: &TypeExpr) -> &'static str {
+    match type_expr {
+        TypeExpr::Named(n) if n == "Int" => "i64",
+        TypeExpr::Named(n) if n == "Float" => "f64",
+        TypeExpr::Named(n) if n == "Bool" => "bool",
+        TypeExpr::Optional(inner) => match plain_param_type(inner) {
+            "i64" => "Option<i64>",
+            "f64" => "Option<f64>",
+            "bool" => "Option<bool>",
+            _ => "Option<&str>",
+        },
+        _ => "&str",
+    }
+}
+
+/// A round-trip test per store whose fields all have obvious sample values:
+/// insert, reopen, look up, reject a duplicate id, delete.
+fn emit_tests(e: &mut Emitter, stores: &[SqliteStore]) {
+    let testable: Vec<&SqliteStore> = stores
+        .iter()
+        .filter(|s| s.typedef.fields.iter().all(|f| sample_value(f).is_some()))
+        .collect();
+    if testable.is_empty() {
+        return;
+    }
+
+    e.line("#[cfg(test)]");
+    e.line("mod tests {");
+    e.indent += 1;
+    e.line("use super::*;");
+    e.blank();
+    e.line("fn temp_db(name: &str) -> std::path::PathBuf {");
+    e.line("    std::env::temp_dir().join(format!(\"{}-{}.db\", name, Uuid::new_v4()))");
+    e.line("}");
+
+    for store in testable {
+        let type_name = &store.typedef.name;
+        let struct_name = format!("Sqlite{}Store", type_name);
+        let snake = store.table.name.clone();
+
+        e.blank();
+        e.line("#[test]");
+        e.line(&format!("fn test_{}_round_trip() {{", snake));
+        e.indent += 1;
+        e.line(&format!("let path = temp_db(\"{}\");", snake));
+        e.line(&format!("let item = {} {{", type_name));
+        e.indent += 1;
+        for (field, column) in store.typedef.fields.iter().zip(&store.table.columns) {
+            e.line(&format!("{}: {},", column.name, sample_value(field).unwrap_or_default()));
+        }
+        e.indent -= 1;
+        e.line("};");
+        e.line("let id = item.id;");
+        e.blank();
+        e.line(&format!("let mut store = {}::open(&path).unwrap();", struct_name));
+        e.line("store.insert(item.clone()).unwrap();");
+        e.line("drop(store);");
+        e.blank();
+        e.line(&format!("let mut store = {}::open(&path).unwrap();", struct_name));
+        e.line("assert_eq!(store.query_by_id(&id).map(|found| found.id), Some(id));");
+        e.line("assert_eq!(store.list_all().len(), 1);");
+        for (field, column) in store.typedef.fields.iter().zip(&store.table.columns) {
+            if column.unique {
+                let arg = match column_kind(&field.type_expr) {
+                    ColumnKind::Plain if plain_param_type(&field.type_expr) == "Option<&str>" => {
+                        format!("item.{}.as_deref()", column.name)
+                    }
+                    ColumnKind::Plain if plain_param_type(&field.type_expr) != "&str" => format!("item.{}", column.name),
+                    _ => format!("&item.{}", column.name),
+                };
+                e.line(&format!(
+                    "assert_eq!(store.find_by_{}({}).map(|found| found.id), Some(id));",
+                    column.name, arg
+                ));
+            }
+        }
+        e.line("assert!(matches!(store.insert(item.clone()), Err(StoreError::UniqueViolation { .. })));");
+        e.line("assert!(store.delete(&id));");
+        e.line("assert!(store.query_by_id(&id).is_none());");
+        e.blank();
+        e.line("drop(store);");
+        e.line("let _ = std::fs::remove_file(&path);");
+        e.indent -= 1;
+        e.line("}");
+    }
+
+    e.indent -= 1;
+    e.line("}");
+}
+
+/// A value satisfying the field's length, range and format constraints
+fn sample_value(field: &FieldDef) -> Option<String> {
+    if matches!(field.type_expr, TypeExpr::Optional(_)) {
+        return Some("None".to_string());
+    }
+    if let TypeExpr::Ref(target) = &field.type_expr {
+        return Some(format!("{}(Uuid::new_v4())", refs::id_type(target)));
+    }
+    let TypeExpr::Named(name) = &field.type_expr else {
+        return None;
+    };
+    match name.as_str() {
+        "UUID" => Some("Uuid::new_v4()".to_string()),
+        "Int" => Some(field.min.map_or("1".to_string(), |min| min.to_string())),
+        "Float" => Some(format!("{:?}", field.min.map_or(1.0, |min| min.as_f64()))),
+        "Bool" => Some("true".to_string()),
+        "String" if field.format.as_deref() == Some("email") => {
+            Some("format!(\"{}@example.com\", Uuid::new_v4())".to_string())
+        }
+        "String" => Some(format!("\"{}\".to_string()", "x".repeat(field.min_len.unwrap_or(1).max(1) as usize))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+    use crate::scaffold::route_analysis;
+
+    fn emit_example(name: &str) -> String {
+        let source = std::fs::read_to_string(
+            format!("{}/examples/{}", env!("CARGO_MANIFEST_DIR"), name),
+        ).unwrap();
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        emit(&module, &route_analysis::analyze(&module))
+    }
+
+    #[test]
+    fn test_sqlite_store_implements_store_trait() {
+        let output = emit_example("user-service.pct");
+        assert!(output.contains("use crate::generated::user_service::User;"));
+        assert!(output.contains("pub struct SqliteUserStore {"));
+        assert!(output.contains("CREATE TABLE IF NOT EXISTS user_store (\n    id TEXT NOT NULL PRIMARY KEY,"));
+        assert!(output.contains("impl Store<User> for SqliteUserStore {"));
+        assert!(output.contains("fn query_by_id(&self, id: &Uuid) -> Option<User> {"));
+        assert!(output.contains("\"INSERT INTO user_store (id, name, email) VALUES (?1, ?2, ?3)\","));
+        assert!(output.contains("params![item.id.to_string(), item.name, item.email],"));
+        assert!(output.contains(".map_err(|e| store_error(e, &[(\"id\", \"id\"), (\"email\", \"email\")]))?;"));
+        assert!(output.contains("fn list_all(&self) -> Vec<User> {"));
+        assert!(output.contains("fn delete(&mut self, id: &Uuid) -> bool {"));
+        assert!(output.contains("pub fn find_by_email(&self, value: &str) -> Option<User> {"));
+    }
+
+    #[test]
+    fn test_sqlite_store_comes_with_temp_file_test() {
+        let output = emit_example("user-service.pct");
+        assert!(output.contains("fn test_user_store_round_trip() {"));
+        assert!(output.contains("std::env::temp_dir()"));
+        assert!(output.contains("email: format!(\"{}@example.com\", Uuid::new_v4()),"));
+        assert!(output.contains("assert_eq!(store.find_by_email(&item.email).map(|found| found.id), Some(id));"));
+        assert!(output.contains("Err(StoreError::UniqueViolation { .. })"));
+    }
+
+    #[test]
+    fn test_only_stores_of_module_types_get_sqlite() {
+        let output = emit_example("inventory.pct");
+        assert!(output.contains("pub struct SqliteProductStore {"));
+        assert!(output.contains("pub struct SqliteReservationStore {"));
+        // stock-store holds no type defined in the module
+        assert!(!output.contains("SqliteStockStore"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::ast::FieldDef;
+use crate::lexer::Lexer;
+use crate::parser::{AtomKind, Parser, SExpr, SExprKind};
+
+use super::{quote, to_snake, Dialect};
+
+/// Translate a type invariant such as `(> (strlen name) 0)` into a CHECK condition.
+/// Supports comparisons, `and`/`or`/`not`, `strlen` and, on Postgres, `matches`.
+/// Returns `None` when the invariant names anything other than the type's fields
+/// or uses an operator the dialect cannot express.
+pub fn to_sql(raw: &str, fields: &[FieldDef], dialect: Dialect) -> Option<String> {
+    let tokens = Lexer::new(raw).tokenize().ok()?;
+    let sexprs = Parser::new(tokens).parse_program().ok()?;
+    match sexprs.as_slice() {
+        [sexpr] => condition(sexpr, fields, dialect),
+        _ => None,
+    }
+}
+
+fn condition(sexpr: &SExpr, fields: &[FieldDef], dialect: Dialect) -> Option<String> {
+    let items = sexpr.as_list()?;
+    let (head, args) = items.split_first()?;
+    match head.as_symbol()? {
+        op @ (">" | "<" | ">=" | "<=" | "=" | "!=") => {
+            let [left, right] = args else {
+                return None;
+            };
+            let op = if op == "!=" { "<>" } else { op };
+            Some(format!("{} {} {}", value(left, fields, dialect)?, op, value(right, fields, dialect)?))
+        }
+        join @ ("and" | "or") if !args.is_empty() => {
+            let parts = args
+                .iter()
+                .map(|a| condition(a, fields, dialect).map(|c| format!("({})", c)))
+                .collect::<Option<Vec<_>>>()?;
+            Some(parts.join(&format!(" {} ", join.to_uppercase())))
+        }
+        "not" => match args {
+            [inner] => Some(format!("NOT ({})", condition(inner, fields, dialect)?)),
+            _ => None,
+        },
+        "matches" if dialect == Dialect::Postgres => match args {
+            [target, pattern] => match &pattern.kind {
+                SExprKind::Atom(AtomKind::RegexLit(re)) => {
+                    Some(format!("{} ~ {}", value(target, fields, dialect)?, quote(re)))
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn value(sexpr: &SExpr, fields: &[FieldDef], dialect: Dialect) -> Option<String> {
+    match &sexpr.kind {
+        SExprKind::Atom(AtomKind::Symbol(name)) => {
+            fields.iter().find(|f| f.name == *name).map(|f| to_snake(&f.name))
+        }
+        SExprKind::Atom(AtomKind::IntLit(n)) => Some(n.to_string()),
+        SExprKind::Atom(AtomKind::FloatLit(n)) => Some(format!("{:?}", n)),
+        SExprKind::Atom(AtomKind::StringLit(s)) => Some(quote(s)),
+        SExprKind::Atom(AtomKind::BoolLit(b)) => Some(match (dialect, b) {
+            (Dialect::Sqlite, true) => "1".to_string(),
+            (Dialect::Sqlite, false) => "0".to_string(),
+            (Dialect::Postgres, b) => b.to_string().to_uppercase(),
+        }),
+        SExprKind::List(items) => match items.as_slice() {
+            [head, arg] if head.as_symbol() == Some("strlen") => {
+                Some(dialect.length(&value(arg, fields, dialect)?))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::TypeExpr;
+    use crate::lexer::Span;
+
+    fn fields() -> Vec<FieldDef> {
+        ["name", "min-age", "age", "active"]
+            .iter()
+            .map(|name| FieldDef {
+                name: name.to_string(),
+                type_expr: TypeExpr::Named("String".to_string()),
+                immutable: false,
+                generated: false,
+                min_len: None,
+                max_len: None,
+                min: None,
+                max: None,
+                scale: None,
+                default: None,
+                format: None,
+                unique_within: None,
+                extra_meta: Vec::new(),
+                span: Span::new(0, 0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_translates_supported_invariants() {
+        let f = fields();
+        assert_eq!(to_sql("(> (strlen name) 0)", &f, Dialect::Sqlite).unwrap(), "length(name) > 0");
+        assert_eq!(
+            to_sql("(and (>= age min-age) (!= name \"root\"))", &f, Dialect::Postgres).unwrap(),
+            "(age >= min_age) AND (name <> 'root')"
+        );
+        assert_eq!(to_sql("(not (= active true))", &f, Dialect::Sqlite).unwrap(), "NOT (active = 1)");
+        assert_eq!(to_sql("(matches name #/^a/)", &f, Dialect::Postgres).unwrap(), "name ~ '^a'");
+    }
+
+    #[test]
+    fn test_rejects_untranslatable_invariants() {
+        let f = fields();
+        assert!(to_sql("(matches name #/^a/)", &f, Dialect::Sqlite).is_none());
+        assert!(to_sql("(> (strlen nickname) 0)", &f, Dialect::Sqlite).is_none());
+        assert!(to_sql("(valid-email? name)", &f, Dialect::Postgres).is_none());
+    }
+}
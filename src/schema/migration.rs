@@ -0,0 +1,188 @@
+use crate::ast::Module;
+
+use super::{build, create_table, header, Dialect, Table};
+
+/// DDL taking the schema of one module version to the next
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// "M-0008-add-role-to-user"
+    pub name: String,
+    pub sql: String,
+    /// Steps that may fail on tables that already hold rows
+    pub warnings: Vec<String>,
+}
+
+/// Diff the schemas of `old` and `new`, where `new` declares `old` as its parent through
+/// `:parent-version`. Added columns use ADD COLUMN and dropped ones DROP COLUMN; tables
+/// whose existing columns or checks changed, or that SQLite cannot alter in place, are
+/// rebuilt, copying the columns both versions share.
+pub fn diff(old: &Module, new: &Module, dialect: Dialect) -> Result<Migration, String> {
+    if old.name != new.name {
+        return Err(format!("cannot migrate '{}' to a different module '{}'", old.name, new.name));
+    }
+    let (Some(old_version), Some(parent), Some(new_version)) = (old.version, new.parent_version, new.version)
+    else {
+        return Err(
+            "migrations need :version on the old module and :version and :parent-version on the new one"
+                .to_string(),
+        );
+    };
+    if parent != old_version {
+        return Err(format!(
+            "version {} declares parent version {}, not {}",
+            new_version, parent, old_version
+        ));
+    }
+
+    let name = match &new.delta {
+        Some(delta) => format!("M-{:04}-{}-{}", new_version, delta.operation, delta.target),
+        None => format!("M-{:04}-{}", new_version, new.name),
+    };
+
+    let before = build(old, dialect);
+    let after = build(new, dialect);
+    let mut steps: Vec<String> = Vec::new();
+    let mut warnings = Vec::new();
+
+    for table in &after.tables {
+        match before.tables.iter().find(|t| t.name == table.name) {
+            None => steps.push(create_table(table)),
+            Some(previous) => steps.extend(alter_table(previous, table, dialect, &mut warnings)),
+        }
+    }
+    for table in &before.tables {
+        if !after.tables.iter().any(|t| t.name == table.name) {
+            steps.push(format!("DROP TABLE {};\n", table.name));
+        }
+    }
+
+    let mut sql = header(new, dialect);
+    sql.push_str(&format!("-- Migration: {} (version {} -> {})\n\n", name, old_version, new_version));
+    if steps.is_empty() {
+        sql.push_str("-- no schema changes\n");
+    } else {
+        sql.push_str("BEGIN;\n\n");
+        sql.push_str(&steps.join("\n"));
+        sql.push_str("\nCOMMIT;\n");
+    }
+
+    Ok(Migration { name, sql, warnings })
+}
+
+fn alter_table(old: &Table, new: &Table, dialect: Dialect, warnings: &mut Vec<String>) -> Vec<String> {
+    let changed = new.checks != old.checks
+        || new.columns.iter().any(|c| old.columns.iter().any(|o| o.name == c.name && o != c));
+    let dropped: Vec<_> = old.columns.iter().filter(|o| !new.columns.iter().any(|c| c.name == o.name)).collect();
+    let added: Vec<_> = new.columns.iter().filter(|c| !old.columns.iter().any(|o| o.name == c.name)).collect();
+    // SQLite cannot add or drop key and unique columns in place, nor add NOT NULL ones without a default
+    let sqlite_rebuild = dialect == Dialect::Sqlite
+        && (dropped.iter().any(|c| c.primary_key || c.unique)
+            || added.iter().any(|c| c.primary_key || c.unique || (c.not_null && c.default.is_none())));
+
+    for column in &added {
+        if column.not_null && column.default.is_none() {
+            warnings.push(format!(
+                "adding NOT NULL column '{}' to '{}' fails if the table has rows; give it a default or backfill first",
+                column.name, new.name
+            ));
+        }
+    }
+
+    if changed || sqlite_rebuild {
+        let shared: Vec<&str> = new
+            .columns
+            .iter()
+            .filter(|c| old.columns.iter().any(|o| o.name == c.name))
+            .map(|c| c.name.as_str())
+            .collect();
+        let rebuilt = Table {
+            name: format!("{}__new", new.name),
+            ..new.clone()
+        };
+        return vec![format!(
+            "-- {} changed: rebuild it, copying the columns both versions share\n{}INSERT INTO {} ({}) SELECT {} FROM {};\nDROP TABLE {};\nALTER TABLE {} RENAME TO {};\n",
+            new.name,
+            create_table(&rebuilt),
+            rebuilt.name,
+            shared.join(", "),
+            shared.join(", "),
+            new.name,
+            new.name,
+            rebuilt.name,
+            new.name
+        )];
+    }
+
+    let mut steps = Vec::new();
+    for column in added {
+        steps.push(format!("ALTER TABLE {} ADD COLUMN {};\n", new.name, column.definition()));
+    }
+    for column in dropped {
+        steps.push(format!("ALTER TABLE {} DROP COLUMN {};\n", new.name, column.name));
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::tests::lower;
+
+    const V7: &str = r#"(module user-service :version 7
+        (type User
+            (field id UUID :immutable :generated)
+            (field name String :min-len 1 :max-len 200)
+            (field email String :unique-within user-store))
+        (effect-set db [:reads user-store :writes user-store]))"#;
+
+    fn v8(fields: &str) -> Module {
+        lower(&format!(
+            r#"(module user-service :version 8 :parent-version 7
+                :delta (add-field role "role-based access control")
+                (type User
+                    (field id UUID :immutable :generated)
+                    {}
+                    (field email String :unique-within user-store))
+                (effect-set db [:reads user-store :writes user-store]))"#,
+            fields
+        ))
+    }
+
+    #[test]
+    fn test_added_column_is_altered_in_place() {
+        let new = v8("(field name String :min-len 1 :max-len 200) (field role String)");
+        let migration = diff(&lower(V7), &new, Dialect::Postgres).unwrap();
+        assert_eq!(migration.name, "M-0008-add-field-role");
+        assert!(migration.sql.contains("-- Migration: M-0008-add-field-role (version 7 -> 8)"));
+        assert!(migration.sql.contains("BEGIN;\n\nALTER TABLE user_store ADD COLUMN role TEXT NOT NULL;\n\nCOMMIT;"));
+        assert_eq!(migration.warnings.len(), 1);
+        assert!(migration.warnings[0].starts_with("adding NOT NULL column 'role' to 'user_store'"));
+
+        // SQLite rejects ADD COLUMN ... NOT NULL without a default, so the table is rebuilt
+        let migration = diff(&lower(V7), &new, Dialect::Sqlite).unwrap();
+        assert!(migration.sql.contains("INSERT INTO user_store__new (id, name, email) SELECT id, name, email FROM user_store;"));
+        assert_eq!(migration.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_changed_constraint_rebuilds_table() {
+        let new = v8("(field name String :min-len 1 :max-len 100)");
+        let migration = diff(&lower(V7), &new, Dialect::Sqlite).unwrap();
+        assert!(migration.sql.contains("CREATE TABLE user_store__new ("));
+        assert!(migration.sql.contains("length(name) <= 100"));
+        assert!(migration.sql.contains(
+            "INSERT INTO user_store__new (id, name, email) SELECT id, name, email FROM user_store;\nDROP TABLE user_store;\nALTER TABLE user_store__new RENAME TO user_store;"
+        ));
+        assert!(migration.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_versions_must_chain() {
+        let unchanged = v8("(field name String :min-len 1 :max-len 200)");
+        let migration = diff(&lower(V7), &unchanged, Dialect::Sqlite).unwrap();
+        assert!(migration.sql.contains("-- no schema changes"));
+
+        let err = diff(&lower(&V7.replace(":version 7", ":version 6")), &unchanged, Dialect::Sqlite).unwrap_err();
+        assert_eq!(err, "version 8 declares parent version 7, not 6");
+    }
+}
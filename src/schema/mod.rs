@@ -0,0 +1,375 @@
+pub mod invariants;
+pub mod migration;
+
+use crate::ast::*;
+use crate::naming::{stored_type, to_snake};
+
+/// SQL dialect for generated DDL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+impl Dialect {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "sqlite" => Ok(Dialect::Sqlite),
+            "postgres" | "postgresql" => Ok(Dialect::Postgres),
+            other => Err(format!("unknown dialect '{}' (expected sqlite or postgres)", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "sqlite",
+            Dialect::Postgres => "postgres",
+        }
+    }
+
+    /// Character length of a text column
+    fn length(&self, column: &str) -> String {
+        match self {
+            Dialect::Sqlite => format!("length({})", column),
+            Dialect::Postgres => format!("char_length({})", column),
+        }
+    }
+}
+
+/// The tables implied by a module's stores
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub tables: Vec<Table>,
+    /// Stores holding no type defined in the module, which get no table
+    pub unmapped_stores: Vec<String>,
+}
+
+/// One table per store holding a type defined in the module
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,       // "user_store"
+    pub store: String,      // "user-store"
+    pub type_name: String,  // "User"
+    pub columns: Vec<Column>,
+    /// Table-level CHECK conditions from the type's invariants
+    pub checks: Vec<String>,
+    /// Invariants the dialect cannot express, kept as comments
+    pub skipped_invariants: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub sql_type: String,
+    pub primary_key: bool,
+    pub not_null: bool,
+    pub unique: bool,
+    pub default: Option<String>,
+    pub check: Option<String>,
+}
+
+impl Column {
+    /// The column as written in CREATE TABLE and ADD COLUMN
+    pub fn definition(&self) -> String {
+        let mut def = format!("{} {}", self.name, self.sql_type);
+        if self.not_null {
+            def.push_str(" NOT NULL");
+        }
+        if self.primary_key {
+            def.push_str(" PRIMARY KEY");
+        }
+        if self.unique {
+            def.push_str(" UNIQUE");
+        }
+        if let Some(ref default) = self.default {
+            def.push_str(&format!(" DEFAULT {}", default));
+        }
+        if let Some(ref check) = self.check {
+            def.push_str(&format!(" CHECK ({})", check));
+        }
+        def
+    }
+}
+
+/// Derive tables from the stores the module's effect sets read or write.
+/// Stores are matched to types by `:unique-within` annotations, or by name
+/// (`user-store` holds `User`).
+pub fn build(module: &Module, dialect: Dialect) -> Schema {
+    let mut tables: Vec<Table> = Vec::new();
+    let mut unmapped_stores: Vec<String> = Vec::new();
+
+    for effect in module.effect_sets.iter().flat_map(|es| &es.effects) {
+        if matches!(effect.kind, EffectKind::Sends)
+            || tables.iter().any(|t| t.store == effect.target)
+            || unmapped_stores.contains(&effect.target)
+        {
+            continue;
+        }
+        match stored_type(&effect.target, module) {
+            Some(typedef) => tables.push(build_table(&effect.target, typedef, dialect)),
+            None => unmapped_stores.push(effect.target.clone()),
+        }
+    }
+
+    Schema { tables, unmapped_stores }
+}
+
+fn build_table(store: &str, typedef: &TypeDef, dialect: Dialect) -> Table {
+    let primary = primary_key(typedef);
+    let columns = typedef
+        .fields
+        .iter()
+        .map(|field| {
+            let name = to_snake(&field.name);
+            let is_primary = primary == Some(field.name.as_str());
+            let type_expr = field.type_expr.non_optional();
+            let mut checks = Vec::new();
+            if let Some(min) = field.min_len {
+                checks.push(format!("{} >= {}", dialect.length(&name), min));
+            }
+            if let Some(max) = field.max_len {
+                checks.push(format!("{} <= {}", dialect.length(&name), max));
+            }
+            // Decimal text on SQLite compares as a number only once cast
+            let value = match type_expr {
+                TypeExpr::Named(n) if n == "Decimal" && dialect == Dialect::Sqlite => {
+                    format!("CAST({} AS REAL)", name)
+                }
+                _ => name.clone(),
+            };
+            if let Some(min) = field.min {
+                checks.push(format!("{} >= {}", value, min));
+            }
+            if let Some(max) = field.max {
+                checks.push(format!("{} <= {}", value, max));
+            }
+            if let TypeExpr::Enum(variants) = type_expr {
+                let values: Vec<String> = variants.iter().map(|v| quote(v)).collect();
+                checks.push(format!("{} IN ({})", name, values.join(", ")));
+            }
+            Column {
+                sql_type: sql_type(type_expr, dialect),
+                primary_key: is_primary,
+                not_null: !matches!(field.type_expr, TypeExpr::Optional(_)),
+                unique: !is_primary && field.unique_within.as_deref() == Some(store),
+                default: match &field.default {
+                    Some(value) => default_sql(value, type_expr, dialect),
+                    None => (is_primary && field.generated && dialect == Dialect::Postgres)
+                        .then(|| "gen_random_uuid()".to_string()),
+                },
+                check: (!checks.is_empty()).then(|| checks.join(" AND ")),
+                name,
+            }
+        })
+        .collect();
+
+    let mut checks = Vec::new();
+    let mut skipped_invariants = Vec::new();
+    for inv in &typedef.invariants {
+        match invariants::to_sql(&inv.raw, &typedef.fields, dialect) {
+            Some(check) => checks.push(check),
+            None => skipped_invariants.push(inv.raw.clone()),
+        }
+    }
+
+    Table {
+        name: to_snake(store),
+        store: store.to_string(),
+        type_name: typedef.name.clone(),
+        columns,
+        checks,
+        skipped_invariants,
+    }
+}
+
+/// The UUID field named `id`, else the first generated UUID field
+fn primary_key(typedef: &TypeDef) -> Option<&str> {
+    let is_uuid = |f: &&FieldDef| matches!(&f.type_expr, TypeExpr::Named(n) if n == "UUID");
+    typedef
+        .fields
+        .iter()
+        .filter(is_uuid)
+        .find(|f| f.name == "id")
+        .or_else(|| typedef.fields.iter().filter(is_uuid).find(|f| f.generated))
+        .map(|f| f.name.as_str())
+}
+
+fn sql_type(type_expr: &TypeExpr, dialect: Dialect) -> String {
+    let sql = match (type_expr, dialect) {
+        (TypeExpr::Named(n), Dialect::Postgres) if n == "UUID" => "UUID",
+        (TypeExpr::Named(n), Dialect::Sqlite) if n == "UUID" => "TEXT",
+        // A (ref T) holds the UUID id of a T
+        (TypeExpr::Ref(_), Dialect::Postgres) => "UUID",
+        (TypeExpr::Ref(_), Dialect::Sqlite) => "TEXT",
+        (TypeExpr::Named(n), _) if n == "String" => "TEXT",
+        (TypeExpr::Named(n), Dialect::Postgres) if n == "Int" => "BIGINT",
+        (TypeExpr::Named(n), Dialect::Sqlite) if n == "Int" => "INTEGER",
+        (TypeExpr::Named(n), Dialect::Postgres) if n == "Float" => "DOUBLE PRECISION",
+        (TypeExpr::Named(n), Dialect::Sqlite) if n == "Float" => "REAL",
+        (TypeExpr::Named(n), Dialect::Postgres) if n == "Decimal" => "NUMERIC",
+        // SQLite would round decimals to REAL; keep their exact text
+        (TypeExpr::Named(n), Dialect::Sqlite) if n == "Decimal" => "TEXT",
+        (TypeExpr::Named(n), Dialect::Postgres) if n == "Bool" => "BOOLEAN",
+        (TypeExpr::Named(n), Dialect::Sqlite) if n == "Bool" => "INTEGER",
+        (TypeExpr::Enum(_), _) => "TEXT",
+        // Lists, maps and other types are stored as JSON
+        (_, Dialect::Postgres) => "JSONB",
+        (_, Dialect::Sqlite) => "TEXT",
+    };
+    sql.to_string()
+}
+
+/// A field's `:default` as a SQL literal of its column type
+fn default_sql(value: &MetaValue, type_expr: &TypeExpr, dialect: Dialect) -> Option<String> {
+    let decimal = matches!(type_expr, TypeExpr::Named(n) if n == "Decimal");
+    match (value, dialect) {
+        (MetaValue::String(s) | MetaValue::Keyword(s), _) => Some(quote(s)),
+        (MetaValue::Int(n), Dialect::Sqlite) if decimal => Some(quote(&n.to_string())),
+        (MetaValue::Float(n), Dialect::Sqlite) if decimal => Some(quote(&n.to_string())),
+        (MetaValue::Int(n), _) => Some(n.to_string()),
+        (MetaValue::Float(n), _) => Some(n.to_string()),
+        (MetaValue::Bool(b), Dialect::Postgres) => Some(if *b { "TRUE" } else { "FALSE" }.to_string()),
+        (MetaValue::Bool(b), Dialect::Sqlite) => Some(if *b { "1" } else { "0" }.to_string()),
+        _ => None,
+    }
+}
+
+/// CREATE TABLE for one table, with invariants the dialect cannot check as comments
+pub fn create_table(table: &Table) -> String {
+    table_ddl(table, "CREATE TABLE")
+}
+
+/// Like `create_table`, but leaves an existing table alone (for stores opening a database)
+pub fn create_table_if_not_exists(table: &Table) -> String {
+    table_ddl(table, "CREATE TABLE IF NOT EXISTS")
+}
+
+fn table_ddl(table: &Table, create: &str) -> String {
+    let mut out = String::new();
+    for raw in &table.skipped_invariants {
+        out.push_str(&format!("-- invariant not checked by the database: {}\n", raw));
+    }
+    out.push_str(&format!("{} {} (\n", create, table.name));
+    let mut lines: Vec<String> = table.columns.iter().map(|c| format!("    {}", c.definition())).collect();
+    lines.extend(table.checks.iter().map(|c| format!("    CHECK ({})", c)));
+    out.push_str(&lines.join(",\n"));
+    out.push_str("\n);\n");
+    out
+}
+
+/// The full schema of a module: a header, then CREATE TABLE per store
+pub fn render(module: &Module, dialect: Dialect) -> String {
+    let schema = build(module, dialect);
+    let mut out = header(module, dialect);
+    for table in &schema.tables {
+        out.push('\n');
+        out.push_str(&format!("-- {} holds {}\n", table.store, table.type_name));
+        out.push_str(&create_table(table));
+    }
+    for store in &schema.unmapped_stores {
+        out.push('\n');
+        out.push_str(&format!("-- {}: holds no type defined in this module; no table generated\n", store));
+    }
+    out
+}
+
+fn header(module: &Module, dialect: Dialect) -> String {
+    let mut out = String::new();
+    out.push_str("-- ============================================================\n");
+    out.push_str(&format!("-- Generated from Pact module: {}\n", module.name));
+    if let Some(v) = module.version {
+        out.push_str(&format!("-- Version: {}\n", v));
+    }
+    out.push_str(&format!("-- Dialect: {}\n", dialect.as_str()));
+    out.push_str("-- ============================================================\n");
+    out
+}
+
+/// A SQL string literal
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    pub(super) fn lower(input: &str) -> Module {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_schema_for_user_service() {
+        let module = lower(include_str!("../../examples/user-service.pct"));
+        let sql = render(&module, Dialect::Sqlite);
+        assert!(sql.contains("-- Dialect: sqlite"));
+        assert!(sql.contains(
+            "CREATE TABLE user_store (\n    id TEXT NOT NULL PRIMARY KEY,\n    name TEXT NOT NULL CHECK (length(name) >= 1 AND length(name) <= 200),\n    email TEXT NOT NULL UNIQUE,\n    CHECK (length(name) > 0)\n);"
+        ));
+        // SQLite has no regex operator
+        assert!(sql.contains("-- invariant not checked by the database: (matches email #/.+@.+\\..+/)"));
+    }
+
+    #[test]
+    fn test_postgres_schema_for_user_service() {
+        let module = lower(include_str!("../../examples/user-service.pct"));
+        let sql = render(&module, Dialect::Postgres);
+        assert!(sql.contains("id UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),"));
+        assert!(sql.contains("CHECK (char_length(name) >= 1 AND char_length(name) <= 200)"));
+        assert!(sql.contains("    CHECK (email ~ '.+@.+\\..+')\n);"));
+        assert!(!sql.contains("not checked by the database"));
+    }
+
+    #[test]
+    fn test_unmapped_stores_and_column_types() {
+        let module = lower(
+            r#"(module m :version 1
+                (type Item
+                    (field id UUID)
+                    (field count Int :min 0)
+                    (field price Decimal :max 9.99)
+                    (field active Bool)
+                    (field kind (enum :a :b))
+                    (field tags (list String)))
+                (effect-set io [:reads item-store :writes audit-log :sends mailer]))"#,
+        );
+        let schema = build(&module, Dialect::Postgres);
+        assert_eq!(schema.tables.len(), 1);
+        assert_eq!(schema.unmapped_stores, vec!["audit-log".to_string()]);
+        let defs: Vec<String> = schema.tables[0].columns.iter().map(|c| c.definition()).collect();
+        assert_eq!(
+            defs,
+            vec![
+                "id UUID NOT NULL PRIMARY KEY",
+                "count BIGINT NOT NULL CHECK (count >= 0)",
+                "price NUMERIC NOT NULL CHECK (price <= 9.99)",
+                "active BOOLEAN NOT NULL",
+                "kind TEXT NOT NULL CHECK (kind IN ('a', 'b'))",
+                "tags JSONB NOT NULL",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optional_columns_and_defaults() {
+        let module = lower(
+            r#"(module m :version 1
+                (type Item
+                    (field id UUID)
+                    (field note (optional String))
+                    (field kind (enum :a :b) :default :a)
+                    (field active Bool :default true))
+                (effect-set io [:reads item-store]))"#,
+        );
+        let sql = render(&module, Dialect::Sqlite);
+        assert!(sql.contains("    note TEXT,\n"));
+        assert!(sql.contains("kind TEXT NOT NULL DEFAULT 'a' CHECK (kind IN ('a', 'b')),"));
+        assert!(sql.contains("active INTEGER NOT NULL DEFAULT 1"));
+        assert!(render(&module, Dialect::Postgres).contains("active BOOLEAN NOT NULL DEFAULT TRUE"));
+    }
+}
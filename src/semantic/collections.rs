@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Span;
+
+use super::operators::{bind_one, bind_pattern, type_of, type_text};
+
+/// The arguments each collection operation takes, and how many parameters
+/// the `(fn [...] ...)` passed to it binds
+fn signature(name: &str) -> Option<(&'static str, usize, usize)> {
+    match name {
+        "map" => Some(("a function and a list", 2, 1)),
+        "filter" => Some(("a predicate and a list", 2, 1)),
+        "fold" => Some(("a function, an initial value and a list", 3, 2)),
+        _ => None,
+    }
+}
+
+/// Check the collection operations of function bodies:
+/// - `map` and `filter` take a one-parameter fn and a list, `fold` a two-parameter fn
+///   (accumulator, element), an initial value and a list
+/// - what `map`, `filter`, `fold` and `for-each` iterate over is a list, when its type is known
+/// - the predicate of `filter` returns `Bool`, when its type is known
+pub fn check_collections(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for func in &module.functions {
+        let env: HashMap<String, TypeExpr> =
+            func.params.iter().map(|p| (p.name.clone(), p.type_expr.clone())).collect();
+        check_expr(&func.body, module, &env, &func.name, &mut diagnostics);
+    }
+    for helper in &module.helpers {
+        let env: HashMap<String, TypeExpr> =
+            helper.params.iter().map(|p| (p.name.clone(), p.type_expr.clone())).collect();
+        check_expr(&helper.body, module, &env, &helper.name, &mut diagnostics);
+    }
+    diagnostics
+}
+
+/// `env` inside the fn passed to a collection operation: its parameters take the
+/// element type of the list, and for `fold` the accumulator takes the initial value's type
+pub(super) fn lambda_env(
+    name: &str,
+    args: &[Expr],
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+) -> HashMap<String, TypeExpr> {
+    let mut inner = env.clone();
+    let Some(Expr::Lambda { params, .. }) = args.first() else {
+        return inner;
+    };
+    let types = match (name, args) {
+        ("map" | "filter", [_, list]) => vec![element_type(list, module, env)],
+        ("fold", [_, init, list]) => vec![type_of(init, module, env), element_type(list, module, env)],
+        _ => Vec::new(),
+    };
+    for (i, param) in params.iter().enumerate() {
+        match types.get(i).cloned().flatten() {
+            Some(t) => inner.insert(param.clone(), t),
+            None => inner.remove(param),
+        };
+    }
+    inner
+}
+
+/// `env` inside `(for-each [var list] ...)`
+pub(super) fn for_each_env(
+    var: &str,
+    list: &Expr,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+) -> HashMap<String, TypeExpr> {
+    let mut inner = env.clone();
+    match element_type(list, module, env) {
+        Some(t) => inner.insert(var.to_string(), t),
+        None => inner.remove(var),
+    };
+    inner
+}
+
+fn element_type(list: &Expr, module: &Module, env: &HashMap<String, TypeExpr>) -> Option<TypeExpr> {
+    match type_of(list, module, env)? {
+        TypeExpr::List(inner) => Some(*inner),
+        _ => None,
+    }
+}
+
+/// Whatever `list` is, when its type is known, must be a list
+fn check_iterated(
+    op: &str,
+    list: &Expr,
+    span: &Span,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if let Some(t) = type_of(list, module, env) {
+        if !matches!(t, TypeExpr::List(_)) {
+            diagnostics.push(Diagnostic::error(
+                format!("in '{}': '{}' iterates over {}, not a list", context, op, type_text(&t)),
+                Some(span.clone()),
+            ));
+        }
+    }
+}
+
+fn check_operation(
+    name: &str,
+    args: &[Expr],
+    span: &Span,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some((expected, arity, params)) = signature(name) else {
+        return;
+    };
+    if args.len() != arity {
+        diagnostics.push(Diagnostic::error(
+            format!("in '{}': '{}' takes {}, not {} arguments", context, name, expected, args.len()),
+            Some(span.clone()),
+        ));
+        return;
+    }
+    if let Expr::Lambda { params: given, body, .. } = &args[0] {
+        if given.len() != params {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "in '{}': the fn passed to '{}' takes {} parameter{}, not {}",
+                    context,
+                    name,
+                    params,
+                    if params == 1 { "" } else { "s" },
+                    given.len()
+                ),
+                Some(span.clone()),
+            ));
+            return;
+        }
+        if name == "filter" {
+            let inner = lambda_env(name, args, module, env);
+            if let Some(t) = type_of(body, module, &inner) {
+                if !matches!(&t, TypeExpr::Named(n) if n == "Bool") {
+                    diagnostics.push(Diagnostic::error(
+                        format!("in '{}': the predicate of 'filter' returns {}, not Bool", context, type_text(&t)),
+                        Some(span.clone()),
+                    ));
+                }
+            }
+        }
+    }
+    check_iterated(name, &args[arity - 1], span, module, env, context, diagnostics);
+}
+
+fn check_expr(
+    expr: &Expr,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match expr {
+        Expr::Call { name, args, span } => {
+            check_operation(name, args, span, module, env, context, diagnostics);
+            for arg in args {
+                match arg {
+                    Expr::Lambda { body, .. } => {
+                        let inner = lambda_env(name, args, module, env);
+                        check_expr(body, module, &inner, context, diagnostics);
+                    }
+                    _ => check_expr(arg, module, env, context, diagnostics),
+                }
+            }
+        }
+        Expr::ForEach { var, list, body, span } => {
+            check_iterated("for-each", list, span, module, env, context, diagnostics);
+            check_expr(list, module, env, context, diagnostics);
+            let inner = for_each_env(var, list, module, env);
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut inner = env.clone();
+            for param in params {
+                inner.remove(param);
+            }
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut inner = env.clone();
+            for (name, value) in bindings {
+                check_expr(value, module, &inner, context, diagnostics);
+                bind_one(name, value, module, &mut inner);
+            }
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::Match { expr: scrutinee, arms, .. } => {
+            check_expr(scrutinee, module, env, context, diagnostics);
+            let scrutinee_type = type_of(scrutinee, module, env);
+            for arm in arms {
+                let mut inner = env.clone();
+                bind_pattern(&arm.pattern, scrutinee_type.as_ref(), &mut inner);
+                check_expr(&arm.body, module, &inner, context, diagnostics);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check_expr(cond, module, env, context, diagnostics);
+            check_expr(then_branch, module, env, context, diagnostics);
+            check_expr(else_branch, module, env, context, diagnostics);
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => check_expr(expr, module, env, context, diagnostics),
+        Expr::Err { payload, .. } => check_expr(payload, module, env, context, diagnostics),
+        Expr::MapLit(entries, _) => {
+            for (_, value) in entries {
+                check_expr(value, module, env, context, diagnostics);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_expr(item, module, env, context, diagnostics);
+            }
+        }
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn errors(input: &str) -> Vec<String> {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        check_collections(&module).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn test_collection_operations_typecheck() {
+        let errs = errors(
+            r#"(module shop :version 1
+                (fn totals
+                    (param prices (list Int))
+                    (param count Int)
+                    (returns (union (ok Int)))
+                    (let [doubled (map (fn [p] (* p 2)) prices)
+                          cheap (filter (fn [p] (< p 10)) doubled)
+                          odd (filter (fn [p] (- p 1)) prices)
+                          bad (map (fn [a b] a) prices)
+                          none (fold (fn [acc p] (+ acc p)) 0 count)]
+                      (for-each [p count] (ok p)))))"#,
+        );
+        assert_eq!(
+            errs,
+            vec![
+                "in 'totals': the predicate of 'filter' returns Int, not Bool".to_string(),
+                "in 'totals': the fn passed to 'map' takes 1 parameter, not 2".to_string(),
+                "in 'totals': 'fold' iterates over Int, not a list".to_string(),
+                "in 'totals': 'for-each' iterates over Int, not a list".to_string(),
+            ]
+        );
+    }
+}
@@ -1,129 +1,415 @@
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 
 use crate::ast::*;
 use crate::diagnostics::Diagnostic;
 
+/// An operation of some kind on a store: `(Writes, "user-store")`
+pub type StoreEffect = (EffectKind, String);
+
 /// Check that function bodies only use effects declared in their effect annotations.
-/// For the prototype, we check that function calls to store operations (query, insert!, etc.)
-/// only happen in functions that declare the appropriate effect sets.
+/// A function performs its own store operations and the effects of the externs it calls,
+/// plus whatever the functions and helpers it calls perform (see [`infer_effects`]).
 pub fn check_effects(module: &Module) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
 
-    // Build effect set map: name -> set of (kind, target)
-    let mut effect_map: HashMap<String, HashSet<(EffectKind, String)>> = HashMap::new();
-    for es in &module.effect_sets {
-        let mut set = HashSet::new();
-        for eff in &es.effects {
-            set.insert((eff.kind.clone(), eff.target.clone()));
+    let effect_map = effect_map(module);
+    let inferred = infer_effects(module);
+
+    for func in &module.functions {
+        // Collect all effects this function is allowed to use
+        let allowed_effects = granted(&func.effects, &effect_map);
+
+        // What the body does itself or through helpers; the rest comes from a fn it calls
+        let calls = collect_calls(&func.body, module);
+        let own = inherited(&func.body, module, &calls, &inferred, |name| {
+            module.helpers.iter().any(|h| h.name == name)
+        });
+
+        for (kind, target) in sorted(&inferred[&func.name]) {
+            if allowed_effects.contains(&(kind.clone(), target.clone())) {
+                continue;
+            }
+            let through = calls.iter().find(|callee| {
+                module.functions.iter().any(|f| f.name == **callee)
+                    && inferred[callee.as_str()].contains(&(kind.clone(), target.clone()))
+            });
+            let message = match through {
+                Some(callee) if !own.contains(&(kind.clone(), target.clone())) => format!(
+                    "function '{}' calls '{}', which performs {:?} on '{}', but does not declare that effect",
+                    func.name, callee, kind, target
+                ),
+                _ => format!(
+                    "function '{}' performs {:?} on '{}' but does not declare that effect",
+                    func.name, kind, target
+                ),
+            };
+            diagnostics.push(Diagnostic::error(message, Some(func.span.clone())));
+        }
+    }
+
+    // Helpers declare no effects: a store operation in one would have nothing to run through.
+    // Only the body's own operations and the fns it calls count; other helpers report theirs.
+    for helper in &module.helpers {
+        let calls = collect_calls(&helper.body, module);
+        let own = inherited(&helper.body, module, &calls, &inferred, |name| {
+            module.functions.iter().any(|f| f.name == name)
+        });
+        for (kind, target) in sorted(&own) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "helper '{}' performs {:?} on '{}', but helpers are pure; do this in a fn that declares the effect",
+                    helper.name, kind, target
+                ),
+                Some(helper.span.clone()),
+            ));
         }
-        effect_map.insert(es.name.clone(), set);
     }
 
+    diagnostics
+}
+
+/// Effect set name → the effects it grants
+fn effect_map(module: &Module) -> HashMap<String, HashSet<StoreEffect>> {
+    module
+        .effect_sets
+        .iter()
+        .map(|es| (es.name.clone(), es.effects.iter().map(|e| (e.kind.clone(), e.target.clone())).collect()))
+        .collect()
+}
+
+fn granted(names: &[String], effect_map: &HashMap<String, HashSet<StoreEffect>>) -> HashSet<StoreEffect> {
+    names.iter().filter_map(|name| effect_map.get(name)).flatten().cloned().collect()
+}
+
+fn sorted(effects: &HashSet<StoreEffect>) -> Vec<StoreEffect> {
+    let mut effects: Vec<StoreEffect> = effects.iter().cloned().collect();
+    effects.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| format!("{:?}", a.0).cmp(&format!("{:?}", b.0))));
+    effects
+}
+
+/// The direct effects of `body`, plus those of the callees `follow` picks
+fn inherited(
+    body: &Expr,
+    module: &Module,
+    calls: &[String],
+    inferred: &HashMap<String, HashSet<StoreEffect>>,
+    follow: impl Fn(&str) -> bool,
+) -> HashSet<StoreEffect> {
+    let mut effects = HashSet::new();
+    collect_direct_effects(body, module, &mut effects);
+    for callee in calls.iter().filter(|c| follow(c)) {
+        effects.extend(inferred[callee.as_str()].iter().cloned());
+    }
+    effects
+}
+
+/// The effects every fn and helper of the module performs, by name, computed as a fixpoint
+/// over the call graph: each starts with its own store operations and extern effects,
+/// and takes on those of everything it calls until nothing changes. Calling `create-user`
+/// thus performs whatever `create-user` writes, through any depth of calls or recursion.
+/// Sending through a gateway is not recognized in bodies, so a fn's declared `:sends`
+/// effects are taken as performed.
+pub fn infer_effects(module: &Module) -> HashMap<String, HashSet<StoreEffect>> {
+    let effect_map = effect_map(module);
+    let bodies: Vec<(&str, &Expr)> = module
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), &f.body))
+        .chain(module.helpers.iter().map(|h| (h.name.as_str(), &h.body)))
+        .collect();
+
+    let mut inferred: HashMap<String, HashSet<StoreEffect>> = HashMap::new();
+    let mut calls: HashMap<&str, Vec<String>> = HashMap::new();
+    for (name, body) in &bodies {
+        let mut effects = HashSet::new();
+        collect_direct_effects(body, module, &mut effects);
+        inferred.entry(name.to_string()).or_default().extend(effects);
+        calls.entry(name).or_default().extend(collect_calls(body, module));
+    }
     for func in &module.functions {
-        // Collect all effects this function is allowed to use
-        let mut allowed_effects: HashSet<(EffectKind, String)> = HashSet::new();
-        for effect_name in &func.effects {
-            if let Some(effects) = effect_map.get(effect_name) {
-                allowed_effects.extend(effects.iter().cloned());
+        let sends = granted(&func.effects, &effect_map).into_iter().filter(|(kind, _)| *kind == EffectKind::Sends);
+        inferred.get_mut(&func.name).unwrap().extend(sends);
+    }
+
+    loop {
+        let mut changed = false;
+        for (name, callees) in &calls {
+            let gained: Vec<StoreEffect> = callees
+                .iter()
+                .flat_map(|callee| inferred[callee].iter())
+                .filter(|effect| !inferred[*name].contains(*effect))
+                .cloned()
+                .collect();
+            if !gained.is_empty() {
+                inferred.get_mut(*name).unwrap().extend(gained);
+                changed = true;
+            }
+        }
+        if !changed {
+            return inferred;
+        }
+    }
+}
+
+/// What `pact check --suggest-effects` reports for one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectSuggestion {
+    pub function: String,
+    /// The fewest effect sets granting everything the function performs, in module order
+    pub effects: Vec<String>,
+    /// Declared effect sets the suggestion leaves out
+    pub over_declared: Vec<String>,
+    /// Effects the function performs that no effect set grants
+    pub ungranted: Vec<(EffectKind, String)>,
+}
+
+/// Past this many candidate sets, trying every subset takes too long and a greedy
+/// cover is suggested instead
+const EXACT_COVER_LIMIT: usize = 12;
+
+/// The minimal `:effects` vector of each function. Among covers with the fewest sets,
+/// the one granting the fewest effects beyond those needed wins, then the one keeping
+/// most of what the function already declares. Above [`EXACT_COVER_LIMIT`] candidate
+/// sets the cover is greedy, so it may use more sets than the fewest.
+pub fn suggest_effects(module: &Module) -> Vec<EffectSuggestion> {
+    let effect_map = effect_map(module);
+    let inferred = infer_effects(module);
+
+    module
+        .functions
+        .iter()
+        .map(|func| {
+            let needed = &inferred[&func.name];
+            // Only sets granting something needed can be part of a minimal cover
+            let candidates: Vec<&EffectSetDef> = module
+                .effect_sets
+                .iter()
+                .filter(|es| effect_map[&es.name].iter().any(|e| needed.contains(e)))
+                .collect();
+            let grantable = granted(&candidates.iter().map(|es| es.name.clone()).collect::<Vec<_>>(), &effect_map);
+            let wanted: HashSet<StoreEffect> = needed.intersection(&grantable).cloned().collect();
+
+            let candidates = undominated(candidates, needed, &func.effects, &effect_map);
+            let effects = if candidates.len() <= EXACT_COVER_LIMIT {
+                exact_cover(&candidates, needed, &wanted, &func.effects, &effect_map)
+            } else {
+                greedy_cover(&candidates, needed, &wanted, &func.effects, &effect_map)
+            };
+
+            EffectSuggestion {
+                function: func.name.clone(),
+                over_declared: func.effects.iter().filter(|name| !effects.contains(name)).cloned().collect(),
+                ungranted: sorted(&needed.difference(&grantable).cloned().collect()),
+                effects,
             }
+        })
+        .collect()
+}
+
+/// Drops each candidate another one can stand in for in any cover at no cost: it grants
+/// all the needed effects the first does, no unneeded effect the first does not, and is
+/// declared if the first is. Of two that stand in for each other, the earlier stays.
+fn undominated<'a>(
+    candidates: Vec<&'a EffectSetDef>,
+    needed: &HashSet<StoreEffect>,
+    declared: &[String],
+    effect_map: &HashMap<String, HashSet<StoreEffect>>,
+) -> Vec<&'a EffectSetDef> {
+    let parts: Vec<(HashSet<StoreEffect>, HashSet<StoreEffect>, bool)> = candidates
+        .iter()
+        .map(|es| {
+            let grants = &effect_map[&es.name];
+            (
+                grants.intersection(needed).cloned().collect(),
+                grants.difference(needed).cloned().collect(),
+                declared.contains(&es.name),
+            )
+        })
+        .collect();
+    let stands_in = |b: usize, a: usize| {
+        parts[a].0.is_subset(&parts[b].0) && parts[b].1.is_subset(&parts[a].1) && (parts[b].2 || !parts[a].2)
+    };
+    candidates
+        .iter()
+        .enumerate()
+        .filter(|(a, _)| !(0..candidates.len()).any(|b| b != *a && stands_in(b, *a) && (b < *a || !stands_in(*a, b))))
+        .map(|(_, es)| *es)
+        .collect()
+}
+
+/// The best cover among every subset of `candidates`, of which there are at most
+/// 2^[`EXACT_COVER_LIMIT`]
+fn exact_cover(
+    candidates: &[&EffectSetDef],
+    needed: &HashSet<StoreEffect>,
+    wanted: &HashSet<StoreEffect>,
+    declared: &[String],
+    effect_map: &HashMap<String, HashSet<StoreEffect>>,
+) -> Vec<String> {
+    let mut best: Option<(usize, usize, usize, Vec<String>)> = None;
+    for mask in 0usize..(1 << candidates.len()) {
+        let chosen: Vec<String> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, es)| es.name.clone())
+            .collect();
+        let grants = granted(&chosen, effect_map);
+        if !wanted.is_subset(&grants) {
+            continue;
         }
+        let extra = grants.difference(needed).count();
+        let undeclared = chosen.iter().filter(|name| !declared.contains(name)).count();
+        let key = (chosen.len(), extra, undeclared);
+        if best.as_ref().is_none_or(|(n, e, u, _)| key < (*n, *e, *u)) {
+            best = Some((key.0, key.1, key.2, chosen));
+        }
+    }
+    best.map(|(_, _, _, chosen)| chosen).unwrap_or_default()
+}
 
-        // Check the body for effectful operations
-        let used_effects = collect_used_effects(&func.body);
-
-        for (kind, target) in &used_effects {
-            if !allowed_effects.contains(&(kind.clone(), target.clone())) {
-                diagnostics.push(Diagnostic::error(
-                    format!(
-                        "function '{}' performs {:?} on '{}' but does not declare that effect",
-                        func.name, kind, target
-                    ),
-                    Some(func.span.clone()),
-                ));
+/// Repeatedly takes the candidate granting the most wanted effects still missing,
+/// preferring fewer unneeded effects and then declared sets, and finally drops any set
+/// the others already cover
+fn greedy_cover(
+    candidates: &[&EffectSetDef],
+    needed: &HashSet<StoreEffect>,
+    wanted: &HashSet<StoreEffect>,
+    declared: &[String],
+    effect_map: &HashMap<String, HashSet<StoreEffect>>,
+) -> Vec<String> {
+    let mut missing = wanted.clone();
+    let mut chosen: Vec<usize> = Vec::new();
+    while !missing.is_empty() {
+        let gain = |es: &EffectSetDef| effect_map[&es.name].intersection(&missing).count();
+        // Reversed, so that of equally good candidates the earliest is the max
+        let best = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !chosen.contains(i))
+            .rev()
+            .max_by_key(|(_, es)| {
+                (
+                    gain(es),
+                    Reverse(effect_map[&es.name].difference(needed).count()),
+                    declared.contains(&es.name),
+                )
+            });
+        match best {
+            Some((i, es)) if gain(es) > 0 => {
+                missing.retain(|e| !effect_map[&es.name].contains(e));
+                chosen.push(i);
             }
+            _ => break,
         }
     }
+    // A later pick may cover everything an earlier one was taken for
+    for i in chosen.clone().into_iter().rev() {
+        let rest: Vec<String> = chosen.iter().filter(|j| **j != i).map(|j| candidates[*j].name.clone()).collect();
+        if wanted.is_subset(&granted(&rest, effect_map)) {
+            chosen.retain(|j| *j != i);
+        }
+    }
+    chosen.sort_unstable();
+    chosen.into_iter().map(|i| candidates[i].name.clone()).collect()
+}
 
-    diagnostics
+/// The fns and helpers of the module that `expr` calls, in order of first call
+fn collect_calls(expr: &Expr, module: &Module) -> Vec<String> {
+    let mut calls = Vec::new();
+    walk_calls(expr, &mut |name| {
+        let callable = module.functions.iter().any(|f| f.name == name) || module.helpers.iter().any(|h| h.name == name);
+        if callable && !calls.iter().any(|c| c == name) {
+            calls.push(name.to_string());
+        }
+    });
+    calls
 }
 
-/// Collect effects used in an expression.
+/// Collect the effects an expression performs itself.
 /// For the prototype, we recognize patterns like:
 /// - (query store-name ...) → Reads on store-name
 /// - (insert! store-name ...) → Writes on store-name
-fn collect_used_effects(expr: &Expr) -> HashSet<(EffectKind, String)> {
-    let mut effects = HashSet::new();
-    collect_effects_inner(expr, &mut effects);
-    effects
+/// - calls to externs → the effects of the sets they declare
+///
+/// Calls to fns and helpers are left to [`infer_effects`].
+fn collect_direct_effects(expr: &Expr, module: &Module, effects: &mut HashSet<StoreEffect>) {
+    walk_calls(expr, &mut |name| {
+        if let Some(ext) = module.externs.iter().find(|e| e.name == name) {
+            for effect_set in module.effect_sets.iter().filter(|es| ext.effects.contains(&es.name)) {
+                for eff in &effect_set.effects {
+                    effects.insert((eff.kind.clone(), eff.target.clone()));
+                }
+            }
+        }
+    });
+    collect_store_operations(expr, effects);
 }
 
-fn collect_effects_inner(expr: &Expr, effects: &mut HashSet<(EffectKind, String)>) {
-    match expr {
-        Expr::Call { name, args, .. } => {
-            // Recognize effectful operations
-            match name.as_str() {
-                "query" | "get" | "lookup" => {
-                    if let Some(Expr::Ref(target, _)) = args.first() {
-                        effects.insert((EffectKind::Reads, target.clone()));
-                    }
-                }
-                n if n.ends_with('!') => {
-                    // Convention: functions ending with ! are write operations
-                    // First arg is typically the store
-                    if let Some(Expr::Ref(target, _)) = args.first() {
-                        effects.insert((EffectKind::Writes, target.clone()));
-                    }
+fn collect_store_operations(expr: &Expr, effects: &mut HashSet<StoreEffect>) {
+    if let Expr::Call { name, args, .. } = expr {
+        // Recognize effectful operations
+        match name.as_str() {
+            "query" | "get" | "lookup" => {
+                if let Some(Expr::Ref(target, _)) = args.first() {
+                    effects.insert((EffectKind::Reads, target.clone()));
                 }
-                _ => {}
             }
-            for arg in args {
-                collect_effects_inner(arg, effects);
+            n if n.ends_with('!') => {
+                // Convention: functions ending with ! are write operations
+                // First arg is typically the store
+                if let Some(Expr::Ref(target, _)) = args.first() {
+                    effects.insert((EffectKind::Writes, target.clone()));
+                }
             }
+            _ => {}
         }
-        Expr::Let {
-            bindings, body, ..
-        } => {
-            for (_, value) in bindings {
-                collect_effects_inner(value, effects);
-            }
-            collect_effects_inner(body, effects);
+    }
+    for child in children(expr) {
+        collect_store_operations(child, effects);
+    }
+}
+
+/// Call `visit` with the name of every call in `expr`
+fn walk_calls(expr: &Expr, visit: &mut impl FnMut(&str)) {
+    if let Expr::Call { name, .. } = expr {
+        visit(name);
+    }
+    for child in children(expr) {
+        walk_calls(child, visit);
+    }
+}
+
+/// The subexpressions of `expr`. A lambda's body is one: its effects happen wherever
+/// it is applied, so they count toward the enclosing function.
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Call { args, .. } | Expr::ListLit(args, _) => args.iter().collect(),
+        Expr::Let { bindings, body, .. } => {
+            bindings.iter().map(|(_, value)| value).chain(std::iter::once(body.as_ref())).collect()
         }
         Expr::Match { expr, arms, .. } => {
-            collect_effects_inner(expr, effects);
-            for arm in arms {
-                collect_effects_inner(&arm.body, effects);
-            }
+            std::iter::once(expr.as_ref()).chain(arms.iter().map(|arm| &arm.body)).collect()
         }
         Expr::If {
             cond,
             then_branch,
             else_branch,
             ..
-        } => {
-            collect_effects_inner(cond, effects);
-            collect_effects_inner(then_branch, effects);
-            collect_effects_inner(else_branch, effects);
-        }
-        Expr::FieldAccess { expr, .. } => {
-            collect_effects_inner(expr, effects);
-        }
-        Expr::Ok(inner, _) => {
-            collect_effects_inner(inner, effects);
-        }
-        Expr::Err { payload, .. } => {
-            collect_effects_inner(payload, effects);
-        }
-        Expr::MapLit(entries, _) => {
-            for (_, val) in entries {
-                collect_effects_inner(val, effects);
-            }
-        }
+        } => vec![cond, then_branch, else_branch],
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => vec![expr],
+        Expr::Err { payload, .. } => vec![payload],
+        Expr::MapLit(entries, _) => entries.iter().map(|(_, value)| value).collect(),
+        Expr::Lambda { body, .. } => vec![body],
+        Expr::ForEach { list, body, .. } => vec![list, body],
         Expr::Ref(_, _)
         | Expr::Keyword(_, _)
         | Expr::StringLit(_, _)
         | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
         | Expr::BoolLit(_, _)
-        | Expr::Wildcard(_) => {}
+        | Expr::Wildcard(_) => Vec::new(),
     }
 }
 
@@ -180,4 +466,159 @@ mod tests {
         assert!(!errors.is_empty(), "expected effect error");
         assert!(errors[0].message.contains("Reads"));
     }
+
+    #[test]
+    fn test_effects_inside_lambdas() {
+        let input = r#"(module test :version 1
+            (fn save-all
+                :effects []
+                (param users (list User))
+                (returns (union (ok Unit :http 200)))
+                (for-each [u users] (insert! user-store u))))"#;
+        let diags = check(input);
+        assert!(
+            diags.iter().any(|d| d.kind == crate::diagnostics::DiagnosticKind::Error && d.message.contains("Writes")),
+            "expected the write inside for-each to need an effect, got: {:?}",
+            diags
+        );
+    }
+
+    #[test]
+    fn test_helpers_are_pure() {
+        let input = r#"(module test :version 1
+            (effect-set db-read [:reads user-store])
+            (defn find-user [id UUID] Bool (query user-store {:id id}))
+            (defn known? [id UUID] Bool (find-user id))
+            (fn get-thing
+                :effects []
+                (param id UUID)
+                (returns (union (ok Bool :http 200)))
+                (ok (known? id))))"#;
+        let errors: Vec<String> = check(input)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "function 'get-thing' performs Reads on 'user-store' but does not declare that effect".to_string(),
+                "helper 'find-user' performs Reads on 'user-store', but helpers are pure; do this in a fn that declares the effect".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extern_effects_are_required_of_callers() {
+        let input = r#"(module test :version 1
+            (effect-set db-read [:reads user-store])
+            (extern load-profile [UUID] Bool :effects [db-read])
+            (defn has-profile? [id UUID] Bool (load-profile id))
+            (fn get-thing
+                :effects []
+                (param id UUID)
+                (returns (union (ok Bool :http 200)))
+                (ok (has-profile? id))))"#;
+        let errors: Vec<String> = check(input)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "function 'get-thing' performs Reads on 'user-store' but does not declare that effect".to_string(),
+                "helper 'has-profile?' performs Reads on 'user-store', but helpers are pure; do this in a fn that declares the effect".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_effects_propagate_through_fn_calls() {
+        let input = r#"(module test :version 1
+            (effect-set db-write [:writes user-store])
+            (fn create-user
+                :effects [db-write]
+                (param name String)
+                (returns (union (ok Bool :http 201)))
+                (insert! user-store {:name name}))
+            (fn register
+                :effects []
+                (param name String)
+                (returns (union (ok Bool :http 201)))
+                (retry name))
+            (fn retry
+                :effects [db-write]
+                (param name String)
+                (returns (union (ok Bool :http 201)))
+                (if (= name "") (register name) (create-user name))))"#;
+        let errors: Vec<String> = check(input)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            errors,
+            vec!["function 'register' calls 'retry', which performs Writes on 'user-store', but does not declare that effect".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_suggest_minimal_effects() {
+        let input = r#"(module test :version 1
+            (effect-set session-read  [:reads session-store])
+            (effect-set session-write [:writes session-store :reads session-store])
+            (effect-set audit-log     [:writes audit-store])
+            (fn refresh
+                :effects [session-read session-write audit-log]
+                (param token String)
+                (returns (union (ok Bool :http 200)))
+                (let [s (query session-store {:token token})]
+                  (ok (rotate! session-store s))))
+            (fn peek
+                :effects []
+                (param token String)
+                (returns (union (ok Bool :http 200)))
+                (ok (query session-store {:token token}))))"#;
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        let suggestions = suggest_effects(&module);
+        assert_eq!(suggestions[0].effects, vec!["session-write".to_string()]);
+        assert_eq!(suggestions[0].over_declared, vec!["session-read".to_string(), "audit-log".to_string()]);
+        assert_eq!(suggestions[1].effects, vec!["session-read".to_string()]);
+        assert!(suggestions[1].over_declared.is_empty() && suggestions[1].ungranted.is_empty());
+    }
+
+    /// `n` stores in a ring, each effect set writing two neighbours: none stands in for
+    /// another, so every set is a candidate
+    fn ring_module(n: usize) -> Module {
+        let sets: String = (0..n)
+            .map(|i| format!("(effect-set w{} [:writes s{} :writes s{}])\n", i, i, (i + 1) % n))
+            .collect();
+        let writes: String = (0..n).map(|i| format!("(insert! s{} x) ", i)).collect();
+        let input = format!(
+            "(module test :version 1 {} (fn all :effects [] (param x Int) (returns (union (ok Int :http 200))) (let [_ [{}]] (ok x))))",
+            sets, writes
+        );
+        let tokens = Lexer::new(&input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    #[test]
+    fn test_suggest_effects_stays_bounded() {
+        // Small enough for the exact search: 3 sets cover a ring of 6
+        assert_eq!(suggest_effects(&ring_module(6))[0].effects, vec!["w0", "w2", "w4"]);
+
+        // Past the limit, and past 64 sets, the greedy cover still grants everything
+        for n in [26, 70] {
+            let module = ring_module(n);
+            let suggestion = &suggest_effects(&module)[0];
+            assert!(suggestion.ungranted.is_empty());
+            assert_eq!(suggestion.effects.len(), n / 2);
+            let needed = &infer_effects(&module)["all"];
+            assert!(needed.is_subset(&granted(&suggestion.effects, &effect_map(&module))));
+        }
+    }
 }
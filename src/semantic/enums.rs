@@ -0,0 +1,228 @@
+use crate::ast::*;
+use crate::naming::to_pascal;
+
+/// An enum declared inline by a field or param: `(field role (enum :admin :member :guest))`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDef {
+    /// "Role", or "UserRole" when "Role" is taken
+    pub name: String,
+    /// Variants as written, without the colon: "admin", "member", "guest"
+    pub variants: Vec<String>,
+}
+
+impl EnumDef {
+    /// The Rust variant for a keyword: "read-only" → "ReadOnly"
+    pub fn variant_ident(variant: &str) -> String {
+        to_pascal(variant)
+    }
+}
+
+/// Give every inline enum a name. An enum takes the PascalCase name of its field or
+/// param (`Role`); enums with the same name and variants are shared. When the name is
+/// a module type or an enum with other variants, it is qualified by its owner (`UserRole`).
+pub fn collect_enums(module: &Module) -> Vec<EnumDef> {
+    let mut enums: Vec<EnumDef> = Vec::new();
+    for typedef in &module.types {
+        for field in &typedef.fields {
+            add_enums(&typedef.name, &field.name, &field.type_expr, module, &mut enums);
+        }
+    }
+    for func in &module.functions {
+        for param in &func.params {
+            add_enums(&func.name, &param.name, &param.type_expr, module, &mut enums);
+        }
+    }
+    for helper in &module.helpers {
+        for param in &helper.params {
+            add_enums(&helper.name, &param.name, &param.type_expr, module, &mut enums);
+        }
+    }
+    enums
+}
+
+fn add_enums(owner: &str, name: &str, type_expr: &TypeExpr, module: &Module, enums: &mut Vec<EnumDef>) {
+    match type_expr {
+        TypeExpr::Enum(variants) => {
+            let plain = to_pascal(name);
+            let qualified = format!("{}{}", to_pascal(owner), plain);
+            for candidate in [plain, qualified] {
+                match enums.iter().find(|e| e.name == candidate) {
+                    Some(existing) if existing.variants == *variants => return,
+                    Some(_) => continue,
+                    None if module.types.iter().any(|t| t.name == candidate) => continue,
+                    None => {
+                        enums.push(EnumDef { name: candidate, variants: variants.clone() });
+                        return;
+                    }
+                }
+            }
+        }
+        TypeExpr::Map(fields) => {
+            for (key, inner) in fields {
+                add_enums(owner, key, inner, module, enums);
+            }
+        }
+        TypeExpr::List(inner) | TypeExpr::Optional(inner) => add_enums(owner, name, inner, module, enums),
+        TypeExpr::MapOf(key, value) => {
+            add_enums(owner, name, key, module, enums);
+            add_enums(owner, name, value, module, enums);
+        }
+        TypeExpr::Generic(_, args) => {
+            for arg in args {
+                add_enums(owner, name, arg, module, enums);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The module with each inline enum replaced by its name, so backends can treat
+/// enums like any other named type.
+pub fn name_enums(module: &Module, enums: &[EnumDef]) -> Module {
+    let mut named = module.clone();
+    for typedef in &mut named.types {
+        let owner = typedef.name.clone();
+        for field in &mut typedef.fields {
+            rename(&owner, &field.name, &mut field.type_expr, enums);
+        }
+    }
+    for func in &mut named.functions {
+        let owner = func.name.clone();
+        for param in &mut func.params {
+            rename(&owner, &param.name, &mut param.type_expr, enums);
+        }
+    }
+    for helper in &mut named.helpers {
+        let owner = helper.name.clone();
+        for param in &mut helper.params {
+            rename(&owner, &param.name, &mut param.type_expr, enums);
+        }
+    }
+    named
+}
+
+fn rename(owner: &str, name: &str, type_expr: &mut TypeExpr, enums: &[EnumDef]) {
+    match type_expr {
+        TypeExpr::Enum(variants) => {
+            let plain = to_pascal(name);
+            let qualified = format!("{}{}", to_pascal(owner), plain);
+            if let Some(e) = [plain, qualified]
+                .iter()
+                .find_map(|n| enums.iter().find(|e| e.name == *n && e.variants == *variants))
+            {
+                *type_expr = TypeExpr::Named(e.name.clone());
+            }
+        }
+        TypeExpr::Map(fields) => {
+            for (key, inner) in fields {
+                rename(owner, key, inner, enums);
+            }
+        }
+        TypeExpr::List(inner) | TypeExpr::Optional(inner) => rename(owner, name, inner, enums),
+        TypeExpr::MapOf(key, value) => {
+            rename(owner, name, key, enums);
+            rename(owner, name, value, enums);
+        }
+        TypeExpr::Generic(_, args) => {
+            for arg in args {
+                rename(owner, name, arg, enums);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The enum a `match` with keyword arms is over. The scrutinee decides when it is an
+/// enum field (`(. user role)`) or param; otherwise the one enum declaring every keyword.
+pub fn match_enum<'e>(
+    scrutinee: &Expr,
+    arms: &[MatchArm],
+    module: &Module,
+    func: &FnDef,
+    enums: &'e [EnumDef],
+) -> Option<&'e EnumDef> {
+    let keywords: Vec<&str> = arms
+        .iter()
+        .filter_map(|arm| match &arm.pattern {
+            Pattern::Keyword(kw, _) => Some(kw.as_str()),
+            _ => None,
+        })
+        .collect();
+    if keywords.is_empty() {
+        return None;
+    }
+
+    let declared = match scrutinee {
+        Expr::FieldAccess { field, .. } => module
+            .types
+            .iter()
+            .flat_map(|t| &t.fields)
+            .find(|f| f.name == *field)
+            .map(|f| &f.type_expr),
+        Expr::Ref(name, _) => func.params.iter().find(|p| p.name == *name).map(|p| &p.type_expr),
+        _ => None,
+    };
+    let by_type = declared.and_then(|t| match t {
+        TypeExpr::Named(n) => enums.iter().find(|e| e.name == *n),
+        TypeExpr::Enum(variants) => enums.iter().find(|e| e.variants == *variants),
+        _ => None,
+    });
+    if by_type.is_some() {
+        return by_type;
+    }
+
+    let mut candidates = enums
+        .iter()
+        .filter(|e| keywords.iter().all(|kw| e.variants.iter().any(|v| v == kw)));
+    match (candidates.next(), candidates.next()) {
+        (Some(e), None) => Some(e),
+        _ => None,
+    }
+}
+
+/// The one enum declaring a keyword used as a value, e.g. `{:role :member}`
+pub fn keyword_enum<'e>(keyword: &str, enums: &'e [EnumDef]) -> Option<&'e EnumDef> {
+    let mut candidates = enums.iter().filter(|e| e.variants.iter().any(|v| v == keyword));
+    match (candidates.next(), candidates.next()) {
+        (Some(e), None) => Some(e),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn lower(input: &str) -> Module {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    #[test]
+    fn test_enums_are_named_after_their_field() {
+        let module = lower(
+            r#"(module m :version 1
+                (type User (field role (enum :admin :member)) (field status (enum :active :banned)))
+                (type Team (field role (enum :admin :member)) (field status (enum :open :closed)))
+                (fn create-user
+                    (param input {:role (enum :admin :member)})
+                    (returns (union (ok Unit)))
+                    (ok input)))"#,
+        );
+        let enums = collect_enums(&module);
+        let names: Vec<&str> = enums.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Role", "Status", "TeamStatus"]);
+
+        let named = name_enums(&module, &enums);
+        assert!(matches!(&named.types[1].fields[1].type_expr, TypeExpr::Named(n) if n == "TeamStatus"));
+        assert!(matches!(
+            &named.functions[0].params[0].type_expr,
+            TypeExpr::Map(fields) if matches!(&fields[0].1, TypeExpr::Named(n) if n == "Role")
+        ));
+        assert_eq!(EnumDef::variant_ident("read-only"), "ReadOnly");
+    }
+}
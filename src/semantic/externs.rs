@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use crate::ast::*;
+
+/// Whether `body` calls an extern, directly or through helpers. Backends pass the
+/// host's `Externs` implementation to each function and helper that does.
+pub fn needs_externs(body: &Expr, module: &Module) -> bool {
+    reaches_extern(body, module, true, &mut HashSet::new())
+}
+
+/// Like [`needs_externs`], but leaves out externs that send through a gateway, which
+/// backends with gateways call on the gateway instead.
+pub fn needs_host_externs(body: &Expr, module: &Module) -> bool {
+    reaches_extern(body, module, false, &mut HashSet::new())
+}
+
+/// The gateway an extern sends through: the `:sends` target of one of its effect sets.
+/// `http-response` is a function's own reply, not a gateway.
+pub fn sends_through<'m>(ext: &ExternDef, module: &'m Module) -> Option<&'m str> {
+    module
+        .effect_sets
+        .iter()
+        .filter(|es| ext.effects.contains(&es.name))
+        .flat_map(|es| &es.effects)
+        .find(|e| matches!(e.kind, EffectKind::Sends) && e.target != "http-response")
+        .map(|e| e.target.as_str())
+}
+
+fn reaches_extern(expr: &Expr, module: &Module, sends: bool, seen: &mut HashSet<String>) -> bool {
+    let mut reaches = |e: &Expr| reaches_extern(e, module, sends, seen);
+    match expr {
+        Expr::Call { name, args, .. } => {
+            if module
+                .externs
+                .iter()
+                .any(|e| e.name == *name && (sends || sends_through(e, module).is_none()))
+            {
+                return true;
+            }
+            if let Some(helper) = module.helpers.iter().find(|h| h.name == *name) {
+                if seen.insert(helper.name.clone()) && reaches_extern(&helper.body, module, sends, seen) {
+                    return true;
+                }
+            }
+            args.iter().any(|arg| reaches_extern(arg, module, sends, seen))
+        }
+        Expr::Let { bindings, body, .. } => bindings.iter().any(|(_, value)| reaches(value)) || reaches(body),
+        Expr::Match { expr, arms, .. } => reaches(expr) || arms.iter().any(|arm| reaches(&arm.body)),
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => reaches(cond) || reaches(then_branch) || reaches(else_branch),
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => reaches(expr),
+        Expr::Err { payload, .. } => reaches(payload),
+        Expr::MapLit(entries, _) => entries.iter().any(|(_, value)| reaches(value)),
+        Expr::ListLit(items, _) => items.iter().any(reaches),
+        Expr::Lambda { body, .. } => reaches(body),
+        Expr::ForEach { list, body, .. } => reaches(list) || reaches(body),
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_externs_are_reached_through_helpers() {
+        let input = r#"(module auth :version 1
+            (extern verify-password [String String] (result Unit Unit) :effects [])
+            (defn matches? [given String stored String] Bool
+                (match (verify-password given stored) (ok _) true (err _) false))
+            (defn same? [a String b String] Bool (= a b))
+            (fn login (param p String) (returns (union (ok Bool))) (ok (matches? p p)))
+            (fn compare (param p String) (returns (union (ok Bool))) (ok (same? p p))))"#;
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        assert!(needs_externs(&module.functions[0].body, &module));
+        assert!(!needs_externs(&module.functions[1].body, &module));
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+
+/// Check generic types and their uses:
+/// - `(type (Page T) ...)` parameters are distinct and do not shadow a type
+/// - `(Page X)` names a generic type and gives one argument per parameter; a generic
+///   type is never used bare
+/// - no type contains itself by value: a cycle needs an `optional`, `list` or `map` on it
+/// - `(. expr field)` on a param of a module type names a field of it, with the type
+///   arguments of generic types substituted into nested accesses
+pub fn check_generics(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for typedef in &module.types {
+        let mut seen = HashSet::new();
+        for param in &typedef.type_params {
+            if !seen.insert(param) {
+                diagnostics.push(Diagnostic::error(
+                    format!("type '{}' declares the type parameter '{}' twice", typedef.name, param),
+                    Some(typedef.span.clone()),
+                ));
+            } else if BUILTINS.contains(&param.as_str()) || module.types.iter().any(|t| t.name == *param) {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "type parameter '{}' of '{}' shadows the type of the same name",
+                        param, typedef.name
+                    ),
+                    Some(typedef.span.clone()),
+                ));
+            }
+        }
+        for field in &typedef.fields {
+            let context = format!("field '{}.{}'", typedef.name, field.name);
+            check_uses(&field.type_expr, module, &context, &mut diagnostics);
+        }
+        if contains_by_value(&typedef.name, &typedef.name, module, &mut HashSet::new()) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "type '{}' contains itself with no optional, list or map in between, so no value of it could be built",
+                    typedef.name
+                ),
+                Some(typedef.span.clone()),
+            ));
+        }
+    }
+
+    for func in &module.functions {
+        let context = format!("'{}'", func.name);
+        for param in &func.params {
+            check_uses(&param.type_expr, module, &context, &mut diagnostics);
+        }
+        for variant in &func.returns.variants {
+            match &variant.kind {
+                VariantKind::Ok { type_expr, .. } => check_uses(type_expr, module, &context, &mut diagnostics),
+                VariantKind::Err { payload, .. } => check_uses(payload, module, &context, &mut diagnostics),
+            }
+        }
+        let env: HashMap<&str, &TypeExpr> = func.params.iter().map(|p| (p.name.as_str(), &p.type_expr)).collect();
+        check_accesses(&func.body, module, &env, &func.name, &mut diagnostics);
+    }
+
+    for helper in &module.helpers {
+        let context = format!("'{}'", helper.name);
+        for param in &helper.params {
+            check_uses(&param.type_expr, module, &context, &mut diagnostics);
+        }
+        check_uses(&helper.returns, module, &context, &mut diagnostics);
+        let env: HashMap<&str, &TypeExpr> = helper.params.iter().map(|p| (p.name.as_str(), &p.type_expr)).collect();
+        check_accesses(&helper.body, module, &env, &helper.name, &mut diagnostics);
+    }
+
+    diagnostics
+}
+
+const BUILTINS: [&str; 8] = ["UUID", "String", "Int", "Float", "Decimal", "Bool", "Unit", "ValidationError"];
+
+/// `type_expr` with each type parameter in `params` replaced by its argument in `args`
+pub fn substitute(type_expr: &TypeExpr, params: &[String], args: &[TypeExpr]) -> TypeExpr {
+    let sub = |t: &TypeExpr| Box::new(substitute(t, params, args));
+    match type_expr {
+        TypeExpr::Named(name) => match params.iter().position(|p| p == name) {
+            Some(i) if i < args.len() => args[i].clone(),
+            _ => type_expr.clone(),
+        },
+        TypeExpr::Map(fields) => {
+            TypeExpr::Map(fields.iter().map(|(n, t)| (n.clone(), substitute(t, params, args))).collect())
+        }
+        TypeExpr::List(inner) => TypeExpr::List(sub(inner)),
+        TypeExpr::Optional(inner) => TypeExpr::Optional(sub(inner)),
+        TypeExpr::MapOf(key, value) => TypeExpr::MapOf(sub(key), sub(value)),
+        TypeExpr::Result(ok, err) => TypeExpr::Result(sub(ok), sub(err)),
+        TypeExpr::Generic(name, inner) => {
+            TypeExpr::Generic(name.clone(), inner.iter().map(|t| substitute(t, params, args)).collect())
+        }
+        TypeExpr::Union(_) | TypeExpr::Enum(_) | TypeExpr::Ref(_) => type_expr.clone(),
+    }
+}
+
+/// The fields of `typedef` with `args` in place of its type parameters:
+/// `(Page User)` has `items (list User)`
+pub fn instantiate(typedef: &TypeDef, args: &[TypeExpr]) -> Vec<FieldDef> {
+    typedef
+        .fields
+        .iter()
+        .map(|field| FieldDef {
+            type_expr: substitute(&field.type_expr, &typedef.type_params, args),
+            ..field.clone()
+        })
+        .collect()
+}
+
+/// Whether a field of `owner` leads back to `owner` without a `list` or `map` in between,
+/// so its value must be boxed to give the type a size: `(field parent (optional Comment))`
+/// in `Comment`.
+pub fn boxed(field: &FieldDef, owner: &str, module: &Module) -> bool {
+    inline_types(&field.type_expr, true)
+        .iter()
+        .any(|t| *t == owner || reaches(t, owner, module, true, &mut HashSet::new()))
+}
+
+/// Module types a value of `type_expr` holds in place, not behind a `list` or `map`.
+/// With `through_optional` unset, values that may be absent are left out too.
+fn inline_types(type_expr: &TypeExpr, through_optional: bool) -> Vec<&str> {
+    match type_expr {
+        TypeExpr::Named(name) => vec![name.as_str()],
+        TypeExpr::Generic(name, args) => std::iter::once(name.as_str())
+            .chain(args.iter().flat_map(|t| inline_types(t, through_optional)))
+            .collect(),
+        TypeExpr::Optional(inner) if through_optional => inline_types(inner, through_optional),
+        TypeExpr::Map(fields) => fields.iter().flat_map(|(_, t)| inline_types(t, through_optional)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn reaches<'m>(from: &'m str, to: &str, module: &'m Module, through_optional: bool, visited: &mut HashSet<&'m str>) -> bool {
+    if !visited.insert(from) {
+        return false;
+    }
+    let Some(typedef) = module.types.iter().find(|t| t.name == from) else {
+        return false;
+    };
+    typedef.fields.iter().any(|f| {
+        inline_types(&f.type_expr, through_optional)
+            .into_iter()
+            .any(|t| t == to || reaches(t, to, module, through_optional, visited))
+    })
+}
+
+fn contains_by_value<'m>(from: &'m str, to: &str, module: &'m Module, visited: &mut HashSet<&'m str>) -> bool {
+    reaches(from, to, module, false, visited)
+}
+
+fn check_uses(type_expr: &TypeExpr, module: &Module, context: &str, diagnostics: &mut Vec<Diagnostic>) {
+    match type_expr {
+        TypeExpr::Named(name) => {
+            if let Some(typedef) = module.types.iter().find(|t| t.name == *name && !t.type_params.is_empty()) {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "in {}: '{}' is generic and needs {} type argument(s), as in ({} {})",
+                        context,
+                        name,
+                        typedef.type_params.len(),
+                        name,
+                        typedef.type_params.join(" ")
+                    ),
+                    None,
+                ));
+            }
+        }
+        TypeExpr::Generic(name, args) => {
+            match module.types.iter().find(|t| t.name == *name) {
+                Some(typedef) if typedef.type_params.is_empty() => {
+                    diagnostics.push(Diagnostic::error(
+                        format!("in {}: type '{}' takes no type arguments", context, name),
+                        None,
+                    ));
+                }
+                Some(typedef) if typedef.type_params.len() != args.len() => {
+                    diagnostics.push(Diagnostic::error(
+                        format!(
+                            "in {}: ({} ...) gives {} type argument(s), but '{}' takes {}",
+                            context,
+                            name,
+                            args.len(),
+                            name,
+                            typedef.type_params.len()
+                        ),
+                        None,
+                    ));
+                }
+                _ => {}
+            }
+            for arg in args {
+                check_uses(arg, module, context, diagnostics);
+            }
+        }
+        TypeExpr::Map(fields) => {
+            for (_, t) in fields {
+                check_uses(t, module, context, diagnostics);
+            }
+        }
+        TypeExpr::List(inner) | TypeExpr::Optional(inner) => check_uses(inner, module, context, diagnostics),
+        TypeExpr::MapOf(key, value) | TypeExpr::Result(key, value) => {
+            check_uses(key, module, context, diagnostics);
+            check_uses(value, module, context, diagnostics);
+        }
+        TypeExpr::Union(variants) => {
+            for v in variants {
+                match &v.kind {
+                    VariantKind::Ok { type_expr, .. } => check_uses(type_expr, module, context, diagnostics),
+                    VariantKind::Err { payload, .. } => check_uses(payload, module, context, diagnostics),
+                }
+            }
+        }
+        TypeExpr::Enum(_) | TypeExpr::Ref(_) => {}
+    }
+}
+
+/// The module type of a value of `type_expr` and the fields it has there
+pub(super) fn fields_of<'m>(type_expr: &TypeExpr, module: &'m Module) -> Option<(&'m TypeDef, Vec<FieldDef>)> {
+    match type_expr {
+        TypeExpr::Named(name) => {
+            let typedef = module.types.iter().find(|t| t.name == *name && t.type_params.is_empty())?;
+            Some((typedef, typedef.fields.clone()))
+        }
+        TypeExpr::Generic(name, args) => {
+            let typedef = module.types.iter().find(|t| t.name == *name && t.type_params.len() == args.len())?;
+            Some((typedef, instantiate(typedef, args)))
+        }
+        _ => None,
+    }
+}
+
+/// The type of `expr` where it is known: a param, or a field of a value of known type
+fn type_of(expr: &Expr, module: &Module, env: &HashMap<&str, &TypeExpr>) -> Option<TypeExpr> {
+    match expr {
+        Expr::Ref(name, _) => env.get(name.as_str()).map(|t| (*t).clone()),
+        Expr::FieldAccess { expr, field, .. } => {
+            let (_, fields) = fields_of(&type_of(expr, module, env)?, module)?;
+            fields.into_iter().find(|f| f.name == *field).map(|f| f.type_expr)
+        }
+        _ => None,
+    }
+}
+
+fn check_accesses(
+    expr: &Expr,
+    module: &Module,
+    env: &HashMap<&str, &TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match expr {
+        Expr::FieldAccess { expr: base, field, span } => {
+            if let Some(base_type) = type_of(base, module, env) {
+                if let Some((typedef, fields)) = fields_of(&base_type, module) {
+                    if !fields.iter().any(|f| f.name == *field) {
+                        diagnostics.push(Diagnostic::error(
+                            format!("in '{}': {} has no field '{}'", context, typedef.name, field),
+                            Some(span.clone()),
+                        ));
+                    }
+                }
+            }
+            check_accesses(base, module, env, context, diagnostics);
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut inner = env.clone();
+            for (name, value) in bindings {
+                check_accesses(value, module, &inner, context, diagnostics);
+                inner.remove(name.as_str());
+            }
+            check_accesses(body, module, &inner, context, diagnostics);
+        }
+        Expr::Match { expr, arms, .. } => {
+            check_accesses(expr, module, env, context, diagnostics);
+            for arm in arms {
+                let mut bound = HashSet::new();
+                pattern_bindings(&arm.pattern, &mut bound);
+                let inner: HashMap<&str, &TypeExpr> =
+                    env.iter().filter(|(n, _)| !bound.contains(**n)).map(|(n, t)| (*n, *t)).collect();
+                check_accesses(&arm.body, module, &inner, context, diagnostics);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check_accesses(cond, module, env, context, diagnostics);
+            check_accesses(then_branch, module, env, context, diagnostics);
+            check_accesses(else_branch, module, env, context, diagnostics);
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                check_accesses(arg, module, env, context, diagnostics);
+            }
+        }
+        Expr::Ok(inner, _) => check_accesses(inner, module, env, context, diagnostics),
+        Expr::Err { payload, .. } => check_accesses(payload, module, env, context, diagnostics),
+        Expr::MapLit(entries, _) => {
+            for (_, value) in entries {
+                check_accesses(value, module, env, context, diagnostics);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_accesses(item, module, env, context, diagnostics);
+            }
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut inner = env.clone();
+            for param in params {
+                inner.remove(param.as_str());
+            }
+            check_accesses(body, module, &inner, context, diagnostics);
+        }
+        Expr::ForEach { var, list, body, .. } => {
+            check_accesses(list, module, env, context, diagnostics);
+            let mut inner = env.clone();
+            inner.remove(var.as_str());
+            check_accesses(body, module, &inner, context, diagnostics);
+        }
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => {}
+    }
+}
+
+fn pattern_bindings<'p>(pattern: &'p Pattern, bound: &mut HashSet<&'p str>) {
+    match pattern {
+        Pattern::Var(name, _) => {
+            bound.insert(name.as_str());
+        }
+        Pattern::Constructor { args, .. } => {
+            for arg in args {
+                pattern_bindings(arg, bound);
+            }
+        }
+        Pattern::Wildcard(_) | Pattern::Keyword(_, _) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn lower(input: &str) -> Module {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    fn errors(input: &str) -> Vec<String> {
+        check_generics(&lower(input)).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn test_instantiate_substitutes_type_arguments() {
+        let module = lower(
+            r#"(module feed :version 1
+                (type (Page T)
+                    (field items (list T))
+                    (field next-cursor (optional String))))"#,
+        );
+        let fields = instantiate(&module.types[0], &[TypeExpr::Named("Comment".into())]);
+        assert!(matches!(&fields[0].type_expr, TypeExpr::List(inner) if matches!(&**inner, TypeExpr::Named(n) if n == "Comment")));
+        assert!(matches!(&fields[1].type_expr, TypeExpr::Optional(_)));
+    }
+
+    #[test]
+    fn test_generic_uses_and_field_accesses_are_checked() {
+        let errs = errors(
+            r#"(module feed :version 1
+                (type Order (field total Int))
+                (type (Envelope T) (field data T) (field sent-at String))
+                (type (Pair A A) (field first A))
+                (type Loop (field next Loop))
+                (type Tree (field children (list Tree)) (field parent (optional Tree)))
+                (fn receive
+                    (param e (Envelope Order))
+                    (param bare Envelope)
+                    (param wrong (Envelope Order Order))
+                    (returns (union (ok Int)))
+                    (let [t (. (. e data) total)
+                          s (. e sent-at)]
+                      (ok (. (. e data) totl)))))"#,
+        );
+        assert_eq!(
+            errs,
+            vec![
+                "type 'Pair' declares the type parameter 'A' twice".to_string(),
+                "type 'Loop' contains itself with no optional, list or map in between, so no value of it could be built"
+                    .to_string(),
+                "in 'receive': 'Envelope' is generic and needs 1 type argument(s), as in (Envelope T)".to_string(),
+                "in 'receive': (Envelope ...) gives 2 type argument(s), but 'Envelope' takes 1".to_string(),
+                "in 'receive': Order has no field 'totl'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_fields_are_boxed_unless_behind_a_list() {
+        let module = lower(
+            r#"(module feed :version 1
+                (type Comment
+                    (field replies (list Comment))
+                    (field parent (optional Comment))
+                    (field body String)))"#,
+        );
+        let comment = &module.types[0];
+        let boxed: Vec<bool> = comment.fields.iter().map(|f| boxed(f, "Comment", &module)).collect();
+        assert_eq!(boxed, vec![false, true, false]);
+    }
+}
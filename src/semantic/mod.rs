@@ -1,6 +1,13 @@
 pub mod resolve;
 pub mod effects;
 pub mod totality;
+pub mod queries;
+pub mod enums;
+pub mod refs;
+pub mod generics;
+pub mod operators;
+pub mod collections;
+pub mod externs;
 
 use crate::ast::Module;
 use crate::diagnostics::Diagnostic;
@@ -13,6 +20,10 @@ pub fn analyze(module: &Module) -> Vec<Diagnostic> {
     diagnostics.extend(resolve::resolve_names(module));
     diagnostics.extend(effects::check_effects(module));
     diagnostics.extend(totality::check_totality(module));
+    diagnostics.extend(queries::check_queries(module));
+    diagnostics.extend(generics::check_generics(module));
+    diagnostics.extend(operators::check_operators(module));
+    diagnostics.extend(collections::check_collections(module));
 
     diagnostics
 }
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Span;
+
+use super::{collections, generics};
+
+/// The primitive operators of function bodies. Backends emit them as the native Rust
+/// operator; everything else in call position is a function call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `+ - * /` over `Int`, `Float` or `Decimal`; `(- x)` negates
+    Arithmetic,
+    /// `< <= > >=` over numbers or strings
+    Ordering,
+    /// `= !=` over two values of the same type
+    Equality,
+    /// `and`, `or` and `not` over `Bool`
+    Logical,
+}
+
+/// The operator a call names, and its Rust spelling: `(= a b)` is `a == b`
+pub fn operator(name: &str) -> Option<(Operator, &'static str)> {
+    Some(match name {
+        "+" => (Operator::Arithmetic, "+"),
+        "-" => (Operator::Arithmetic, "-"),
+        "*" => (Operator::Arithmetic, "*"),
+        "/" => (Operator::Arithmetic, "/"),
+        "<" => (Operator::Ordering, "<"),
+        "<=" => (Operator::Ordering, "<="),
+        ">" => (Operator::Ordering, ">"),
+        ">=" => (Operator::Ordering, ">="),
+        "=" => (Operator::Equality, "=="),
+        "!=" => (Operator::Equality, "!="),
+        "and" => (Operator::Logical, "&&"),
+        "or" => (Operator::Logical, "||"),
+        "not" => (Operator::Logical, "!"),
+        _ => return None,
+    })
+}
+
+/// Whether `expr` is an operator call, which backends parenthesize as an operand
+pub fn is_operator_call(expr: &Expr) -> bool {
+    matches!(expr, Expr::Call { name, .. } if operator(name).is_some())
+}
+
+/// Check the operators of function bodies:
+/// - `not` and unary `-` take one operand, comparisons two, the rest two or more
+/// - operands whose type is known fit the operator, and all have the same type:
+///   there is no implicit conversion between `Int`, `Float` and `Decimal`
+/// - the condition of `if` is a `Bool`, when its type is known
+///
+/// Types are known for literals, params, `let` bindings of known values, fields of
+/// those, `(some x)` bindings of a `query` and the results of operators.
+pub fn check_operators(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for func in &module.functions {
+        let env: HashMap<String, TypeExpr> =
+            func.params.iter().map(|p| (p.name.clone(), p.type_expr.clone())).collect();
+        check_expr(&func.body, module, &env, &func.name, &mut diagnostics);
+    }
+    for helper in &module.helpers {
+        let env: HashMap<String, TypeExpr> =
+            helper.params.iter().map(|p| (p.name.clone(), p.type_expr.clone())).collect();
+        check_expr(&helper.body, module, &env, &helper.name, &mut diagnostics);
+    }
+    diagnostics
+}
+
+/// The type of `expr` where it is known
+pub fn type_of(expr: &Expr, module: &Module, env: &HashMap<String, TypeExpr>) -> Option<TypeExpr> {
+    let named = |n: &str| Some(TypeExpr::Named(n.to_string()));
+    match expr {
+        Expr::IntLit(_, _) => named("Int"),
+        Expr::FloatLit(_, _) => named("Float"),
+        Expr::StringLit(_, _) => named("String"),
+        Expr::BoolLit(_, _) => named("Bool"),
+        Expr::Ref(name, _) => env.get(name).cloned(),
+        Expr::FieldAccess { expr, field, .. } => {
+            let (_, fields) = generics::fields_of(&type_of(expr, module, env)?, module)?;
+            fields.into_iter().find(|f| f.name == *field).map(|f| f.type_expr)
+        }
+        Expr::Call { name, args, .. } => match operator(name) {
+            Some((Operator::Arithmetic, _)) => args.iter().find_map(|a| type_of(a, module, env)),
+            Some(_) => named("Bool"),
+            None if name == "query" => match args.first() {
+                Some(Expr::Ref(store, _)) => crate::naming::stored_type(store, module)
+                    .map(|t| TypeExpr::Optional(Box::new(TypeExpr::Named(t.name.clone())))),
+                _ => None,
+            },
+            None => match (name.as_str(), args.as_slice()) {
+                ("map", [Expr::Lambda { body, .. }, _]) => {
+                    let inner = collections::lambda_env(name, args, module, env);
+                    type_of(body, module, &inner).map(|t| TypeExpr::List(Box::new(t)))
+                }
+                ("filter", [_, list]) => type_of(list, module, env),
+                ("fold", [_, init, _]) => type_of(init, module, env),
+                _ => module
+                    .helpers
+                    .iter()
+                    .find(|h| h.name == *name)
+                    .map(|h| h.returns.clone())
+                    .or_else(|| module.externs.iter().find(|e| e.name == *name).map(|e| e.returns.clone())),
+            },
+        },
+        Expr::ListLit(items, _) => items
+            .iter()
+            .find_map(|item| type_of(item, module, env))
+            .map(|t| TypeExpr::List(Box::new(t))),
+        Expr::If { then_branch, else_branch, .. } => {
+            type_of(then_branch, module, env).or_else(|| type_of(else_branch, module, env))
+        }
+        Expr::Let { bindings, body, .. } => type_of(body, module, &bind(bindings, module, env)),
+        _ => None,
+    }
+}
+
+/// `env` with the names `let` binds, typed where their values are
+fn bind(bindings: &[(String, Expr)], module: &Module, env: &HashMap<String, TypeExpr>) -> HashMap<String, TypeExpr> {
+    let mut inner = env.clone();
+    for (name, value) in bindings {
+        bind_one(name, value, module, &mut inner);
+    }
+    inner
+}
+
+pub(super) fn bind_one(name: &str, value: &Expr, module: &Module, env: &mut HashMap<String, TypeExpr>) {
+    match type_of(value, module, env) {
+        Some(t) => env.insert(name.to_string(), t),
+        None => env.remove(name),
+    };
+}
+
+/// A type as Pact writes it, for messages
+pub(super) fn type_text(type_expr: &TypeExpr) -> String {
+    match type_expr {
+        TypeExpr::Named(name) => name.clone(),
+        TypeExpr::Optional(inner) => format!("(optional {})", type_text(inner)),
+        TypeExpr::List(inner) => format!("(list {})", type_text(inner)),
+        TypeExpr::Ref(target) => format!("(ref {})", target),
+        TypeExpr::MapOf(key, value) => format!("(map {} {})", type_text(key), type_text(value)),
+        TypeExpr::Result(ok, err) => format!("(result {} {})", type_text(ok), type_text(err)),
+        TypeExpr::Generic(name, args) => {
+            format!("({} {})", name, args.iter().map(type_text).collect::<Vec<_>>().join(" "))
+        }
+        TypeExpr::Enum(variants) => format!("(enum {})", variants.iter().map(|v| format!(":{}", v)).collect::<Vec<_>>().join(" ")),
+        TypeExpr::Map(_) => "a map".to_string(),
+        TypeExpr::Union(_) => "a union".to_string(),
+    }
+}
+
+fn check_operator(
+    name: &str,
+    args: &[Expr],
+    span: &Span,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some((kind, _)) = operator(name) else {
+        return;
+    };
+    let arity_ok = match (kind, name) {
+        (_, "not") => args.len() == 1,
+        (_, "-") => !args.is_empty(),
+        (Operator::Ordering | Operator::Equality, _) => args.len() == 2,
+        _ => args.len() >= 2,
+    };
+    if !arity_ok {
+        let expected = match (kind, name) {
+            (_, "not") => "one operand",
+            (_, "-") => "one or more operands",
+            (Operator::Ordering | Operator::Equality, _) => "two operands",
+            _ => "two or more operands",
+        };
+        diagnostics.push(Diagnostic::error(
+            format!("in '{}': '{}' takes {}, not {}", context, name, expected, args.len()),
+            Some(span.clone()),
+        ));
+        return;
+    }
+
+    let types: Vec<TypeExpr> = args.iter().filter_map(|a| type_of(a, module, env)).collect();
+    let fits = |t: &TypeExpr| match (kind, t) {
+        (Operator::Arithmetic, TypeExpr::Named(n)) if name == "/" => matches!(n.as_str(), "Int" | "Float"),
+        (Operator::Arithmetic, TypeExpr::Named(n)) => matches!(n.as_str(), "Int" | "Float" | "Decimal"),
+        (Operator::Ordering, TypeExpr::Named(n)) => matches!(n.as_str(), "Int" | "Float" | "Decimal" | "String"),
+        (Operator::Logical, TypeExpr::Named(n)) => n == "Bool",
+        (Operator::Equality, TypeExpr::Optional(_) | TypeExpr::Named(_) | TypeExpr::Ref(_) | TypeExpr::Enum(_)) => true,
+        _ => false,
+    };
+    if let Some(bad) = types.iter().find(|t| !fits(t)) {
+        diagnostics.push(Diagnostic::error(
+            format!("in '{}': '{}' is not defined on {}", context, name, type_text(bad)),
+            Some(span.clone()),
+        ));
+        return;
+    }
+    if let Some(other) = types.iter().find(|t| type_text(t) != type_text(&types[0])) {
+        diagnostics.push(Diagnostic::error(
+            format!(
+                "in '{}': '{}' mixes {} and {}; there is no implicit conversion between them",
+                context,
+                name,
+                type_text(&types[0]),
+                type_text(other)
+            ),
+            Some(span.clone()),
+        ));
+    }
+}
+
+fn check_expr(
+    expr: &Expr,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match expr {
+        Expr::Call { name, args, span } => {
+            check_operator(name, args, span, module, env, context, diagnostics);
+            for arg in args {
+                match arg {
+                    Expr::Lambda { body, .. } => {
+                        let inner = collections::lambda_env(name, args, module, env);
+                        check_expr(body, module, &inner, context, diagnostics);
+                    }
+                    _ => check_expr(arg, module, env, context, diagnostics),
+                }
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut inner = env.clone();
+            for (name, value) in bindings {
+                check_expr(value, module, &inner, context, diagnostics);
+                bind_one(name, value, module, &mut inner);
+            }
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::Match { expr: scrutinee, arms, .. } => {
+            check_expr(scrutinee, module, env, context, diagnostics);
+            let scrutinee_type = type_of(scrutinee, module, env);
+            for arm in arms {
+                let mut inner = env.clone();
+                bind_pattern(&arm.pattern, scrutinee_type.as_ref(), &mut inner);
+                check_expr(&arm.body, module, &inner, context, diagnostics);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            span,
+        } => {
+            if let Some(t) = type_of(cond, module, env) {
+                if !matches!(&t, TypeExpr::Named(n) if n == "Bool") {
+                    diagnostics.push(Diagnostic::error(
+                        format!("in '{}': the condition of if is {}, not Bool", context, type_text(&t)),
+                        Some(span.clone()),
+                    ));
+                }
+            }
+            check_expr(cond, module, env, context, diagnostics);
+            check_expr(then_branch, module, env, context, diagnostics);
+            check_expr(else_branch, module, env, context, diagnostics);
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => check_expr(expr, module, env, context, diagnostics),
+        Expr::Err { payload, .. } => check_expr(payload, module, env, context, diagnostics),
+        Expr::MapLit(entries, _) => {
+            for (_, value) in entries {
+                check_expr(value, module, env, context, diagnostics);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_expr(item, module, env, context, diagnostics);
+            }
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut inner = env.clone();
+            for param in params {
+                inner.remove(param);
+            }
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::ForEach { var, list, body, .. } => {
+            check_expr(list, module, env, context, diagnostics);
+            let inner = collections::for_each_env(var, list, module, env);
+            check_expr(body, module, &inner, context, diagnostics);
+        }
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => {}
+    }
+}
+
+/// Bind the names of a match arm's pattern: `(some x)` on an optional value gives `x`
+/// its type, as do `(ok x)` and `(err x)` on a result; other bindings shadow whatever
+/// the name was.
+pub(super) fn bind_pattern(pattern: &Pattern, scrutinee: Option<&TypeExpr>, env: &mut HashMap<String, TypeExpr>) {
+    match pattern {
+        Pattern::Constructor { name, args, .. } => match (name.as_str(), args.as_slice(), scrutinee) {
+            ("some", [Pattern::Var(var, _)], Some(TypeExpr::Optional(inner)))
+            | ("ok", [Pattern::Var(var, _)], Some(TypeExpr::Result(inner, _)))
+            | ("err", [Pattern::Var(var, _)], Some(TypeExpr::Result(_, inner))) => {
+                env.insert(var.clone(), (**inner).clone());
+            }
+            _ => {
+                for arg in args {
+                    bind_pattern(arg, None, env);
+                }
+            }
+        },
+        Pattern::Var(var, _) => {
+            match scrutinee {
+                Some(t) => env.insert(var.clone(), t.clone()),
+                None => env.remove(var),
+            };
+        }
+        Pattern::Wildcard(_) | Pattern::Keyword(_, _) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn errors(input: &str) -> Vec<String> {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        check_operators(&module).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn test_examples_have_no_operator_errors() {
+        assert!(errors(include_str!("../../examples/inventory.pct")).is_empty());
+    }
+
+    #[test]
+    fn test_operator_arity_and_operand_types() {
+        let errs = errors(
+            r#"(module shop :version 1
+                (type Item (field id UUID) (field name String) (field price Float) (field qty Int))
+                (effect-set item-read [:reads item-store])
+                (fn check
+                    :effects [item-read]
+                    (param id UUID)
+                    (param count Int)
+                    (returns (union (ok Int) (err :bad {})))
+                    (match (query item-store {:id id})
+                        (some i) (let [total (* (. i price) count)
+                                       left (- (. i qty) count)]
+                                   (if (and (> left 0) (not (= (. i name) "")))
+                                     (if left (ok left) (ok (- left)))
+                                     (err :bad {:why (< (. i name) 1 2)})))
+                        (none) (err :bad {}))))"#,
+        );
+        assert_eq!(
+            errs,
+            vec![
+                "in 'check': '*' mixes Float and Int; there is no implicit conversion between them".to_string(),
+                "in 'check': the condition of if is Int, not Bool".to_string(),
+                "in 'check': '<' takes two operands, not 3".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Span;
+use crate::naming::stored_type;
+
+/// Check that `(query store {...})` filters only by fields of the type held in the store,
+/// and that `query`, `get`, `lookup` and `insert!` name a store some effect set targets.
+/// Stores are matched to types by `:unique-within` annotations, or by name
+/// (`user-store` holds `User`). A store whose type is not defined here cannot be
+/// filtered by fields, since neither the fields nor their types are known.
+pub fn check_queries(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for func in &module.functions {
+        check_expr(&func.body, module, &mut diagnostics);
+    }
+    for helper in &module.helpers {
+        check_expr(&helper.body, module, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn check_expr(expr: &Expr, module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    match expr {
+        Expr::Call { name, args, span } => {
+            let is_store = |store: &str| module.effect_sets.iter().flat_map(|es| &es.effects).any(|e| e.target == store);
+            if matches!(name.as_str(), "query" | "get" | "lookup" | "insert!") {
+                match args.first() {
+                    // A query filtering by fields is reported below
+                    Some(Expr::Ref(store, _))
+                        if is_store(store)
+                            && stored_type(store, module).is_none()
+                            && !(name == "query" && matches!(args.get(1), Some(Expr::MapLit(..)))) =>
+                    {
+                        diagnostics.push(Diagnostic::error(
+                            format!(
+                                "'{}' on '{}', but no type defined here is held in it; \
+                                 mark a field of its type :unique-within {}",
+                                name, store, store
+                            ),
+                            Some(span.clone()),
+                        ))
+                    }
+                    Some(Expr::Ref(store, _)) if is_store(store) => {}
+                    Some(Expr::Ref(store, _)) => diagnostics.push(Diagnostic::error(
+                        format!("'{}' names '{}', which is not a store any effect set targets", name, store),
+                        Some(span.clone()),
+                    )),
+                    _ => diagnostics.push(Diagnostic::error(
+                        format!("'{}' takes the name of a store as its first argument", name),
+                        Some(span.clone()),
+                    )),
+                }
+            }
+            if name == "query" {
+                if let (Some(Expr::Ref(store, _)), Some(Expr::MapLit(entries, _))) = (args.first(), args.get(1)) {
+                    match stored_type(store, module) {
+                        Some(type_def) => {
+                            for (key, _) in entries {
+                                if !type_def.fields.iter().any(|f| f.name == *key) {
+                                    diagnostics.push(Diagnostic::error(
+                                        format!(
+                                            "query on '{}' filters by '{}', which is not a field of {}",
+                                            store, key, type_def.name
+                                        ),
+                                        Some(span.clone()),
+                                    ));
+                                }
+                            }
+                        }
+                        None if is_store(store) => diagnostics.push(Diagnostic::error(
+                            format!(
+                                "query on '{}' filters by fields, but no type defined here is held in it; \
+                                 mark a field of its type :unique-within {}",
+                                store, store
+                            ),
+                            Some(span.clone()),
+                        )),
+                        None => {}
+                    }
+                }
+            }
+            for arg in args {
+                check_expr(arg, module, diagnostics);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (_, value) in bindings {
+                check_expr(value, module, diagnostics);
+            }
+            check_expr(body, module, diagnostics);
+        }
+        Expr::Match { expr, arms, .. } => {
+            check_expr(expr, module, diagnostics);
+            for arm in arms {
+                check_expr(&arm.body, module, diagnostics);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check_expr(cond, module, diagnostics);
+            check_expr(then_branch, module, diagnostics);
+            check_expr(else_branch, module, diagnostics);
+        }
+        Expr::FieldAccess { expr, .. } => check_expr(expr, module, diagnostics),
+        Expr::Ok(inner, _) => check_expr(inner, module, diagnostics),
+        Expr::Err { payload, .. } => check_expr(payload, module, diagnostics),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                check_expr(val, module, diagnostics);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_expr(item, module, diagnostics);
+            }
+        }
+        Expr::Lambda { body, .. } => check_expr(body, module, diagnostics),
+        Expr::ForEach { list, body, .. } => {
+            check_expr(list, module, diagnostics);
+            check_expr(body, module, diagnostics);
+        }
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => {}
+    }
+}
+
+/// The spans of the `query` calls whose result is matched with `(some x)` and `(none)`,
+/// directly or through the name a `let` binds it to. Such a query wants one entity
+/// rather than every one its filter matches.
+pub fn option_matched_queries(body: &Expr) -> Vec<Span> {
+    let mut spans = Vec::new();
+    collect_option_matched(body, &mut Vec::new(), &mut spans);
+    spans
+}
+
+/// `bound`: the names in scope, with the query each is bound to, if any
+fn collect_option_matched<'a>(expr: &'a Expr, bound: &mut Vec<(&'a str, Option<&'a Span>)>, spans: &mut Vec<Span>) {
+    match expr {
+        Expr::Match { expr: matched, arms, .. } => {
+            let option_arms = arms.iter().any(|arm| {
+                matches!(&arm.pattern, Pattern::Constructor { name, .. } if name == "some" || name == "none")
+            });
+            let query = match matched.as_ref() {
+                Expr::Call { name, span, .. } if name == "query" => Some(span),
+                Expr::Ref(name, _) => bound.iter().rev().find(|(n, _)| n == name).and_then(|(_, span)| *span),
+                _ => None,
+            };
+            if let (true, Some(span)) = (option_arms, query) {
+                if !spans.contains(span) {
+                    spans.push(span.clone());
+                }
+            }
+            collect_option_matched(matched, bound, spans);
+            for arm in arms {
+                collect_option_matched(&arm.body, bound, spans);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            let scope = bound.len();
+            for (name, value) in bindings {
+                collect_option_matched(value, bound, spans);
+                let query = match value {
+                    Expr::Call { name, span, .. } if name == "query" => Some(span),
+                    _ => None,
+                };
+                bound.push((name, query));
+            }
+            collect_option_matched(body, bound, spans);
+            bound.truncate(scope);
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                collect_option_matched(arg, bound, spans);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_option_matched(cond, bound, spans);
+            collect_option_matched(then_branch, bound, spans);
+            collect_option_matched(else_branch, bound, spans);
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => collect_option_matched(expr, bound, spans),
+        Expr::Err { payload, .. } => collect_option_matched(payload, bound, spans),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                collect_option_matched(val, bound, spans);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                collect_option_matched(item, bound, spans);
+            }
+        }
+        Expr::Lambda { body, .. } => collect_option_matched(body, bound, spans),
+        Expr::ForEach { list, body, .. } => {
+            collect_option_matched(list, bound, spans);
+            collect_option_matched(body, bound, spans);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn check(input: &str) -> Vec<Diagnostic> {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        check_queries(&module)
+    }
+
+    const MODULE: &str = r#"(module test :version 1
+        (type User
+            (field id UUID :immutable :generated)
+            (field email String :unique-within user-store)
+            (field role String))
+        (effect-set db-read [:reads user-store])
+        (fn find-user
+            :effects [db-read]
+            :total true
+            (param email String)
+            (returns (union (ok User :http 200)))
+            (query user-store {:QUERY email})))"#;
+
+    #[test]
+    fn test_query_by_fields_ok() {
+        assert!(check(&MODULE.replace(":QUERY", ":email")).is_empty());
+        assert!(check(&MODULE.replace(":QUERY email", ":role email :id email")).is_empty());
+    }
+
+    #[test]
+    fn test_query_by_unknown_field() {
+        let diags = check(&MODULE.replace(":QUERY", ":username"));
+        assert_eq!(diags.len(), 1);
+        assert_eq!(
+            diags[0].message,
+            "query on 'user-store' filters by 'username', which is not a field of User"
+        );
+
+        // A store holding no type defined here has no fields to filter by
+        let diags = check(
+            &MODULE
+                .replace("(type User", "(type Account")
+                .replace(" :unique-within user-store", "")
+                .replace(":QUERY", ":email"),
+        );
+        assert_eq!(
+            diags.iter().map(|d| d.message.as_str()).collect::<Vec<_>>(),
+            vec!["query on 'user-store' filters by fields, but no type defined here is held in it; mark a field of its type :unique-within user-store"]
+        );
+    }
+
+    #[test]
+    fn test_queries_matched_as_options() {
+        let module = |body: &str| {
+            let input = MODULE.replace("(query user-store {:QUERY email})", body);
+            let tokens = Lexer::new(&input).tokenize().unwrap();
+            let sexprs = Parser::new(tokens).parse_program().unwrap();
+            Lowerer::new().lower_module(&sexprs[0]).unwrap()
+        };
+        let matched = |body: &str| option_matched_queries(&module(body).functions[0].body).len();
+        assert_eq!(matched("(match (query user-store {:role email}) (some u) (ok u) (none) (ok u))"), 1);
+        assert_eq!(matched("(let [u (query user-store {:role email})] (match u (some x) (ok x) (none) (ok x)))"), 1);
+        // A list result, and a name the query's binding is shadowed by, are not
+        assert_eq!(matched("(ok (query user-store {:role email}))"), 0);
+        assert_eq!(
+            matched("(let [u (query user-store {:role email})] (let [u (ok email)] (match u (some x) (ok x) (none) (ok x))))"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_store_builtins_name_a_store() {
+        let messages = |input: String| check(&input).into_iter().map(|d| d.message).collect::<Vec<_>>();
+        assert_eq!(
+            messages(MODULE.replace("(query user-store {:QUERY email})", "(query users {:email email})")),
+            vec!["'query' names 'users', which is not a store any effect set targets".to_string()]
+        );
+        assert_eq!(
+            messages(MODULE.replace("(query user-store {:QUERY email})", "(insert! (. email x) email)")),
+            vec!["'insert!' takes the name of a store as its first argument".to_string()]
+        );
+        assert_eq!(
+            messages(
+                MODULE
+                    .replace("(type User", "(type Account")
+                    .replace(" :unique-within user-store", "")
+                    .replace("(query user-store {:QUERY email})", "(insert! user-store email)")
+            ),
+            vec!["'insert!' on 'user-store', but no type defined here is held in it; mark a field of its type :unique-within user-store".to_string()]
+        );
+    }
+}
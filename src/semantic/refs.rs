@@ -0,0 +1,84 @@
+use crate::ast::*;
+
+use crate::naming;
+
+/// Fields of `typedef` that hold `(ref T)`, possibly `(optional (ref T))`, with the
+/// name of the type they refer to.
+pub fn ref_fields(typedef: &TypeDef) -> Vec<(&FieldDef, &str)> {
+    typedef
+        .fields
+        .iter()
+        .filter_map(|field| match field.type_expr.non_optional() {
+            TypeExpr::Ref(target) => Some((field, target.as_str())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Types some field refers to, in declaration order. Each gets a typed id (`UserId`).
+pub fn referenced_types(module: &Module) -> Vec<&TypeDef> {
+    module
+        .types
+        .iter()
+        .filter(|t| {
+            module
+                .types
+                .iter()
+                .any(|owner| ref_fields(owner).iter().any(|(_, target)| *target == t.name))
+        })
+        .collect()
+}
+
+/// The typed id of a type: "User" → "UserId"
+pub fn id_type(type_name: &str) -> String {
+    format!("{}Id", type_name)
+}
+
+/// The store a function declares `:reads` on that holds `type_name` (`user-store` for
+/// `User`), where `insert!` can check that a `(ref User)` names an existing row.
+pub fn read_store<'m>(func: &FnDef, module: &'m Module, type_name: &str) -> Option<&'m str> {
+    func.effects
+        .iter()
+        .filter_map(|name| module.effect_sets.iter().find(|es| es.name == *name))
+        .flat_map(|es| &es.effects)
+        .find(|e| matches!(e.kind, EffectKind::Reads) && naming::stored_type(&e.target, module).is_some_and(|t| t.name == type_name))
+        .map(|e| e.target.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn lower(input: &str) -> Module {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        Lowerer::new().lower_module(&sexprs[0]).unwrap()
+    }
+
+    #[test]
+    fn test_referenced_types_and_read_stores() {
+        let module = lower(
+            r#"(module blog :version 1
+                (type Author (field id UUID))
+                (type Post (field id UUID) (field author (ref Author)) (field editor (optional (ref Author))))
+                (effect-set author-read [:reads author-store])
+                (effect-set post-write [:writes post-store])
+                (fn create-post
+                    :effects [post-write author-read]
+                    (param input {:author UUID})
+                    (returns (union (ok Unit)))
+                    (ok input)))"#,
+        );
+        let fields: Vec<(&str, &str)> =
+            ref_fields(&module.types[1]).iter().map(|(f, t)| (f.name.as_str(), *t)).collect();
+        assert_eq!(fields, vec![("author", "Author"), ("editor", "Author")]);
+        let referenced: Vec<&str> = referenced_types(&module).iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(referenced, vec!["Author"]);
+        assert_eq!(id_type("Author"), "AuthorId");
+        assert_eq!(read_store(&module.functions[0], &module, "Author"), Some("author-store"));
+        assert_eq!(read_store(&module.functions[0], &module, "Post"), None);
+    }
+}
@@ -4,6 +4,10 @@ use crate::ast::*;
 use crate::diagnostics::Diagnostic;
 use crate::lexer::Span;
 
+use super::collections::{for_each_env, lambda_env};
+use super::externs;
+use super::operators::{bind_one, bind_pattern, type_of, type_text};
+
 /// Symbol table for name resolution
 pub struct SymbolTable {
     pub types: HashMap<String, TypeInfo>,
@@ -89,6 +93,15 @@ pub fn resolve_names(module: &Module) -> Vec<Diagnostic> {
         );
     }
 
+    // Check numeric field annotations and defaults
+    for typedef in &module.types {
+        for field in &typedef.fields {
+            check_numeric_annotations(&typedef.name, field, &mut diagnostics);
+            check_default(&typedef.name, field, &mut diagnostics);
+            check_ref(&typedef.name, field, module, &mut diagnostics);
+        }
+    }
+
     // Second pass: check references
     for func in &module.functions {
         // Check effect set references
@@ -126,9 +139,225 @@ pub fn resolve_names(module: &Module) -> Vec<Diagnostic> {
         check_expr_refs(&func.body, &symtab, &mut scope, &func.name, &mut diagnostics);
     }
 
+    for helper in &module.helpers {
+        if symtab.functions.contains_key(&helper.name)
+            || module.helpers.iter().filter(|h| h.name == helper.name).count() > 1
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("helper '{}' is defined more than once", helper.name),
+                Some(helper.span.clone()),
+            ));
+        }
+        for param in &helper.params {
+            check_type_ref(&param.type_expr, &symtab, &helper.name, &mut diagnostics);
+        }
+        check_type_ref(&helper.returns, &symtab, &helper.name, &mut diagnostics);
+
+        let mut scope: HashSet<String> = helper.params.iter().map(|p| p.name.clone()).collect();
+        check_expr_refs(&helper.body, &symtab, &mut scope, &helper.name, &mut diagnostics);
+    }
+
+    for ext in &module.externs {
+        if symtab.functions.contains_key(&ext.name)
+            || module.helpers.iter().any(|h| h.name == ext.name)
+            || module.externs.iter().filter(|e| e.name == ext.name).count() > 1
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("extern '{}' is defined more than once", ext.name),
+                Some(ext.span.clone()),
+            ));
+        }
+        for effect_name in &ext.effects {
+            if !symtab.effect_sets.contains_key(effect_name) {
+                diagnostics.push(Diagnostic::error(
+                    format!("extern '{}' references unknown effect set '{}'", ext.name, effect_name),
+                    Some(ext.span.clone()),
+                ));
+            }
+        }
+        for param in &ext.params {
+            check_type_ref(param, &symtab, &ext.name, &mut diagnostics);
+        }
+        check_type_ref(&ext.returns, &symtab, &ext.name, &mut diagnostics);
+        if let Some(gateway) = externs::sends_through(ext, module) {
+            let acknowledged = matches!(&ext.returns, TypeExpr::Result(ok, err)
+                if matches!(ok.as_ref(), TypeExpr::Named(n) if n == "Unit")
+                    && matches!(err.as_ref(), TypeExpr::Named(n) if n == "String"));
+            if !acknowledged {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "extern '{}' sends through '{}', so it must return (result Unit String)",
+                        ext.name, gateway
+                    ),
+                    Some(ext.span.clone()),
+                ));
+            }
+        }
+    }
+
+    check_calls(module, &mut diagnostics);
+
     diagnostics
 }
 
+/// Calls to helpers and externs pass as many arguments as they have params, each of
+/// the param's type where it is known, and helper bodies are of their declared type.
+fn check_calls(module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    let env_of = |params: &[ParamDef]| -> HashMap<String, TypeExpr> {
+        params.iter().map(|p| (p.name.clone(), p.type_expr.clone())).collect()
+    };
+    for func in &module.functions {
+        check_call_args(&func.body, module, &env_of(&func.params), &func.name, diagnostics);
+    }
+    for helper in &module.helpers {
+        let env = env_of(&helper.params);
+        check_call_args(&helper.body, module, &env, &helper.name, diagnostics);
+        if let Some(t) = type_of(&helper.body, module, &env) {
+            if type_text(&t) != type_text(&helper.returns) {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "helper '{}' returns {}, not the declared {}",
+                        helper.name,
+                        type_text(&t),
+                        type_text(&helper.returns)
+                    ),
+                    Some(helper.span.clone()),
+                ));
+            }
+        }
+    }
+}
+
+fn check_call(
+    name: &str,
+    args: &[Expr],
+    span: &Span,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let params: Vec<&TypeExpr> = match module.helpers.iter().find(|h| h.name == name) {
+        Some(helper) => helper.params.iter().map(|p| &p.type_expr).collect(),
+        None => match module.externs.iter().find(|e| e.name == name) {
+            Some(ext) => ext.params.iter().collect(),
+            None => return,
+        },
+    };
+    if args.len() != params.len() {
+        diagnostics.push(Diagnostic::error(
+            format!(
+                "in '{}': '{}' takes {} argument{}, not {}",
+                context,
+                name,
+                params.len(),
+                if params.len() == 1 { "" } else { "s" },
+                args.len()
+            ),
+            Some(span.clone()),
+        ));
+        return;
+    }
+    for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+        if let Some(t) = type_of(arg, module, env) {
+            if type_text(&t) != type_text(param) {
+                diagnostics.push(Diagnostic::error(
+                    format!(
+                        "in '{}': argument {} of '{}' is {}, not {}",
+                        context,
+                        i + 1,
+                        name,
+                        type_text(&t),
+                        type_text(param)
+                    ),
+                    Some(span.clone()),
+                ));
+            }
+        }
+    }
+}
+
+fn check_call_args(
+    expr: &Expr,
+    module: &Module,
+    env: &HashMap<String, TypeExpr>,
+    context: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match expr {
+        Expr::Call { name, args, span } => {
+            check_call(name, args, span, module, env, context, diagnostics);
+            for arg in args {
+                match arg {
+                    Expr::Lambda { body, .. } => {
+                        let inner = lambda_env(name, args, module, env);
+                        check_call_args(body, module, &inner, context, diagnostics);
+                    }
+                    _ => check_call_args(arg, module, env, context, diagnostics),
+                }
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            let mut inner = env.clone();
+            for (name, value) in bindings {
+                check_call_args(value, module, &inner, context, diagnostics);
+                bind_one(name, value, module, &mut inner);
+            }
+            check_call_args(body, module, &inner, context, diagnostics);
+        }
+        Expr::Match { expr: scrutinee, arms, .. } => {
+            check_call_args(scrutinee, module, env, context, diagnostics);
+            let scrutinee_type = type_of(scrutinee, module, env);
+            for arm in arms {
+                let mut inner = env.clone();
+                bind_pattern(&arm.pattern, scrutinee_type.as_ref(), &mut inner);
+                check_call_args(&arm.body, module, &inner, context, diagnostics);
+            }
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check_call_args(cond, module, env, context, diagnostics);
+            check_call_args(then_branch, module, env, context, diagnostics);
+            check_call_args(else_branch, module, env, context, diagnostics);
+        }
+        Expr::ForEach { var, list, body, .. } => {
+            check_call_args(list, module, env, context, diagnostics);
+            let inner = for_each_env(var, list, module, env);
+            check_call_args(body, module, &inner, context, diagnostics);
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut inner = env.clone();
+            for param in params {
+                inner.remove(param);
+            }
+            check_call_args(body, module, &inner, context, diagnostics);
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Ok(expr, _) => check_call_args(expr, module, env, context, diagnostics),
+        Expr::Err { payload, .. } => check_call_args(payload, module, env, context, diagnostics),
+        Expr::MapLit(entries, _) => {
+            for (_, value) in entries {
+                check_call_args(value, module, env, context, diagnostics);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_call_args(item, module, env, context, diagnostics);
+            }
+        }
+        Expr::Ref(_, _)
+        | Expr::Keyword(_, _)
+        | Expr::StringLit(_, _)
+        | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
+        | Expr::BoolLit(_, _)
+        | Expr::Wildcard(_) => {}
+    }
+}
+
 fn check_type_ref(
     type_expr: &TypeExpr,
     symtab: &SymbolTable,
@@ -139,7 +368,7 @@ fn check_type_ref(
         TypeExpr::Named(name) => {
             // Built-in types
             let builtins = [
-                "UUID", "String", "Int", "Bool", "Unit", "ValidationError",
+                "UUID", "String", "Int", "Float", "Decimal", "Bool", "Unit", "ValidationError",
             ];
             if !builtins.contains(&name.as_str()) && !symtab.types.contains_key(name) {
                 diagnostics.push(Diagnostic::warning(
@@ -156,9 +385,27 @@ fn check_type_ref(
                 check_type_ref(typ, symtab, context, diagnostics);
             }
         }
-        TypeExpr::List(inner) => {
+        TypeExpr::List(inner) | TypeExpr::Optional(inner) => {
             check_type_ref(inner, symtab, context, diagnostics);
         }
+        TypeExpr::MapOf(key, value) | TypeExpr::Result(key, value) => {
+            check_type_ref(key, symtab, context, diagnostics);
+            check_type_ref(value, symtab, context, diagnostics);
+        }
+        TypeExpr::Generic(name, args) => {
+            if !symtab.types.contains_key(name) {
+                diagnostics.push(Diagnostic::warning(
+                    format!(
+                        "in '{}': type '{}' is not defined in this module",
+                        context, name
+                    ),
+                    None,
+                ));
+            }
+            for arg in args {
+                check_type_ref(arg, symtab, context, diagnostics);
+            }
+        }
         TypeExpr::Union(variants) => {
             for v in variants {
                 match &v.kind {
@@ -172,9 +419,121 @@ fn check_type_ref(
             }
         }
         TypeExpr::Enum(_) => {}
+        TypeExpr::Ref(name) => {
+            if !symtab.types.contains_key(name) {
+                diagnostics.push(Diagnostic::error(
+                    format!("in '{}': (ref {}) names no type defined in this module", context, name),
+                    None,
+                ));
+            }
+        }
+    }
+}
+
+/// `(ref T)` needs `T` to be a module type with a UUID `id` to refer to
+fn check_ref(type_name: &str, field: &FieldDef, module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    let TypeExpr::Ref(target) = field.type_expr.non_optional() else {
+        return;
+    };
+    let problem = match module.types.iter().find(|t| t.name == *target) {
+        None => "names no type defined in this module",
+        Some(typedef)
+            if !typedef
+                .fields
+                .iter()
+                .any(|f| f.name == "id" && matches!(&f.type_expr, TypeExpr::Named(n) if n == "UUID")) =>
+        {
+            "names a type without a UUID id field"
+        }
+        Some(_) => return,
+    };
+    diagnostics.push(Diagnostic::error(
+        format!("field '{}.{}' is (ref {}), which {}", type_name, field.name, target, problem),
+        Some(field.span.clone()),
+    ));
+}
+
+/// `:min`/`:max` need a numeric field (and whole bounds on `Int`), `:scale` a `Decimal` one
+fn check_numeric_annotations(type_name: &str, field: &FieldDef, diagnostics: &mut Vec<Diagnostic>) {
+    let field_type = match field.type_expr.non_optional() {
+        TypeExpr::Named(name) => name.as_str(),
+        _ => "",
+    };
+    let numeric = matches!(field_type, "Int" | "Float" | "Decimal");
+    for (keyword, bound) in [("min", field.min), ("max", field.max)] {
+        let Some(bound) = bound else {
+            continue;
+        };
+        let problem = if !numeric {
+            Some("only Int, Float and Decimal fields take it".to_string())
+        } else if field_type == "Int" && matches!(bound, Number::Float(_)) {
+            Some(format!("{} is not a whole number", bound))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            diagnostics.push(Diagnostic::error(
+                format!("field '{}.{}' has :{} {}: {}", type_name, field.name, keyword, bound, problem),
+                Some(field.span.clone()),
+            ));
+        }
+    }
+    if field.scale.is_some() && field_type != "Decimal" {
+        diagnostics.push(Diagnostic::error(
+            format!("field '{}.{}' has :scale, which only Decimal fields take", type_name, field.name),
+            Some(field.span.clone()),
+        ));
+    }
+}
+
+/// `:default` must be a literal of the field's type: a variant keyword for enums, and a
+/// whole or fractional number for `Float` and `Decimal`
+fn check_default(type_name: &str, field: &FieldDef, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(default) = &field.default else {
+        return;
+    };
+    let expected = match (field.type_expr.non_optional(), default) {
+        (TypeExpr::Named(n), MetaValue::String(_)) if n == "String" => return,
+        (TypeExpr::Named(n), MetaValue::Int(_)) if n == "Int" => return,
+        (TypeExpr::Named(n), MetaValue::Int(_) | MetaValue::Float(_)) if n == "Float" || n == "Decimal" => return,
+        (TypeExpr::Named(n), MetaValue::Bool(_)) if n == "Bool" => return,
+        (TypeExpr::Enum(variants), MetaValue::Keyword(kw)) if variants.contains(kw) => return,
+        (TypeExpr::Enum(variants), _) => {
+            let keywords: Vec<String> = variants.iter().map(|v| format!(":{}", v)).collect();
+            format!("one of {}", keywords.join(" "))
+        }
+        (TypeExpr::Named(n), _) if matches!(n.as_str(), "String" | "Int" | "Float" | "Decimal" | "Bool") => {
+            let article = if n.starts_with(['I', 'U']) { "an" } else { "a" };
+            format!("{} {}", article, n)
+        }
+        _ => "a literal of a built-in or enum type".to_string(),
+    };
+    diagnostics.push(Diagnostic::error(
+        format!(
+            "field '{}.{}' has :default {}, which is not {}",
+            type_name,
+            field.name,
+            literal_text(default),
+            expected
+        ),
+        Some(field.span.clone()),
+    ));
+}
+
+/// A default as written in source
+fn literal_text(value: &MetaValue) -> String {
+    match value {
+        MetaValue::String(s) => format!("{:?}", s),
+        MetaValue::Int(n) => n.to_string(),
+        MetaValue::Float(n) => format!("{:?}", n),
+        MetaValue::Bool(b) => b.to_string(),
+        MetaValue::Symbol(s) => s.clone(),
+        MetaValue::Keyword(kw) => format!(":{}", kw),
+        _ => "(expression)".to_string(),
     }
 }
 
+#[allow(clippy::only_used_in_recursion)]
 fn check_expr_refs(
     expr: &Expr,
     symtab: &SymbolTable,
@@ -229,10 +588,27 @@ fn check_expr_refs(
                 check_expr_refs(val, symtab, scope, context, diagnostics);
             }
         }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_expr_refs(item, symtab, scope, context, diagnostics);
+            }
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut inner = scope.clone();
+            inner.extend(params.iter().cloned());
+            check_expr_refs(body, symtab, &mut inner, context, diagnostics);
+        }
+        Expr::ForEach { var, list, body, .. } => {
+            check_expr_refs(list, symtab, scope, context, diagnostics);
+            let mut inner = scope.clone();
+            inner.insert(var.clone());
+            check_expr_refs(body, symtab, &mut inner, context, diagnostics);
+        }
         Expr::Ref(_, _)
         | Expr::Keyword(_, _)
         | Expr::StringLit(_, _)
         | Expr::IntLit(_, _)
+        | Expr::FloatLit(_, _)
         | Expr::BoolLit(_, _)
         | Expr::Wildcard(_) => {}
     }
@@ -251,3 +627,172 @@ fn collect_pattern_bindings(pattern: &Pattern, scope: &mut HashSet<String>) {
         Pattern::Wildcard(_) | Pattern::Keyword(_, _) => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lower::Lowerer;
+    use crate::parser::Parser;
+
+    fn resolve(input: &str) -> Vec<Diagnostic> {
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let sexprs = Parser::new(tokens).parse_program().unwrap();
+        let module = Lowerer::new().lower_module(&sexprs[0]).unwrap();
+        resolve_names(&module)
+    }
+
+    #[test]
+    fn test_numeric_types_and_ranges() {
+        let diags = resolve(
+            r#"(module shop :version 1
+                (type Product
+                    (field price Decimal :scale 2 :min 0.01 :max 99999.99)
+                    (field weight Float :min 0)
+                    (field stock Int :min 0))
+                (fn price-of
+                    (param p Product)
+                    (returns (union (ok Decimal :http 200)))
+                    (ok (. p price))))"#,
+        );
+        assert!(diags.is_empty(), "expected no diagnostics, got: {:?}", diags);
+    }
+
+    #[test]
+    fn test_misplaced_numeric_annotations() {
+        let diags = resolve(
+            r#"(module shop :version 1
+                (type Product
+                    (field name String :min 1)
+                    (field stock Int :max 2.5)
+                    (field weight Float :scale 2)))"#,
+        );
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "field 'Product.name' has :min 1: only Int, Float and Decimal fields take it",
+                "field 'Product.stock' has :max 2.5: 2.5 is not a whole number",
+                "field 'Product.weight' has :scale, which only Decimal fields take",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defaults_match_field_types() {
+        let diags = resolve(
+            r#"(module team :version 1
+                (type Member
+                    (field role (enum :admin :member) :default :member)
+                    (field nickname (optional String) :default "anon")
+                    (field rate Decimal :default 1)
+                    (field active Bool :default :yes)
+                    (field level (enum :low :high) :default :medium)
+                    (field age Int :default 1.5)))"#,
+        );
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "field 'Member.active' has :default :yes, which is not a Bool",
+                "field 'Member.level' has :default :medium, which is not one of :low :high",
+                "field 'Member.age' has :default 1.5, which is not an Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refs_need_a_type_with_a_uuid_id() {
+        let diags = resolve(
+            r#"(module blog :version 1
+                (type Tag (field name String))
+                (type Author (field id UUID))
+                (type Post
+                    (field author (ref Author))
+                    (field editor (optional (ref Author)))
+                    (field tag (ref Tag))
+                    (field series (ref Series))))"#,
+        );
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "field 'Post.tag' is (ref Tag), which names a type without a UUID id field",
+                "field 'Post.series' is (ref Series), which names no type defined in this module",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_helper_calls_check_arity_and_types() {
+        let errors: Vec<String> = resolve(
+            r#"(module shop :version 1
+                (type Session (field token String) (field expires-at Int))
+                (defn expired? [s Session] Bool (<= (. s expires-at) 0))
+                (defn ttl [s Session] String (. s expires-at))
+                (fn check
+                    (param s Session)
+                    (param token String)
+                    (returns (union (ok Bool)))
+                    (if (expired? s) (ok (expired? token)) (ok (expired? s s)))))"#,
+        )
+        .into_iter()
+        .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+        .map(|d| d.message)
+        .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "in 'check': argument 1 of 'expired?' is String, not Session".to_string(),
+                "in 'check': 'expired?' takes 1 argument, not 2".to_string(),
+                "helper 'ttl' returns Int, not the declared String".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extern_calls_check_arity_and_types() {
+        let errors: Vec<String> = resolve(
+            r#"(module auth :version 1
+                (extern verify-password [String String] (result Unit Unit) :effects [])
+                (extern send-sms [String] Bool :effects [sms])
+                (fn login
+                    (param password String)
+                    (param attempts Int)
+                    (returns (union (ok Bool)))
+                    (match (verify-password password attempts)
+                        (ok _) (ok (send-sms password password))
+                        (err _) (ok false))))"#,
+        )
+        .into_iter()
+        .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+        .map(|d| d.message)
+        .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "extern 'send-sms' references unknown effect set 'sms'".to_string(),
+                "in 'login': argument 2 of 'verify-password' is Int, not String".to_string(),
+                "in 'login': 'send-sms' takes 1 argument, not 2".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sending_externs_return_an_acknowledgement() {
+        let errors: Vec<String> = resolve(
+            r#"(module notify :version 1
+                (effect-set sms-send [:sends sms-gateway])
+                (extern send-sms [String] Bool :effects [sms-send])
+                (extern deliver-email [String String] (result Unit String) :effects [sms-send]))"#,
+        )
+        .into_iter()
+        .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+        .map(|d| d.message)
+        .collect();
+        assert_eq!(
+            errors,
+            vec!["extern 'send-sms' sends through 'sms-gateway', so it must return (result Unit String)".to_string()]
+        );
+    }
+}
@@ -1,13 +1,23 @@
+use std::collections::HashSet;
+
 use crate::ast::*;
 use crate::diagnostics::Diagnostic;
+use crate::lexer::Span;
+
+use super::enums::{self, EnumDef};
 
 /// Check match exhaustiveness for functions marked as total.
 /// For the prototype, we check that match expressions on union return types
 /// cover all declared variants (ok + all err tags).
 pub fn check_totality(module: &Module) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
+    let enums = enums::collect_enums(module);
+    let named = enums::name_enums(module, &enums);
+
+    for func in &named.functions {
+        check_store_error_mapping(func, &mut diagnostics);
+        check_enum_matches(&func.body, func, &named, &enums, &mut diagnostics);
 
-    for func in &module.functions {
         if !func.total {
             continue;
         }
@@ -25,6 +35,7 @@ pub fn check_totality(module: &Module) -> Vec<Diagnostic> {
 
         // Check the body expression for exhaustiveness
         check_expr_totality(&func.body, &expected_tags, &func.name, &mut diagnostics);
+        check_recursion(func, &named, &mut diagnostics);
     }
 
     diagnostics
@@ -103,18 +114,343 @@ fn check_expr_totality(
                 check_expr_totality(val, _expected_return_tags, fn_name, diagnostics);
             }
         }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check_expr_totality(item, _expected_return_tags, fn_name, diagnostics);
+            }
+        }
+        Expr::Lambda { body, .. } => {
+            check_expr_totality(body, _expected_return_tags, fn_name, diagnostics);
+        }
+        Expr::ForEach { list, body, .. } => {
+            check_expr_totality(list, _expected_return_tags, fn_name, diagnostics);
+            check_expr_totality(body, _expected_return_tags, fn_name, diagnostics);
+        }
         _ => {}
     }
 }
 
-fn is_catch_all(pattern: &Pattern) -> bool {
-    match pattern {
-        Pattern::Wildcard(_) => true,
-        Pattern::Var(_, _) => true,
+/// A match over an enum must name only its variants, and all of them unless an arm
+/// catches the rest: the generated Rust `match` would not compile otherwise.
+fn check_enum_matches(expr: &Expr, func: &FnDef, module: &Module, enums: &[EnumDef], diagnostics: &mut Vec<Diagnostic>) {
+    if let Expr::Match { expr: scrutinee, arms, span } = expr {
+        if let Some(def) = enums::match_enum(scrutinee, arms, module, func, enums) {
+            check_enum_arms(def, arms, span, &func.name, diagnostics);
+        }
+    }
+    let mut check = |e: &Expr| check_enum_matches(e, func, module, enums, diagnostics);
+    match expr {
+        Expr::Match { expr: scrutinee, arms, .. } => {
+            check(scrutinee);
+            for arm in arms {
+                check(&arm.body);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (_, val) in bindings {
+                check(val);
+            }
+            check(body);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check(cond);
+            check(then_branch);
+            check(else_branch);
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                check(arg);
+            }
+        }
+        Expr::Ok(inner, _) | Expr::FieldAccess { expr: inner, .. } => check(inner),
+        Expr::Err { payload, .. } => check(payload),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                check(val);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                check(item);
+            }
+        }
+        Expr::Lambda { body, .. } => check(body),
+        Expr::ForEach { list, body, .. } => {
+            check(list);
+            check(body);
+        }
+        _ => {}
+    }
+}
+
+fn check_enum_arms(def: &EnumDef, arms: &[MatchArm], span: &Span, fn_name: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let matched: Vec<&str> = arms
+        .iter()
+        .filter_map(|arm| match &arm.pattern {
+            Pattern::Keyword(kw, _) => Some(kw.as_str()),
+            _ => None,
+        })
+        .collect();
+    for kw in matched.iter().filter(|kw| !def.variants.iter().any(|v| v == *kw)) {
+        diagnostics.push(Diagnostic::error(
+            format!("match in '{}': :{} is not a variant of {}", fn_name, kw, def.name),
+            Some(span.clone()),
+        ));
+    }
+    let missing: Vec<String> = def
+        .variants
+        .iter()
+        .filter(|v| !matched.contains(&v.as_str()))
+        .map(|v| format!(":{}", v))
+        .collect();
+    if !missing.is_empty() && !arms.iter().any(|arm| is_catch_all(&arm.pattern)) {
+        diagnostics.push(Diagnostic::error(
+            format!("match in '{}' on {} does not cover {}", fn_name, def.name, missing.join(" ")),
+            Some(span.clone()),
+        ));
+    }
+}
+
+/// Store errors that no `(err :kw)` arm matches must land on a declared variant:
+/// the one named by `:on-store-error`, or an `(err _)` arm. Otherwise the generated
+/// code would have to panic, which a `:total` function may not do.
+fn check_store_error_mapping(func: &FnDef, diagnostics: &mut Vec<Diagnostic>) {
+    if let Some(tag) = &func.on_store_error {
+        let payload = func.returns.variants.iter().find_map(|v| match &v.kind {
+            VariantKind::Err { tag: t, payload, .. } if t == tag => Some(payload),
+            _ => None,
+        });
+        match payload {
+            None => diagnostics.push(Diagnostic::error(
+                format!(
+                    "fn '{}' maps store errors to :{}, which is not a declared err variant",
+                    func.name, tag
+                ),
+                Some(func.returns.span.clone()),
+            )),
+            Some(payload) if !store_error_payload_ok(payload) => diagnostics.push(Diagnostic::error(
+                format!(
+                    "fn '{}': :on-store-error variant :{} must carry {{}}, Unit, String or a single String field",
+                    func.name, tag
+                ),
+                Some(func.returns.span.clone()),
+            )),
+            Some(_) => {}
+        }
+        return;
+    }
+
+    if func.total {
+        let mut unmapped = Vec::new();
+        find_unmapped_store_matches(&func.body, &mut unmapped);
+        for span in unmapped {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "fn '{}' is :total, but store errors not matched here would panic; add an (err _) arm or :on-store-error",
+                    func.name
+                ),
+                Some(span),
+            ));
+        }
+    }
+}
+
+fn store_error_payload_ok(payload: &TypeExpr) -> bool {
+    match payload {
+        TypeExpr::Map(fields) => {
+            fields.is_empty()
+                || matches!(fields.as_slice(), [(_, TypeExpr::Named(t))] if t == "String")
+        }
+        TypeExpr::Named(name) => name == "Unit" || name == "String",
         _ => false,
     }
 }
 
+/// The `insert!` an expression yields the result of, looking through `let` bodies
+fn written_insert(expr: &Expr) -> Option<&Span> {
+    match expr {
+        Expr::Call { name, span, .. } if name == "insert!" => Some(span),
+        Expr::Let { body, .. } => written_insert(body),
+        _ => None,
+    }
+}
+
+/// Matches with `(err :kw)` arms but no `(err _)` arm, and `insert!`s bound by `let`
+/// or written once per `for-each` item.
+fn find_unmapped_store_matches(expr: &Expr, spans: &mut Vec<Span>) {
+    match expr {
+        Expr::Match { expr, arms, span } => {
+            let err_arm = |catch_all: bool| {
+                arms.iter().any(|arm| match &arm.pattern {
+                    Pattern::Constructor { name, args, .. } if name == "err" => {
+                        args.iter().all(is_catch_all) == catch_all
+                    }
+                    _ => false,
+                })
+            };
+            if err_arm(false) && !err_arm(true) {
+                spans.push(span.clone());
+            }
+            find_unmapped_store_matches(expr, spans);
+            for arm in arms {
+                find_unmapped_store_matches(&arm.body, spans);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (_, val) in bindings {
+                // A bound `insert!` binds the row; nothing matches its store errors
+                if let Some(span) = written_insert(val) {
+                    spans.push(span.clone());
+                }
+                find_unmapped_store_matches(val, spans);
+            }
+            find_unmapped_store_matches(body, spans);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            find_unmapped_store_matches(cond, spans);
+            find_unmapped_store_matches(then_branch, spans);
+            find_unmapped_store_matches(else_branch, spans);
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                find_unmapped_store_matches(arg, spans);
+            }
+        }
+        Expr::Ok(inner, _) | Expr::FieldAccess { expr: inner, .. } => find_unmapped_store_matches(inner, spans),
+        Expr::Err { payload, .. } => find_unmapped_store_matches(payload, spans),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                find_unmapped_store_matches(val, spans);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                find_unmapped_store_matches(item, spans);
+            }
+        }
+        Expr::Lambda { body, .. } => find_unmapped_store_matches(body, spans),
+        Expr::ForEach { list, body, .. } => {
+            find_unmapped_store_matches(list, spans);
+            if let Some(span) = written_insert(body) {
+                spans.push(span.clone());
+            }
+            find_unmapped_store_matches(body, spans);
+        }
+        _ => {}
+    }
+}
+
+/// A `:total` function may not reach itself through calls to module functions or helpers:
+/// only iteration over a list, whose length is finite, is known to terminate.
+fn check_recursion(func: &FnDef, module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    let mut calls = Vec::new();
+    collect_calls(&func.body, &mut calls);
+    let cycle = calls
+        .into_iter()
+        .find(|(callee, _)| reaches(callee, &func.name, module, &mut HashSet::new()));
+    if let Some((callee, span)) = cycle {
+        let via = if callee == func.name {
+            String::new()
+        } else {
+            format!(" through '{}'", callee)
+        };
+        diagnostics.push(Diagnostic::error(
+            format!(
+                "fn '{}' is :total, but calls itself{}, which may not terminate; iterate with map, filter, fold or for-each instead",
+                func.name, via
+            ),
+            Some(span),
+        ));
+    }
+}
+
+fn reaches(from: &str, target: &str, module: &Module, seen: &mut HashSet<String>) -> bool {
+    if from == target {
+        return true;
+    }
+    if !seen.insert(from.to_string()) {
+        return false;
+    }
+    let body = module
+        .functions
+        .iter()
+        .find(|f| f.name == from)
+        .map(|f| &f.body)
+        .or_else(|| module.helpers.iter().find(|h| h.name == from).map(|h| &h.body));
+    let Some(body) = body else {
+        return false;
+    };
+    let mut calls = Vec::new();
+    collect_calls(body, &mut calls);
+    calls.iter().any(|(name, _)| reaches(name, target, module, seen))
+}
+
+/// The names called in `expr`, with the span of each call
+fn collect_calls(expr: &Expr, calls: &mut Vec<(String, Span)>) {
+    match expr {
+        Expr::Call { name, args, span } => {
+            calls.push((name.clone(), span.clone()));
+            for arg in args {
+                collect_calls(arg, calls);
+            }
+        }
+        Expr::Match { expr, arms, .. } => {
+            collect_calls(expr, calls);
+            for arm in arms {
+                collect_calls(&arm.body, calls);
+            }
+        }
+        Expr::Let { bindings, body, .. } => {
+            for (_, val) in bindings {
+                collect_calls(val, calls);
+            }
+            collect_calls(body, calls);
+        }
+        Expr::If {
+            cond,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_calls(cond, calls);
+            collect_calls(then_branch, calls);
+            collect_calls(else_branch, calls);
+        }
+        Expr::Ok(inner, _) | Expr::FieldAccess { expr: inner, .. } => collect_calls(inner, calls),
+        Expr::Err { payload, .. } => collect_calls(payload, calls),
+        Expr::MapLit(entries, _) => {
+            for (_, val) in entries {
+                collect_calls(val, calls);
+            }
+        }
+        Expr::ListLit(items, _) => {
+            for item in items {
+                collect_calls(item, calls);
+            }
+        }
+        Expr::Lambda { body, .. } => collect_calls(body, calls),
+        Expr::ForEach { list, body, .. } => {
+            collect_calls(list, calls);
+            collect_calls(body, calls);
+        }
+        _ => {}
+    }
+}
+
+fn is_catch_all(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard(_) | Pattern::Var(_, _))
+}
+
 fn pattern_tag(pattern: &Pattern) -> Option<String> {
     match pattern {
         Pattern::Constructor { name, .. } => Some(name.clone()),
@@ -178,4 +514,100 @@ mod tests {
             .collect();
         assert!(errors.is_empty());
     }
+
+    const STORE_WRITE: &str = r#"(module test :version 1
+        (type User (field id UUID :immutable :generated))
+        (effect-set db-write [:writes user-store])
+        (fn create-user
+            :effects [db-write]
+            :total true
+            MAPPING
+            (param input {})
+            (returns (union
+                (ok User :http 201)
+                (err :duplicate {} :http 409)
+                (err :store-unavailable {} :http 503)))
+            (match (insert! user-store (User.from-input input))
+                (ok user)             (ok user)
+                (err :unique-violation) (err :duplicate {}))))"#;
+
+    fn errors(input: &str) -> Vec<String> {
+        check(input)
+            .into_iter()
+            .filter(|d| d.kind == crate::diagnostics::DiagnosticKind::Error)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_total_store_match_needs_mapping() {
+        assert_eq!(
+            errors(&STORE_WRITE.replace("MAPPING", "")),
+            vec!["fn 'create-user' is :total, but store errors not matched here would panic; add an (err _) arm or :on-store-error"]
+        );
+        assert!(errors(&STORE_WRITE.replace("MAPPING", ":on-store-error :store-unavailable")).is_empty());
+
+        // A let-bound insert! leaves every store error unmatched
+        let bound = STORE_WRITE.replace(
+            "(match (insert! user-store (User.from-input input))\n                (ok user)             (ok user)\n                (err :unique-violation) (err :duplicate {}))",
+            "(let [user (insert! user-store (User.from-input input))] (ok user))",
+        );
+        assert!(bound.contains("(let [user"));
+        assert_eq!(errors(&bound.replace("MAPPING", "")).len(), 1);
+        assert!(errors(&bound.replace("MAPPING", ":on-store-error :store-unavailable")).is_empty());
+
+        // So does an insert! written for each item of a list
+        let each = STORE_WRITE.replace(
+            "(match (insert! user-store (User.from-input input))\n                (ok user)             (ok user)\n                (err :unique-violation) (err :duplicate {}))",
+            "(let [_ (for-each [i (list input)] (insert! user-store (User.from-input i)))] (ok (User.from-input input)))",
+        );
+        assert!(each.contains("for-each"));
+        assert_eq!(errors(&each.replace("MAPPING", "")).len(), 1);
+        assert!(errors(&each.replace("MAPPING", ":on-store-error :store-unavailable")).is_empty());
+    }
+
+    #[test]
+    fn test_store_error_mapping_must_be_declared() {
+        let errs = errors(&STORE_WRITE.replace("MAPPING", ":on-store-error :timeout"));
+        assert_eq!(errs.len(), 1);
+        assert!(errs[0].contains("timeout"), "{}", errs[0]);
+    }
+
+    const ROLE_MATCH: &str = r#"(module test :version 1
+        (type User (field role (enum :admin :member :guest)))
+        (fn can-edit
+            (param user User)
+            (returns (union (ok Bool :http 200)))
+            (match (. user role)
+                ARMS)))"#;
+
+    #[test]
+    fn test_enum_match_must_cover_variants() {
+        assert!(errors(&ROLE_MATCH.replace("ARMS", ":admin (ok true) :member (ok false) :guest (ok false)")).is_empty());
+        assert!(errors(&ROLE_MATCH.replace("ARMS", ":admin (ok true) _ (ok false)")).is_empty());
+        assert_eq!(
+            errors(&ROLE_MATCH.replace("ARMS", ":admin (ok true) :owner (ok true)")),
+            vec![
+                "match in 'can-edit': :owner is not a variant of Role",
+                "match in 'can-edit' on Role does not cover :member :guest",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_total_recursion_must_iterate_instead() {
+        let sum = |body: &str| {
+            format!(
+                r#"(module test :version 1
+                    (fn sum :total true (param xs (list Int)) (returns (union (ok Int))) {})
+                    (fn helper (param xs (list Int)) (returns (union (ok Int))) (sum xs)))"#,
+                body
+            )
+        };
+        assert!(errors(&sum("(ok (fold (fn [acc x] (+ acc x)) 0 xs))")).is_empty());
+        assert_eq!(
+            errors(&sum("(helper xs)")),
+            vec!["fn 'sum' is :total, but calls itself through 'helper', which may not terminate; iterate with map, filter, fold or for-each instead"]
+        );
+    }
 }